@@ -1,3 +1,5 @@
+// Without a GPU, run it on the software rasterizer:
+// cargo bench --features soft_gl
 #![feature(test)]
 
 extern crate test;
@@ -1,160 +1,55 @@
-extern crate nalgebra as na;
-extern crate ncollide;
-extern crate nphysics3d;
 extern crate unrust;
 
+use std::rc::Rc;
 use unrust::world::{Actor, Handle, World, WorldBuilder};
 use unrust::engine::{Camera, Directional, GameObject, Light, Material, Mesh, Point};
 use unrust::world::events::*;
 use unrust::math;
 
-use ncollide::shape::{Cuboid, Cuboid3, Plane, Plane3};
-use nphysics3d::world::World as PhyWorld;
-use nphysics3d::object::{RigidBody, RigidBodyHandle};
-
-use unrust::engine::ComponentBased;
-use unrust::actors::{ShadowPass, SkyBox};
+use unrust::actors::{Collider, Physics, RigidBody, ShadowPass, SkyBox};
 
 // GUI
 use unrust::imgui;
-use na::*;
-
-pub struct Scene {
-    pub world: PhyWorld<f32>,
-}
-
-impl Scene {
-    pub fn step(&mut self) {
-        self.world.step(0.016)
-    }
-
-    pub fn add_box(&mut self) -> RigidBodyHandle<f32> {
-        let rad = 1.0;
-        let geom = Cuboid::new(Vector3::new(rad - 0.04, rad - 0.04, rad - 0.04));
-        let mut rb = RigidBody::new_dynamic(geom, 1.0, 0.3, 0.5);
-        rb.append_translation(&Translation3::new(0.0, 30.0, 0.0));
-
-        self.world.add_rigid_body(rb)
-    }
-
-    pub fn new() -> Scene {
-        /*
-         * World
-         */
-        let mut world = PhyWorld::new();
-        world.set_gravity(Vector3::new(0.0, -9.81, 0.0));
-
-        /*
-         * Plane
-         */
-        let geom = Plane::new(Vector3::new(0.0, 1.0, 0.0));
-
-        world.add_rigid_body(RigidBody::new_static(geom, 0.3, 0.6));
-
-        /*
-         * Create the boxes
-         */
-        let num = 4;
-        let rad = 1.0;
-        let shift = rad * 2.0;
-        let centerx = shift * (num as f32) / 2.0;
-        let centery = shift / 2.0 + 0.04;
-        let centerz = shift * (num as f32) / 2.0;
-
-        for i in 0usize..num {
-            for j in 0usize..num {
-                for k in 0usize..num {
-                    let x = i as f32 * shift - centerx;
-                    let y = j as f32 * shift + centery;
-                    let z = k as f32 * shift - centerz;
-
-                    let geom = Cuboid::new(Vector3::new(rad - 0.04, rad - 0.04, rad - 0.04));
-                    let mut rb = RigidBody::new_dynamic(geom, 1.0, 0.3, 0.5);
-
-                    rb.append_translation(&Translation3::new(x, y, z));
-
-                    world.add_rigid_body(rb);
-                }
-            }
-        }
-
-        Scene { world: world }
-    }
-}
-
-// Physic Object Component
-struct PhysicObject(Handle<RigidBody<f32>>);
-impl PhysicObject {
-    fn phy_transform(&self) -> math::Isometry3<f32> {
-        let na_pos = *self.0.borrow().position();
-
-        use unrust::math::InnerSpace;
-
-        math::Isometry3 {
-            scale: 1.0,
-            rot: math::Quaternion::new(
-                na_pos.rotation.coords.w,
-                na_pos.rotation.coords.x,
-                na_pos.rotation.coords.y,
-                na_pos.rotation.coords.z,
-            ).normalize(),
-            disp: math::Vector3::new(
-                na_pos.translation.vector.x,
-                na_pos.translation.vector.y,
-                na_pos.translation.vector.z,
-            ),
-        }
-    }
-}
-impl ComponentBased for PhysicObject {}
 
 pub struct MainScene {
     eye: math::Vector3<f32>,
     last_event: Option<AppEvent>,
-    phy_scene: Scene,
     counter: u32,
     point_lights: Vec<Handle<GameObject>>,
+    // Shared by the boxes, so they are drawn instanced
+    materials: Vec<Rc<Material>>,
 }
 
 impl MainScene {
-    fn rigid_bodies(&mut self) -> Vec<Handle<RigidBody<f32>>> {
-        self.phy_scene
-            .world
-            .rigid_bodies()
-            .map(|rb| rb.clone())
-            .collect()
-    }
-
-    fn add_box(&mut self, world: &mut World) {
-        let bx = self.phy_scene.add_box();
-        self.add_object(bx, world);
-    }
-
-    fn add_object(&mut self, rb: Handle<RigidBody<f32>>, world: &mut World) {
-        let rbody = rb.borrow();
-        let shape = rbody.shape();
+    fn add_box(&mut self, world: &mut World, pos: math::Vector3<f32>) {
+        let rad = 1.0 - 0.04;
 
         let go = world.new_game_object();
-        go.borrow_mut().add_component(PhysicObject(rb.clone()));
+        let mut go = go.borrow_mut();
+        go.transform.set_global(math::Isometry3 {
+            scale: 1.0,
+            rot: math::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            disp: pos,
+        });
+        go.add_component(Collider::cuboid(math::Vector3::new(rad, rad, rad)));
+        go.add_component(RigidBody::new(1.0));
+        let material = match self.counter % 5 {
+            0 => self.materials[0].clone(),
+            1 => self.materials[1].clone(),
+            _ => self.materials[2].clone(),
+        };
+        go.add_component(CubeActor::new(material));
 
-        if let Some(_) = shape.as_shape::<Cuboid3<f32>>() {
-            let actor = CubeActor::new(self.counter);
-            self.counter += 1;
-            go.borrow_mut().add_component(actor);
-        } else if let Some(_) = shape.as_shape::<Plane3<f32>>() {
-            go.borrow_mut().add_component(PlaneActor::new());
-        } else {
-            unimplemented!();
-        }
+        self.counter += 1;
     }
 
     fn new() -> Box<Actor> {
         Box::new(MainScene {
             eye: math::Vector3::new(26.0, 38.0, -43.0),
             last_event: None,
-            phy_scene: Scene::new(),
             point_lights: Vec::new(),
             counter: 0,
+            materials: Vec::new(),
         })
     }
 }
@@ -195,12 +90,50 @@ impl Actor for MainScene {
             self.point_lights.push(go.clone());
         }
 
-        // Add the physics object
+        // Add the ground, its collider is a thick box below the plane
+        {
+            let go = world.new_game_object();
+            go.borrow_mut().add_component(PlaneActor::new());
+
+            let go = world.new_game_object();
+            let mut go = go.borrow_mut();
+            go.transform.set_global(math::Isometry3 {
+                scale: 1.0,
+                rot: math::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+                disp: math::Vector3::new(0.0, -10.0, 0.0),
+            });
+            go.add_component(Collider::cuboid(math::Vector3::new(100.0, 10.0, 100.0)));
+        }
+
+        // Create the boxes
         {
-            let rigid_bodies = self.rigid_bodies();
+            let db = world.asset_system();
+            for tex in ["tex_a.png", "tex_r.png", "tex_b.png"].iter() {
+                let material = Material::new(db.new_program("unrust/phong_shadow"));
+                material.set("uMaterial.diffuse", db.new_texture(tex));
+                material.set("uMaterial.shininess", 32.0);
+                self.materials.push(Rc::new(material));
+            }
+        }
 
-            for rb in rigid_bodies.into_iter() {
-                self.add_object(rb, world);
+        {
+            let num = 4;
+            let rad = 1.0;
+            let shift = rad * 2.0;
+            let centerx = shift * (num as f32) / 2.0;
+            let centery = shift / 2.0 + 0.04;
+            let centerz = shift * (num as f32) / 2.0;
+
+            for i in 0usize..num {
+                for j in 0usize..num {
+                    for k in 0usize..num {
+                        let x = i as f32 * shift - centerx;
+                        let y = j as f32 * shift + centery;
+                        let z = k as f32 * shift - centerz;
+
+                        self.add_box(world, math::Vector3::new(x, y, z));
+                    }
+                }
             }
         }
     }
@@ -208,8 +141,6 @@ impl Actor for MainScene {
     fn update(&mut self, _go: &mut GameObject, world: &mut World) {
         use unrust::math::{EuclideanSpace, InnerSpace, Rotation3};
 
-        self.phy_scene.step();
-
         // Update point lights
         for lgo in self.point_lights.iter() {
             lgo.try_borrow().ok().map(|light_go| {
@@ -256,7 +187,7 @@ impl Actor for MainScene {
             }
 
             if addbox {
-                self.add_box(world);
+                self.add_box(world, math::Vector3::new(0.0, 30.0, 0.0));
             }
 
             if reset {
@@ -299,12 +230,12 @@ impl Actor for MainScene {
 }
 
 pub struct CubeActor {
-    id: u32,
+    material: Rc<Material>,
 }
 
 impl CubeActor {
-    fn new(id: u32) -> Box<Actor> {
-        Box::new(CubeActor { id: id })
+    fn new(material: Rc<Material>) -> Box<Actor> {
+        Box::new(CubeActor { material })
     }
 }
 
@@ -312,29 +243,10 @@ impl Actor for CubeActor {
     fn start(&mut self, go: &mut GameObject, world: &mut World) {
         let db = &mut world.asset_system();
 
-        let texture = match self.id % 5 {
-            0 => db.new_texture("tex_a.png"),
-            1 => db.new_texture("tex_r.png"),
-            _ => db.new_texture("tex_b.png"),
-        };
-
-        let material = Material::new(db.new_program("unrust/phong_shadow"));
-        material.set("uMaterial.diffuse", texture);
-        material.set("uMaterial.shininess", 32.0);
-
         let mut mesh = Mesh::new();
-        mesh.add_surface(db.new_mesh_buffer("cube"), material);
+        mesh.add_surface(db.new_mesh_buffer("cube"), self.material.clone());
         go.add_component(mesh);
     }
-
-    fn update(&mut self, go: &mut GameObject, _world: &mut World) {
-        let new_trans = {
-            let (phy, _) = go.find_component::<PhysicObject>().unwrap();
-            phy.phy_transform()
-        };
-
-        go.transform.set_global(new_trans);
-    }
 }
 
 pub struct PlaneActor {}
@@ -356,15 +268,6 @@ impl Actor for PlaneActor {
         let mut mesh = Mesh::new();
         mesh.add_surface(db.new_mesh_buffer("plane"), material);
         go.add_component(mesh);
-    }
-
-    fn update(&mut self, go: &mut GameObject, _world: &mut World) {
-        let new_trans = {
-            let (phy, _) = go.find_component::<PhysicObject>().unwrap();
-            phy.phy_transform()
-        };
-
-        go.transform.set_global(new_trans);
         go.transform
             .set_local_scale(math::Vector3::new(3.0, 1.0, 3.0));
     }
@@ -374,6 +277,7 @@ pub fn main() {
     let mut world = WorldBuilder::new("Boxes with physics demo")
         .with_size((800, 600))
         .with_stats(true)
+        .with_processor::<Physics>()
         .with_processor::<ShadowPass>()
         .with_processor::<SkyBox>()
         .build();
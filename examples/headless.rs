@@ -1,3 +1,5 @@
+// Renders without a window. On a machine without a GPU, use the software rasterizer:
+// cargo run --example headless --features soft_gl
 extern crate uni_pad;
 extern crate unrust;
 
@@ -82,6 +84,25 @@ impl Actor for Cube {
     }
 }
 
+// The software rasterizer is much slower than a GPU
+#[cfg(not(feature = "soft_gl"))]
+const FRAMES: usize = 100000;
+#[cfg(feature = "soft_gl")]
+const FRAMES: usize = 100;
+
+// There is no window, the last frame of the software rasterizer is saved to be seen
+#[cfg(feature = "soft_gl")]
+fn save_frame(world: &World) {
+    let img = world
+        .engine()
+        .capture_frame_buffer()
+        .expect("Cannot capture frame buffer");
+    img.save("headless.png").expect("Cannot save to file");
+}
+
+#[cfg(not(feature = "soft_gl"))]
+fn save_frame(_world: &World) {}
+
 pub fn main() {
     let mut world = WorldBuilder::new("Headless")
         .with_headless(true)
@@ -94,10 +115,12 @@ pub fn main() {
     scene.borrow_mut().add_component(MainScene::new());
     drop(scene);
 
-    // We try to render 100000 frames
-    for _ in 0..100000 {
+    // We try to render FRAMES frames
+    for _ in 0..FRAMES {
         if !world.poll_events() {
             break;
         }
     }
+
+    save_frame(&world);
 }
@@ -1,8 +1,8 @@
 extern crate unrust;
 
 use unrust::actors::ShadowPass;
-use unrust::engine::{AssetError, AssetSystem, Camera, Directional, GameObject, Light, Material,
-                     Mesh, ObjMaterial, Prefab, RenderQueue, TextureWrap};
+use unrust::engine::{AssetSystem, Camera, Directional, GameObject, Light, Material, Mesh,
+                     ObjMaterial, RenderQueue, TextureWrap};
 use unrust::math::*;
 use unrust::world::events::*;
 use unrust::world::{Actor, Handle, World, WorldBuilder};
@@ -203,24 +203,7 @@ fn build_material(asys: &AssetSystem, obj_mat: ObjMaterial) -> Rc<Material> {
 
 impl Actor for WaveObjActor {
     fn start_rc(&mut self, go: Handle<GameObject>, world: &mut World) {
-        let db = &mut world.asset_system();
-
-        let prefab_handler = {
-            let go = go.clone();
-            move |r: Result<Prefab, AssetError>| {
-                if let Ok(prefab) = r {
-                    for c in prefab.components {
-                        go.borrow_mut().add_component(c.clone());
-                    }
-                }
-            }
-        };
-
-        db.new_prefab(
-            "meshobj_test_model.obj",
-            Box::new(build_material),
-            Box::new(prefab_handler),
-        );
+        world.load_prefab("meshobj_test_model.obj", Box::new(build_material), &go);
     }
 }
 
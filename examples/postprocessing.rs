@@ -1,8 +1,8 @@
 extern crate unrust;
 
-use unrust::world::{Actor, Handle, World, WorldBuilder};
-use unrust::engine::{Camera, ClearOption, Directional, GameObject, Light, Material, Mesh,
-                     RenderTexture, TextureAttachment};
+use unrust::world::{Actor, World, WorldBuilder};
+use unrust::engine::{Camera, Directional, GameObject, Light, Material, Mesh, PostProcessEffect,
+                     PostProcessStack};
 use unrust::world::events::*;
 use unrust::math::*;
 
@@ -14,6 +14,9 @@ use std::rc::Rc;
 pub struct MainScene {
     eye: Vector3<f32>,
     last_event: Option<AppEvent>,
+    crt: bool,
+    /// Without and with the crt effect
+    stacks: Vec<Rc<PostProcessStack>>,
 }
 
 // Actor is a trait object which would act like an component
@@ -23,6 +26,8 @@ impl MainScene {
         Box::new(MainScene {
             eye: Vector3::new(-3.0, 3.0, -3.0),
             last_event: None,
+            crt: true,
+            stacks: Vec::new(),
         })
     }
 }
@@ -40,9 +45,14 @@ impl Actor for MainScene {
         go.borrow_mut()
             .add_component(Light::new(Directional::default()));
 
-        // Added Crt
+        // Added a cube in the scene
         let go = world.new_game_object();
-        go.borrow_mut().add_component(Crt::new());
+        go.borrow_mut().add_component(Cube::new());
+
+        self.stacks = vec![
+            Rc::new(post_process_stack(world, false)),
+            Rc::new(post_process_stack(world, true)),
+        ];
     }
 
     fn update(&mut self, _go: &mut GameObject, world: &mut World) {
@@ -61,6 +71,7 @@ impl Actor for MainScene {
                             "KeyD" => self.eye = Quaternion::from_angle_y(Rad(0.2)) * self.eye,
                             "KeyW" => self.eye -= front * 2.0,
                             "KeyS" => self.eye += front * 2.0,
+                            "KeyC" => self.crt = !self.crt,
                             "Escape" => reset = true,
                             _ => (),
                         };
@@ -84,12 +95,15 @@ impl Actor for MainScene {
         // Update Camera
         {
             let cam = world.current_camera().unwrap();
+            let mut cam = cam.borrow_mut();
 
-            cam.borrow_mut().lookat(
+            cam.lookat(
                 &Point3::from_vec(self.eye),
                 &Point3::new(0.0, 0.0, 0.0),
                 &Vector3::new(0.0, 1.0, 0.0),
             );
+
+            cam.post_process = Some(self.stacks[self.crt as usize].clone());
         }
 
         // GUI
@@ -98,7 +112,7 @@ impl Actor for MainScene {
         imgui::pivot((1.0, 1.0));
         imgui::label(
             Native(1.0, 1.0) - Pixel(8.0, 8.0),
-            "[WASD] : control camera\n[Esc]  : reload all (include assets)",
+            "[WASD] : control camera\n[C]    : toggle crt\n[Esc]  : reload all (include assets)",
         );
 
         imgui::pivot((1.0, 0.0));
@@ -109,70 +123,23 @@ impl Actor for MainScene {
     }
 }
 
-pub struct Crt {
-    rt: Rc<RenderTexture>,
-    cube: Handle<GameObject>,
-}
-
-impl Crt {
-    fn new() -> Box<Actor> {
-        Box::new(Crt {
-            rt: Rc::new(RenderTexture::new(1024, 1024, TextureAttachment::Color0)),
-            cube: GameObject::empty(),
-        })
-    }
-}
-
-impl Actor for Crt {
-    fn start(&mut self, go: &mut GameObject, world: &mut World) {
-        // add main camera to scene
-        {
-            let go = world.new_game_object();
-            go.borrow_mut().add_component(Camera::default());
-        }
-
-        {
-            let db = &mut world.asset_system();
+/// Bloom, tone mapping and a vignette, with an optional CRT effect
+/// which is just a Material drawing the "screen_quad" mesh
+fn post_process_stack(world: &World, crt: bool) -> PostProcessStack {
+    let db = world.asset_system();
 
-            let material = Material::new(db.new_program("crt"));
-            material.set("uDiffuse", self.rt.as_texture());
+    let mut stack = PostProcessStack::new()
+        .with_effect(PostProcessEffect::bloom(db))
+        .with_effect(PostProcessEffect::tone_mapping(db, 1.2))
+        .with_effect(PostProcessEffect::vignette(db, 0.6))
+        .with_effect(PostProcessEffect::fxaa(db));
 
-            let mut mesh = Mesh::new();
-            mesh.add_surface(db.new_mesh_buffer("screen_quad"), material);
-            go.add_component(mesh);
-        }
-
-        // Added a cube in the scene
-        self.cube = world.new_game_object();
-        self.cube.borrow_mut().add_component(Cube::new());
+    if crt {
+        let material = Material::new(db.new_program("crt"));
+        stack = stack.with_effect(PostProcessEffect::Material(Rc::new(material)));
     }
 
-    fn update(&mut self, go: &mut GameObject, world: &mut World) {
-        // Setup fb for camera
-        let cam_borrow = world.current_camera().unwrap();
-        let mut cam = cam_borrow.borrow_mut();
-
-        cam.render_texture = Some(self.rt.clone());
-
-        // Setup proper viewport to render to the whole texture
-        cam.rect = Some(((0, 0), (1024, 1024)));
-
-        // show only cube
-        // TODO it is a little bit hacky, we should support a PostProcessing Component
-        self.cube.borrow_mut().active = true;
-        go.active = false;
-
-        // Render current scene by camera using given frame buffer
-        world.engine_mut().render_pass(&cam, ClearOption::default());
-
-        // show only this crt
-        self.cube.borrow_mut().active = false;
-        go.active = true;
-
-        // Clean up stuffs in camera, as later we could render normally
-        cam.render_texture = None;
-        cam.rect = None;
-    }
+    stack
 }
 
 pub struct Cube {}
@@ -2,7 +2,8 @@ extern crate unrust;
 
 use unrust::actors::{FirstPersonCamera, ShadowPass, SkyBox};
 use unrust::engine::{AssetError, AssetSystem, ComponentBased, Directional, GameObject, Light,
-                     Material, Mesh, ObjMaterial, Point, Prefab, RenderQueue, TextureWrap};
+                     Material, Mesh, ObjMaterial, Point, Prefab, RenderQueue, StaticBatch,
+                     TextureWrap};
 use unrust::math::*;
 use unrust::world::events::*;
 use unrust::world::{Actor, Handle, Processor, World, WorldBuilder};
@@ -307,9 +308,16 @@ impl Actor for WaveObjActor {
         let prefab_handler = {
             let go = go.clone();
             move |r: Result<Prefab, AssetError>| match r {
-                Ok(prefab) => for c in prefab.components {
-                    go.borrow_mut().add_component(c.clone());
-                },
+                Ok(prefab) => {
+                    for c in prefab.components {
+                        go.borrow_mut().add_component(c.clone());
+                    }
+
+                    // The obj groups sharing a material are drawn together
+                    StaticBatch::default()
+                        .build(&mut go.borrow_mut())
+                        .unwrap();
+                }
                 Err(err) => {
                     panic!(format!("Cannot load prefab, reason:{:?}", err));
                 }
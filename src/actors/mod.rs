@@ -1,7 +1,11 @@
 mod skybox;
 mod shadow_pass;
 mod first_person_camera;
+#[cfg(feature = "physics")]
+mod physics;
 
 pub use self::skybox::SkyBox;
-pub use self::shadow_pass::ShadowPass;
+pub use self::shadow_pass::{ShadowAtlas, ShadowFilter, ShadowPass};
 pub use self::first_person_camera::FirstPersonCamera;
+#[cfg(feature = "physics")]
+pub use self::physics::{Collider, ColliderShape, CollisionEvent, Physics, RigidBody};
@@ -0,0 +1,473 @@
+use world::{each_actor, Actor, Handle, IProcessorBuilder, Processor, TypeWatcherBuilder, Watcher,
+            World};
+use engine::{Component, ComponentBased, GameObject, MeshData};
+
+use na;
+use ncollide::shape::{Ball, Capsule, Cuboid, TriMesh};
+use nphysics3d::object::{RigidBody as PhyBody, RigidBodyHandle, WorldObject};
+use nphysics3d::world::World as PhyWorld;
+
+use fnv::{FnvHashMap, FnvHashSet};
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use std::sync::{self, Arc};
+
+use math::*;
+
+/// Large steps make the simulation unstable, so delta time is clamped to it
+const MAX_STEP: f32 = 0.05;
+
+pub enum ColliderShape {
+    /// Box with the given half extents
+    Cuboid(Vector3f),
+    /// Sphere with the given radius
+    Sphere(f32),
+    /// Capsule along the y axis, with half height and radius
+    Capsule(f32, f32),
+    /// Triangle mesh, which is always static
+    Mesh(Vec<Vector3f>, Vec<[usize; 3]>),
+}
+
+/// Collision shape of a game object.
+///
+/// Objects with only a collider are static and follow their transform,
+/// add a `RigidBody` too to let the simulation move them.
+/// Changes after the first physics step are not applied.
+pub struct Collider {
+    pub shape: ColliderShape,
+    pub restitution: f32,
+    pub friction: f32,
+}
+
+impl ComponentBased for Collider {}
+
+impl Collider {
+    pub fn new(shape: ColliderShape) -> Collider {
+        Collider {
+            shape,
+            restitution: 0.3,
+            friction: 0.5,
+        }
+    }
+
+    pub fn cuboid(half_extents: Vector3f) -> Collider {
+        Collider::new(ColliderShape::Cuboid(half_extents))
+    }
+
+    pub fn sphere(radius: f32) -> Collider {
+        Collider::new(ColliderShape::Sphere(radius))
+    }
+
+    pub fn capsule(half_height: f32, radius: f32) -> Collider {
+        Collider::new(ColliderShape::Capsule(half_height, radius))
+    }
+
+    pub fn mesh(data: &MeshData) -> Collider {
+        let vertices = data.vertices
+            .chunks(3)
+            .map(|v| Vector3::new(v[0], v[1], v[2]))
+            .collect();
+
+        let triangles = data.indices
+            .to_u32()
+            .chunks(3)
+            .filter(|t| t.len() == 3)
+            .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+            .collect();
+
+        Collider::new(ColliderShape::Mesh(vertices, triangles))
+    }
+}
+
+/// Makes the collider of the game object dynamic
+pub struct RigidBody {
+    pub density: f32,
+
+    velocity: Vector3f,
+    new_velocity: Option<Vector3f>,
+    impulse: Vector3f,
+}
+
+impl ComponentBased for RigidBody {}
+
+impl RigidBody {
+    pub fn new(density: f32) -> RigidBody {
+        RigidBody {
+            density,
+            velocity: Vector3::zero(),
+            new_velocity: None,
+            impulse: Vector3::zero(),
+        }
+    }
+
+    /// Linear velocity after the last physics step
+    pub fn velocity(&self) -> Vector3f {
+        self.velocity
+    }
+
+    pub fn set_velocity(&mut self, v: Vector3f) {
+        self.new_velocity = Some(v);
+    }
+
+    /// Apply an impulse at the center of mass in the next physics step
+    pub fn apply_impulse(&mut self, impulse: Vector3f) {
+        self.impulse += impulse;
+    }
+}
+
+/// Delivered to the actors of both game objects of a collision
+pub enum CollisionEvent {
+    Started(Handle<GameObject>),
+    Stopped(Handle<GameObject>),
+}
+
+impl CollisionEvent {
+    pub fn other(&self) -> &Handle<GameObject> {
+        match self {
+            &CollisionEvent::Started(ref go) => go,
+            &CollisionEvent::Stopped(ref go) => go,
+        }
+    }
+}
+
+fn to_na_vector(v: Vector3f) -> na::Vector3<f32> {
+    na::Vector3::new(v.x, v.y, v.z)
+}
+
+fn from_na_vector(v: &na::Vector3<f32>) -> Vector3f {
+    Vector3::new(v.x, v.y, v.z)
+}
+
+fn to_na_isometry(iso: &Isometry3<f32>) -> na::Isometry3<f32> {
+    let q = iso.rot;
+
+    na::Isometry3::from_parts(
+        na::Translation3::new(iso.disp.x, iso.disp.y, iso.disp.z),
+        na::UnitQuaternion::from_quaternion(na::Quaternion::new(q.s, q.v.x, q.v.y, q.v.z)),
+    )
+}
+
+fn from_na_isometry(iso: &na::Isometry3<f32>) -> (Quaternion<f32>, Vector3f) {
+    let q = iso.rotation.coords;
+
+    (
+        Quaternion::new(q.w, q.x, q.y, q.z).normalize(),
+        from_na_vector(&iso.translation.vector),
+    )
+}
+
+/// Create a dynamic body if density is given, otherwise a static one
+fn new_body(collider: &Collider, density: Option<f32>) -> PhyBody<f32> {
+    let (restitution, friction) = (collider.restitution, collider.friction);
+
+    macro_rules! body {
+        ($shape:expr) => {
+            match density {
+                Some(density) => PhyBody::new_dynamic($shape, density, restitution, friction),
+                None => PhyBody::new_static($shape, restitution, friction),
+            }
+        };
+    }
+
+    match collider.shape {
+        ColliderShape::Cuboid(half) => body!(Cuboid::new(to_na_vector(half))),
+        ColliderShape::Sphere(radius) => body!(Ball::new(radius)),
+        ColliderShape::Capsule(half_height, radius) => body!(Capsule::new(half_height, radius)),
+        ColliderShape::Mesh(ref vertices, ref triangles) => {
+            let vertices = vertices
+                .iter()
+                .map(|v| na::Point3::new(v.x, v.y, v.z))
+                .collect();
+            let triangles = triangles
+                .iter()
+                .map(|t| na::Point3::new(t[0], t[1], t[2]))
+                .collect();
+
+            let mesh = TriMesh::new(Arc::new(vertices), Arc::new(triangles), None, None);
+            PhyBody::new_static(mesh, restitution, friction)
+        }
+    }
+}
+
+/// Bodies are keyed by the address of their collider component,
+/// `Body::component` tells which component it was
+fn component_key(c: &Arc<Component>) -> usize {
+    &**c as *const Component as *const u8 as usize
+}
+
+fn handle_key(h: &RigidBodyHandle<f32>) -> usize {
+    &**h as *const RefCell<PhyBody<f32>> as usize
+}
+
+struct Body {
+    go: Weak<RefCell<GameObject>>,
+    component: sync::Weak<Component>,
+    handle: RigidBodyHandle<f32>,
+    dynamic: bool,
+}
+
+struct PhysicsState {
+    world: PhyWorld<f32>,
+    // Bodies by their collider component
+    bodies: FnvHashMap<usize, Body>,
+    colliders: FnvHashMap<usize, usize>,
+    contacts: FnvHashSet<(usize, usize)>,
+}
+
+impl PhysicsState {
+    fn new() -> PhysicsState {
+        let mut world = PhyWorld::new();
+        world.set_gravity(na::Vector3::new(0.0, -9.81, 0.0));
+
+        PhysicsState {
+            world,
+            bodies: FnvHashMap::default(),
+            colliders: FnvHashMap::default(),
+            contacts: FnvHashSet::default(),
+        }
+    }
+
+    fn add_body(&mut self, go: &Handle<GameObject>, com: &Arc<Component>) {
+        let key = component_key(com);
+        let collider = com.try_as::<Collider>().unwrap().borrow();
+        let go_ref = go.borrow();
+
+        let density = match collider.shape {
+            ColliderShape::Mesh(..) => None,
+            _ => go_ref.find_component::<RigidBody>().map(|(rb, _)| rb.density),
+        };
+
+        let mut body = new_body(&collider, density);
+        body.set_transformation(to_na_isometry(&go_ref.transform.global()));
+
+        let handle = self.world.add_rigid_body(body);
+        self.colliders.insert(handle_key(&handle), key);
+        self.bodies.insert(
+            key,
+            Body {
+                go: Rc::downgrade(go),
+                component: Arc::downgrade(com),
+                handle,
+                dynamic: density.is_some(),
+            },
+        );
+    }
+
+    /// Sync user changes into the simulation
+    fn sync_body(&self, key: usize, go: &GameObject) {
+        let body = &self.bodies[&key];
+        let mut rb = body.handle.borrow_mut();
+
+        if !body.dynamic {
+            // Static bodies follow their game objects
+            let pose = to_na_isometry(&go.transform.global());
+            if *rb.position() != pose {
+                rb.set_transformation(pose);
+            }
+            return;
+        }
+
+        if let Some((mut c, _)) = go.find_component_mut::<RigidBody>() {
+            if let Some(v) = c.new_velocity.take() {
+                rb.set_lin_vel(to_na_vector(v));
+                rb.activate(1.0);
+            }
+
+            if c.impulse != Vector3::zero() {
+                rb.apply_central_impulse(to_na_vector(c.impulse));
+                rb.activate(1.0);
+                c.impulse = Vector3::zero();
+            }
+        }
+    }
+
+    fn remove_body(&mut self, key: usize) {
+        if let Some(body) = self.bodies.remove(&key) {
+            self.colliders.remove(&handle_key(&body.handle));
+            self.world.remove_rigid_body(&body.handle);
+        }
+    }
+
+    /// Write the simulated poses back to the game objects
+    fn write_back(&self) {
+        for body in self.bodies.values().filter(|b| b.dynamic) {
+            let go = match body.go.upgrade() {
+                Some(go) => go,
+                None => continue,
+            };
+
+            let rb = body.handle.borrow();
+            let mut go = go.borrow_mut();
+
+            if let Some((mut c, _)) = go.find_component_mut::<RigidBody>() {
+                c.velocity = from_na_vector(&rb.lin_vel());
+            }
+
+            let (rot, disp) = from_na_isometry(rb.position());
+            let mut global = go.transform.global();
+            global.rot = rot;
+            global.disp = disp;
+            go.transform.set_global(global);
+        }
+    }
+
+    fn find_contacts(&self) -> FnvHashSet<(usize, usize)> {
+        let mut contacts = FnvHashSet::default();
+
+        for (co1, co2, algorithm) in self.world.collision_world().contact_pairs() {
+            if algorithm.num_contacts() == 0 {
+                continue;
+            }
+
+            if let (&WorldObject::RigidBody(ref rb1), &WorldObject::RigidBody(ref rb2)) =
+                (&co1.data, &co2.data)
+            {
+                let k1 = self.colliders.get(&handle_key(rb1));
+                let k2 = self.colliders.get(&handle_key(rb2));
+
+                if let (Some(&k1), Some(&k2)) = (k1, k2) {
+                    contacts.insert((k1.min(k2), k1.max(k2)));
+                }
+            }
+        }
+
+        contacts
+    }
+
+    fn step(
+        &mut self,
+        objects: &Vec<(Handle<GameObject>, Arc<Component>)>,
+        dt: f32,
+    ) -> Vec<(Handle<GameObject>, CollisionEvent)> {
+        let mut alive = FnvHashSet::default();
+
+        for &(ref go, ref com) in objects.iter() {
+            let key = component_key(com);
+            alive.insert(key);
+
+            // A body made for another component is never reused
+            let known = self.bodies.get(&key).map_or(false, |b| {
+                b.component
+                    .upgrade()
+                    .map_or(false, |c| Arc::ptr_eq(&c, com))
+            });
+            if !known {
+                self.remove_body(key);
+                self.add_body(go, com);
+            }
+
+            self.sync_body(key, &go.borrow());
+        }
+
+        let removed: Vec<usize> = self.bodies
+            .keys()
+            .filter(|k| !alive.contains(k))
+            .cloned()
+            .collect();
+        for key in removed.into_iter() {
+            self.remove_body(key);
+        }
+
+        if dt > 0.0 {
+            self.world.step(dt);
+        }
+
+        self.write_back();
+
+        let contacts = self.find_contacts();
+        let mut events = Vec::new();
+        {
+            let go = |key: &usize| self.bodies.get(key).and_then(|b| b.go.upgrade());
+
+            for &(k1, k2) in contacts.difference(&self.contacts) {
+                if let (Some(go1), Some(go2)) = (go(&k1), go(&k2)) {
+                    events.push((go1.clone(), CollisionEvent::Started(go2.clone())));
+                    events.push((go2, CollisionEvent::Started(go1)));
+                }
+            }
+
+            // Pairs with removed objects are dropped silently
+            for &(k1, k2) in self.contacts.difference(&contacts) {
+                if let (Some(go1), Some(go2)) = (go(&k1), go(&k2)) {
+                    events.push((go1.clone(), CollisionEvent::Stopped(go2.clone())));
+                    events.push((go2, CollisionEvent::Stopped(go1)));
+                }
+            }
+        }
+
+        self.contacts = contacts;
+        events
+    }
+}
+
+struct ColliderWatcher {}
+
+impl Watcher for ColliderWatcher {
+    fn is(&self, c: &Arc<Component>) -> bool {
+        c.try_as::<Collider>().is_some()
+    }
+
+    fn watch_step(&self, objects: &Vec<(Handle<GameObject>, Arc<Component>)>, world: &mut World) {
+        // The simulation is the one of the Physics processor in the world,
+        // not borrowed meanwhile so actors can change it on collisions
+        let state = match world.find_component::<Physics>() {
+            Some(physics) => physics.borrow().state.clone(),
+            None => return,
+        };
+
+        let dt = (world.delta_time() as f32).min(MAX_STEP);
+        let events = state.borrow_mut().step(objects, dt);
+
+        for (go, event) in events.into_iter() {
+            each_actor(&go, |actor| actor.collision_rc(go.clone(), world, &event));
+        }
+    }
+}
+
+struct PhysicsBuilder {}
+
+impl IProcessorBuilder for PhysicsBuilder {
+    fn new_processor(&self) -> Arc<Component> {
+        Component::new(Physics::new())
+    }
+
+    fn register_watchers(&self, builder: TypeWatcherBuilder) -> TypeWatcherBuilder {
+        builder.add_watcher(ColliderWatcher {})
+    }
+}
+
+/// Simulate all game objects with a `Collider`.
+///
+/// Dynamic objects are moved with `Transform::set_global` after each step,
+/// and collisions are delivered to actors with `Actor::collision`.
+///
+/// The processor added to the world owns the simulation,
+/// get it with `World::find_component` to change its settings.
+pub struct Physics {
+    state: Rc<RefCell<PhysicsState>>,
+}
+
+impl Physics {
+    pub fn set_gravity(&self, gravity: Vector3f) {
+        self.state
+            .borrow_mut()
+            .world
+            .set_gravity(to_na_vector(gravity));
+    }
+}
+
+impl Actor for Physics {}
+
+impl ComponentBased for Physics {}
+
+impl Processor for Physics {
+    fn new_builder() -> Box<IProcessorBuilder> {
+        Box::new(PhysicsBuilder {})
+    }
+
+    fn new() -> Physics {
+        Physics {
+            state: Rc::new(RefCell::new(PhysicsState::new())),
+        }
+    }
+}
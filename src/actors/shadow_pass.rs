@@ -1,7 +1,7 @@
 use world::{Actor, Handle, World};
 use engine::{Asset, Camera, ClearOption, Component, ComponentBased, CullMode, GameObject, Light,
-             Material, MaterialParamMap, Mesh, MeshBuffer, MeshData, RenderQueue, RenderTexture,
-             TextureAttachment};
+             Material, MaterialParamMap, Mesh, MeshBuffer, MeshData, Projection, RenderQueue,
+             RenderTexture, ShadowTile, TextureAttachment};
 use engine::mesh_util::*;
 
 use world::Processor;
@@ -20,6 +20,188 @@ struct ShadowMap {
     viewport: ((i32, i32), (u32, u32)),
 }
 
+/// Allocates shadow maps in a square texture, in rows as high as their first map
+pub struct ShadowAtlas {
+    pub size: u32,
+    /// y, height and used width of each row
+    shelves: Vec<(u32, u32, u32)>,
+}
+
+impl ShadowAtlas {
+    pub fn new(size: u32) -> ShadowAtlas {
+        ShadowAtlas {
+            size,
+            shelves: Vec::new(),
+        }
+    }
+
+    /// Free all the maps
+    pub fn clear(&mut self) {
+        self.shelves.clear();
+    }
+
+    /// The offset in texels of a new map, None when it does not fit
+    pub fn allocate(&mut self, w: u32, h: u32) -> Option<(u32, u32)> {
+        if w > self.size || h > self.size {
+            return None;
+        }
+
+        let size = self.size;
+        if let Some(shelf) = self.shelves
+            .iter_mut()
+            .find(|s| s.1 >= h && s.2 + w <= size)
+        {
+            let x = shelf.2;
+            shelf.2 += w;
+            return Some((x, shelf.0));
+        }
+
+        let y = self.shelves.last().map_or(0, |s| s.0 + s.1);
+        if y + h > size {
+            return None;
+        }
+
+        self.shelves.push((y, h, w));
+        Some((0, y))
+    }
+}
+
+/// Direction and up vector of the cube faces of a point light shadow map,
+/// as local_shadows.glsl reads them
+const CUBE_FACES: [([f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, -1.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, 0.0, 1.0], [0.0, -1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, -1.0, 0.0]),
+];
+
+/// Near plane of the point and spot light shadow maps
+const LOCAL_SHADOW_NEAR: f32 = 0.05;
+/// Far plane of the point and spot light shadow maps with an unbounded range
+const LOCAL_SHADOW_MAX_RANGE: f32 = 100.0;
+
+/// Allocate the shadow map of a point or spot light casting shadows in the atlas,
+/// and return the light matrix and viewport of each of its faces
+fn allocate_local_shadow(
+    light: &mut Light,
+    atlas: &mut ShadowAtlas,
+) -> Vec<(Matrix4f, ((i32, i32), (u32, u32)))> {
+    let position = light.world_position().unwrap_or(Vector3::zero());
+    let near = LOCAL_SHADOW_NEAR;
+    let far = light
+        .range()
+        .unwrap_or(0.0)
+        .min(LOCAL_SHADOW_MAX_RANGE)
+        .max(near * 2.0);
+
+    let (shadow, faces, fovy) = match *light {
+        Light::Point(ref mut l) => {
+            let faces: Vec<_> = CUBE_FACES
+                .iter()
+                .map(|&(dir, up)| (Vector3::from(dir), Vector3::from(up)))
+                .collect();
+            (&mut l.shadow, faces, Deg(90.0))
+        }
+        Light::Spot(ref mut l) => {
+            let dir = l.world_space_direction;
+            let up = if dir.normalize().y.abs() > 0.9999 {
+                Vector3::unit_z()
+            } else {
+                Vector3::unit_y()
+            };
+            let fovy = Deg((l.outer_cut_off.0 * 2.0).max(1.0).min(170.0));
+            (&mut l.shadow, vec![(dir, up)], fovy)
+        }
+        Light::Directional(_) => return Vec::new(),
+    };
+
+    shadow.tile = None;
+    if !shadow.casts_shadows {
+        return Vec::new();
+    }
+
+    // Point lights have 3 columns and 2 rows of faces
+    let res = shadow.resolution;
+    let (cols, rows) = if faces.len() > 1 { (3, 2) } else { (1, 1) };
+    let (x, y) = match atlas.allocate(res * cols, res * rows) {
+        Some(offset) => offset,
+        None => return Vec::new(),
+    };
+
+    let proj = Projection::perspective(fovy).matrix(1.0, near, far);
+
+    let result: Vec<_> = faces
+        .iter()
+        .enumerate()
+        .map(|(i, &(dir, up))| {
+            let eye = Point3::from_vec(position);
+            let view = Matrix4::look_at(eye, eye + dir, up);
+            let offset = (x + (i as u32 % cols) * res, y + (i as u32 / cols) * res);
+            (proj * view, ((offset.0 as i32, offset.1 as i32), (res, res)))
+        })
+        .collect();
+
+    let size = atlas.size as f32;
+    shadow.tile = Some(ShadowTile {
+        viewport: Vector4::new(
+            x as f32 / size,
+            y as f32 / size,
+            res as f32 / size,
+            res as f32 / size,
+        ),
+        light_matrix: result[0].0,
+        depth_range: Vector2::new(near, far),
+    });
+
+    result
+}
+
+/// Size of the texture the cascades of the main light are drawn in
+const SHADOW_MAP_SIZE: u32 = 2048;
+
+/// How the shadow map of the main light is sampled, from the cheapest to the softest
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShadowFilter {
+    Hard,
+    Pcf3x3,
+    Pcf5x5,
+    /// 16 samples on a disk of the softness radius
+    PoissonPcf,
+    /// Percentage-closer soft shadows, sharper near the blockers
+    Pcss,
+    /// Variance shadow maps, the depth and squared depth are drawn in a float texture
+    Vsm,
+}
+
+impl Default for ShadowFilter {
+    fn default() -> ShadowFilter {
+        ShadowFilter::Pcf3x3
+    }
+}
+
+/// Viewport of a cascade in the shadow map, a quarter of it when there are several
+fn cascade_viewport(index: usize, count: usize) -> ((i32, i32), (u32, u32)) {
+    if count <= 1 {
+        return ((0, 0), (SHADOW_MAP_SIZE, SHADOW_MAP_SIZE));
+    }
+
+    let half = SHADOW_MAP_SIZE / 2;
+    let x = (index % 2) as u32 * half;
+    let y = (index / 2) as u32 * half;
+    ((x as i32, y as i32), (half, half))
+}
+
+/// Far plane of the cascade i of count, between the uniform and logarithmic splits
+fn split_distance(i: usize, count: usize, lambda: f32, near: f32, far: f32) -> f32 {
+    let t = i as f32 / count as f32;
+    let log = near * (far / near).powf(t);
+    let uniform = near + (far - near) * t;
+
+    lambda * log + (1.0 - lambda) * uniform
+}
+
 pub struct ShadowPass {
     rt: Rc<RenderTexture>,
     shadow_maps: [ShadowMap; 4],
@@ -28,6 +210,23 @@ pub struct ShadowPass {
     shadow_material: Option<Rc<Material>>,
     light_camera: Camera,
 
+    filter: ShadowFilter,
+    softness: f32,
+    /// Moments of the depth, for ShadowFilter::Vsm
+    vsm_rt: Option<Rc<RenderTexture>>,
+    vsm_material: Option<Rc<Material>>,
+
+    cascade_count: usize,
+    split_lambda: Option<f32>,
+    blend_band: f32,
+    /// Near and far planes of the camera the cascades are fit to
+    camera_range: (f32, f32),
+
+    /// Shadow maps of the point and spot lights casting shadows
+    local_rt: Rc<RenderTexture>,
+    local_atlas: ShadowAtlas,
+    local_material: Option<Rc<Material>>,
+
     debug_gameobjects: Vec<Handle<GameObject>>,
     debug_mode: bool,
 
@@ -134,6 +333,7 @@ fn add_debug_frustum(
 // https://developer.nvidia.com/content/depth-precision-visualized
 
 struct LightMatrixContext {
+    proj: Matrix4f,
     inv_pv: Matrix4f,
     view: Matrix4f,
     light_space_scene_aabb: Aabb,
@@ -151,7 +351,7 @@ impl LightMatrixContext {
         let cam_borrow = world.current_camera().unwrap();
         let cam = cam_borrow.borrow();
 
-        let p = cam.perspective(world.engine().screen_size);
+        let p = cam.projection_matrix(world.engine().screen_size);
         let v = cam.v;
         let inv_pv = (p * v).inverse_transform().unwrap();
 
@@ -185,6 +385,7 @@ impl LightMatrixContext {
         Some(LightMatrixContext {
             cam_znear: cam.znear,
             cam_zfar: cam.zfar,
+            proj: p,
             inv_pv,
             view,
             light_space_scene_aabb,
@@ -192,16 +393,10 @@ impl LightMatrixContext {
     }
 }
 
-fn z_to_ndc(z: f32, n: f32, f: f32) -> f32 {
-    // a = -(f+n)/(f-n)
-    // b = 2fn/(f-n)
-    // z_ndc = -a - b / z
-
-    let r = 1.0 / (f - n);
-    let a = -(f + n) * r;
-    let b = 2.0 * f * n * r;
-
-    return -a - (b / z);
+/// The ndc depth of a distance to the camera, for any projection of it
+fn z_to_ndc(proj: &Matrix4f, z: f32) -> f32 {
+    let p = proj * Vector4::new(0.0, 0.0, -z, 1.0);
+    p.z / p.w
 }
 
 fn compute_light_matrix(
@@ -239,9 +434,9 @@ fn compute_light_matrix(
     let (aabb, (nearz, farz)) = if use_scene_aabb {
         (ctx.light_space_scene_aabb, (-1.0, 1.0))
     } else {
-        let nearz = z_to_ndc(z_range.0, ctx.cam_znear, ctx.cam_zfar);
+        let nearz = z_to_ndc(&ctx.proj, z_range.0);
         //let farz = 0.99398189;
-        let farz = z_to_ndc(z_range.1, ctx.cam_znear, ctx.cam_zfar);
+        let farz = z_to_ndc(&ctx.proj, z_range.1);
 
         let corners = [
             bound_m.transform_point(Point3::new(-1.0, -1.0, nearz)),
@@ -295,9 +490,14 @@ fn compute_light_matrix(
     let far = -aabb.min.z;
     let near = -aabb.max.z;
 
-    let proj = ortho(aabb.min.x, aabb.max.x, aabb.min.y, aabb.max.y, near, far);
+    // The orthographic projection is centered, so is the view on the aabb
+    let center = (aabb.min + aabb.max) * 0.5;
+    let half = (aabb.max - aabb.min) * 0.5;
+    let view = Matrix4::from_translation(Vector3::new(-center.x, -center.y, 0.0)) * ctx.view;
+
+    let proj = Projection::orthographic(half.y).matrix(half.x / half.y, near, far);
 
-    return (proj * ctx.view, (nearz, farz));
+    return (proj * view, (nearz, farz));
 }
 
 impl ShadowMap {
@@ -399,8 +599,10 @@ impl ShadowMap {
             shadow_material.set("uShadowMatrix", self.light_matrix);
         }
 
-        // Render current scene by camera using given frame buffer
+        // Render current scene by camera using given frame buffer,
+        // variance shadow maps are cleared to the farthest moments
         let mut clear_option = ClearOption::default();
+        clear_option.color = Some((1.0, 1.0, 1.0, 1.0));
         if !first_render {
             clear_option.clear_depth = false;
             clear_option.clear_color = false;
@@ -418,23 +620,159 @@ impl ComponentBased for ShadowPass {}
 
 impl ShadowPass {
     pub fn disable_cascaded(&mut self) {
+        self.set_cascade_count(1);
         self.shadow_maps[0].partition_z = 1000.0;
-        self.shadow_maps[0].viewport = ((0, 0), (2048, 2048));
 
         self.use_scene_aabb = true;
     }
 
+    /// Far planes of the cascades, used while no split lambda is set
     pub fn set_partitions(&mut self, partitions: &[f32; 4]) {
+        self.split_lambda = None;
         self.shadow_maps[0].partition_z = partitions[0];
         self.shadow_maps[1].partition_z = partitions[1];
         self.shadow_maps[2].partition_z = partitions[2];
         self.shadow_maps[3].partition_z = partitions[3];
     }
 
+    /// Number of cascades of the main light shadow, from 1 to 4
+    pub fn set_cascade_count(&mut self, count: usize) {
+        let count = count.max(1).min(self.shadow_maps.len());
+        self.cascade_count = count;
+
+        for (i, map) in self.shadow_maps.iter_mut().enumerate() {
+            map.viewport = cascade_viewport(i, count);
+        }
+    }
+
+    pub fn cascade_count(&self) -> usize {
+        self.cascade_count
+    }
+
+    /// Split the view of the camera into cascades, from uniform splits at 0
+    /// to logarithmic ones at 1, in place of the partitions
+    pub fn set_split_lambda(&mut self, lambda: f32) {
+        self.split_lambda = Some(lambda.max(0.0).min(1.0));
+    }
+
+    /// Part of each cascade blended with the next one, 0 to disable
+    pub fn set_blend_band(&mut self, band: f32) {
+        self.blend_band = band.max(0.0).min(1.0);
+    }
+
+    pub fn set_filter(&mut self, filter: ShadowFilter) {
+        self.filter = filter;
+
+        let rt = match filter {
+            ShadowFilter::Vsm => self.vsm_rt
+                .get_or_insert_with(|| {
+                    Rc::new(RenderTexture::new_with_depth(
+                        SHADOW_MAP_SIZE,
+                        SHADOW_MAP_SIZE,
+                        TextureAttachment::Color0Float,
+                    ))
+                })
+                .clone(),
+            _ => self.rt.clone(),
+        };
+
+        for map in self.shadow_maps.iter_mut() {
+            map.rt = rt.clone();
+        }
+    }
+
+    pub fn filter(&self) -> ShadowFilter {
+        self.filter
+    }
+
+    /// Radius in texels of ShadowFilter::PoissonPcf, and size of the light for
+    /// ShadowFilter::Pcss
+    pub fn set_softness(&mut self, texels: f32) {
+        self.softness = texels;
+    }
+
+    /// The texture the cascades are drawn in for the current filter
+    fn shadow_rt(&self) -> &Rc<RenderTexture> {
+        match self.vsm_rt {
+            Some(ref rt) if self.filter == ShadowFilter::Vsm => rt,
+            _ => &self.rt,
+        }
+    }
+
+    /// Size of the texture the point and spot light shadow maps are allocated in,
+    /// 2048 by default. The maps which do not fit are not drawn.
+    pub fn set_local_atlas_size(&mut self, size: u32) {
+        self.local_rt = Rc::new(RenderTexture::new(size, size, TextureAttachment::Depth));
+        self.local_atlas = ShadowAtlas::new(size);
+    }
+
+    /// Draw the shadow maps of the point and spot lights casting shadows
+    fn render_local_shadows(&mut self, world: &mut World) {
+        let material = match self.local_material {
+            Some(ref m) => m.clone(),
+            None => return,
+        };
+
+        self.local_atlas.clear();
+        let mut first_render = true;
+
+        let lights = world.engine().find_local_lights();
+        for light_com in lights {
+            // The light is not borrowed while rendering, the engine updates it
+            let faces = {
+                let mut light = light_com.try_as::<Light>().unwrap().borrow_mut();
+                allocate_local_shadow(&mut light, &mut self.local_atlas)
+            };
+
+            for (light_matrix, viewport) in faces {
+                material.set("uShadowMatrix", light_matrix);
+                self.light_camera.render_texture = Some(self.local_rt.clone());
+                self.light_camera.rect = Some(viewport);
+
+                // The whole atlas is cleared before its first map
+                let mut clear_option = ClearOption::default();
+                if !first_render {
+                    clear_option.clear_depth = false;
+                    clear_option.clear_color = false;
+                }
+                first_render = false;
+
+                world.engine_mut().render_pass_with_material(
+                    &self.light_camera,
+                    Some(&material),
+                    clear_option,
+                );
+            }
+        }
+    }
+
     fn apply(&self, material: &Material) {
+        let rt = self.shadow_rt();
+        let cascade_count = if self.use_scene_aabb {
+            1
+        } else {
+            self.cascade_count
+        };
+
         material.set("uShadowEnabled", true);
-        material.set("uShadowMapTexture", self.rt.as_texture());
+        material.set(
+            "uShadowMapTexture",
+            rt.depth_texture().unwrap_or_else(|| rt.as_texture()),
+        );
+        material.set("uShadowMomentsTexture", rt.as_texture());
         material.set("ShadowMapParams", self.material_params.clone());
+
+        material.set("uShadowFilter", self.filter as u32 as f32);
+        material.set("uShadowSoftness", self.softness);
+        material.set("uShadowCascadeCount", cascade_count as f32);
+        material.set("uShadowBlendBand", self.blend_band);
+        material.set(
+            "uShadowCameraRange",
+            Vector2::new(self.camera_range.0, self.camera_range.1),
+        );
+
+        material.set("uLocalShadowMap", self.local_rt.as_texture());
+        material.set("uLocalShadowTexelSize", 1.0 / self.local_atlas.size as f32);
     }
 }
 
@@ -445,6 +783,12 @@ impl Actor for ShadowPass {
         let shadow_mat = Material::new(db.new_program("unrust/shadow"));
         self.shadow_material = Some(Rc::new(shadow_mat));
 
+        let vsm_mat = Material::new(db.new_program("unrust/shadow_vsm"));
+        self.vsm_material = Some(Rc::new(vsm_mat));
+
+        let local_mat = Material::new(db.new_program("unrust/local_shadow"));
+        self.local_material = Some(Rc::new(local_mat));
+
         // Setup proper viewport to render to the whole texture
         self.light_camera.enable_frustum_culling = false;
         self.light_camera.included_render_queues = Some(Default::default());
@@ -474,6 +818,9 @@ impl Actor for ShadowPass {
             }
         }
 
+        // Scenes lit only by point or spot lights have shadows too
+        self.render_local_shadows(world);
+
         // update light
         let main_light = match world.engine().find_main_light() {
             Some(l) => l,
@@ -484,7 +831,14 @@ impl Actor for ShadowPass {
 
         let ctx = LightMatrixContext::new(&self.light_camera, &main_light, world);
 
+        let material = match self.filter {
+            ShadowFilter::Vsm => self.vsm_material.clone(),
+            _ => self.shadow_material.clone(),
+        }.unwrap();
+
         if let Some(ctx) = ctx {
+            self.camera_range = (ctx.cam_znear, ctx.cam_zfar);
+
             if self.use_scene_aabb {
                 let near = self.light_camera.znear;
 
@@ -492,7 +846,7 @@ impl Actor for ShadowPass {
                     world,
                     &mut self.light_camera,
                     &ctx,
-                    &self.shadow_material.as_ref().unwrap(),
+                    &material,
                     near,
                     true,
                     true,
@@ -507,14 +861,22 @@ impl Actor for ShadowPass {
                 self.shadow_maps[2].light_space_range = (1.0, 1.0);
                 self.shadow_maps[3].light_space_range = (1.0, 1.0);
             } else {
+                let count = self.cascade_count;
+                if let Some(lambda) = self.split_lambda {
+                    for (i, map) in self.shadow_maps.iter_mut().take(count).enumerate() {
+                        map.partition_z =
+                            split_distance(i + 1, count, lambda, ctx.cam_znear, ctx.cam_zfar);
+                    }
+                }
+
                 let mut last_partition_z = self.light_camera.znear;
 
-                for (i, map) in self.shadow_maps.iter_mut().enumerate() {
+                for (i, map) in self.shadow_maps.iter_mut().take(count).enumerate() {
                     map.render(
                         world,
                         &mut self.light_camera,
                         &ctx,
-                        &self.shadow_material.as_ref().unwrap(),
+                        &material,
                         last_partition_z,
                         i == 0,
                         false,
@@ -527,6 +889,11 @@ impl Actor for ShadowPass {
 
                     last_partition_z = map.partition_z;
                 }
+
+                // The unused cascades are never selected
+                for map in self.shadow_maps.iter_mut().skip(count) {
+                    map.light_space_range = (1.0, 1.0);
+                }
             }
         }
 
@@ -543,7 +910,7 @@ impl Actor for ShadowPass {
 
         imgui::pivot((0.0, 1.0));
         let mut mat = Material::new(world.asset_system().new_program("unrust/shadow_display"));
-        mat.set("uDepthMap", self.rt.as_texture());
+        mat.set("uDepthMap", self.shadow_rt().as_texture());
         mat.render_queue = RenderQueue::UI;
 
         imgui::image_with_material(Native(0.0, 1.0), Pixel(100.0, 100.0), Rc::new(mat));
@@ -552,13 +919,18 @@ impl Actor for ShadowPass {
 
 impl Processor for ShadowPass {
     fn new() -> ShadowPass {
-        let texture_size = 2048;
+        let texture_size = SHADOW_MAP_SIZE;
         let texture_size2 = texture_size / 2;
         let rt = Rc::new(RenderTexture::new(
             texture_size,
             texture_size,
             TextureAttachment::Depth,
         ));
+        let local_rt = Rc::new(RenderTexture::new(
+            texture_size,
+            texture_size,
+            TextureAttachment::Depth,
+        ));
 
         ShadowPass {
             rt: rt.clone(),
@@ -603,6 +975,17 @@ impl Processor for ShadowPass {
             ],
             shadow_material: None,
             light_camera: Camera::new(),
+            filter: ShadowFilter::default(),
+            softness: 2.0,
+            vsm_rt: None,
+            vsm_material: None,
+            cascade_count: 4,
+            split_lambda: None,
+            blend_band: 0.0,
+            camera_range: (0.3, 1000.0),
+            local_rt,
+            local_atlas: ShadowAtlas::new(texture_size),
+            local_material: None,
             debug_gameobjects: Vec::new(),
             debug_mode: false,
         }
@@ -0,0 +1,78 @@
+use super::AnimationClip;
+use engine::core::ComponentBased;
+
+/// Plays animation clips, usually on the joints of a `Skeleton`.
+///
+/// The world advances all animators every frame.
+pub struct Animator {
+    pub clips: Vec<AnimationClip>,
+    pub speed: f32,
+    pub looping: bool,
+
+    current: Option<usize>,
+    time: f32,
+}
+
+impl ComponentBased for Animator {}
+
+impl Animator {
+    pub fn new() -> Animator {
+        Animator {
+            clips: Vec::new(),
+            speed: 1.0,
+            looping: true,
+            current: None,
+            time: 0.0,
+        }
+    }
+
+    pub fn add_clip(&mut self, clip: AnimationClip) {
+        self.clips.push(clip);
+    }
+
+    /// Play the clip from the start, return false if no clip is named so
+    pub fn play(&mut self, name: &str) -> bool {
+        self.current = self.clips.iter().position(|c| c.name == name);
+        self.time = 0.0;
+
+        self.current.is_some()
+    }
+
+    pub fn stop(&mut self) {
+        self.current = None;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.current.is_some()
+    }
+
+    pub fn current_clip(&self) -> Option<&AnimationClip> {
+        self.current.map(|i| &self.clips[i])
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Advance the current clip by dt seconds and pose its targets
+    pub fn advance(&mut self, dt: f32) {
+        let clip = match self.current {
+            Some(i) => &self.clips[i],
+            None => return,
+        };
+
+        let duration = clip.duration();
+        self.time += dt * self.speed;
+
+        if self.time < 0.0 || self.time >= duration {
+            if self.looping && duration > 0.0 {
+                self.time = (self.time % duration + duration) % duration;
+            } else {
+                self.time = self.time.max(0.0).min(duration);
+                self.current = None;
+            }
+        }
+
+        clip.sample(self.time);
+    }
+}
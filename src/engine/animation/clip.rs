@@ -0,0 +1,152 @@
+use engine::asset::{AssetError, AssetResult};
+use engine::core::GameObject;
+use math::*;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::rc::{Rc, Weak};
+
+/// Values of a track, one per key time
+pub enum Keyframes {
+    Translation(Vec<Vector3f>),
+    Rotation(Vec<Quaternion<f32>>),
+    Scale(Vec<Vector3f>),
+}
+
+impl Keyframes {
+    fn len(&self) -> usize {
+        match self {
+            &Keyframes::Translation(ref v) => v.len(),
+            &Keyframes::Rotation(ref v) => v.len(),
+            &Keyframes::Scale(ref v) => v.len(),
+        }
+    }
+}
+
+/// Linearly interpolated keyframes driving the local transform of one node
+pub struct AnimationTrack {
+    pub target: Weak<RefCell<GameObject>>,
+    pub times: Vec<f32>,
+    pub keyframes: Keyframes,
+}
+
+impl AnimationTrack {
+    /// Fails if there is not a keyframe per key time
+    pub fn new(
+        target: &Rc<RefCell<GameObject>>,
+        times: Vec<f32>,
+        keyframes: Keyframes,
+    ) -> AssetResult<AnimationTrack> {
+        if times.len() != keyframes.len() {
+            return Err(AssetError::InvalidFormat {
+                path: "<animation>".to_owned(),
+                len: times.len(),
+                reason: format!(
+                    "{} key times for {} keyframes",
+                    times.len(),
+                    keyframes.len()
+                ),
+            });
+        }
+
+        Ok(AnimationTrack {
+            target: Rc::downgrade(target),
+            times,
+            keyframes,
+        })
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.times.last().cloned().unwrap_or(0.0)
+    }
+
+    /// Keyframe indices around time t and the blend factor between them
+    fn find(&self, t: f32) -> (usize, usize, f32) {
+        let last = self.times.len() - 1;
+
+        if t <= self.times[0] {
+            return (0, 0, 0.0);
+        }
+        if t >= self.times[last] {
+            return (last, last, 0.0);
+        }
+
+        let next = match self.times
+            .binary_search_by(|k| k.partial_cmp(&t).unwrap_or(Ordering::Equal))
+        {
+            Ok(i) => return (i, i, 0.0),
+            Err(i) => i,
+        };
+
+        let prev = next - 1;
+        let span = self.times[next] - self.times[prev];
+        (prev, next, (t - self.times[prev]) / span)
+    }
+
+    /// Apply the track at time t to its target, unless the target is borrowed
+    pub fn sample(&self, t: f32) {
+        if self.times.is_empty() {
+            return;
+        }
+
+        let target = match self.target.upgrade() {
+            Some(target) => target,
+            None => return,
+        };
+        let mut go = match target.try_borrow_mut() {
+            Ok(go) => go,
+            Err(_) => return,
+        };
+        let (a, b, s) = self.find(t);
+
+        match self.keyframes {
+            Keyframes::Translation(ref v) => {
+                let mut local = go.transform.local();
+                local.disp = v[a].lerp(v[b], s);
+                go.transform.set_local(local);
+            }
+            Keyframes::Rotation(ref v) => {
+                // Take the shortest path
+                let to = if v[a].dot(v[b]) < 0.0 { -v[b] } else { v[b] };
+
+                let mut local = go.transform.local();
+                local.rot = v[a].nlerp(to, s);
+                go.transform.set_local(local);
+            }
+            Keyframes::Scale(ref v) => {
+                go.transform.set_local_scale(v[a].lerp(v[b], s));
+            }
+        }
+    }
+}
+
+pub struct AnimationClip {
+    pub name: String,
+    pub tracks: Vec<AnimationTrack>,
+}
+
+impl AnimationClip {
+    pub fn new(name: &str) -> AnimationClip {
+        AnimationClip {
+            name: name.to_string(),
+            tracks: Vec::new(),
+        }
+    }
+
+    pub fn add_track(&mut self, track: AnimationTrack) {
+        self.tracks.push(track);
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.tracks
+            .iter()
+            .map(|t| t.duration())
+            .fold(0.0, |a: f32, b| a.max(b))
+    }
+
+    /// Pose all targets at time t
+    pub fn sample(&self, t: f32) {
+        for track in self.tracks.iter() {
+            track.sample(t);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use math::*;
+
+/// Values which could be blended between two keys of a curve
+pub trait Interpolate: Copy {
+    fn interpolate(a: Self, b: Self, s: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(a: f32, b: f32, s: f32) -> f32 {
+        a + (b - a) * s
+    }
+}
+
+impl Interpolate for Vector3<f32> {
+    fn interpolate(a: Vector3<f32>, b: Vector3<f32>, s: f32) -> Vector3<f32> {
+        a.lerp(b, s)
+    }
+}
+
+impl Interpolate for Vector4<f32> {
+    fn interpolate(a: Vector4<f32>, b: Vector4<f32>, s: f32) -> Vector4<f32> {
+        a.lerp(b, s)
+    }
+}
+
+impl Interpolate for Quaternion<f32> {
+    fn interpolate(a: Quaternion<f32>, b: Quaternion<f32>, s: f32) -> Quaternion<f32> {
+        // Take the shortest path
+        let b = if a.dot(b) < 0.0 { -b } else { b };
+        a.nlerp(b, s)
+    }
+}
+
+/// How a key blends into the next one
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    /// Hold the value until the next key
+    Step,
+    /// Css style easing with control points (x1, y1) and (x2, y2)
+    CubicBezier(f32, f32, f32, f32),
+}
+
+fn bezier(p1: f32, p2: f32, s: f32) -> f32 {
+    let inv = 1.0 - s;
+    3.0 * inv * inv * s * p1 + 3.0 * inv * s * s * p2 + s * s * s
+}
+
+impl Interpolation {
+    /// Map the linear progress u between two keys to the blend factor
+    pub fn ease(&self, u: f32) -> f32 {
+        match *self {
+            Interpolation::Linear => u,
+            Interpolation::Step => 0.0,
+            Interpolation::CubicBezier(x1, y1, x2, y2) => {
+                // x is monotonic when both control points are in [0, 1],
+                // so solve x(s) = u by bisection
+                let (x1, x2) = (x1.max(0.0).min(1.0), x2.max(0.0).min(1.0));
+                let (mut lo, mut hi) = (0.0, 1.0);
+
+                for _ in 0..24 {
+                    let mid = (lo + hi) * 0.5;
+                    if bezier(x1, x2, mid) < u {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+
+                bezier(y1, y2, (lo + hi) * 0.5)
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct CurveKey<T> {
+    pub time: f32,
+    pub value: T,
+    pub interpolation: Interpolation,
+}
+
+/// Keys sorted by time, evaluated with the interpolation of the earlier key
+#[derive(Clone, Debug)]
+pub struct Curve<T> {
+    keys: Vec<CurveKey<T>>,
+}
+
+impl<T> Curve<T>
+where
+    T: Interpolate,
+{
+    pub fn new() -> Curve<T> {
+        Curve { keys: Vec::new() }
+    }
+
+    /// Curve linearly interpolated between the given (time, value) pairs
+    pub fn linear(points: &[(f32, T)]) -> Curve<T> {
+        let mut curve = Curve::new();
+        for &(time, value) in points.iter() {
+            curve.add_key(time, value, Interpolation::Linear);
+        }
+
+        curve
+    }
+
+    pub fn add_key(&mut self, time: f32, value: T, interpolation: Interpolation) {
+        let i = self.keys
+            .iter()
+            .position(|k| k.time > time)
+            .unwrap_or(self.keys.len());
+
+        self.keys.insert(
+            i,
+            CurveKey {
+                time,
+                value,
+                interpolation,
+            },
+        );
+    }
+
+    pub fn keys(&self) -> &[CurveKey<T>] {
+        &self.keys
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map(|k| k.time).unwrap_or(0.0)
+    }
+
+    /// Value at time t, clamped to the first and last keys
+    pub fn evaluate(&self, t: f32) -> Option<T> {
+        let first = self.keys.first()?;
+        let last = self.keys.last()?;
+
+        if t <= first.time {
+            return Some(first.value);
+        }
+        if t >= last.time {
+            return Some(last.value);
+        }
+
+        let next = self.keys.iter().position(|k| k.time > t).unwrap();
+        let (a, b) = (&self.keys[next - 1], &self.keys[next]);
+        let u = (t - a.time) / (b.time - a.time);
+
+        Some(T::interpolate(a.value, b.value, a.interpolation.ease(u)))
+    }
+}
@@ -0,0 +1,9 @@
+mod clip;
+mod animator;
+mod curve;
+mod tween;
+
+pub use self::clip::{AnimationClip, AnimationTrack, Keyframes};
+pub use self::animator::Animator;
+pub use self::curve::{Curve, CurveKey, Interpolate, Interpolation};
+pub use self::tween::{Animation, AnimationChannel, WrapMode};
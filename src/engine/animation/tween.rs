@@ -0,0 +1,174 @@
+use super::Curve;
+use engine::core::{ComponentBased, GameObject};
+use engine::render::Material;
+use math::*;
+use std::borrow::Cow;
+use std::rc::Rc;
+
+/// A property of the owning game object driven by a curve
+pub enum AnimationChannel {
+    Position(Curve<Vector3f>),
+    Rotation(Curve<Quaternion<f32>>),
+    Scale(Curve<Vector3f>),
+    Float(Rc<Material>, Cow<'static, str>, Curve<f32>),
+    Vec3(Rc<Material>, Cow<'static, str>, Curve<Vector3f>),
+    Vec4(Rc<Material>, Cow<'static, str>, Curve<Vector4<f32>>),
+}
+
+impl AnimationChannel {
+    fn duration(&self) -> f32 {
+        match *self {
+            AnimationChannel::Position(ref c) => c.duration(),
+            AnimationChannel::Rotation(ref c) => c.duration(),
+            AnimationChannel::Scale(ref c) => c.duration(),
+            AnimationChannel::Float(_, _, ref c) => c.duration(),
+            AnimationChannel::Vec3(_, _, ref c) => c.duration(),
+            AnimationChannel::Vec4(_, _, ref c) => c.duration(),
+        }
+    }
+
+    fn apply(&self, go: &mut GameObject, t: f32) {
+        match *self {
+            AnimationChannel::Position(ref c) => if let Some(v) = c.evaluate(t) {
+                let mut local = go.transform.local();
+                local.disp = v;
+                go.transform.set_local(local);
+            },
+            AnimationChannel::Rotation(ref c) => if let Some(v) = c.evaluate(t) {
+                let mut local = go.transform.local();
+                local.rot = v;
+                go.transform.set_local(local);
+            },
+            AnimationChannel::Scale(ref c) => if let Some(v) = c.evaluate(t) {
+                go.transform.set_local_scale(v);
+            },
+            AnimationChannel::Float(ref m, ref name, ref c) => if let Some(v) = c.evaluate(t) {
+                m.set(name.clone(), v);
+            },
+            AnimationChannel::Vec3(ref m, ref name, ref c) => if let Some(v) = c.evaluate(t) {
+                m.set(name.clone(), v);
+            },
+            AnimationChannel::Vec4(ref m, ref name, ref c) => if let Some(v) = c.evaluate(t) {
+                m.set(name.clone(), v);
+            },
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WrapMode {
+    /// Play once and stop at the end
+    Once,
+    Loop,
+    /// Play forward then backward, forever
+    PingPong,
+}
+
+/// Tween the transform and material params of the owning game object.
+///
+/// The world advances all animations every frame, and notifies the actors
+/// of the game object with `Actor::animation_finished` when a one-shot
+/// animation reaches its end.
+pub struct Animation {
+    pub name: String,
+    pub channels: Vec<AnimationChannel>,
+    pub wrap_mode: WrapMode,
+    pub speed: f32,
+
+    playing: bool,
+    time: f32,
+}
+
+impl ComponentBased for Animation {}
+
+impl Animation {
+    pub fn new(name: &str) -> Animation {
+        Animation {
+            name: name.to_string(),
+            channels: Vec::new(),
+            wrap_mode: WrapMode::Once,
+            speed: 1.0,
+            playing: false,
+            time: 0.0,
+        }
+    }
+
+    pub fn add_channel(&mut self, channel: AnimationChannel) {
+        self.channels.push(channel);
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.channels
+            .iter()
+            .map(|c| c.duration())
+            .fold(0.0, |a: f32, b| a.max(b))
+    }
+
+    /// Play from the start
+    pub fn play(&mut self) {
+        self.playing = true;
+        self.time = 0.0;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Time since start, ping-pong animations count the way back too
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Curve time for the current play time
+    fn curve_time(&self) -> f32 {
+        let duration = self.duration();
+
+        match self.wrap_mode {
+            WrapMode::PingPong if self.time > duration => 2.0 * duration - self.time,
+            _ => self.time,
+        }
+    }
+
+    /// Apply all channels at the current time
+    pub fn sample(&self, go: &mut GameObject) {
+        let t = self.curve_time();
+
+        for channel in self.channels.iter() {
+            channel.apply(go, t);
+        }
+    }
+
+    /// Advance by dt seconds and apply the channels to go,
+    /// return true if a one-shot animation finished in this step.
+    pub fn advance(&mut self, dt: f32, go: &mut GameObject) -> bool {
+        if !self.playing {
+            return false;
+        }
+
+        let duration = self.duration();
+        let period = match self.wrap_mode {
+            WrapMode::PingPong => 2.0 * duration,
+            _ => duration,
+        };
+
+        self.time += dt * self.speed;
+
+        let mut finished = false;
+        if self.time < 0.0 || self.time >= period {
+            if self.wrap_mode != WrapMode::Once && period > 0.0 {
+                self.time = (self.time % period + period) % period;
+            } else {
+                self.time = self.time.max(0.0).min(period);
+                self.playing = false;
+                finished = true;
+            }
+        }
+
+        self.sample(go);
+        finished
+    }
+}
@@ -8,8 +8,8 @@ use engine::asset::fs;
 use engine::asset::loader;
 use engine::asset::Resource;
 
-use engine::{Material, MeshBuffer, ShaderFs, ShaderProgram, ShaderVs, Texture, TextureFiltering,
-             TextureImage};
+use engine::{Material, MeshBuffer, ShaderFs, ShaderProgram, ShaderVs, SpriteAtlas, Texture,
+             TextureFiltering, TextureImage};
 use std::fmt::Debug;
 use std::ops::Deref;
 use futures::{Async, Future};
@@ -49,8 +49,27 @@ pub trait AssetSystem {
 
     fn new_mesh_buffer(&self, name: &str) -> Rc<MeshBuffer>;
 
+    /// Load a prefab, the meshes of the glTF nodes are merged into its components
     fn new_prefab(&self, name: &str, mh: MaterialHandler, f: PrefabHandler);
 
+    /// Load a prefab keeping the glTF nodes in Prefab::children,
+    /// World::load_prefab creates a game object for each of them
+    fn new_prefab_nodes(&self, name: &str, mh: MaterialHandler, f: PrefabHandler);
+
+    fn new_sprite_atlas(&self, name: &str) -> Rc<SpriteAtlas>;
+
+    /// Lookup the name of a loaded program, None if it is not owned by the system
+    fn program_name(&self, program: &Rc<ShaderProgram>) -> Option<String>;
+
+    /// Lookup the name of a loaded texture, None if it is not owned by the system
+    fn texture_name(&self, texture: &Rc<Texture>) -> Option<String>;
+
+    /// Lookup the name of a loaded mesh buffer, None if it is not owned by the system
+    fn mesh_buffer_name(&self, buffer: &Rc<MeshBuffer>) -> Option<String>;
+
+    /// Add a texture which is not loaded by name, e.g. an image embedded in a model file
+    fn register_texture(&self, name: &str, texture: Rc<Texture>);
+
     fn reset(&mut self);
 
     fn step(&mut self);
@@ -98,6 +117,7 @@ pub struct AssetDatabaseContext<FS> {
     textures: RefCell<HashMap<String, Rc<Texture>>>,
     mesh_buffers: RefCell<HashMap<String, Rc<MeshBuffer>>>,
     programs: RefCell<HashMap<String, Rc<ShaderProgram>>>,
+    sprite_atlases: RefCell<HashMap<String, Rc<SpriteAtlas>>>,
 
     pending_prefabs: RefCell<Vec<(PrefabHandler, PrefabFuture)>>,
     pending_tasks: RefCell<Vec<AssetTask>>,
@@ -146,7 +166,9 @@ where
 
     fn new_program(&self, name: &str) -> Rc<ShaderProgram> {
         let mut a = self.programs.borrow_mut();
-        self.new_asset(&mut a, name)
+        let program = self.new_asset(&mut a, name);
+        program.set_name(name);
+        program
     }
 
     fn new_texture(&self, name: &str) -> Rc<Texture> {
@@ -159,16 +181,50 @@ where
         self.new_asset(&mut a, name)
     }
 
+    fn new_sprite_atlas(&self, name: &str) -> Rc<SpriteAtlas> {
+        let mut a = self.sprite_atlases.borrow_mut();
+        self.new_asset(&mut a, name)
+    }
+
+    fn program_name(&self, program: &Rc<ShaderProgram>) -> Option<String> {
+        Self::asset_name(&self.programs.borrow(), program)
+    }
+
+    fn texture_name(&self, texture: &Rc<Texture>) -> Option<String> {
+        Self::asset_name(&self.textures.borrow(), texture)
+    }
+
+    fn mesh_buffer_name(&self, buffer: &Rc<MeshBuffer>) -> Option<String> {
+        Self::asset_name(&self.mesh_buffers.borrow(), buffer)
+    }
+
+    fn register_texture(&self, name: &str, texture: Rc<Texture>) {
+        self.textures.borrow_mut().insert(name.into(), texture);
+    }
+
     fn reset(&mut self) {
         self.textures.borrow_mut().clear();
         self.mesh_buffers.borrow_mut().clear();
         self.programs.borrow_mut().clear();
+        self.sprite_atlases.borrow_mut().clear();
 
         self.setup();
     }
 
     fn new_prefab(&self, name: &str, mh: MaterialHandler, f: PrefabHandler) {
-        let prefab = loader::Prefab::load_future(self.clone(), self.new_file(name), mh);
+        let prefab = self.prefab_future(name, mh);
+
+        // The callers only add the components of the prefab
+        let prefab: PrefabFuture = if loader::GltfLoader::accept(name) {
+            Box::new(prefab.and_then(|p| p.flatten()))
+        } else {
+            prefab
+        };
+        self.pending_prefabs.borrow_mut().push((f, prefab));
+    }
+
+    fn new_prefab_nodes(&self, name: &str, mh: MaterialHandler, f: PrefabHandler) {
+        let prefab = self.prefab_future(name, mh);
         self.pending_prefabs.borrow_mut().push((f, prefab));
     }
 
@@ -184,6 +240,7 @@ where
                 textures: RefCell::new(HashMap::new()),
                 mesh_buffers: RefCell::new(HashMap::new()),
                 programs: RefCell::new(HashMap::new()),
+                sprite_atlases: RefCell::new(HashMap::new()),
                 pending_prefabs: RefCell::new(Vec::new()),
                 pending_tasks: RefCell::new(Vec::new()),
             }),
@@ -263,6 +320,23 @@ where
         }
     }
 
+    fn prefab_future(&self, name: &str, mh: MaterialHandler) -> PrefabFuture {
+        let file = self.new_file(name);
+        if loader::GltfLoader::accept(name) {
+            loader::GltfLoader::load_future(self.clone(), file, mh)
+        } else if loader::TiledLoader::accept(name) {
+            loader::TiledLoader::load_future(self.clone(), file)
+        } else {
+            loader::Prefab::load_future(self.clone(), file, mh)
+        }
+    }
+
+    fn asset_name<R>(hm: &HashMap<String, Rc<R>>, asset: &Rc<R>) -> Option<String> {
+        hm.iter()
+            .find(|&(_, a)| Rc::ptr_eq(a, asset))
+            .map(|(name, _)| name.clone())
+    }
+
     fn setup(&mut self) {
         {
             let mut hm = self.mesh_buffers.borrow_mut();
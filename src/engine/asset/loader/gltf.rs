@@ -0,0 +1,869 @@
+// glTF 2.0 loader, supports .gltf files (with external or embedded buffers) and .glb files.
+//
+// Nodes are loaded as a hierarchy of PrefabNode, meshes as Mesh components and
+// metallic-roughness materials are passed to the MaterialBuilder as ObjMaterial.
+// Cameras, skins and animations are ignored.
+
+use engine::asset::{Asset, AssetError, AssetResult, AssetSystem, File, FileFuture, FileIoError,
+                    LoadableAsset, Resource};
+use engine::render::{Material, Mesh, MeshBuffer, MeshData, MeshIndices, Texture, TextureFiltering,
+                     TextureWrap};
+use engine::render::mesh_util::compute_vertex_tangents;
+use engine::core::Component;
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::json::Json;
+use std::collections::HashMap;
+use std::rc::Rc;
+use math::*;
+
+use futures::prelude::*;
+use futures::future;
+
+use super::prefab::{parent_path, MaterialBuilder, ObjMaterial, Prefab, PrefabNode};
+
+const GLB_MAGIC: &'static [u8] = b"glTF";
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+const GLB_CHUNK_BIN: u32 = 0x004E_4942;
+
+// Accessor component types
+const BYTE: u64 = 5120;
+const UNSIGNED_BYTE: u64 = 5121;
+const SHORT: u64 = 5122;
+const UNSIGNED_SHORT: u64 = 5123;
+const UNSIGNED_INT: u64 = 5125;
+const FLOAT: u64 = 5126;
+
+// Primitive modes
+const TRIANGLES: u64 = 4;
+const TRIANGLE_STRIP: u64 = 5;
+const TRIANGLE_FAN: u64 = 6;
+
+// Sampler parameters
+const NEAREST: u64 = 9728;
+const NEAREST_MIPMAP_NEAREST: u64 = 9984;
+const NEAREST_MIPMAP_LINEAR: u64 = 9986;
+const CLAMP_TO_EDGE: u64 = 33071;
+const MIRRORED_REPEAT: u64 = 33648;
+
+const MAX_NODE_DEPTH: usize = 256;
+
+pub struct GltfLoader {}
+
+type BufferFuture = Box<Future<Item = Vec<u8>, Error = AssetError>>;
+
+fn invalid<T>(path: &str, reason: String) -> AssetResult<T> {
+    Err(AssetError::InvalidFormat {
+        path: path.to_owned(),
+        len: 0,
+        reason,
+    })
+}
+
+fn read_u16(b: &[u8], offset: usize) -> u16 {
+    (b[offset] as u16) | ((b[offset + 1] as u16) << 8)
+}
+
+fn read_u32(b: &[u8], offset: usize) -> u32 {
+    (b[offset] as u32) | ((b[offset + 1] as u32) << 8) | ((b[offset + 2] as u32) << 16)
+        | ((b[offset + 3] as u32) << 24)
+}
+
+fn get_u64(v: &Json, key: &str) -> Option<u64> {
+    v.find(key).and_then(|x| x.as_u64())
+}
+
+fn get_usize(v: &Json, key: &str) -> Option<usize> {
+    get_u64(v, key).map(|x| x as usize)
+}
+
+fn get_f32(v: &Json, key: &str) -> Option<f32> {
+    v.find(key).and_then(|x| x.as_f64()).map(|x| x as f32)
+}
+
+fn get_str<'a>(v: &'a Json, key: &str) -> Option<&'a str> {
+    v.find(key).and_then(|x| x.as_string())
+}
+
+fn get_array<'a>(v: &'a Json, key: &str) -> &'a [Json] {
+    match v.find(key).and_then(|x| x.as_array()) {
+        Some(a) => a,
+        None => &[],
+    }
+}
+
+fn get_floats(v: &Json, key: &str, len: usize) -> Option<Vec<f32>> {
+    let arr = get_array(v, key);
+    if arr.len() != len {
+        return None;
+    }
+
+    arr.iter().map(|x| x.as_f64().map(|f| f as f32)).collect()
+}
+
+fn hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0'...b'9' => Some(c - b'0'),
+        b'a'...b'f' => Some(c - b'a' + 10),
+        b'A'...b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Uris in glTF are percent encoded
+fn decode_uri(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(h), Some(l)) = (hex_digit(bytes[i + 1]), hex_digit(bytes[i + 2])) {
+                out.push(h * 16 + l);
+                i += 3;
+                continue;
+            }
+        }
+
+        out.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8(out).unwrap_or(uri.to_owned())
+}
+
+fn decode_data_uri(path: &str, uri: &str) -> AssetResult<Vec<u8>> {
+    const BASE64_TAG: &'static str = ";base64,";
+
+    match uri.find(BASE64_TAG) {
+        Some(i) => match uri[i + BASE64_TAG.len()..].from_base64() {
+            Ok(data) => Ok(data),
+            Err(e) => invalid(path, format!("invalid base64 data uri: {}", e)),
+        },
+        None => invalid(path, "only base64 data uri is supported".to_owned()),
+    }
+}
+
+/// Convert a triangle strip or fan to a triangle list
+fn triangle_list(mode: u64, indices: Vec<u32>) -> Vec<u32> {
+    let n = indices.len();
+    if mode == TRIANGLES || n < 3 {
+        return indices;
+    }
+
+    let mut list = Vec::with_capacity((n - 2) * 3);
+    for i in 0..n - 2 {
+        match mode {
+            TRIANGLE_STRIP if i % 2 == 1 => {
+                list.extend_from_slice(&[indices[i + 1], indices[i], indices[i + 2]])
+            }
+            TRIANGLE_STRIP => list.extend_from_slice(&indices[i..i + 3]),
+            _ => list.extend_from_slice(&[indices[0], indices[i + 1], indices[i + 2]]),
+        }
+    }
+
+    list
+}
+
+fn roughness_to_shininess(roughness: f32) -> f32 {
+    let a = (roughness * roughness).max(0.01);
+    (2.0 / (a * a) - 2.0).max(1.0).min(512.0)
+}
+
+fn node_transform(node: &Json) -> (Isometry3<f32>, Vector3f) {
+    if let Some(m) = get_floats(node, "matrix", 16) {
+        let m = Matrix4::new(
+            m[0], m[1], m[2], m[3], m[4], m[5], m[6], m[7], m[8], m[9], m[10], m[11], m[12], m[13],
+            m[14], m[15],
+        );
+
+        let (x, y, z) = (m.x.truncate(), m.y.truncate(), m.z.truncate());
+        let mut scale = Vector3::new(x.magnitude(), y.magnitude(), z.magnitude());
+        // A negative determinant means the matrix is mirrored
+        if Matrix3::from_cols(x, y, z).determinant() < 0.0 {
+            scale.x = -scale.x;
+        }
+
+        let safe = |s: f32| if s.abs() < 0.000001 { 1.0 } else { s };
+        let rot = Matrix3::from_cols(x / safe(scale.x), y / safe(scale.y), z / safe(scale.z));
+
+        let transform = Isometry3 {
+            scale: 1.0,
+            rot: Quaternion::from(rot).normalize(),
+            disp: m.w.truncate(),
+        };
+        return (transform, scale);
+    }
+
+    let t = get_floats(node, "translation", 3).unwrap_or(vec![0.0, 0.0, 0.0]);
+    let r = get_floats(node, "rotation", 4).unwrap_or(vec![0.0, 0.0, 0.0, 1.0]);
+    let s = get_floats(node, "scale", 3).unwrap_or(vec![1.0, 1.0, 1.0]);
+
+    let transform = Isometry3 {
+        scale: 1.0,
+        rot: Quaternion::new(r[3], r[0], r[1], r[2]),
+        disp: Vector3::new(t[0], t[1], t[2]),
+    };
+    (transform, Vector3::new(s[0], s[1], s[2]))
+}
+
+/// A file already in memory, e.g. an image embedded in a glb file
+struct MemoryFile {
+    name: String,
+    data: Option<Vec<u8>>,
+}
+
+impl File for MemoryFile {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn read_binary(&mut self) -> Result<Vec<u8>, FileIoError> {
+        self.data
+            .take()
+            .ok_or_else(|| FileIoError::Unknown(format!("{} is already read", self.name)))
+    }
+}
+
+struct Document {
+    path: String,
+    parent: String,
+    json: Json,
+    bin: Option<Vec<u8>>,
+}
+
+impl Document {
+    fn parse(path: String, bytes: Vec<u8>) -> AssetResult<Document> {
+        let len = bytes.len();
+        let (json_bytes, bin) = if bytes.starts_with(GLB_MAGIC) {
+            Self::read_glb(&path, &bytes)?
+        } else {
+            (bytes, None)
+        };
+
+        let json = match String::from_utf8(json_bytes) {
+            Ok(s) => Json::from_str(&s).map_err(|e| format!("{}", e)),
+            Err(e) => Err(format!("{}", e)),
+        };
+        let json = match json {
+            Ok(json) => json,
+            Err(reason) => return Err(AssetError::InvalidFormat { path, len, reason }),
+        };
+
+        let version = json.find("asset").and_then(|a| get_str(a, "version"));
+        match version {
+            Some(v) if v.starts_with("2.") => (),
+            _ => {
+                return invalid(
+                    &path,
+                    format!("unsupported glTF version {}", version.unwrap_or("unknown")),
+                )
+            }
+        }
+
+        Ok(Document {
+            parent: parent_path(&path),
+            path,
+            json,
+            bin,
+        })
+    }
+
+    fn read_glb(path: &str, b: &[u8]) -> AssetResult<(Vec<u8>, Option<Vec<u8>>)> {
+        if b.len() < 20 {
+            return invalid(path, "glb file is too small".to_owned());
+        }
+        if read_u32(b, 4) != 2 {
+            return invalid(path, format!("unsupported glb version {}", read_u32(b, 4)));
+        }
+
+        let total = (read_u32(b, 8) as usize).min(b.len());
+        let mut json = None;
+        let mut bin = None;
+
+        let mut offset = 12;
+        while offset + 8 <= total {
+            let chunk_len = read_u32(b, offset) as usize;
+            let chunk_type = read_u32(b, offset + 4);
+            let start = offset + 8;
+            let end = start + chunk_len;
+            if end > total {
+                return invalid(path, format!("glb chunk at {} is truncated", offset));
+            }
+
+            match chunk_type {
+                GLB_CHUNK_JSON if json.is_none() => json = Some(b[start..end].to_vec()),
+                GLB_CHUNK_BIN if bin.is_none() => bin = Some(b[start..end].to_vec()),
+                _ => (),
+            }
+
+            offset = end;
+        }
+
+        match json {
+            Some(json) => Ok((json, bin)),
+            None => invalid(path, "glb file without json chunk".to_owned()),
+        }
+    }
+
+    fn invalid<T>(&self, reason: String) -> AssetResult<T> {
+        invalid(&self.path, reason)
+    }
+
+    fn item(&self, kind: &str, index: usize) -> AssetResult<&Json> {
+        match get_array(&self.json, kind).get(index) {
+            Some(v) => Ok(v),
+            None => self.invalid(format!("{} {} not found", kind, index)),
+        }
+    }
+
+    fn load_buffers<A>(&mut self, asys: &A) -> AssetResult<Vec<BufferFuture>>
+    where
+        A: AssetSystem,
+    {
+        let uris: Vec<Option<String>> = get_array(&self.json, "buffers")
+            .iter()
+            .map(|b| get_str(b, "uri").map(|s| s.to_owned()))
+            .collect();
+
+        let mut buffers: Vec<BufferFuture> = Vec::new();
+        for (i, uri) in uris.into_iter().enumerate() {
+            let f: BufferFuture = match uri {
+                // The first buffer of a glb file without uri is the binary chunk
+                None => match self.bin.take() {
+                    Some(bin) if i == 0 => Box::new(future::ok(bin)),
+                    _ => return self.invalid(format!("buffer {} has no data", i)),
+                },
+                Some(ref uri) if uri.starts_with("data:") => {
+                    Box::new(future::ok(decode_data_uri(&self.path, uri)?))
+                }
+                Some(uri) => {
+                    let name = self.parent.clone() + &decode_uri(&uri);
+                    Box::new(asys.new_file(&name).then(
+                        |r: Result<Box<File>, FileIoError>| -> AssetResult<Vec<u8>> {
+                            let mut f = r.map_err(AssetError::FileIoError)?;
+                            f.read_binary().map_err(AssetError::FileIoError)
+                        },
+                    ))
+                }
+            };
+
+            buffers.push(f);
+        }
+
+        Ok(buffers)
+    }
+}
+
+type Surfaces = Vec<(Rc<MeshBuffer>, Rc<Material>)>;
+
+struct PrefabBuilder<A>
+where
+    A: AssetSystem + Clone + 'static,
+{
+    asys: A,
+    doc: Document,
+    buffers: Vec<Vec<u8>>,
+    material_builder: MaterialBuilder,
+
+    materials: HashMap<Option<usize>, (Rc<Material>, bool)>,
+    meshes: HashMap<usize, Surfaces>,
+    textures: HashMap<usize, String>,
+}
+
+impl<A> PrefabBuilder<A>
+where
+    A: AssetSystem + Clone + 'static,
+{
+    fn build(mut self) -> AssetResult<Prefab> {
+        let mut children = Vec::new();
+        for n in self.root_nodes()? {
+            children.push(self.build_node(n, 0)?);
+        }
+
+        Ok(Prefab {
+            components: Vec::new(),
+            children,
+        })
+    }
+
+    fn root_nodes(&self) -> AssetResult<Vec<usize>> {
+        let json = &self.doc.json;
+
+        if !get_array(json, "scenes").is_empty() {
+            let scene = self.doc.item("scenes", get_usize(json, "scene").unwrap_or(0))?;
+            return Ok(get_array(scene, "nodes")
+                .iter()
+                .filter_map(|n| n.as_u64())
+                .map(|n| n as usize)
+                .collect());
+        }
+
+        // Without scenes, every node which is not a child is a root
+        let nodes = get_array(json, "nodes");
+        let mut is_child = vec![false; nodes.len()];
+        for n in nodes.iter() {
+            for c in get_array(n, "children").iter().filter_map(|c| c.as_u64()) {
+                if let Some(flag) = is_child.get_mut(c as usize) {
+                    *flag = true;
+                }
+            }
+        }
+
+        Ok((0..nodes.len()).filter(|i| !is_child[*i]).collect())
+    }
+
+    fn build_node(&mut self, index: usize, depth: usize) -> AssetResult<PrefabNode> {
+        if depth > MAX_NODE_DEPTH {
+            return self.doc
+                .invalid(format!("node {} is too deep, the hierarchy is cyclic?", index));
+        }
+
+        let node = self.doc.item("nodes", index)?.clone();
+        let (transform, scale) = node_transform(&node);
+
+        let mut components = Vec::new();
+        if let Some(mesh_index) = get_usize(&node, "mesh") {
+            let surfaces = self.mesh(mesh_index)?;
+            if !surfaces.is_empty() {
+                let mut mesh = Mesh::new();
+                for (buffer, material) in surfaces.into_iter() {
+                    mesh.add_surface(buffer, material);
+                }
+                components.push(Component::new(mesh));
+            }
+        }
+
+        let mut children = Vec::new();
+        for c in get_array(&node, "children").iter() {
+            match c.as_u64() {
+                Some(c) => children.push(self.build_node(c as usize, depth + 1)?),
+                None => return self.doc.invalid(format!("invalid child {} in node {}", c, index)),
+            }
+        }
+
+        Ok(PrefabNode {
+            transform,
+            scale,
+            components,
+            children,
+        })
+    }
+
+    fn mesh(&mut self, index: usize) -> AssetResult<Surfaces> {
+        if let Some(surfaces) = self.meshes.get(&index) {
+            return Ok(surfaces.clone());
+        }
+
+        let mesh = self.doc.item("meshes", index)?.clone();
+        let mut surfaces = Vec::new();
+
+        for prim in get_array(&mesh, "primitives").iter() {
+            let (material, has_normal_map) = self.material(get_usize(prim, "material"))?;
+
+            if let Some(mesh_data) = self.primitive(prim, has_normal_map)? {
+                surfaces.push((
+                    MeshBuffer::new_from_resource(Resource::new(mesh_data)),
+                    material,
+                ));
+            }
+        }
+
+        self.meshes.insert(index, surfaces.clone());
+        Ok(surfaces)
+    }
+
+    /// Read a primitive as an indexed triangle list,
+    /// None if it is not made of triangles.
+    fn primitive(&self, prim: &Json, with_normal_map: bool) -> AssetResult<Option<MeshData>> {
+        let mode = get_u64(prim, "mode").unwrap_or(TRIANGLES);
+        if mode != TRIANGLES && mode != TRIANGLE_STRIP && mode != TRIANGLE_FAN {
+            return Ok(None);
+        }
+
+        let attributes = match prim.find("attributes") {
+            Some(a) => a,
+            None => return self.doc.invalid("primitive without attributes".to_owned()),
+        };
+        let vertices = match get_usize(attributes, "POSITION") {
+            Some(a) => self.read_accessor(a, "VEC3", 3)?,
+            None => return Ok(None),
+        };
+
+        let count = vertices.len() / 3;
+
+        let normals = self.read_attribute(attributes, "NORMAL", "VEC3", 3, count)?;
+        let uvs = self.read_attribute(attributes, "TEXCOORD_0", "VEC2", 2, count)?;
+        let tangents = self.read_attribute(attributes, "TANGENT", "VEC4", 4, count)?;
+
+        let indices = match get_usize(prim, "indices") {
+            Some(a) => self.read_indices(a)?,
+            None => (0..count as u32).collect(),
+        };
+        if let Some(i) = indices.iter().find(|i| **i as usize >= count) {
+            return self.doc
+                .invalid(format!("index {} is out of range ({} vertices)", i, count));
+        }
+        let indices = MeshIndices::with_vertex_count(triangle_list(mode, indices), count);
+
+        let (tangents, bitangents) = match (tangents, &normals, &uvs) {
+            (Some(t), &Some(ref n), _) => {
+                let mut tangents = Vec::with_capacity(count * 3);
+                let mut bitangents = Vec::with_capacity(count * 3);
+
+                for i in 0..count {
+                    let tangent = Vector3::new(t[i * 4], t[i * 4 + 1], t[i * 4 + 2]);
+                    let normal = Vector3::new(n[i * 3], n[i * 3 + 1], n[i * 3 + 2]);
+                    // w is the handedness of the tangent space
+                    let bitangent = normal.cross(tangent) * t[i * 4 + 3];
+
+                    tangents.extend_from_slice(&tangent[..]);
+                    bitangents.extend_from_slice(&bitangent[..]);
+                }
+
+                (Some(tangents), Some(bitangents))
+            }
+            (None, &Some(ref n), &Some(ref uv)) if with_normal_map => {
+                let (t, b) = compute_vertex_tangents(&vertices, uv, n, &indices);
+                (Some(t), Some(b))
+            }
+            _ => (None, None),
+        };
+
+        Ok(Some(MeshData {
+            indices,
+            vertices,
+            uvs,
+            normals,
+            tangents,
+            bitangents,
+            joints: None,
+            weights: None,
+            colors: None,
+        }))
+    }
+
+    fn read_attribute(
+        &self,
+        attributes: &Json,
+        name: &str,
+        ty: &str,
+        ncomp: usize,
+        count: usize,
+    ) -> AssetResult<Option<Vec<f32>>> {
+        let index = match get_usize(attributes, name) {
+            Some(i) => i,
+            None => return Ok(None),
+        };
+
+        let values = self.read_accessor(index, ty, ncomp)?;
+        if values.len() != count * ncomp {
+            return self.doc
+                .invalid(format!("attribute {} count does not match POSITION", name));
+        }
+
+        Ok(Some(values))
+    }
+
+    fn read_accessor(&self, index: usize, ty: &str, ncomp: usize) -> AssetResult<Vec<f32>> {
+        let acc = self.doc.item("accessors", index)?;
+        if get_str(acc, "type") != Some(ty) {
+            return self.doc
+                .invalid(format!("accessor {} should be of type {}", index, ty));
+        }
+
+        Ok(self.read_values(index, ncomp)?
+            .into_iter()
+            .map(|v| v as f32)
+            .collect())
+    }
+
+    fn read_indices(&self, index: usize) -> AssetResult<Vec<u32>> {
+        let acc = self.doc.item("accessors", index)?;
+        match (get_str(acc, "type"), get_u64(acc, "componentType")) {
+            (Some("SCALAR"), Some(UNSIGNED_BYTE))
+            | (Some("SCALAR"), Some(UNSIGNED_SHORT))
+            | (Some("SCALAR"), Some(UNSIGNED_INT)) => (),
+            _ => return self.doc.invalid(format!("invalid index accessor {}", index)),
+        }
+
+        Ok(self.read_values(index, 1)?
+            .into_iter()
+            .map(|v| v as u32)
+            .collect())
+    }
+
+    /// Read all components of an accessor, normalized integers are converted to [0, 1] or [-1, 1]
+    fn read_values(&self, index: usize, ncomp: usize) -> AssetResult<Vec<f64>> {
+        let acc = self.doc.item("accessors", index)?;
+        if acc.find("sparse").is_some() {
+            return self.doc
+                .invalid(format!("accessor {} is sparse, which is not supported", index));
+        }
+
+        let count = get_usize(acc, "count").unwrap_or(0);
+        let ctype = get_u64(acc, "componentType").unwrap_or(0);
+        let csize = match ctype {
+            BYTE | UNSIGNED_BYTE => 1,
+            SHORT | UNSIGNED_SHORT => 2,
+            UNSIGNED_INT | FLOAT => 4,
+            _ => {
+                return self.doc
+                    .invalid(format!("accessor {} has unknown component type {}", index, ctype))
+            }
+        };
+        let normalized = acc.find("normalized")
+            .and_then(|n| n.as_boolean())
+            .unwrap_or(false);
+
+        // An accessor without buffer view is all zeros
+        let view_index = match get_usize(acc, "bufferView") {
+            Some(v) => v,
+            None => return Ok(vec![0.0; count * ncomp]),
+        };
+        let view = self.doc.item("bufferViews", view_index)?;
+        let buffer = match get_usize(view, "buffer").and_then(|b| self.buffers.get(b)) {
+            Some(b) => b,
+            None => return self.doc.invalid(format!("buffer view {} has no buffer", view_index)),
+        };
+
+        let view_offset = get_usize(view, "byteOffset").unwrap_or(0);
+        let view_end = view_offset + get_usize(view, "byteLength").unwrap_or(0);
+        let offset = view_offset + get_usize(acc, "byteOffset").unwrap_or(0);
+        let elem_size = csize * ncomp;
+        let stride = get_usize(view, "byteStride").unwrap_or(elem_size).max(elem_size);
+
+        if count > 0 {
+            let end = offset + stride * (count - 1) + elem_size;
+            if end > view_end || end > buffer.len() {
+                return self.doc
+                    .invalid(format!("accessor {} is out of buffer bounds", index));
+            }
+        }
+
+        let mut values = Vec::with_capacity(count * ncomp);
+        for i in 0..count {
+            for c in 0..ncomp {
+                let p = offset + i * stride + c * csize;
+                let v = match ctype {
+                    BYTE if normalized => (buffer[p] as i8 as f64 / 127.0).max(-1.0),
+                    BYTE => buffer[p] as i8 as f64,
+                    UNSIGNED_BYTE if normalized => buffer[p] as f64 / 255.0,
+                    UNSIGNED_BYTE => buffer[p] as f64,
+                    SHORT if normalized => (read_u16(buffer, p) as i16 as f64 / 32767.0).max(-1.0),
+                    SHORT => read_u16(buffer, p) as i16 as f64,
+                    UNSIGNED_SHORT if normalized => read_u16(buffer, p) as f64 / 65535.0,
+                    UNSIGNED_SHORT => read_u16(buffer, p) as f64,
+                    UNSIGNED_INT => read_u32(buffer, p) as f64,
+                    _ => f32::from_bits(read_u32(buffer, p)) as f64,
+                };
+                values.push(v);
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Return the material and whether it has a normal map,
+    /// primitives without material use the default one.
+    fn material(&mut self, index: Option<usize>) -> AssetResult<(Rc<Material>, bool)> {
+        if let Some(m) = self.materials.get(&index) {
+            return Ok(m.clone());
+        }
+
+        let obj_mat = match index {
+            Some(i) => self.obj_material(i)?,
+            None => ObjMaterial::default(),
+        };
+        let has_normal_map = obj_mat.normal_map.is_some();
+        let material = (*self.material_builder)(&self.asys, obj_mat);
+
+        self.materials
+            .insert(index, (material.clone(), has_normal_map));
+        Ok((material, has_normal_map))
+    }
+
+    fn obj_material(&mut self, index: usize) -> AssetResult<ObjMaterial> {
+        let mat = self.doc.item("materials", index)?.clone();
+        let pbr = mat.find("pbrMetallicRoughness").cloned();
+        let pbr = pbr.unwrap_or(Json::Object(Default::default()));
+
+        let base = get_floats(&pbr, "baseColorFactor", 4).unwrap_or(vec![1.0, 1.0, 1.0, 1.0]);
+        let base_color = Vector3::new(base[0], base[1], base[2]);
+        let metallic = get_f32(&pbr, "metallicFactor").unwrap_or(1.0);
+        let roughness = get_f32(&pbr, "roughnessFactor").unwrap_or(1.0);
+
+        let mut m = ObjMaterial::default();
+        m.diffuse = Some(base_color);
+        // Approximation for phong materials: dielectrics reflect 4% of the light,
+        // metals reflect with their base color
+        let dielectric = Vector3::new(0.04, 0.04, 0.04);
+        m.specular = Some(dielectric + (base_color - dielectric) * metallic);
+        m.shininess = Some(roughness_to_shininess(roughness));
+        m.metallic = Some(metallic);
+        m.roughness = Some(roughness);
+        m.emissive = get_floats(&mat, "emissiveFactor", 3).map(|e| Vector3::new(e[0], e[1], e[2]));
+        m.double_sided = Some(
+            mat.find("doubleSided")
+                .and_then(|d| d.as_boolean())
+                .unwrap_or(false),
+        );
+
+        m.diffuse_map = self.texture(pbr.find("baseColorTexture"))?;
+        m.metallic_roughness_map = self.texture(pbr.find("metallicRoughnessTexture"))?;
+        m.normal_map = self.texture(mat.find("normalTexture"))?;
+        m.occlusion_map = self.texture(mat.find("occlusionTexture"))?;
+        m.emissive_map = self.texture(mat.find("emissiveTexture"))?;
+
+        match get_str(&mat, "alphaMode").unwrap_or("OPAQUE") {
+            "BLEND" => {
+                m.alpha = Some(base[3]);
+                m.alpha_mask = m.diffuse_map.clone();
+            }
+            "MASK" => {
+                m.alpha_cutoff = Some(get_f32(&mat, "alphaCutoff").unwrap_or(0.5));
+                m.alpha_mask = m.diffuse_map.clone();
+            }
+            _ => (),
+        }
+
+        Ok(m)
+    }
+
+    /// Return the asset name of a texture from a textureInfo object
+    fn texture(&mut self, info: Option<&Json>) -> AssetResult<Option<String>> {
+        let index = match info.and_then(|t| get_usize(t, "index")) {
+            Some(i) => i,
+            None => return Ok(None),
+        };
+        if let Some(name) = self.textures.get(&index) {
+            return Ok(Some(name.clone()));
+        }
+
+        let tex = self.doc.item("textures", index)?.clone();
+        let source = match get_usize(&tex, "source") {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+
+        let image = self.doc.item("images", source)?.clone();
+        let name = match get_str(&image, "uri") {
+            Some(uri) if !uri.starts_with("data:") => self.doc.parent.clone() + &decode_uri(uri),
+            Some(uri) => {
+                let data = decode_data_uri(&self.doc.path, uri)?;
+                self.embedded_texture(source, data)
+            }
+            None => match get_usize(&image, "bufferView") {
+                Some(view) => {
+                    let data = self.buffer_view(view)?;
+                    self.embedded_texture(source, data)
+                }
+                None => return self.doc.invalid(format!("image {} has no data", source)),
+            },
+        };
+
+        let texture = self.asys.new_texture(&name);
+        let (wrap_s, wrap_t, min_filter) = match get_usize(&tex, "sampler") {
+            Some(s) => {
+                let sampler = self.doc.item("samplers", s)?;
+                (
+                    get_u64(sampler, "wrapS"),
+                    get_u64(sampler, "wrapT"),
+                    get_u64(sampler, "minFilter"),
+                )
+            }
+            None => (None, None, None),
+        };
+
+        let wrap = |w| match w {
+            Some(CLAMP_TO_EDGE) => TextureWrap::ClampToEdge,
+            Some(MIRRORED_REPEAT) => TextureWrap::MirroredRepeat,
+            _ => TextureWrap::Repeat,
+        };
+        texture.wrap_u.set(wrap(wrap_s));
+        texture.wrap_v.set(wrap(wrap_t));
+
+        match min_filter {
+            Some(NEAREST) | Some(NEAREST_MIPMAP_NEAREST) | Some(NEAREST_MIPMAP_LINEAR) => {
+                texture.filtering.set(TextureFiltering::Nearest)
+            }
+            _ => texture.filtering.set(TextureFiltering::Linear),
+        }
+
+        self.textures.insert(index, name.clone());
+        Ok(Some(name))
+    }
+
+    fn buffer_view(&self, index: usize) -> AssetResult<Vec<u8>> {
+        let view = self.doc.item("bufferViews", index)?;
+        let buffer = match get_usize(view, "buffer").and_then(|b| self.buffers.get(b)) {
+            Some(b) => b,
+            None => return self.doc.invalid(format!("buffer view {} has no buffer", index)),
+        };
+
+        let start = get_usize(view, "byteOffset").unwrap_or(0);
+        let end = start + get_usize(view, "byteLength").unwrap_or(0);
+        if end > buffer.len() {
+            return self.doc
+                .invalid(format!("buffer view {} is out of buffer bounds", index));
+        }
+
+        Ok(buffer[start..end].to_vec())
+    }
+
+    /// Register an image embedded in the document as a texture asset
+    fn embedded_texture(&self, image: usize, data: Vec<u8>) -> String {
+        let name = format!("{}#image{}", self.doc.path, image);
+
+        let file: Box<File> = Box::new(MemoryFile {
+            name: name.clone(),
+            data: Some(data),
+        });
+        let texture = Texture::new(<Texture as LoadableAsset>::load(
+            &self.asys,
+            vec![Box::new(future::ok(file))],
+        ));
+        self.asys.register_texture(&name, texture);
+
+        name
+    }
+}
+
+impl GltfLoader {
+    /// Return true if the file should be loaded as a glTF file
+    pub fn accept(name: &str) -> bool {
+        let name = name.to_lowercase();
+        name.ends_with(".gltf") || name.ends_with(".glb")
+    }
+
+    pub fn load_future<A>(
+        asys: A,
+        file: FileFuture,
+        builder: MaterialBuilder,
+    ) -> Box<Future<Item = Prefab, Error = AssetError>>
+    where
+        A: AssetSystem + Clone + 'static,
+    {
+        let doc = {
+            let asys = asys.clone();
+            file.map_err(AssetError::FileIoError).and_then(move |mut f| {
+                let bytes = f.read_binary().map_err(AssetError::FileIoError)?;
+                let mut doc = Document::parse(f.name(), bytes)?;
+                let buffers = future::join_all(doc.load_buffers(&asys)?);
+
+                Ok(buffers.map(move |buffers| (doc, buffers)))
+            })
+        };
+
+        Box::new(doc.flatten().and_then(move |(doc, buffers)| {
+            PrefabBuilder {
+                asys,
+                doc,
+                buffers,
+                material_builder: builder,
+                materials: HashMap::new(),
+                meshes: HashMap::new(),
+                textures: HashMap::new(),
+            }.build()
+        }))
+    }
+}
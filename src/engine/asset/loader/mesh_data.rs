@@ -1,6 +1,6 @@
 use engine::asset::loader::{Loadable, Loader};
 use engine::asset::{AssetError, AssetResult, File};
-use engine::render::MeshData;
+use engine::render::{MeshData, MeshIndices};
 
 use obj;
 use obj::SimplePolygon;
@@ -34,25 +34,28 @@ impl Loader<MeshData> for MeshDataLoader {
             .flat_map(|s| s.to_vec().into_iter())
             .collect();
 
-        let mut indices: Vec<u16> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
 
         for o in model.objects {
             for g in o.groups {
                 for poly in g.polys {
                     for index_tuple in poly {
-                        indices.push(index_tuple.0 as u16);
+                        indices.push(index_tuple.0 as u32);
                     }
                 }
             }
         }
 
         Ok(MeshData {
-            indices,
+            indices: MeshIndices::with_vertex_count(indices, vertices.len() / 3),
             vertices,
             uvs: Some(uvs),
             normals: Some(normals),
             tangents: None,
             bitangents: None,
+            joints: None,
+            weights: None,
+            colors: None,
         })
     }
 }
@@ -3,10 +3,17 @@ mod image;
 mod shader;
 mod mesh_data;
 mod prefab;
+mod gltf;
 mod dds;
+mod sprite_atlas;
+mod xml;
+mod tiled;
 
 pub use self::loader::{Loadable, Loader};
 pub use self::image::ImageLoader;
 pub use self::shader::{ShaderFSLoader, ShaderVSLoader};
-pub use self::prefab::{ObjMaterial, Prefab, PrefabLoader};
+pub use self::prefab::{ObjMaterial, Prefab, PrefabLoader, PrefabNode};
+pub use self::gltf::GltfLoader;
 pub use self::dds::DDS;
+pub use self::sprite_atlas::SpriteAtlasLoader;
+pub use self::tiled::TiledLoader;
@@ -1,5 +1,7 @@
-use engine::asset::{Asset, AssetError, AssetSystem, FileFuture, Resource};
-use engine::render::{Material, Mesh, MeshBuffer, MeshData};
+use engine::asset::{Asset, AssetError, AssetResult, AssetSystem, FileFuture, Resource};
+use engine::render::{Material, Mesh, MeshBuffer, MeshData, MeshIndices, MeshSurface,
+                     StaticBatch};
+use engine::render::mesh_util::{compute_vertex_tangents, triangulate_polygon};
 use engine::core::Component;
 use std::sync::Arc;
 use std::borrow::Cow;
@@ -18,9 +20,40 @@ use futures::future::*;
 pub struct Prefab {
     // TODO what is a prefab actually ??
     pub components: Vec<Arc<Component>>,
+
+    /// Child nodes of the prefab, only hierarchical formats (e.g. glTF) have them
+    pub children: Vec<PrefabNode>,
+}
+
+pub struct PrefabNode {
+    pub transform: Isometry3<f32>,
+    pub scale: Vector3f,
+    pub components: Vec<Arc<Component>>,
+    pub children: Vec<PrefabNode>,
+}
+
+/// The surfaces of the meshes of the nodes, placed by the node transforms
+fn collect_surfaces(
+    nodes: &[PrefabNode],
+    parent: Matrix4f,
+    parts: &mut Vec<(Matrix4f, Rc<MeshSurface>)>,
+) {
+    for node in nodes.iter() {
+        let local: Matrix4f = node.transform.into();
+        let m = parent * local
+            * Matrix4::from_nonuniform_scale(node.scale.x, node.scale.y, node.scale.z);
+
+        for c in node.components.iter() {
+            if let Some(mesh) = c.try_as::<Mesh>() {
+                parts.extend(mesh.borrow().surfaces.iter().map(|s| (m, s.clone())));
+            }
+        }
+
+        collect_surfaces(&node.children, m, parts);
+    }
 }
 
-fn parent_path(filename: &str) -> String {
+pub fn parent_path(filename: &str) -> String {
     let path = Path::new(filename);
     let parent = path.parent();
     let mut parent = parent
@@ -46,26 +79,11 @@ fn from_slice_v3(i: usize, s: &[f32]) -> Vector3f {
     return Vector3::new(s[i * 3 + 0], s[i * 3 + 1], s[i * 3 + 2]);
 }
 
-#[inline]
-fn from_slice_v2(i: usize, s: &[f32]) -> Vector2f {
-    return Vector2::new(s[i * 2 + 0], s[i * 2 + 1]);
-}
-
-trait CloseToZero {
-    fn close_to_zero(&self) -> bool;
-}
-
-impl CloseToZero for f32 {
-    fn close_to_zero(&self) -> bool {
-        self.abs() < 0.0001
-    }
-}
-
 fn compute_tangents(
     v_array: &Vec<f32>,
     uv_array: &Option<Vec<f32>>,
     n_array: &Option<Vec<f32>>,
-    indices: &Vec<u16>,
+    indices: &MeshIndices,
 ) -> TangentSpace {
     if uv_array.is_none() || n_array.is_none() {
         return TangentSpace {
@@ -74,95 +92,65 @@ fn compute_tangents(
         };
     }
 
-    let uv_array = uv_array.as_ref().unwrap();
-    let n_array = n_array.as_ref().unwrap();
+    // Vertices are welded, so the tangents of all triangles sharing one are averaged
+    let (tangents, bitangents) = compute_vertex_tangents(
+        v_array,
+        uv_array.as_ref().unwrap(),
+        n_array.as_ref().unwrap(),
+        indices,
+    );
 
-    assert!(v_array.len() % 9 == 0);
-    assert!(uv_array.len() % 6 == 0);
-    assert!(n_array.len() % 9 == 0);
-    assert!(indices.len() % 3 == 0);
-
-    let mut tangents = Vec::with_capacity(v_array.len());
-    let mut bittangents = Vec::with_capacity(v_array.len());
-
-    let mut i = 0;
-    while i < indices.len() {
-        let pos1 = from_slice_v3(i, &v_array);
-        let pos2 = from_slice_v3(i + 1, &v_array);
-        let pos3 = from_slice_v3(i + 2, &v_array);
-
-        let uv1 = from_slice_v2(i, &uv_array);
-        let uv2 = from_slice_v2(i + 1, &uv_array);
-        let uv3 = from_slice_v2(i + 2, &uv_array);
-
-        let edge1 = pos2 - pos1;
-        let edge2 = pos3 - pos1;
-        let delta_uv1 = uv2 - uv1;
-        let delta_uv2 = uv3 - uv1;
+    return TangentSpace {
+        tangents: Some(tangents),
+        bitangents: Some(bitangents),
+    };
+}
 
-        let d = delta_uv1.x * delta_uv2.y - delta_uv2.x * delta_uv1.y;
-        let tangent: Vector3f;
-        let bitangent: Vector3f;
+fn invalid_obj<T>(path: &str, len: usize, reason: String) -> AssetResult<T> {
+    Err(AssetError::InvalidFormat {
+        path: path.to_string(),
+        len,
+        reason,
+    })
+}
 
-        if !d.close_to_zero() {
-            let r = 1.0 / d;
-            tangent = (edge1 * delta_uv2.y - edge2 * delta_uv1.y) * r;
-            bitangent = (edge2 * delta_uv1.x - edge1 * delta_uv2.x) * r;
-        } else {
-            // For some ill condition mesh, d would be zero, just fix it
-            if !delta_uv1.x.close_to_zero() {
-                tangent = edge1 / delta_uv1.x;
-            } else if !delta_uv2.x.close_to_zero() {
-                tangent = edge2 / delta_uv2.x;
-            } else {
-                tangent = Vector3::zero();
-            }
+/// Smooth normals for obj files without any, the face normals are weighted by
+/// area and shared by all vertices at the same position (e.g. across uv seams).
+fn compute_normals(
+    positions: &Vec<[f32; 3]>,
+    v_positions: &Vec<usize>,
+    v_array: &Vec<f32>,
+    indices: &Vec<u32>,
+) -> Vec<f32> {
+    let mut acc = vec![Vector3f::zero(); positions.len()];
 
-            if !delta_uv1.y.close_to_zero() {
-                bitangent = edge1 / delta_uv1.y;
-            } else if !delta_uv2.y.close_to_zero() {
-                bitangent = edge2 / delta_uv2.y;
-            } else {
-                bitangent = Vector3::zero();
-            }
+    for tri in indices.chunks(3) {
+        if tri.len() < 3 {
+            break;
         }
 
-        // Triangle share same tangent space
-        tangents.extend_from_slice(&tangent[..]);
-        tangents.extend_from_slice(&tangent[..]);
-        tangents.extend_from_slice(&tangent[..]);
-
-        bittangents.extend_from_slice(&bitangent[..]);
-        bittangents.extend_from_slice(&bitangent[..]);
-        bittangents.extend_from_slice(&bitangent[..]);
-
-        i += 3;
-    }
-
-    for i in 0..indices.len() {
-        let n = from_slice_v3(i, &n_array);
-        let mut t = from_slice_v3(i, &tangents);
-        let b = from_slice_v3(i, &bittangents);
-
-        // Gram-Schmidt orthogonalize
-        t = t - n * n.dot(t);
+        let p1 = from_slice_v3(tri[0] as usize, v_array);
+        let p2 = from_slice_v3(tri[1] as usize, v_array);
+        let p3 = from_slice_v3(tri[2] as usize, v_array);
+        let n = (p2 - p1).cross(p3 - p1);
 
-        if n.cross(t).dot(b) < 0.0 {
-            t = -t;
+        for &i in tri.iter() {
+            acc[v_positions[i as usize]] += n;
         }
-
-        tangents[i * 3 + 0] = t.x;
-        tangents[i * 3 + 1] = t.y;
-        tangents[i * 3 + 2] = t.z;
     }
 
-    assert!(tangents.len() == v_array.len());
-    assert!(bittangents.len() == v_array.len());
+    let mut n_array = Vec::with_capacity(v_array.len());
+    for &p in v_positions.iter() {
+        let n = acc[p];
+        let n = if n.magnitude2() > 0.0 {
+            n.normalize()
+        } else {
+            Vector3::unit_y()
+        };
+        n_array.extend_from_slice(&[n.x, n.y, n.z]);
+    }
 
-    return TangentSpace {
-        tangents: Some(tangents),
-        bitangents: Some(bittangents),
-    };
+    n_array
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -181,6 +169,17 @@ pub struct ObjMaterial {
 
     pub alpha_mask: Option<String>,
     pub normal_map: Option<String>,
+
+    // Metallic-roughness parameters, only filled by glTF files
+    pub metallic: Option<f32>,
+    pub roughness: Option<f32>,
+    pub emissive: Option<Vector3f>,
+    pub alpha_cutoff: Option<f32>,
+    pub double_sided: Option<bool>,
+
+    pub metallic_roughness_map: Option<String>,
+    pub occlusion_map: Option<String>,
+    pub emissive_map: Option<String>,
 }
 
 impl Default for ObjMaterial {
@@ -196,6 +195,14 @@ impl Default for ObjMaterial {
             specular_map: None,
             alpha_mask: None,
             normal_map: None,
+            metallic: None,
+            roughness: None,
+            emissive: None,
+            alpha_cutoff: None,
+            double_sided: None,
+            metallic_roughness_map: None,
+            occlusion_map: None,
+            emissive_map: None,
         }
     }
 }
@@ -234,7 +241,7 @@ impl ObjMaterial {
     }
 }
 
-type MaterialBuilder = Box<Fn(&AssetSystem, ObjMaterial) -> Rc<Material>>;
+pub type MaterialBuilder = Box<Fn(&AssetSystem, ObjMaterial) -> Rc<Material>>;
 
 struct MaterialCache<A>
 where
@@ -285,10 +292,12 @@ where
 impl PrefabLoader {
     fn load_model<A>(
         asys: A,
+        path: &str,
+        len: usize,
         parent: String,
         model: obj::Obj<SimplePolygon>,
         builder: MaterialBuilder,
-    ) -> Prefab
+    ) -> AssetResult<Prefab>
     where
         A: AssetSystem + Clone + 'static,
     {
@@ -309,56 +318,88 @@ impl PrefabLoader {
                 let material = g.material.as_ref().unwrap();
                 let (has_normal_map, material) = material_cache.get_or_insert(&material);
 
+                let has_uv = g.polys
+                    .iter()
+                    .any(|poly| poly.iter().any(|t| t.1.is_some()));
+                let has_normal = g.polys
+                    .iter()
+                    .all(|poly| poly.iter().all(|t| t.2.is_some()));
+
+                for poly in g.polys.iter() {
+                    if poly.len() < 3 {
+                        return invalid_obj(path, len, format!("face with {} vertices", poly.len()));
+                    }
+
+                    let out_of_range = poly.iter().any(|t| {
+                        t.0 >= vertices.len() || t.1.map_or(false, |uv| uv >= uvs.len())
+                            || t.2.map_or(false, |n| n >= normals.len())
+                    });
+                    if out_of_range {
+                        return invalid_obj(path, len, "face index out of range".to_string());
+                    }
+                }
+
+                // Identical (position, uv, normal) tuples are welded to one vertex
+                let mut welded: HashMap<(usize, Option<usize>, Option<usize>), u32> =
+                    HashMap::new();
+                let mut v_positions = Vec::new();
                 let mut indices = Vec::new();
                 let mut v_array = Vec::new();
                 let mut uv_array = Vec::new();
                 let mut n_array = Vec::new();
 
-                let mut add_v = |index_tuple: obj::IndexTuple| {
-                    indices.push(indices.len() as u16);
-                    v_array.extend_from_slice(&vertices[index_tuple.0]);
-                    index_tuple.1.map(|uv| {
-                        uv_array.push(uvs[uv][0]);
-                        uv_array.push(1.0 - uvs[uv][1]);
-                    });
-                    index_tuple.2.map(|n| {
-                        n_array.extend_from_slice(&normals[n]);
-                    });
-                };
-
-                for poly in g.polys {
-                    // assert_eq!(poly.len(), 3, "We only handle triangle obj files");
-                    match poly.len() {
-                        3 => {
-                            add_v(poly[0]);
-                            add_v(poly[1]);
-                            add_v(poly[2]);
-                        }
-                        4 => {
-                            add_v(poly[0]);
-                            add_v(poly[1]);
-                            add_v(poly[2]);
-
-                            add_v(poly[2]);
-                            add_v(poly[3]);
-                            add_v(poly[0]);
+                {
+                    let mut add_v = |index_tuple: obj::IndexTuple| {
+                        let key = (
+                            index_tuple.0,
+                            index_tuple.1,
+                            if has_normal { index_tuple.2 } else { None },
+                        );
+
+                        let next = v_positions.len() as u32;
+                        let index = *welded.entry(key).or_insert_with(|| {
+                            v_positions.push(index_tuple.0);
+                            v_array.extend_from_slice(&vertices[index_tuple.0]);
+                            if has_uv {
+                                let uv = index_tuple.1.map_or([0.0, 0.0], |uv| uvs[uv]);
+                                uv_array.push(uv[0]);
+                                uv_array.push(1.0 - uv[1]);
+                            }
+                            if has_normal {
+                                n_array.extend_from_slice(&normals[index_tuple.2.unwrap()]);
+                            }
+                            next
+                        });
+
+                        indices.push(index);
+                    };
+
+                    for poly in g.polys.iter() {
+                        let points: Vec<Vector3f> =
+                            poly.iter().map(|t| vertices[t.0].into()).collect();
+
+                        for tri in triangulate_polygon(&points) {
+                            add_v(poly[tri[0]]);
+                            add_v(poly[tri[1]]);
+                            add_v(poly[tri[2]]);
                         }
-
-                        _ => panic!("We only handle triangle or quad obj files"),
                     }
                 }
 
-                let uv_array = if uv_array.len() > 0 {
-                    Some(uv_array)
-                } else {
-                    None
-                };
+                if !has_normal {
+                    n_array = compute_normals(&vertices, &v_positions, &v_array, &indices);
+                }
+
+                let uv_array = if has_uv { Some(uv_array) } else { None };
                 let n_array = if n_array.len() > 0 {
                     Some(n_array)
                 } else {
                     None
                 };
 
+                let nvertices = v_array.len() / 3;
+                let indices = MeshIndices::with_vertex_count(indices, nvertices);
+
                 let tangent_space = if has_normal_map.0 {
                     compute_tangents(&v_array, &uv_array, &n_array, &indices)
                 } else {
@@ -374,6 +415,9 @@ impl PrefabLoader {
                     uvs: uv_array,
                     tangents: tangent_space.tangents,
                     bitangents: tangent_space.bitangents,
+                    joints: None,
+                    weights: None,
+                    colors: None,
                     normals: n_array,
                 };
 
@@ -384,9 +428,10 @@ impl PrefabLoader {
             }
         }
 
-        Prefab {
+        Ok(Prefab {
             components: vec![Component::new(mesh)],
-        }
+            children: Vec::new(),
+        })
     }
 }
 
@@ -403,6 +448,22 @@ where
 }
 
 impl Prefab {
+    /// Merge the meshes of the child nodes into a Mesh component of the prefab,
+    /// the transforms of the nodes are baked into the vertices as StaticBatch does.
+    /// The other components of the nodes are dropped.
+    pub fn flatten(mut self) -> AssetResult<Prefab> {
+        let mut parts = Vec::new();
+        collect_surfaces(&self.children, Matrix4::identity(), &mut parts);
+        self.children.clear();
+
+        let mesh = StaticBatch::default().merge_surfaces(&parts)?;
+        if !mesh.surfaces.is_empty() {
+            self.components.push(Component::new(mesh));
+        }
+
+        Ok(self)
+    }
+
     pub fn load_future<A>(
         asys: A,
         objfile: FileFuture,
@@ -414,26 +475,34 @@ impl Prefab {
     {
         let allmat = {
             let asys = asys.clone();
-            objfile.and_then(move |mut f| {
-                let bytes = f.read_binary()?;
-                let mut r = BufReader::new(bytes.as_slice());
-
-                let mut model = obj::Obj::<SimplePolygon>::load_buf(&mut r)?;
-                let parent = parent_path(&f.name());
-                let files = join_all(get_mtl_files(asys, &parent, &mut model));
-
-                // attach the model to future
-                Ok(files.map(move |x| (x, parent, model)))
-            })
+            objfile
+                .map_err(|e| AssetError::FileIoError(e))
+                .and_then(move |mut f| {
+                    let bytes = f.read_binary().map_err(|e| AssetError::FileIoError(e))?;
+                    let path = f.name();
+                    let len = bytes.len();
+                    let mut r = BufReader::new(bytes.as_slice());
+
+                    let mut model = match obj::Obj::<SimplePolygon>::load_buf(&mut r) {
+                        Ok(model) => model,
+                        Err(e) => return invalid_obj(&path, len, format!("{}", e)),
+                    };
+                    let parent = parent_path(&path);
+                    let files = join_all(get_mtl_files(asys, &parent, &mut model))
+                        .map_err(|e| AssetError::FileIoError(e));
+
+                    // attach the model to future
+                    Ok(files.map(move |x| (x, path, len, parent, model)))
+                })
         };
 
         // TODO I don't know why it is needed !!
         let allmat = allmat.and_then(|r| r);
 
-        let final_future = allmat.and_then(move |(files, parent, mut model)| {
+        let final_future = allmat.and_then(move |(files, path, len, parent, mut model)| {
             let mut materials = HashMap::new();
             for mut f in files {
-                let bytes = f.read_binary()?;
+                let bytes = f.read_binary().map_err(|e| AssetError::FileIoError(e))?;
                 let mtl = obj::Mtl::load(&mut BufReader::new(bytes.as_slice()));
                 for m in mtl.materials {
                     materials.insert(m.name.clone(), Cow::from(m));
@@ -450,10 +519,10 @@ impl Prefab {
                 }
             }
 
-            Ok(PrefabLoader::load_model(asys, parent, model, builder))
+            PrefabLoader::load_model(asys, &path, len, parent, model, builder)
         });
 
         // futurize
-        Box::new(final_future.map_err(|e| AssetError::FileIoError(e)))
+        Box::new(final_future)
     }
 }
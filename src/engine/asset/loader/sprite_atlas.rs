@@ -0,0 +1,132 @@
+// Sprite atlas loader, supports the JSON files of TexturePacker,
+// with the frames as a hash or as an array.
+//
+// The image of the atlas is loaded relatively to the JSON file. Trimmed frames
+// keep the pivot of their untrimmed size, rotated frames are not supported.
+
+use engine::asset::loader::{Loadable, Loader};
+use engine::asset::{AssetError, AssetResult, AssetSystem, File};
+use engine::render::{SpriteAtlasData, SpriteFrame};
+use rustc_serialize::json::Json;
+use std::collections::HashMap;
+use std::str;
+use math::*;
+
+use super::prefab::parent_path;
+
+pub struct SpriteAtlasLoader {}
+
+fn invalid<T>(path: &str, reason: String) -> AssetResult<T> {
+    Err(AssetError::InvalidFormat {
+        path: path.to_owned(),
+        len: 0,
+        reason,
+    })
+}
+
+fn get_f32(v: &Json, key: &str) -> Option<f32> {
+    v.find(key).and_then(|x| x.as_f64()).map(|x| x as f32)
+}
+
+fn get_u32(v: &Json, key: &str) -> Option<u32> {
+    v.find(key).and_then(|x| x.as_u64()).map(|x| x as u32)
+}
+
+/// ((x, y), (w, h)) of a {"x", "y", "w", "h"} object
+fn get_rect(v: &Json, key: &str) -> Option<((u32, u32), (u32, u32))> {
+    let r = v.find(key)?;
+    Some((
+        (get_u32(r, "x")?, get_u32(r, "y")?),
+        (get_u32(r, "w")?, get_u32(r, "h")?),
+    ))
+}
+
+fn parse_frame(path: &str, name: &str, v: &Json) -> AssetResult<SpriteFrame> {
+    let rect = match get_rect(v, "frame") {
+        Some(rect) => rect,
+        None => return invalid(path, format!("frame {} has no rect", name)),
+    };
+    if v.find("rotated").and_then(|r| r.as_boolean()) == Some(true) {
+        return invalid(path, format!("frame {} is rotated", name));
+    }
+
+    let (_, (w, h)) = rect;
+    if w == 0 || h == 0 {
+        return invalid(path, format!("frame {} is empty", name));
+    }
+    let (w, h) = (w as f32, h as f32);
+
+    // The pivot is relative to the untrimmed size, from the top-left corner
+    let (px, py) = match v.find("pivot") {
+        Some(p) => (get_f32(p, "x").unwrap_or(0.5), get_f32(p, "y").unwrap_or(0.5)),
+        None => (0.5, 0.5),
+    };
+    let ((ox, oy), (sw, sh)) = match (get_rect(v, "spriteSourceSize"), v.find("sourceSize")) {
+        (Some(((ox, oy), _)), Some(size)) => (
+            (ox as f32, oy as f32),
+            (
+                get_f32(size, "w").unwrap_or(w),
+                get_f32(size, "h").unwrap_or(h),
+            ),
+        ),
+        _ => ((0.0, 0.0), (w, h)),
+    };
+
+    Ok(SpriteFrame {
+        rect,
+        pivot: Vector2::new((px * sw - ox) / w, 1.0 - (py * sh - oy) / h),
+    })
+}
+
+impl Loader<SpriteAtlasData> for SpriteAtlasLoader {
+    fn load<A>(asys: A, mut file: Box<File>) -> AssetResult<SpriteAtlasData>
+    where
+        A: AssetSystem + Clone,
+    {
+        let path = file.name();
+        let buf = file.read_binary()
+            .map_err(|_| AssetError::ReadBufferFail(path.clone()))?;
+        let len = buf.len();
+
+        let json = match str::from_utf8(&buf) {
+            Ok(s) => Json::from_str(s).map_err(|e| format!("{}", e)),
+            Err(e) => Err(format!("{}", e)),
+        };
+        let json = match json {
+            Ok(json) => json,
+            Err(reason) => return Err(AssetError::InvalidFormat { path, len, reason }),
+        };
+
+        let image = json.find("meta")
+            .and_then(|m| m.find("image"))
+            .and_then(|i| i.as_string());
+        let image = match image {
+            Some(image) => image,
+            None => return invalid(&path, "no meta.image".into()),
+        };
+
+        let mut frames = HashMap::new();
+        match json.find("frames") {
+            Some(&Json::Object(ref obj)) => for (name, v) in obj.iter() {
+                frames.insert(name.clone(), parse_frame(&path, name, v)?);
+            },
+            Some(&Json::Array(ref arr)) => for v in arr.iter() {
+                let name = match v.find("filename").and_then(|n| n.as_string()) {
+                    Some(name) => name,
+                    None => return invalid(&path, "frame without filename".into()),
+                };
+                frames.insert(name.to_owned(), parse_frame(&path, name, v)?);
+            },
+            _ => return invalid(&path, "no frames".into()),
+        }
+
+        Ok(SpriteAtlasData {
+            texture: asys.new_texture(&(parent_path(&path) + image)),
+            frames,
+        })
+    }
+}
+
+impl Loadable for SpriteAtlasData {
+    type Loader = SpriteAtlasLoader;
+}
@@ -0,0 +1,779 @@
+// Tiled map loader, supports the orthogonal maps of the TMX and JSON formats,
+// with inline or external (TSX or JSON) tilesets.
+//
+// A map is loaded as a Prefab with a Tilemap component, and a child node for each
+// object of its object layers, with a TileObject component and a Sprite for the
+// visible tile objects. Group layers are flattened and image layers are ignored.
+// A map tile is one world unit wide.
+//
+// Infinite maps, compressed layer data and image collection tilesets are not supported.
+
+use engine::asset::{AssetError, AssetResult, AssetSystem, File, FileFuture, FileIoError};
+use engine::render::{Sprite, TileObject, TileObjectShape, TileProperties, Tilemap, Tileset,
+                     TILE_FLIPPED_HORIZONTALLY, TILE_FLIPPED_VERTICALLY};
+use engine::core::Component;
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::json::Json;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::str;
+use math::*;
+
+use futures::prelude::*;
+use futures::future;
+
+use super::prefab::{parent_path, Prefab, PrefabNode};
+use super::xml::{self, Element};
+
+pub struct TiledLoader {}
+
+type TilesetFuture = Box<Future<Item = TilesetDef, Error = AssetError>>;
+
+fn invalid<T>(path: &str, reason: String) -> AssetResult<T> {
+    Err(AssetError::InvalidFormat {
+        path: path.to_owned(),
+        len: 0,
+        reason,
+    })
+}
+
+struct TilesetDef {
+    first_id: u32,
+    name: String,
+    tile_size: (u32, u32),
+    tile_count: u32,
+    columns: u32,
+    margin: u32,
+    spacing: u32,
+    /// Asset name of the image, relative to the file of the tileset
+    image: String,
+    image_size: (u32, u32),
+    tile_properties: HashMap<u32, TileProperties>,
+}
+
+enum TilesetSource {
+    Inline(TilesetDef),
+    /// The first id and the file of the tileset
+    External(u32, String),
+}
+
+struct LayerDef {
+    name: String,
+    visible: bool,
+    opacity: f32,
+    properties: TileProperties,
+    tiles: Vec<u32>,
+}
+
+/// An object, in pixels from the top-left corner of the map
+struct ObjectDef {
+    id: u32,
+    name: String,
+    kind: String,
+    layer: String,
+    position: (f32, f32),
+    size: (f32, f32),
+    /// Clockwise, in degrees
+    rotation: f32,
+    gid: Option<u32>,
+    visible: bool,
+    shape: TileObjectShape,
+    properties: TileProperties,
+}
+
+struct MapDef {
+    path: String,
+    size: (u32, u32),
+    tile_size: (u32, u32),
+    properties: TileProperties,
+    tilesets: Vec<TilesetSource>,
+    layers: Vec<LayerDef>,
+    objects: Vec<ObjectDef>,
+}
+
+/// Visibility and opacity of the group of a layer
+#[derive(Copy, Clone)]
+struct Group {
+    visible: bool,
+    opacity: f32,
+}
+
+fn parse_document(path: &str, bytes: &[u8]) -> AssetResult<Result<Element, Json>> {
+    let s = match str::from_utf8(bytes) {
+        Ok(s) => s,
+        Err(e) => return invalid(path, format!("{}", e)),
+    };
+
+    let result = if s.trim_left_matches('\u{feff}').trim_left().starts_with('<') {
+        xml::parse(s).map(Ok)
+    } else {
+        Json::from_str(s).map(Err).map_err(|e| format!("{}", e))
+    };
+
+    result.or_else(|reason| invalid(path, reason))
+}
+
+/// The tile ids of a layer data, csv or base64 encoded
+fn decode_tiles(
+    path: &str,
+    encoding: Option<&str>,
+    compression: Option<&str>,
+    text: &str,
+) -> AssetResult<Vec<u32>> {
+    if let Some(c) = compression {
+        if !c.is_empty() {
+            return invalid(path, format!("unsupported {} compressed layer data", c));
+        }
+    }
+
+    match encoding {
+        Some("csv") => text.split(',')
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<u32>().or_else(|e| invalid(path, format!("{}", e))))
+            .collect(),
+        Some("base64") => {
+            let bytes = match text.trim().from_base64() {
+                Ok(bytes) => bytes,
+                Err(e) => return invalid(path, format!("{}", e)),
+            };
+            Ok(bytes
+                .chunks(4)
+                .filter(|b| b.len() == 4)
+                .map(|b| {
+                    (b[0] as u32) | ((b[1] as u32) << 8) | ((b[2] as u32) << 16)
+                        | ((b[3] as u32) << 24)
+                })
+                .collect())
+        }
+        e => invalid(
+            path,
+            format!("unsupported layer data encoding {}", e.unwrap_or("none")),
+        ),
+    }
+}
+
+fn attr_u32(e: &Element, name: &str) -> Option<u32> {
+    e.attr(name).and_then(|v| v.parse().ok())
+}
+
+fn attr_f32(e: &Element, name: &str) -> Option<f32> {
+    e.attr(name).and_then(|v| v.parse().ok())
+}
+
+fn attr_string(e: &Element, name: &str) -> String {
+    e.attr(name).unwrap_or("").to_owned()
+}
+
+fn xml_properties(e: &Element) -> TileProperties {
+    let mut properties = TileProperties::new();
+
+    if let Some(props) = e.child("properties") {
+        for p in props.children_named("property") {
+            // Multiline strings are stored as text
+            let value = p.attr("value").map_or(p.text.clone(), |v| v.to_owned());
+            properties.insert(attr_string(p, "name"), value);
+        }
+    }
+
+    properties
+}
+
+fn xml_points(path: &str, points: &str) -> AssetResult<Vec<Vector2f>> {
+    let mut result = Vec::new();
+
+    for p in points.split_whitespace() {
+        let mut xy = p.split(',').map(|v| v.parse::<f32>());
+        match (xy.next(), xy.next()) {
+            (Some(Ok(x)), Some(Ok(y))) => result.push(Vector2::new(x, y)),
+            _ => return invalid(path, format!("invalid point {}", p)),
+        }
+    }
+
+    Ok(result)
+}
+
+fn xml_tileset(path: &str, e: &Element, first_id: u32) -> AssetResult<TilesetDef> {
+    let image = match e.child("image") {
+        Some(image) => image,
+        None => {
+            return invalid(
+                path,
+                format!("tileset {} has no image", attr_string(e, "name")),
+            )
+        }
+    };
+
+    let mut tile_properties = HashMap::new();
+    for tile in e.children_named("tile") {
+        if let Some(id) = attr_u32(tile, "id") {
+            tile_properties.insert(id, xml_properties(tile));
+        }
+    }
+
+    let tile_size = (
+        attr_u32(e, "tilewidth").unwrap_or(0),
+        attr_u32(e, "tileheight").unwrap_or(0),
+    );
+    let image_size = (
+        attr_u32(image, "width").unwrap_or(0),
+        attr_u32(image, "height").unwrap_or(0),
+    );
+
+    Ok(TilesetDef {
+        first_id,
+        name: attr_string(e, "name"),
+        tile_size,
+        tile_count: attr_u32(e, "tilecount").unwrap_or(0),
+        columns: attr_u32(e, "columns").unwrap_or(0),
+        margin: attr_u32(e, "margin").unwrap_or(0),
+        spacing: attr_u32(e, "spacing").unwrap_or(0),
+        image: parent_path(path) + &attr_string(image, "source"),
+        image_size,
+        tile_properties,
+    })
+}
+
+fn xml_object(path: &str, e: &Element, layer: &str, group: Group) -> AssetResult<ObjectDef> {
+    let shape = if e.child("ellipse").is_some() {
+        TileObjectShape::Ellipse
+    } else if e.child("point").is_some() {
+        TileObjectShape::Point
+    } else if let Some(p) = e.child("polygon") {
+        TileObjectShape::Polygon(xml_points(path, p.attr("points").unwrap_or(""))?)
+    } else if let Some(p) = e.child("polyline") {
+        TileObjectShape::Polyline(xml_points(path, p.attr("points").unwrap_or(""))?)
+    } else {
+        TileObjectShape::Rectangle
+    };
+
+    Ok(ObjectDef {
+        id: attr_u32(e, "id").unwrap_or(0),
+        name: attr_string(e, "name"),
+        // Renamed to class in Tiled 1.9
+        kind: e.attr("type")
+            .or_else(|| e.attr("class"))
+            .unwrap_or("")
+            .to_owned(),
+        layer: layer.to_owned(),
+        position: (
+            attr_f32(e, "x").unwrap_or(0.0),
+            attr_f32(e, "y").unwrap_or(0.0),
+        ),
+        size: (
+            attr_f32(e, "width").unwrap_or(0.0),
+            attr_f32(e, "height").unwrap_or(0.0),
+        ),
+        rotation: attr_f32(e, "rotation").unwrap_or(0.0),
+        gid: attr_u32(e, "gid"),
+        visible: group.visible && e.attr("visible") != Some("0"),
+        shape,
+        properties: xml_properties(e),
+    })
+}
+
+fn xml_layers(path: &str, e: &Element, map: &mut MapDef, parent: Group) -> AssetResult<()> {
+    for child in e.children.iter() {
+        let name = attr_string(child, "name");
+        let group = Group {
+            visible: parent.visible && child.attr("visible") != Some("0"),
+            opacity: parent.opacity * attr_f32(child, "opacity").unwrap_or(1.0),
+        };
+
+        match child.name.as_str() {
+            "layer" => {
+                let data = match child.child("data") {
+                    Some(data) => data,
+                    None => return invalid(path, format!("layer {} has no data", name)),
+                };
+                if data.child("chunk").is_some() {
+                    return invalid(path, "infinite maps are not supported".into());
+                }
+
+                let tiles = match data.attr("encoding") {
+                    None => data.children_named("tile")
+                        .iter()
+                        .map(|t| attr_u32(t, "gid").unwrap_or(0))
+                        .collect(),
+                    encoding => {
+                        decode_tiles(path, encoding, data.attr("compression"), &data.text)?
+                    }
+                };
+
+                map.layers.push(LayerDef {
+                    name,
+                    visible: group.visible,
+                    opacity: group.opacity,
+                    properties: xml_properties(child),
+                    tiles,
+                });
+            }
+            "objectgroup" => for object in child.children_named("object") {
+                let object = xml_object(path, object, &name, group)?;
+                map.objects.push(object);
+            },
+            "group" => xml_layers(path, child, map, group)?,
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+fn xml_map(path: &str, e: &Element) -> AssetResult<MapDef> {
+    if e.name != "map" {
+        return invalid(path, format!("unexpected {} element", e.name));
+    }
+
+    let orientation = e.attr("orientation").unwrap_or("orthogonal");
+    if orientation != "orthogonal" {
+        return invalid(path, format!("unsupported {} orientation", orientation));
+    }
+    if e.attr("infinite") == Some("1") {
+        return invalid(path, "infinite maps are not supported".into());
+    }
+
+    let mut map = MapDef {
+        path: path.to_owned(),
+        size: (
+            attr_u32(e, "width").unwrap_or(0),
+            attr_u32(e, "height").unwrap_or(0),
+        ),
+        tile_size: (
+            attr_u32(e, "tilewidth").unwrap_or(0),
+            attr_u32(e, "tileheight").unwrap_or(0),
+        ),
+        properties: xml_properties(e),
+        tilesets: Vec::new(),
+        layers: Vec::new(),
+        objects: Vec::new(),
+    };
+
+    for tileset in e.children_named("tileset") {
+        let first_id = attr_u32(tileset, "firstgid").unwrap_or(1);
+        map.tilesets.push(match tileset.attr("source") {
+            Some(source) => TilesetSource::External(first_id, source.to_owned()),
+            None => TilesetSource::Inline(xml_tileset(path, tileset, first_id)?),
+        });
+    }
+
+    let root = Group {
+        visible: true,
+        opacity: 1.0,
+    };
+    xml_layers(path, e, &mut map, root)?;
+
+    Ok(map)
+}
+
+fn get_u32(v: &Json, key: &str) -> Option<u32> {
+    v.find(key).and_then(|x| x.as_u64()).map(|x| x as u32)
+}
+
+fn get_f32(v: &Json, key: &str) -> Option<f32> {
+    v.find(key).and_then(|x| x.as_f64()).map(|x| x as f32)
+}
+
+fn get_bool(v: &Json, key: &str) -> Option<bool> {
+    v.find(key).and_then(|x| x.as_boolean())
+}
+
+fn get_string(v: &Json, key: &str) -> String {
+    v.find(key)
+        .and_then(|x| x.as_string())
+        .unwrap_or("")
+        .to_owned()
+}
+
+fn get_array<'a>(v: &'a Json, key: &str) -> &'a [Json] {
+    match v.find(key).and_then(|x| x.as_array()) {
+        Some(a) => a,
+        None => &[],
+    }
+}
+
+fn json_properties(v: &Json) -> TileProperties {
+    let mut properties = TileProperties::new();
+
+    for p in get_array(v, "properties") {
+        let value = match p.find("value") {
+            Some(&Json::String(ref s)) => s.clone(),
+            Some(value) => format!("{}", value),
+            None => String::new(),
+        };
+        properties.insert(get_string(p, "name"), value);
+    }
+
+    properties
+}
+
+fn json_points(v: &Json, key: &str) -> Vec<Vector2f> {
+    get_array(v, key)
+        .iter()
+        .map(|p| Vector2::new(get_f32(p, "x").unwrap_or(0.0), get_f32(p, "y").unwrap_or(0.0)))
+        .collect()
+}
+
+fn json_tileset(path: &str, v: &Json, first_id: u32) -> AssetResult<TilesetDef> {
+    let image = match v.find("image").and_then(|i| i.as_string()) {
+        Some(image) => image,
+        None => return invalid(path, format!("tileset {} has no image", get_string(v, "name"))),
+    };
+
+    let mut tile_properties = HashMap::new();
+    for tile in get_array(v, "tiles") {
+        if let Some(id) = get_u32(tile, "id") {
+            tile_properties.insert(id, json_properties(tile));
+        }
+    }
+
+    let tile_size = (
+        get_u32(v, "tilewidth").unwrap_or(0),
+        get_u32(v, "tileheight").unwrap_or(0),
+    );
+    let image_size = (
+        get_u32(v, "imagewidth").unwrap_or(0),
+        get_u32(v, "imageheight").unwrap_or(0),
+    );
+
+    Ok(TilesetDef {
+        first_id,
+        name: get_string(v, "name"),
+        tile_size,
+        tile_count: get_u32(v, "tilecount").unwrap_or(0),
+        columns: get_u32(v, "columns").unwrap_or(0),
+        margin: get_u32(v, "margin").unwrap_or(0),
+        spacing: get_u32(v, "spacing").unwrap_or(0),
+        image: parent_path(path) + image,
+        image_size,
+        tile_properties,
+    })
+}
+
+fn json_object(v: &Json, layer: &str, group: Group) -> ObjectDef {
+    let shape = if get_bool(v, "ellipse") == Some(true) {
+        TileObjectShape::Ellipse
+    } else if get_bool(v, "point") == Some(true) {
+        TileObjectShape::Point
+    } else if v.find("polygon").is_some() {
+        TileObjectShape::Polygon(json_points(v, "polygon"))
+    } else if v.find("polyline").is_some() {
+        TileObjectShape::Polyline(json_points(v, "polyline"))
+    } else {
+        TileObjectShape::Rectangle
+    };
+
+    let kind = match v.find("type").or_else(|| v.find("class")) {
+        Some(kind) => kind.as_string().unwrap_or("").to_owned(),
+        None => String::new(),
+    };
+
+    ObjectDef {
+        id: get_u32(v, "id").unwrap_or(0),
+        name: get_string(v, "name"),
+        kind,
+        layer: layer.to_owned(),
+        position: (
+            get_f32(v, "x").unwrap_or(0.0),
+            get_f32(v, "y").unwrap_or(0.0),
+        ),
+        size: (
+            get_f32(v, "width").unwrap_or(0.0),
+            get_f32(v, "height").unwrap_or(0.0),
+        ),
+        rotation: get_f32(v, "rotation").unwrap_or(0.0),
+        gid: get_u32(v, "gid"),
+        visible: group.visible && get_bool(v, "visible") != Some(false),
+        shape,
+        properties: json_properties(v),
+    }
+}
+
+fn json_layers(path: &str, layers: &[Json], map: &mut MapDef, parent: Group) -> AssetResult<()> {
+    for layer in layers {
+        let name = get_string(layer, "name");
+        let group = Group {
+            visible: parent.visible && get_bool(layer, "visible") != Some(false),
+            opacity: parent.opacity * get_f32(layer, "opacity").unwrap_or(1.0),
+        };
+
+        match layer.find("type").and_then(|t| t.as_string()) {
+            Some("tilelayer") => {
+                if layer.find("chunks").is_some() {
+                    return invalid(path, "infinite maps are not supported".into());
+                }
+
+                let tiles = match layer.find("data") {
+                    Some(&Json::Array(ref data)) => {
+                        data.iter().map(|t| t.as_u64().unwrap_or(0) as u32).collect()
+                    }
+                    Some(&Json::String(ref data)) => {
+                        let compression = layer.find("compression").and_then(|c| c.as_string());
+                        decode_tiles(path, Some("base64"), compression, data)?
+                    }
+                    _ => return invalid(path, format!("layer {} has no data", name)),
+                };
+
+                map.layers.push(LayerDef {
+                    name,
+                    visible: group.visible,
+                    opacity: group.opacity,
+                    properties: json_properties(layer),
+                    tiles,
+                });
+            }
+            Some("objectgroup") => for object in get_array(layer, "objects") {
+                map.objects.push(json_object(object, &name, group));
+            },
+            Some("group") => json_layers(path, get_array(layer, "layers"), map, group)?,
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+fn json_map(path: &str, v: &Json) -> AssetResult<MapDef> {
+    let orientation = v.find("orientation")
+        .and_then(|o| o.as_string())
+        .unwrap_or("orthogonal");
+    if orientation != "orthogonal" {
+        return invalid(path, format!("unsupported {} orientation", orientation));
+    }
+    if get_bool(v, "infinite") == Some(true) {
+        return invalid(path, "infinite maps are not supported".into());
+    }
+
+    let mut map = MapDef {
+        path: path.to_owned(),
+        size: (
+            get_u32(v, "width").unwrap_or(0),
+            get_u32(v, "height").unwrap_or(0),
+        ),
+        tile_size: (
+            get_u32(v, "tilewidth").unwrap_or(0),
+            get_u32(v, "tileheight").unwrap_or(0),
+        ),
+        properties: json_properties(v),
+        tilesets: Vec::new(),
+        layers: Vec::new(),
+        objects: Vec::new(),
+    };
+
+    for tileset in get_array(v, "tilesets") {
+        let first_id = get_u32(tileset, "firstgid").unwrap_or(1);
+        map.tilesets.push(match tileset.find("source").and_then(|s| s.as_string()) {
+            Some(source) => TilesetSource::External(first_id, source.to_owned()),
+            None => TilesetSource::Inline(json_tileset(path, tileset, first_id)?),
+        });
+    }
+
+    let root = Group {
+        visible: true,
+        opacity: 1.0,
+    };
+    json_layers(path, get_array(v, "layers"), &mut map, root)?;
+
+    Ok(map)
+}
+
+fn parse_map(path: &str, bytes: &[u8]) -> AssetResult<MapDef> {
+    let map = match parse_document(path, bytes)? {
+        Ok(e) => xml_map(path, &e)?,
+        Err(v) => json_map(path, &v)?,
+    };
+
+    if map.size.0 == 0 || map.size.1 == 0 || map.tile_size.0 == 0 || map.tile_size.1 == 0 {
+        return invalid(path, "empty map".into());
+    }
+
+    let count = (map.size.0 * map.size.1) as usize;
+    for layer in map.layers.iter() {
+        if layer.tiles.len() != count {
+            let reason = format!("layer {} has {} tiles", layer.name, layer.tiles.len());
+            return invalid(path, reason);
+        }
+    }
+
+    Ok(map)
+}
+
+fn parse_tileset(path: &str, bytes: &[u8], first_id: u32) -> AssetResult<TilesetDef> {
+    match parse_document(path, bytes)? {
+        Ok(ref e) if e.name == "tileset" => xml_tileset(path, e, first_id),
+        Ok(e) => invalid(path, format!("unexpected {} element", e.name)),
+        Err(v) => json_tileset(path, &v, first_id),
+    }
+}
+
+fn load_tileset<A>(asys: &A, map_path: &str, source: TilesetSource) -> TilesetFuture
+where
+    A: AssetSystem,
+{
+    let (first_id, path) = match source {
+        TilesetSource::Inline(def) => return Box::new(future::ok(def)),
+        TilesetSource::External(first_id, source) => (first_id, parent_path(map_path) + &source),
+    };
+
+    Box::new(asys.new_file(&path).then(
+        move |r: Result<Box<File>, FileIoError>| -> AssetResult<TilesetDef> {
+            let mut f = r.map_err(AssetError::FileIoError)?;
+            let bytes = f.read_binary().map_err(AssetError::FileIoError)?;
+            parse_tileset(&path, &bytes, first_id)
+        },
+    ))
+}
+
+fn object_node(map: &Tilemap, map_height: f32, object: ObjectDef, scale: f32) -> PrefabNode {
+    let (x, y) = object.position;
+    let mut node = PrefabNode {
+        transform: Isometry3 {
+            scale: 1.0,
+            rot: Quaternion::from_angle_z(Deg(-object.rotation)),
+            disp: Vector3::new(x * scale, (map_height - y) * scale, 0.0),
+        },
+        scale: Vector3::new(1.0, 1.0, 1.0),
+        components: Vec::new(),
+        children: Vec::new(),
+    };
+
+    let tileset = object.gid.and_then(|gid| map.tileset_of(gid).map(|t| (gid, t)));
+    if let Some((gid, tileset)) = tileset {
+        // The origin of a tile object is at its bottom-left corner
+        let (pos, size) = tileset.tile_rect(gid);
+        let mut sprite = Sprite::new(tileset.texture.clone())
+            .with_rect(pos, size)
+            .with_pivot(Vector2::new(0.0, 0.0))
+            .with_pixels_per_unit(1.0 / scale);
+
+        if gid & TILE_FLIPPED_HORIZONTALLY != 0 {
+            sprite.flip_x = true;
+            sprite.pivot.x = 1.0;
+        }
+        if gid & TILE_FLIPPED_VERTICALLY != 0 {
+            sprite.flip_y = true;
+            sprite.pivot.y = 1.0;
+        }
+
+        if object.size.0 > 0.0 && object.size.1 > 0.0 {
+            node.scale = Vector3::new(
+                object.size.0 / size.0 as f32,
+                object.size.1 / size.1 as f32,
+                1.0,
+            );
+        }
+        if object.visible {
+            node.components.push(Component::new(sprite));
+        }
+    }
+
+    let point = |p: &Vector2f| Vector2::new(p.x * scale, -p.y * scale);
+    let shape = match object.shape {
+        TileObjectShape::Polygon(ref points) => {
+            TileObjectShape::Polygon(points.iter().map(&point).collect())
+        }
+        TileObjectShape::Polyline(ref points) => {
+            TileObjectShape::Polyline(points.iter().map(&point).collect())
+        }
+        ref shape => shape.clone(),
+    };
+
+    node.components.push(Component::new(TileObject {
+        id: object.id,
+        name: object.name,
+        kind: object.kind,
+        layer: object.layer,
+        size: Vector2::new(object.size.0 * scale, object.size.1 * scale),
+        shape,
+        properties: object.properties,
+    }));
+
+    node
+}
+
+fn build_prefab<A>(asys: &A, map: MapDef, tilesets: Vec<TilesetDef>) -> AssetResult<Prefab>
+where
+    A: AssetSystem,
+{
+    let (tw, th) = map.tile_size;
+    let scale = 1.0 / tw as f32;
+    let mut tilemap = Tilemap::new(map.size, Vector2::new(1.0, th as f32 * scale));
+    tilemap.properties = map.properties;
+
+    for def in tilesets {
+        if def.tile_size.0 == 0 || def.tile_size.1 == 0 {
+            return invalid(&map.path, format!("tileset {} has no tile size", def.name));
+        }
+
+        let mut tileset = Tileset::new(asys.new_texture(&def.image), def.image_size, def.tile_size);
+        tileset.name = def.name;
+        tileset.first_id = def.first_id;
+        tileset.margin = def.margin;
+        tileset.spacing = def.spacing;
+        if def.columns > 0 {
+            tileset.columns = def.columns;
+        }
+        if def.tile_count > 0 {
+            tileset.tile_count = def.tile_count;
+        }
+        tileset.tile_properties = def.tile_properties;
+
+        tilemap.add_tileset(Rc::new(tileset));
+    }
+
+    for def in map.layers {
+        let index = tilemap.add_layer_with_tiles(&def.name, def.tiles);
+        let layer = tilemap.layer_mut(index);
+        layer.visible = def.visible;
+        layer.opacity = def.opacity;
+        layer.properties = def.properties;
+    }
+
+    let map_height = (map.size.1 * th) as f32;
+    let children = map.objects
+        .into_iter()
+        .map(|object| object_node(&tilemap, map_height, object, scale))
+        .collect();
+
+    Ok(Prefab {
+        components: vec![Component::new(tilemap)],
+        children,
+    })
+}
+
+impl TiledLoader {
+    /// Return true if the file should be loaded as a Tiled map
+    pub fn accept(name: &str) -> bool {
+        let name = name.to_lowercase();
+        name.ends_with(".tmx") || name.ends_with(".tmj") || name.ends_with(".json")
+    }
+
+    pub fn load_future<A>(
+        asys: A,
+        file: FileFuture,
+    ) -> Box<Future<Item = Prefab, Error = AssetError>>
+    where
+        A: AssetSystem + Clone + 'static,
+    {
+        let map = {
+            let asys = asys.clone();
+            file.map_err(AssetError::FileIoError).and_then(move |mut f| {
+                let path = f.name();
+                let bytes = f.read_binary().map_err(AssetError::FileIoError)?;
+                let mut map = parse_map(&path, &bytes)?;
+
+                let tilesets: Vec<_> = map.tilesets
+                    .drain(..)
+                    .map(|t| load_tileset(&asys, &path, t))
+                    .collect();
+
+                Ok(future::join_all(tilesets).map(move |tilesets| (map, tilesets)))
+            })
+        };
+
+        Box::new(
+            map.flatten()
+                .and_then(move |(map, tilesets)| build_prefab(&asys, map, tilesets)),
+        )
+    }
+}
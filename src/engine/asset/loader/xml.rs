@@ -0,0 +1,206 @@
+// A minimal XML parser, enough for the TMX files of Tiled.
+//
+// Namespaces, DTDs and the encodings other than UTF-8 are not supported.
+
+#[derive(Debug, Default)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    /// The text between the tags, without the text of the children
+    pub text: String,
+}
+
+impl Element {
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|a| a.0 == name)
+            .map(|a| a.1.as_str())
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    pub fn children_named(&self, name: &str) -> Vec<&Element> {
+        self.children.iter().filter(|c| c.name == name).collect()
+    }
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+fn unescape(s: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let end = match rest[start..].find(';') {
+            Some(end) => start + end,
+            None => return Err(format!("unterminated entity in {}", s)),
+        };
+
+        let c = match &rest[start + 1..end] {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            e if e.starts_with("#x") => u32::from_str_radix(&e[2..], 16)
+                .ok()
+                .and_then(::std::char::from_u32),
+            e if e.starts_with('#') => e[1..].parse().ok().and_then(::std::char::from_u32),
+            _ => None,
+        };
+        match c {
+            Some(c) => result.push(c),
+            None => return Err(format!("unknown entity {}", &rest[start..end + 1])),
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    result.push_str(rest);
+    Ok(result)
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn error<T>(&self, reason: &str) -> Result<T, String> {
+        let line = self.s[..self.pos].matches('\n').count() + 1;
+        Err(format!("{} at line {}", reason, line))
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if !self.rest().starts_with(token) {
+            return self.error(&format!("expected {}", token));
+        }
+
+        self.pos += token.len();
+        Ok(())
+    }
+
+    /// Skip to the end token, returning what was before it
+    fn until(&mut self, end: &str) -> Result<&'a str, String> {
+        match self.rest().find(end) {
+            Some(i) => {
+                let s = &self.rest()[..i];
+                self.pos += i + end.len();
+                Ok(s)
+            }
+            None => self.error(&format!("expected {}", end)),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_left().len();
+    }
+
+    /// Skip the whitespace, comments, processing instructions and doctypes
+    fn skip_misc(&mut self) -> Result<(), String> {
+        loop {
+            self.skip_whitespace();
+
+            if self.rest().starts_with("<!--") {
+                self.until("-->")?;
+            } else if self.rest().starts_with("<?") {
+                self.until("?>")?;
+            } else if self.rest().starts_with("<!") {
+                self.until(">")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<&'a str, String> {
+        let rest = self.rest();
+        let len = rest.find(|c: char| c.is_whitespace() || "/>=".contains(c))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return self.error("expected a name");
+        }
+
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    fn element(&mut self) -> Result<Element, String> {
+        self.expect("<")?;
+        let mut element = Element {
+            name: self.name()?.to_owned(),
+            ..Element::default()
+        };
+
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.pos += 2;
+                return Ok(element);
+            }
+            if self.rest().starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+
+            let name = self.name()?.to_owned();
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+
+            let quote = if self.rest().starts_with('\'') { "'" } else { "\"" };
+            self.expect(quote)?;
+            let value = unescape(self.until(quote)?)?;
+            element.attributes.push((name, value));
+        }
+
+        loop {
+            let rest = self.rest();
+            if rest.starts_with("</") {
+                self.pos += 2;
+                if self.name()? != element.name {
+                    return self.error(&format!("expected the end of {}", element.name));
+                }
+                self.skip_whitespace();
+                self.expect(">")?;
+                return Ok(element);
+            } else if rest.starts_with("<![CDATA[") {
+                self.pos += 9;
+                element.text.push_str(self.until("]]>")?);
+            } else if rest.starts_with("<!--") {
+                self.until("-->")?;
+            } else if rest.starts_with("<?") {
+                self.until("?>")?;
+            } else if rest.starts_with('<') {
+                element.children.push(self.element()?);
+            } else if rest.is_empty() {
+                return self.error(&format!("expected the end of {}", element.name));
+            } else {
+                let len = rest.find('<').unwrap_or(rest.len());
+                element.text.push_str(&unescape(&rest[..len])?);
+                self.pos += len;
+            }
+        }
+    }
+}
+
+/// Parse the root element of a document
+pub fn parse(s: &str) -> Result<Element, String> {
+    let mut parser = Parser { s, pos: 0 };
+
+    // Byte order mark
+    if parser.rest().starts_with('\u{feff}') {
+        parser.pos += '\u{feff}'.len_utf8();
+    }
+
+    parser.skip_misc()?;
+    parser.element()
+}
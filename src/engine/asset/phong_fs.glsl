@@ -5,7 +5,18 @@ out vec4 FragColor;
 #define texture2D texture
 #endif
 
+// Light counts, set by the engine for each variant of the program
+#ifndef UNI_DIRECTIONAL_LIGHTS
+#define UNI_DIRECTIONAL_LIGHTS 1
+#endif
+
+#ifndef UNI_POINT_LIGHTS
 #define UNI_POINT_LIGHTS 4
+#endif
+
+#ifndef UNI_SPOT_LIGHTS
+#define UNI_SPOT_LIGHTS 0
+#endif
 
 struct DirectionalLight {
     vec3 direction;
@@ -29,6 +40,24 @@ struct PointLight {
     float rate;
 };
 
+struct SpotLight {
+    vec3 position;
+    vec3 direction;
+
+    float cutOff;
+    float outerCutOff;
+
+    float constant;
+    float linear;
+    float quadratic;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+
+    float rate;
+};
+
 struct Material {
     sampler2D diffuse;
     float shininess;
@@ -42,22 +71,38 @@ varying vec2 vTexCoords;
 varying vec3 vNormal;                       
 
 // Lights
-uniform DirectionalLight uDirectionalLight;
+uniform DirectionalLight uDirectionalLights[UNI_DIRECTIONAL_LIGHTS];
+#if UNI_POINT_LIGHTS > 0
 uniform PointLight uPointLights[UNI_POINT_LIGHTS];
+#endif
+#if UNI_SPOT_LIGHTS > 0
+uniform SpotLight uSpotLights[UNI_SPOT_LIGHTS];
+#endif
 
 vec3 CalcDirectionalLight(DirectionalLight light, vec3 normal, vec3 viewDir);
 vec3 CalcPointLight(PointLight light, vec3 normal, vec3 fragPos, vec3 viewDir);
+vec3 CalcSpotLight(SpotLight light, vec3 normal, vec3 fragPos, vec3 viewDir);
 
 void main(void) {
     vec3 norm = normalize(vNormal);
     vec3 viewDir = normalize(uViewPos - vFragPos);
+    vec3 result = vec3(0.0);
 
-    // Directional Light
-    vec3 result = CalcDirectionalLight(uDirectionalLight, norm, viewDir);
+    // Directional Lights
+    for(int i = 0; i < UNI_DIRECTIONAL_LIGHTS; i++)
+        result += CalcDirectionalLight(uDirectionalLights[i], norm, viewDir);
     
     // Point Lights
+#if UNI_POINT_LIGHTS > 0
     for(int i = 0; i < UNI_POINT_LIGHTS; i++)
         result += CalcPointLight(uPointLights[i], norm, vFragPos, viewDir);
+#endif
+
+    // Spot Lights
+#if UNI_SPOT_LIGHTS > 0
+    for(int i = 0; i < UNI_SPOT_LIGHTS; i++)
+        result += CalcSpotLight(uSpotLights[i], norm, vFragPos, viewDir);
+#endif
 
     gl_FragColor = vec4(result, 1.0);           
 }
@@ -105,3 +150,35 @@ vec3 CalcPointLight(PointLight light, vec3 normal, vec3 fragPos, vec3 viewDir)
     
     return (ambient + diffuse + specular) * light.rate;        
 }
+
+vec3 CalcSpotLight(SpotLight light, vec3 normal, vec3 fragPos, vec3 viewDir)
+{
+    vec3 lightDir = normalize(light.position - fragPos);
+
+    // diffuse shading
+    float diff = max(dot(normal, lightDir), 0.0);
+    // specular shading
+    vec3 reflectDir = reflect(-lightDir, normal);
+    float spec = pow(max(dot(viewDir, reflectDir), 0.0), uMaterial.shininess);
+
+    // attenuation
+    float distance = length(light.position - fragPos);
+    float d = (light.constant + light.linear * distance + light.quadratic * (distance * distance));
+    float attenuation = 1.0 / max(d, 0.001);
+
+    // soft edge between the inner and outer cones
+    float theta = dot(lightDir, normalize(-light.direction));
+    float epsilon = max(light.cutOff - light.outerCutOff, 0.001);
+    float intensity = clamp((theta - light.outerCutOff) / epsilon, 0.0, 1.0);
+
+    // combine results
+    vec3 ambient = light.ambient * vec3(texture2D(uMaterial.diffuse, vTexCoords));
+    vec3 diffuse = light.diffuse * diff * vec3(texture2D(uMaterial.diffuse, vTexCoords));
+    vec3 specular = light.specular * spec;
+
+    ambient *= attenuation;
+    diffuse *= attenuation * intensity;
+    specular *= attenuation * intensity;
+
+    return (ambient + diffuse + specular) * light.rate;
+}
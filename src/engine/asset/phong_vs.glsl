@@ -6,20 +6,52 @@
 attribute vec3 aVertexPosition;
 attribute vec3 aVertexNormal;
 attribute vec2 aTextureCoord;
+attribute vec4 aJoints;
+attribute vec4 aWeights;
+attribute mat4 aInstanceMatrix;
 
 uniform mat4 uMVMatrix;
 uniform mat4 uPMatrix;
 uniform mat4 uNMatrix;
 uniform mat4 uMMatrix;
+uniform mat4 uPVMatrix;
+
+// Set when drawn instanced, with the model matrices in aInstanceMatrix
+uniform bool uInstanced;
+
+#define MAX_JOINTS 32
+uniform bool uSkinned;
+uniform mat4 uJointMatrices[MAX_JOINTS];
 
 varying vec3 vFragPos;
 varying vec3 vNormal;
 varying vec2 vTexCoords;
 
+mat4 skinMatrix() {
+    if (!uSkinned) {
+        return mat4(1.0);
+    }
+
+    return aWeights.x * uJointMatrices[int(aJoints.x)] +
+        aWeights.y * uJointMatrices[int(aJoints.y)] +
+        aWeights.z * uJointMatrices[int(aJoints.z)] +
+        aWeights.w * uJointMatrices[int(aJoints.w)];
+}
+
 void main(void) {
-    vFragPos = vec3(uMMatrix * vec4(aVertexPosition, 1.0));            
-    vNormal = mat3(uNMatrix) * aVertexNormal;
-    vTexCoords = aTextureCoord;
+    mat4 skin = skinMatrix();
+    vec4 pos = skin * vec4(aVertexPosition, 1.0);
 
-    gl_Position = uPMatrix * uMVMatrix * vec4(aVertexPosition, 1.0);
+    if (uInstanced) {
+        // Transforms only have a uniform scale, the model matrix keeps the normals direction
+        vFragPos = vec3(aInstanceMatrix * pos);
+        vNormal = mat3(aInstanceMatrix) * mat3(skin) * aVertexNormal;
+        gl_Position = uPVMatrix * aInstanceMatrix * pos;
+    } else {
+        vFragPos = vec3(uMMatrix * pos);            
+        vNormal = mat3(uNMatrix) * mat3(skin) * aVertexNormal;
+        gl_Position = uPMatrix * uMVMatrix * pos;
+    }
+
+    vTexCoords = aTextureCoord;
 }
@@ -120,9 +120,12 @@ impl CubeMesh {
             vertices: vertices,
             uvs: Some(uvs),
             normals: Some(normals),
-            indices: indices,
+            indices: indices.into(),
             tangents: None,
             bitangents: None,
+            joints: None,
+            weights: None,
+            colors: None,
         }
     }
 }
@@ -161,9 +164,12 @@ impl PlaneMesh {
             vertices: vertices,
             uvs: Some(uvs),
             normals: Some(normals),
-            indices: indices,
+            indices: indices.into(),
             tangents: None,
             bitangents: None,
+            joints: None,
+            weights: None,
+            colors: None,
         }
     }
 }
@@ -27,9 +27,12 @@ impl QuadMesh {
             vertices: vertices,
             uvs: Some(uvs),
             normals: None,
-            indices: indices,
+            indices: indices.into(),
             tangents: None,
             bitangents: None,
+            joints: None,
+            weights: None,
+            colors: None,
         }
     }
 }
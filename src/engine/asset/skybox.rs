@@ -25,9 +25,12 @@ impl SkyboxMesh {
             vertices: vertices,
             uvs: None,
             normals: None,
-            indices: indices,
+            indices: indices.into(),
             tangents: None,
             bitangents: None,
+            joints: None,
+            weights: None,
+            colors: None,
         }
     }
 }
@@ -1,13 +1,15 @@
 use engine::asset::AssetResult;
 use engine::core::Component;
 use engine::engine::EngineStats;
-use engine::render::{CullMode, DepthTest, Material, MaterialState, MeshBuffer, ShaderProgram,
-                     Texture};
+use engine::render::{Blending, CullMode, DepthTest, Material, MaterialState, MeshBuffer,
+                     ShaderProgram, Texture, MAX_POINT_LIGHTS, MAX_SPOT_LIGHTS};
+use math::*;
+use std::cmp::Ordering;
 use std::collections::VecDeque;
 use std::rc::{Rc, Weak};
 use std::sync::Arc;
 use webgl;
-use webgl::{Culling, Flag, WebGLRenderingContext};
+use webgl::{BlendMode, Culling, Flag, WebGLRenderingContext};
 
 trait ToGLState<T> {
     fn as_gl_state(&self) -> T;
@@ -28,10 +30,23 @@ impl ToGLState<webgl::DepthTest> for DepthTest {
     }
 }
 
+/// The test keeping the same fragments when the depth goes from 1 to 0
+fn reverse_depth_test(d: DepthTest) -> DepthTest {
+    match d {
+        DepthTest::Less => DepthTest::Greater,
+        DepthTest::LessEqual => DepthTest::GreaterEqual,
+        DepthTest::Greater => DepthTest::Less,
+        DepthTest::GreaterEqual => DepthTest::LessEqual,
+        d => d,
+    }
+}
+
 #[derive(Default)]
 pub struct StateCache {
     state: MaterialState,
     curr: MaterialState,
+    /// Set for the cameras with a reversed depth
+    pub reversed_z: bool,
 }
 
 impl StateCache {
@@ -40,6 +55,7 @@ impl StateCache {
             cull: Some(CullMode::Back),
             depth_test: Some(DepthTest::Less),
             alpha_blending: Some(false),
+            blending: Some(Blending::Alpha),
             depth_write: Some(true),
         }
     }
@@ -50,6 +66,7 @@ impl StateCache {
         ms.depth_write.map(|s| self.curr.depth_write = Some(s));
         ms.alpha_blending
             .map(|s| self.curr.alpha_blending = Some(s));
+        ms.blending.map(|s| self.curr.blending = Some(s));
     }
 
     pub fn commit(&mut self, gl: &WebGLRenderingContext) {
@@ -59,6 +76,7 @@ impl StateCache {
         self.curr
             .alpha_blending
             .map(|s| self.apply_alpha_blending(gl, s));
+        self.curr.blending.map(|s| self.apply_blending(gl, s));
     }
 
     fn apply_depth_write(&mut self, gl: &WebGLRenderingContext, b: bool) {
@@ -88,21 +106,40 @@ impl StateCache {
         self.state.alpha_blending = Some(b);
     }
 
+    fn apply_blending(&mut self, gl: &WebGLRenderingContext, b: Blending) {
+        if self.state.blending == Some(b) {
+            return;
+        }
+
+        match b {
+            Blending::Alpha => gl.blend_func(BlendMode::SrcAlpha, BlendMode::OneMinusSrcAlpha),
+            Blending::Additive => gl.blend_func(BlendMode::SrcAlpha, BlendMode::One),
+        }
+
+        self.state.blending = Some(b);
+    }
+
     fn apply_depth_test(&mut self, gl: &WebGLRenderingContext, ct: &DepthTest) {
+        let ct = if self.reversed_z {
+            reverse_depth_test(*ct)
+        } else {
+            *ct
+        };
+
         if let Some(s) = self.state.depth_test {
-            if s == *ct {
+            if s == ct {
                 return;
             }
         }
 
-        if let &DepthTest::Never = ct {
+        if let DepthTest::Never = ct {
             gl.disable(webgl::Flag::DepthTest as i32);
         } else {
             gl.enable(webgl::Flag::DepthTest as i32);
             gl.depth_func(ct.as_gl_state());
         }
 
-        self.state.depth_test = Some(*ct);
+        self.state.depth_test = Some(ct);
     }
 
     fn apply_cull(&mut self, gl: &WebGLRenderingContext, cm: &CullMode) {
@@ -134,13 +171,61 @@ impl StateCache {
     }
 }
 
+/// A point or spot light, with the world position and range it is selected by
+pub struct LocalLight {
+    pub component: Arc<Component>,
+    pub position: Vector3f,
+    pub range: f32,
+}
+
+/// Indices of the point and spot lights selected for a render command
+#[derive(Default, Clone, PartialEq, Debug)]
+pub struct LightSelection {
+    pub points: Vec<usize>,
+    pub spots: Vec<usize>,
+}
+
+fn select_nearest(
+    lights: &[LocalLight],
+    sphere: &Option<(Vector3f, f32)>,
+    max: usize,
+) -> Vec<usize> {
+    let mut found: Vec<(f32, usize)> = lights
+        .iter()
+        .enumerate()
+        .filter_map(|(i, l)| {
+            let d = match *sphere {
+                Some((center, r)) => ((l.position - center).magnitude() - r).max(0.0),
+                None => 0.0,
+            };
+
+            if l.range <= 0.0 || d > l.range {
+                None
+            } else {
+                Some((d / l.range, i))
+            }
+        })
+        .collect();
+
+    // Nearest relatively to their range first
+    found.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    found.truncate(max);
+
+    // In a stable order, so the same lights keep the same slots
+    let mut result: Vec<usize> = found.into_iter().map(|(_, i)| i).collect();
+    result.sort();
+    result
+}
+
 pub struct EngineContext {
     pub mesh_buffer: Weak<MeshBuffer>,
     pub prog: Weak<ShaderProgram>,
     pub textures: VecDeque<(u32, Weak<Texture>)>,
 
-    pub main_light: Option<Arc<Component>>,
-    pub point_lights: Vec<Arc<Component>>,
+    /// The main light first
+    pub directional_lights: Vec<Arc<Component>>,
+    pub point_lights: Vec<LocalLight>,
+    pub spot_lights: Vec<LocalLight>,
 
     pub switch_mesh: u32,
     pub switch_prog: u32,
@@ -150,6 +235,7 @@ pub struct EngineContext {
     pub states: StateCache,
 
     pub last_light_bound: Option<Weak<ShaderProgram>>,
+    pub last_local_lights_bound: Option<(Weak<ShaderProgram>, LightSelection)>,
     pub last_material_bound: Option<Weak<Material>>,
 }
 
@@ -160,8 +246,9 @@ impl EngineContext {
             prog: Default::default(),
             textures: Default::default(),
 
-            main_light: Default::default(),
+            directional_lights: Default::default(),
             point_lights: Default::default(),
+            spot_lights: Default::default(),
 
             switch_mesh: 0,
             switch_prog: 0,
@@ -171,9 +258,30 @@ impl EngineContext {
 
             states: Default::default(),
             last_light_bound: None,
+            last_local_lights_bound: None,
             last_material_bound: None,
         }
     }
+
+    /// Forget what is bound, after a frame buffer switch or a program variant change
+    pub fn invalidate(&mut self) {
+        self.mesh_buffer = Weak::new();
+        self.prog = Weak::new();
+        self.textures.clear();
+
+        self.last_light_bound = None;
+        self.last_local_lights_bound = None;
+        self.last_material_bound = None;
+    }
+
+    /// The point and spot lights most relevant to an object in the world sphere,
+    /// or the first ones if it has no bounds
+    pub fn select_lights(&self, sphere: &Option<(Vector3f, f32)>) -> LightSelection {
+        LightSelection {
+            points: select_nearest(&self.point_lights, sphere, MAX_POINT_LIGHTS),
+            spots: select_nearest(&self.spot_lights, sphere, MAX_SPOT_LIGHTS),
+        }
+    }
 }
 
 macro_rules! impl_cacher {
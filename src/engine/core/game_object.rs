@@ -99,6 +99,7 @@ impl GameObjectUtil {
         GameObject {
             transform: Transform::new(node_id, tree),
             active: true,
+            layers: DEFAULT_LAYER,
             components: vec![],
         }
     }
@@ -198,9 +199,14 @@ impl Transform {
     }
 }
 
+/// The layer of new game objects
+pub const DEFAULT_LAYER: u32 = 1;
+
 pub struct GameObject {
     pub transform: Transform,
     pub active: bool,
+    /// Bit field of the layers of this object, see Camera::culling_mask
+    pub layers: u32,
     components: Vec<Arc<Component>>,
 }
 
@@ -210,6 +216,7 @@ impl GameObject {
         Rc::new(RefCell::new(GameObject {
             transform: Transform::new(0, rc::Weak::new()),
             active: true,
+            layers: DEFAULT_LAYER,
             components: vec![],
         }))
     }
@@ -248,6 +255,10 @@ impl GameObject {
         }
     }
 
+    pub fn components(&self) -> &[Arc<Component>] {
+        &self.components
+    }
+
     pub fn add_component<T>(&mut self, c: T) -> Arc<Component>
     where
         T: IntoComponentPtr,
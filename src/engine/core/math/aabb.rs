@@ -64,6 +64,39 @@ impl Aabb {
         ]
     }
 
+    pub fn union(&self, other: &Self) -> Self {
+        let mut r = *self;
+        r.merge(other);
+        r
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x && self.min.y <= other.max.y
+            && self.max.y >= other.min.y && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+
+    pub fn contains(&self, other: &Self) -> bool {
+        self.min.x <= other.min.x && self.min.y <= other.min.y && self.min.z <= other.min.z
+            && self.max.x >= other.max.x && self.max.y >= other.max.y
+            && self.max.z >= other.max.z
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let d = self.max - self.min;
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// Squared distance from p to the box, 0 if p is inside
+    pub fn distance2(&self, p: &Vector3f) -> f32 {
+        let mut d2 = 0.0;
+        for i in 0..3 {
+            let d = (self.min[i] - p[i]).max(0.0).max(p[i] - self.max[i]);
+            d2 += d * d;
+        }
+        d2
+    }
+
     pub fn sphere(&self) -> (Vector3f, f32) {
         let center = (self.max + self.min) * 0.5;
 
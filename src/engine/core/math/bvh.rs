@@ -0,0 +1,385 @@
+use math::Vector3f;
+use super::Aabb;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Handle of a leaf inserted in a Bvh
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BvhProxy(usize);
+
+struct BvhNode<T> {
+    /// Enlarged by the margin for leaves
+    aabb: Aabb,
+    /// The exact bounds of leaves
+    tight: Aabb,
+    parent: Option<usize>,
+    children: Option<(usize, usize)>,
+    height: i32,
+    data: Option<T>,
+}
+
+/// A dynamic bounding volume hierarchy, balanced with tree rotations.
+///
+/// Leaves are stored with a fat box, so objects moving inside
+/// it do not change the tree.
+pub struct Bvh<T> {
+    nodes: Vec<BvhNode<T>>,
+    free: Vec<usize>,
+    root: Option<usize>,
+    len: usize,
+    pub margin: f32,
+}
+
+struct Candidate {
+    d2: f32,
+    node: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Candidate) -> bool {
+        self.d2 == other.d2
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Candidate) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    // Reversed, BinaryHeap pops the nearest first
+    fn cmp(&self, other: &Candidate) -> Ordering {
+        other
+            .d2
+            .partial_cmp(&self.d2)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl<T> Default for Bvh<T> {
+    fn default() -> Bvh<T> {
+        Bvh::new()
+    }
+}
+
+impl<T> Bvh<T> {
+    pub fn new() -> Bvh<T> {
+        Bvh {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: None,
+            len: 0,
+            margin: 0.1,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, aabb: Aabb, data: T) -> BvhProxy {
+        let leaf = self.alloc(BvhNode {
+            aabb: self.fatten(&aabb),
+            tight: aabb,
+            parent: None,
+            children: None,
+            height: 0,
+            data: Some(data),
+        });
+
+        self.insert_leaf(leaf);
+        self.len += 1;
+        BvhProxy(leaf)
+    }
+
+    pub fn remove(&mut self, proxy: BvhProxy) -> T {
+        let leaf = proxy.0;
+        self.remove_leaf(leaf);
+        self.len -= 1;
+
+        self.free.push(leaf);
+        self.nodes[leaf].data.take().expect("Invalid BvhProxy")
+    }
+
+    /// Move a leaf, return true if the tree was changed
+    pub fn update(&mut self, proxy: BvhProxy, aabb: Aabb) -> bool {
+        let leaf = proxy.0;
+        self.nodes[leaf].tight = aabb;
+
+        if self.nodes[leaf].aabb.contains(&aabb) {
+            return false;
+        }
+
+        self.remove_leaf(leaf);
+        self.nodes[leaf].aabb = self.fatten(&aabb);
+        self.insert_leaf(leaf);
+        true
+    }
+
+    pub fn get(&self, proxy: BvhProxy) -> &T {
+        self.nodes[proxy.0].data.as_ref().expect("Invalid BvhProxy")
+    }
+
+    pub fn aabb(&self, proxy: BvhProxy) -> &Aabb {
+        &self.nodes[proxy.0].tight
+    }
+
+    /// Visit the leaves for which test returns true on their bounds
+    /// and on the bounds of all their ancestors.
+    pub fn query<F, V>(&self, mut test: F, mut visit: V)
+    where
+        F: FnMut(&Aabb) -> bool,
+        V: FnMut(BvhProxy, &T),
+    {
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+
+            match node.children {
+                Some((a, b)) => {
+                    if test(&node.aabb) {
+                        stack.push(a);
+                        stack.push(b);
+                    }
+                }
+                None => {
+                    if test(&node.tight) {
+                        visit(BvhProxy(i), node.data.as_ref().unwrap());
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn query_aabb<V>(&self, aabb: &Aabb, visit: V)
+    where
+        V: FnMut(BvhProxy, &T),
+    {
+        self.query(|b| b.intersects(aabb), visit)
+    }
+
+    pub fn query_sphere<V>(&self, center: Vector3f, r: f32, visit: V)
+    where
+        V: FnMut(BvhProxy, &T),
+    {
+        self.query(|b| b.distance2(&center) <= r * r, visit)
+    }
+
+    /// The k leaves nearest to p with their distance, nearest first
+    pub fn nearest(&self, p: Vector3f, k: usize) -> Vec<(BvhProxy, f32)> {
+        let mut result = Vec::new();
+        let mut heap = BinaryHeap::new();
+
+        if let Some(root) = self.root {
+            heap.push(Candidate {
+                d2: self.distance2(root, &p),
+                node: root,
+            });
+        }
+
+        // A leaf popped is nearer than the bounds of everything left
+        while let Some(Candidate { d2, node }) = heap.pop() {
+            if result.len() >= k {
+                break;
+            }
+
+            match self.nodes[node].children {
+                Some((a, b)) => {
+                    heap.push(Candidate {
+                        d2: self.distance2(a, &p),
+                        node: a,
+                    });
+                    heap.push(Candidate {
+                        d2: self.distance2(b, &p),
+                        node: b,
+                    });
+                }
+                None => result.push((BvhProxy(node), d2.sqrt())),
+            }
+        }
+
+        result
+    }
+
+    fn distance2(&self, i: usize, p: &Vector3f) -> f32 {
+        let node = &self.nodes[i];
+        match node.children {
+            Some(_) => node.aabb.distance2(p),
+            None => node.tight.distance2(p),
+        }
+    }
+
+    fn fatten(&self, aabb: &Aabb) -> Aabb {
+        let m = Vector3f::new(self.margin, self.margin, self.margin);
+        Aabb {
+            min: aabb.min - m,
+            max: aabb.max + m,
+        }
+    }
+
+    fn alloc(&mut self, node: BvhNode<T>) -> usize {
+        match self.free.pop() {
+            Some(i) => {
+                self.nodes[i] = node;
+                i
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn replace_child(&mut self, parent: Option<usize>, old: usize, new: usize) {
+        match parent {
+            Some(p) => {
+                let (a, b) = self.nodes[p].children.unwrap();
+                self.nodes[p].children = Some(if a == old { (new, b) } else { (a, new) });
+            }
+            None => self.root = Some(new),
+        }
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        let mut index = match self.root {
+            Some(root) => root,
+            None => {
+                self.root = Some(leaf);
+                self.nodes[leaf].parent = None;
+                return;
+            }
+        };
+
+        // Find the best sibling by the surface area heuristic
+        let leaf_aabb = self.nodes[leaf].aabb;
+        while let Some((a, b)) = self.nodes[index].children {
+            let area = self.nodes[index].aabb.surface_area();
+            let combined = self.nodes[index].aabb.union(&leaf_aabb).surface_area();
+
+            let cost = 2.0 * combined;
+            let inheritance = 2.0 * (combined - area);
+
+            let child_cost = |c: usize| {
+                let n = &self.nodes[c];
+                let area = n.aabb.union(&leaf_aabb).surface_area();
+                match n.children {
+                    Some(_) => area - n.aabb.surface_area() + inheritance,
+                    None => area + inheritance,
+                }
+            };
+            let cost_a = child_cost(a);
+            let cost_b = child_cost(b);
+
+            if cost < cost_a && cost < cost_b {
+                break;
+            }
+
+            index = if cost_a < cost_b { a } else { b };
+        }
+
+        let sibling = index;
+        let old_parent = self.nodes[sibling].parent;
+        let new_parent = self.alloc(BvhNode {
+            aabb: self.nodes[sibling].aabb.union(&leaf_aabb),
+            tight: Aabb::empty(),
+            parent: old_parent,
+            children: Some((sibling, leaf)),
+            height: self.nodes[sibling].height + 1,
+            data: None,
+        });
+
+        self.replace_child(old_parent, sibling, new_parent);
+        self.nodes[sibling].parent = Some(new_parent);
+        self.nodes[leaf].parent = Some(new_parent);
+
+        self.refit(Some(new_parent));
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        let parent = match self.nodes[leaf].parent {
+            Some(parent) => parent,
+            None => {
+                self.root = None;
+                return;
+            }
+        };
+
+        let grand_parent = self.nodes[parent].parent;
+        let (a, b) = self.nodes[parent].children.unwrap();
+        let sibling = if a == leaf { b } else { a };
+
+        self.replace_child(grand_parent, parent, sibling);
+        self.nodes[sibling].parent = grand_parent;
+        self.nodes[parent].children = None;
+        self.free.push(parent);
+
+        self.refit(grand_parent);
+    }
+
+    /// Walk back to the root, fixing the bounds and heights
+    fn refit(&mut self, mut index: Option<usize>) {
+        while let Some(i) = index {
+            let i = self.balance(i);
+            let (a, b) = self.nodes[i].children.unwrap();
+
+            self.nodes[i].height = 1 + self.nodes[a].height.max(self.nodes[b].height);
+            self.nodes[i].aabb = self.nodes[a].aabb.union(&self.nodes[b].aabb);
+
+            index = self.nodes[i].parent;
+        }
+    }
+
+    /// Rotate the taller grand child up if a is unbalanced,
+    /// return the new root of the subtree
+    fn balance(&mut self, a: usize) -> usize {
+        let (b, c) = match self.nodes[a].children {
+            Some(children) if self.nodes[a].height >= 2 => children,
+            _ => return a,
+        };
+
+        let diff = self.nodes[c].height - self.nodes[b].height;
+        if diff > 1 {
+            self.rotate(a, c, b)
+        } else if diff < -1 {
+            self.rotate(a, b, c)
+        } else {
+            a
+        }
+    }
+
+    /// Lift the child up, in place of a
+    fn rotate(&mut self, a: usize, up: usize, other: usize) -> usize {
+        let (f, g) = self.nodes[up].children.unwrap();
+
+        let parent = self.nodes[a].parent;
+        self.nodes[up].parent = parent;
+        self.nodes[a].parent = Some(up);
+        self.replace_child(parent, a, up);
+
+        // The taller grand child stays under the lifted node
+        let (keep, give) = if self.nodes[f].height > self.nodes[g].height {
+            (f, g)
+        } else {
+            (g, f)
+        };
+
+        self.nodes[up].children = Some((a, keep));
+        self.nodes[a].children = Some((other, give));
+        self.nodes[give].parent = Some(a);
+
+        self.nodes[a].aabb = self.nodes[other].aabb.union(&self.nodes[give].aabb);
+        self.nodes[a].height = 1 + self.nodes[other].height.max(self.nodes[give].height);
+        self.nodes[up].aabb = self.nodes[a].aabb.union(&self.nodes[keep].aabb);
+        self.nodes[up].height = 1 + self.nodes[a].height.max(self.nodes[keep].height);
+
+        up
+    }
+}
@@ -1,3 +1,7 @@
 mod aabb;
+mod bvh;
+mod ray;
 
 pub use self::aabb::Aabb;
+pub use self::bvh::{Bvh, BvhProxy};
+pub use self::ray::Ray;
@@ -0,0 +1,118 @@
+use math::*;
+use super::Aabb;
+
+/// A half line from origin, hits are reported in multiples of direction
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+    pub origin: Vector3f,
+    pub direction: Vector3f,
+}
+
+impl Ray {
+    pub fn new(origin: Vector3f, direction: Vector3f) -> Ray {
+        Ray {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    pub fn at(&self, t: f32) -> Vector3f {
+        self.origin + self.direction * t
+    }
+
+    /// Transform the ray without normalizing its direction,
+    /// so the hit distances are the same in both spaces.
+    pub fn transform(&self, m: &Matrix4f) -> Ray {
+        Ray {
+            origin: m.transform_point(Point3::from_vec(self.origin)).to_vec(),
+            direction: m.transform_vector(self.direction),
+        }
+    }
+
+    /// Nearest non negative hit on the sphere, or 0 when starting inside it
+    pub fn intersect_sphere(&self, center: Vector3f, r: f32) -> Option<f32> {
+        let oc = self.origin - center;
+        let a = self.direction.magnitude2();
+        let b = oc.dot(self.direction);
+        let c = oc.magnitude2() - r * r;
+
+        if c <= 0.0 {
+            return Some(0.0);
+        }
+
+        let disc = b * b - a * c;
+        if disc < 0.0 || b > 0.0 {
+            return None;
+        }
+
+        Some((-b - disc.sqrt()) / a)
+    }
+
+    /// Slab test, return the entry distance (0 when starting inside)
+    /// and the axis of the entered face
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<(f32, usize)> {
+        let mut tmin: f32 = 0.0;
+        let mut tmax = ::std::f32::MAX;
+        let mut axis = 0;
+
+        for i in 0..3 {
+            let d = self.direction[i];
+            let o = self.origin[i];
+
+            if d.abs() < ::std::f32::EPSILON {
+                if o < aabb.min[i] || o > aabb.max[i] {
+                    return None;
+                }
+                continue;
+            }
+
+            let t1 = (aabb.min[i] - o) / d;
+            let t2 = (aabb.max[i] - o) / d;
+            let (near, far) = if t1 < t2 { (t1, t2) } else { (t2, t1) };
+
+            if near > tmin {
+                tmin = near;
+                axis = i;
+            }
+            tmax = tmax.min(far);
+
+            if tmin > tmax {
+                return None;
+            }
+        }
+
+        Some((tmin, axis))
+    }
+
+    /// Moller-Trumbore test against both sides of the triangle
+    pub fn intersect_triangle(&self, a: Vector3f, b: Vector3f, c: Vector3f) -> Option<f32> {
+        let e1 = b - a;
+        let e2 = c - a;
+        let p = self.direction.cross(e2);
+        let det = e1.dot(p);
+
+        if det.abs() < 1e-8 {
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+        let s = self.origin - a;
+        let u = s.dot(p) * inv_det;
+        if u < 0.0 || u > 1.0 {
+            return None;
+        }
+
+        let q = s.cross(e1);
+        let v = self.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = e2.dot(q) * inv_det;
+        if t < 0.0 {
+            None
+        } else {
+            Some(t)
+        }
+    }
+}
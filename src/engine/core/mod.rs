@@ -2,7 +2,7 @@ mod game_object;
 mod scene_tree;
 mod math;
 
-pub use self::game_object::{Component, ComponentBased, GameObject};
+pub use self::game_object::{Component, ComponentBased, GameObject, DEFAULT_LAYER};
 pub use self::scene_tree::{ComponentEvent, SceneTree};
 pub use self::math::*;
 
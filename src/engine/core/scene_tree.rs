@@ -42,6 +42,10 @@ pub struct SceneTree {
     curr_id: Cell<u64>,
     weak_self: RefCell<Weak<SceneTree>>,
 
+    tracking: Cell<bool>,
+    moved: RefCell<Vec<u64>>,
+    removed: RefCell<Vec<u64>>,
+
     component_watcher:
         RefCell<Vec<Box<FnMut(ComponentEvent, &Rc<RefCell<GameObject>>, &Arc<Component>)>>>,
 }
@@ -54,12 +58,30 @@ impl SceneTree {
         self.component_watcher.borrow_mut().push(Box::new(f));
     }
 
+    /// Start recording the moved and removed nodes
+    pub fn track_changes(&self) {
+        self.tracking.set(true);
+    }
+
+    /// Nodes which global transform changed since the last call, new nodes included
+    pub fn take_moved(&self) -> Vec<u64> {
+        self.moved.replace(Vec::new())
+    }
+
+    /// Nodes removed since the last call
+    pub fn take_removed(&self) -> Vec<u64> {
+        self.removed.replace(Vec::new())
+    }
+
     pub fn new() -> Rc<SceneTree> {
         let s = SceneTree {
             nodes: RefCell::new(BTreeMap::default()),
             root: GameObject::empty(),
             weak_self: RefCell::new(Weak::new()),
             curr_id: Cell::new(1),
+            tracking: Cell::new(false),
+            moved: Default::default(),
+            removed: Default::default(),
             component_watcher: Default::default(),
         };
 
@@ -123,6 +145,10 @@ impl SceneTree {
             },
         );
 
+        if self.tracking.get() {
+            self.moved.borrow_mut().push(id);
+        }
+
         go
     }
 
@@ -140,11 +166,20 @@ impl SceneTree {
         parent_node.children.retain(|&x| x != node_id);
         drop(parent_node);
 
-        for child_id in children_id {
-            let child_node = nodes.get_mut(&child_id).unwrap();
+        for child_id in children_id.iter() {
+            let child_node = nodes.get_mut(child_id).unwrap();
             // Root adapted.
             child_node.parent = 0;
         }
+        drop(nodes);
+
+        if self.tracking.get() {
+            self.removed.borrow_mut().push(node_id);
+        }
+
+        for child_id in children_id {
+            self.set_dirty(child_id);
+        }
     }
 
     pub fn add_child(&self, parent_id: u64, child_id: u64) -> Rc<RefCell<GameObject>> {
@@ -163,7 +198,11 @@ impl SceneTree {
         let parent_node = nodes.get_mut(&old_parent_id).unwrap();
         parent_node.children.retain(|&x| x != child_id);
 
-        parent_node.go.upgrade().unwrap_or(self.root.clone())
+        let old_parent = parent_node.go.upgrade().unwrap_or(self.root.clone());
+        drop(nodes);
+
+        self.set_dirty(child_id);
+        old_parent
     }
 
     pub fn set_local_transform(&self, node_id: u64, t: NodeTransform) {
@@ -171,23 +210,31 @@ impl SceneTree {
         let n = nodes.get_mut(&node_id).unwrap();
 
         n.transform = t;
-        n.dirty = true;
         drop(nodes);
 
         // set all child
         self.set_dirty(node_id);
     }
 
+    /// Invalidate the global matrix of the node and its descendants
     pub fn set_dirty(&self, node_id: u64) {
         let mut nodes = self.nodes.borrow_mut();
-        let n = nodes.get_mut(&node_id).unwrap();
-
-        if n.dirty {
-            return;
-        }
-
-        for c in n.children.iter() {
-            self.set_dirty(*c);
+        let mut moved = self.moved.borrow_mut();
+        let mut stack = vec![node_id];
+
+        while let Some(id) = stack.pop() {
+            let n = nodes.get_mut(&id).unwrap();
+
+            // Descendants of a dirty node are dirty already
+            if n.dirty && id != node_id {
+                continue;
+            }
+
+            n.dirty = true;
+            if self.tracking.get() {
+                moved.push(id);
+            }
+            stack.extend(n.children.iter());
         }
     }
 
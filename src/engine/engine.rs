@@ -1,18 +1,22 @@
 use math::*;
 use webgl::*;
 
+use std::any::TypeId;
 use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::rc::{Rc, Weak};
-use std::sync::Arc;
+use std::sync::{self, Arc};
 
 use engine::asset::{AssetError, AssetResult, AssetSystem};
-use engine::context::EngineContext;
-use engine::core::{Component, ComponentBased, GameObject, SceneTree};
-use engine::render::Camera;
-use engine::render::{DepthTest, Directional, Light, Material, MaterialState, Mesh, MeshSurface,
-                     ShaderProgram};
-use engine::render::{Frustum, RenderQueue};
+use engine::context::{EngineContext, LightSelection, LocalLight};
+use engine::core::{Component, ComponentBased, ComponentEvent, GameObject, SceneTree};
+use engine::render::{Camera, RenderingPath};
+use engine::render::{CullMode, DepthTest, Directional, GBuffer, InstanceBuffer, Light, Material,
+                     MaterialState, Mesh, MeshBuffer, MeshSurface, ParticleSystem,
+                     PostProcessStack, RenderTexture, ShaderProgram, Skeleton, Sprite,
+                     SpriteBatch, Tilemap, MAX_DIRECTIONAL_LIGHTS, MAX_POINT_LIGHTS, MAX_SPOT_LIGHTS};
+use engine::render::{Frustum, RenderQueue, SpatialIndex};
 use image;
 use math::Aabb;
 
@@ -41,6 +45,19 @@ pub struct EngineStats {
     pub transparent_count: u32,
     pub total_opaque_count: u32,
     pub total_transparent_count: u32,
+    /// Draw submissions, an instanced draw counts once
+    pub batch_count: u32,
+}
+
+impl EngineStats {
+    fn add(&mut self, other: &EngineStats) {
+        self.surfaces_count += other.surfaces_count;
+        self.opaque_count += other.opaque_count;
+        self.transparent_count += other.transparent_count;
+        self.total_opaque_count += other.total_opaque_count;
+        self.total_transparent_count += other.total_transparent_count;
+        self.batch_count += other.batch_count;
+    }
 }
 
 pub struct Engine<A>
@@ -58,12 +75,40 @@ where
     pub gui_context: Rc<RefCell<imgui::Context>>,
 
     pub stats: EngineStats,
+
+    spatial_index: RefCell<SpatialIndex>,
+    components: Rc<RefCell<ComponentIndex>>,
+    instance_buffer: InstanceBuffer,
+    gbuffer: RefCell<Option<GBuffer>>,
+    sprite_batch: RefCell<SpriteBatch>,
+}
+
+/// Components by type, in the order they were added
+#[derive(Default)]
+struct ComponentIndex {
+    by_type: HashMap<TypeId, Vec<(Weak<RefCell<GameObject>>, sync::Weak<Component>)>>,
+}
+
+impl ComponentIndex {
+    fn notify(&mut self, evt: ComponentEvent, go: &Rc<RefCell<GameObject>>, c: &Arc<Component>) {
+        let list = self.by_type.entry(c.typeid()).or_insert_with(Vec::new);
+
+        match evt {
+            ComponentEvent::Add => list.push((Rc::downgrade(go), Arc::downgrade(c))),
+            ComponentEvent::Remove => {
+                list.retain(|&(_, ref wc)| wc.upgrade().map_or(false, |wc| !Arc::ptr_eq(&wc, c)))
+            }
+        }
+    }
 }
 
 struct RenderCommand {
     pub surface: Rc<MeshSurface>,
     pub model_m: Matrix4<f32>,
+    /// World bounding sphere, to select the lights
+    pub sphere: Option<(Vector3f, f32)>,
     pub cam_distance: f32,
+    pub joint_matrices: Option<Rc<Vec<Matrix4<f32>>>>,
 }
 
 #[derive(Default)]
@@ -78,7 +123,8 @@ impl RenderQueueState {
             let adist: f32 = a.cam_distance;
             let bdist: f32 = b.cam_distance;
 
-            bdist.partial_cmp(&adist).unwrap()
+            // Particles with non-finite positions give non-finite distances
+            bdist.partial_cmp(&adist).unwrap_or(Ordering::Equal)
         });
 
         self
@@ -89,19 +135,20 @@ impl RenderQueueState {
             let adist: f32 = a.cam_distance;
             let bdist: f32 = b.cam_distance;
 
-            adist.partial_cmp(&bdist).unwrap()
+            adist.partial_cmp(&bdist).unwrap_or(Ordering::Equal)
         });
 
         self
     }
 
     fn sort_by_material(&mut self) -> &mut Self {
+        // Commands sharing the buffer too are kept together, to be batched
         self.commands.sort_by(|a, b| {
             let prog_a: &Material = &a.surface.material;
             let prog_b: &Material = &b.surface.material;
 
-            let adist = prog_a as *const Material;
-            let bdist = prog_b as *const Material;
+            let adist = (prog_a as *const Material, &*a.surface.buffer as *const MeshBuffer);
+            let bdist = (prog_b as *const Material, &*b.surface.buffer as *const MeshBuffer);
 
             adist.partial_cmp(&bdist).unwrap()
         });
@@ -110,9 +157,17 @@ impl RenderQueueState {
     }
 }
 
+/// How render_commands draws the surfaces
+#[derive(Copy, Clone)]
+enum DrawMode<'a> {
+    /// With their material, or the replacement one
+    Forward(Option<&'a Rc<Material>>),
+    /// Into the G-buffer, with the gbuffer_program of their material
+    GBuffer,
+}
+
 #[derive(Default)]
 struct RenderQueueList {
-    aabb: Option<Aabb>,
     queues: BTreeMap<RenderQueue, RenderQueueState>,
 }
 
@@ -159,6 +214,7 @@ fn compute_model_m(object: &GameObject) -> Matrix4<f32> {
     object.transform.as_global_matrix()
 }
 
+#[derive(Copy, Clone, Debug)]
 pub struct ClearOption {
     pub color: Option<(f32, f32, f32, f32)>,
     pub clear_color: bool,
@@ -206,6 +262,19 @@ where
         }
     }
 
+    /// Clear the depth to the far plane of the camera, 0 when its depth is reversed
+    fn clear_camera(&self, camera: &Camera, option: ClearOption) {
+        if camera.reversed_z {
+            self.gl.clear_depth(0.0);
+        }
+
+        self.clear(option);
+
+        if camera.reversed_z {
+            self.gl.clear_depth(1.0);
+        }
+    }
+
     pub fn resize(&mut self, size: (u32, u32)) {
         self.screen_size = size;
 
@@ -213,22 +282,29 @@ where
     }
 
     #[cfg_attr(feature = "flame_it", flame)]
-    fn setup_material(&self, ctx: &mut EngineContext, material: &Rc<Material>) -> AssetResult<()> {
+    fn setup_material(
+        &self,
+        ctx: &mut EngineContext,
+        material: &Rc<Material>,
+        program: &Rc<ShaderProgram>,
+    ) -> AssetResult<()> {
         if let Some(ref last_material) = ctx.last_material_bound {
             if let Some(last_material) = last_material.upgrade() {
-                if Rc::ptr_eq(&last_material, &material) {
+                // The gbuffer_program of the material is another program
+                let same_prog = ctx.prog.upgrade().map_or(false, |p| Rc::ptr_eq(&p, program));
+                if Rc::ptr_eq(&last_material, &material) && same_prog {
                     return Ok(());
                 }
             }
         }
 
-        ctx.prepare_cache(&material.program, |ctx| {
-            material.program.bind(&self.gl)?;
+        ctx.prepare_cache(program, |ctx| {
+            program.bind(&self.gl)?;
             ctx.switch_prog += 1;
             Ok(())
         })?;
 
-        material.bind(|tex| {
+        material.bind_to(program, |tex| {
             ctx.prepare_cache_tex(tex, |ctx, unit| {
                 // Binding texture
                 tex.bind(&self.gl, unit)?;
@@ -249,7 +325,7 @@ where
     fn setup_camera(&self, ctx: &mut EngineContext, modelm: Matrix4<f32>, camera: &Camera) {
         let prog = ctx.prog.upgrade().unwrap();
         // setup_camera
-        let perspective = camera.perspective(self.screen_size);
+        let perspective = camera.projection_matrix(self.screen_size);
 
         prog.set("uMVMatrix", camera.v * modelm);
         prog.set("uPMatrix", perspective);
@@ -260,14 +336,36 @@ where
             camera.v.z.truncate(),
         );
 
+        // The skybox is drawn at the far plane, at ndc depth 1 or -1 when reversed
+        let mut skybox_pv = perspective * Matrix4::from(skybox_v);
+        let far = if camera.reversed_z { -1.0 } else { 1.0 };
+        skybox_pv.x.z = skybox_pv.x.w * far;
+        skybox_pv.y.z = skybox_pv.y.w * far;
+        skybox_pv.z.z = skybox_pv.z.w * far;
+        skybox_pv.w.z = skybox_pv.w.w * far;
+
         prog.set("uPVMatrix", perspective * camera.v);
-        prog.set("uPVSkyboxMatrix", perspective * Matrix4::from(skybox_v));
+        prog.set("uPVSkyboxMatrix", skybox_pv);
 
         prog.set("uNMatrix", modelm.inverse_transform().unwrap().transpose());
         prog.set("uMMatrix", modelm);
         prog.set("uViewPos", camera.eye());
     }
 
+    fn setup_skin(&self, ctx: &mut EngineContext, joint_matrices: &Option<Rc<Vec<Matrix4<f32>>>>) {
+        let prog = ctx.prog.upgrade().unwrap();
+
+        match *joint_matrices {
+            Some(ref matrices) => {
+                prog.set("uSkinned", true);
+                for (i, m) in matrices.iter().enumerate() {
+                    prog.set(format!("uJointMatrices[{}]", i), *m);
+                }
+            }
+            None => prog.set("uSkinned", false),
+        }
+    }
+
     #[cfg_attr(feature = "flame_it", flame)]
     fn setup_light(&self, ctx: &mut EngineContext) {
         // Setup light
@@ -283,20 +381,66 @@ where
 
         ctx.last_light_bound = Some(ctx.prog.clone());
 
-        let light_com = ctx.main_light.as_ref().unwrap();
+        let light_com = &ctx.directional_lights[0];
         let light = light_com.try_as::<Light>().unwrap();
 
         light.borrow().bind("uDirectionalLight", &prog);
         // So shader needs to have a vs stage light
         light.borrow().bind("uDirectionalLightVS", &prog);
 
-        for (i, plight_com) in ctx.point_lights.iter().enumerate() {
-            let plight = plight_com.try_as::<Light>().unwrap();
-            let name = format!("uPointLights[{}]", i);
-            plight.borrow().bind(&name, &prog);
+        for (i, dlight_com) in ctx.directional_lights.iter().enumerate() {
+            let dlight = dlight_com.try_as::<Light>().unwrap();
+            let name = format!("uDirectionalLights[{}]", i);
+            dlight.borrow().bind(&name, &prog);
+        }
+    }
+
+    /// Bind the point and spot lights selected for a command
+    fn setup_local_lights(&self, ctx: &mut EngineContext, selection: &LightSelection) {
+        let prog = ctx.prog.upgrade().unwrap();
 
-            let name = format!("uPointLightsVS[{}]", i);
-            plight.borrow().bind(&name, &prog);
+        if let Some((ref last_prog, ref last_selection)) = ctx.last_local_lights_bound {
+            if let Some(last_prog) = last_prog.upgrade() {
+                if Rc::ptr_eq(&prog, &last_prog) && last_selection == selection {
+                    return;
+                }
+            }
+        }
+
+        ctx.last_local_lights_bound = Some((ctx.prog.clone(), selection.clone()));
+
+        for i in 0..MAX_POINT_LIGHTS {
+            match selection.points.get(i) {
+                Some(&index) => {
+                    let plight = ctx.point_lights[index].component.try_as::<Light>().unwrap();
+                    plight.borrow().bind(&format!("uPointLights[{}]", i), &prog);
+                    plight.borrow().bind(&format!("uPointLightsVS[{}]", i), &prog);
+                    plight
+                        .borrow()
+                        .bind_shadow(&format!("uPointShadows[{}]", i), &prog);
+                }
+                // Programs with a fixed number of lights skip the unused ones
+                None => {
+                    prog.set(format!("uPointLights[{}].rate", i), 0.0);
+                    prog.set(format!("uPointShadows[{}].enabled", i), false);
+                }
+            }
+        }
+
+        for i in 0..MAX_SPOT_LIGHTS {
+            match selection.spots.get(i) {
+                Some(&index) => {
+                    let slight = ctx.spot_lights[index].component.try_as::<Light>().unwrap();
+                    slight.borrow().bind(&format!("uSpotLights[{}]", i), &prog);
+                    slight
+                        .borrow()
+                        .bind_shadow(&format!("uSpotShadows[{}]", i), &prog);
+                }
+                None => {
+                    prog.set(format!("uSpotLights[{}].rate", i), 0.0);
+                    prog.set(format!("uSpotShadows[{}].enabled", i), false);
+                }
+            }
         }
     }
 
@@ -306,22 +450,67 @@ where
         ctx: &mut EngineContext,
         q: &RenderQueueState,
         camera: &Camera,
-        material: Option<&Rc<Material>>,
+        mode: DrawMode,
     ) {
         let gl = &self.gl;
+        let material_of = |cmd: &RenderCommand| match mode {
+            DrawMode::Forward(Some(m)) => m.clone(),
+            _ => cmd.surface.material.clone(),
+        };
+        let program_of = |mat: &Rc<Material>| match mode {
+            DrawMode::GBuffer => mat.gbuffer_program
+                .clone()
+                .unwrap_or_else(|| mat.program.clone()),
+            DrawMode::Forward(_) => mat.program.clone(),
+        };
+        // The lights are applied to the G-buffer afterward
+        let lit = match mode {
+            DrawMode::GBuffer => false,
+            DrawMode::Forward(_) => true,
+        };
 
-        for cmd in q.commands.iter() {
-            let mat = match material.as_ref() {
-                Some(&m) => &m,
-                None => &cmd.surface.material,
-            };
+        let selections: Vec<LightSelection> = q.commands
+            .iter()
+            .map(|c| ctx.select_lights(&c.sphere))
+            .collect();
+
+        let mut i = 0;
+        while i < q.commands.len() {
+            let start = i;
+            let cmd = &q.commands[start];
+            let mat = &material_of(cmd);
+            let program = &program_of(mat);
+            let lights = &selections[start];
+            i += 1;
+
+            // This command and the following ones which can be drawn instanced with it
+            let batch_len = q.commands[start..]
+                .iter()
+                .zip(selections[start..].iter())
+                .take_while(|&(c, s)| {
+                    Rc::ptr_eq(&c.surface.buffer, &cmd.surface.buffer)
+                        && Rc::ptr_eq(&material_of(c), mat)
+                        && c.joint_matrices.is_none() && s == lights
+                })
+                .count();
+
+            // The program variant is chosen by the number of lights
+            let defines = [
+                ("UNI_DIRECTIONAL_LIGHTS", ctx.directional_lights.len()),
+                ("UNI_POINT_LIGHTS", lights.points.len()),
+                ("UNI_SPOT_LIGHTS", lights.spots.len()),
+            ];
+            if lit && program.select_variant(&defines) {
+                ctx.prog = Weak::new();
+                ctx.last_material_bound = None;
+            }
 
             ctx.states.apply_defaults();
             ctx.states.apply(&q.states);
             ctx.states.apply(&mat.states);
             ctx.states.commit(gl);
 
-            if let Err(err) = self.setup_material(ctx, mat) {
+            if let Err(err) = self.setup_material(ctx, mat, program) {
                 if let AssetError::NotReady = err {
                     continue;
                 }
@@ -330,6 +519,7 @@ where
             }
 
             let prog = ctx.prog.upgrade().unwrap();
+            let instanced = batch_len > 1 && gl.instanced_arrays && prog.supports_instancing();
 
             let r = ctx.prepare_cache(&cmd.surface.buffer, |ctx| {
                 cmd.surface.buffer.bind(&self.gl, &prog)?;
@@ -340,11 +530,25 @@ where
             match r {
                 Ok(_) => {
                     self.setup_camera(ctx, cmd.model_m, camera);
+                    self.setup_skin(ctx, &cmd.joint_matrices);
+                    if lit {
+                        self.setup_local_lights(ctx, lights);
+                    }
+                    prog.set("uInstanced", instanced);
                     prog.commit(gl);
-                    // if let RenderQueue::UI = mat.render_queue
-                    {
+
+                    if instanced {
+                        let batch = &q.commands[start..start + batch_len];
+                        self.instance_buffer
+                            .update(batch.iter().map(|c| c.model_m).collect());
+                        cmd.surface
+                            .buffer
+                            .render_instanced(gl, &self.instance_buffer, batch_len);
+                        i = start + batch_len;
+                    } else {
                         cmd.surface.buffer.render(gl);
                     }
+                    ctx.stats.batch_count += 1;
 
                     cmd.surface.buffer.unbind(gl);
                 }
@@ -361,15 +565,19 @@ where
         T: 'static + ComponentBased,
         F: FnMut(Rc<RefCell<GameObject>>, Arc<Component>) -> bool,
     {
-        for obj in self.objects.iter() {
-            let result = obj.upgrade().and_then(|obj| {
-                obj.try_borrow()
-                    .ok()
-                    .and_then(|o| o.find_component::<T>().map(|(_, c)| c.clone()))
-            });
+        let list = match self.components.borrow().by_type.get(&TypeId::of::<T>()) {
+            Some(list) => list.clone(),
+            None => return,
+        };
+
+        for (obj, com) in list {
+            if let (Some(obj), Some(com)) = (obj.upgrade(), com.upgrade()) {
+                // Skip the objects borrowed by the caller
+                if obj.try_borrow().is_err() {
+                    continue;
+                }
 
-            if let Some(com) = result {
-                if !func(obj.upgrade().unwrap(), com) {
+                if !func(obj, com) {
                     return;
                 }
             }
@@ -415,6 +623,18 @@ where
             .nth(0)
     }
 
+    /// The point and spot lights, in the order they were added
+    pub fn find_local_lights(&self) -> Vec<Arc<Component>> {
+        self.find_all_components::<Light>()
+            .into_iter()
+            .filter(|c| {
+                let light_com = c.try_as::<Light>().unwrap();
+                let is_local = light_com.borrow().world_position().is_some();
+                is_local
+            })
+            .collect()
+    }
+
     fn prepare_ctx(&self, ctx: &mut EngineContext) {
         // Update all components which need to update
         // Update lights
@@ -425,39 +645,50 @@ where
             true
         });
 
-        // prepare main light.
-        let main_light = self.find_main_light()
-            .unwrap_or({ Component::new(Light::new(Directional::default())) });
+        for c in self.find_all_components::<Light>() {
+            let local = {
+                let light = c.try_as::<Light>().unwrap().borrow();
+                let (position, range) = (light.world_position(), light.range());
+                position.and_then(|p| range.map(|r| (p, r)))
+            };
 
-        ctx.main_light = Some(main_light);
+            match local {
+                Some((position, range)) => {
+                    let is_spot = c.try_as::<Light>().unwrap().borrow().spot().is_some();
+                    let list = if is_spot {
+                        &mut ctx.spot_lights
+                    } else {
+                        &mut ctx.point_lights
+                    };
+
+                    list.push(LocalLight {
+                        component: c,
+                        position,
+                        range,
+                    });
+                }
+                None => ctx.directional_lights.push(c),
+            }
+        }
 
-        ctx.point_lights = self.find_all_components::<Light>()
-                .into_iter()
-                .filter(|c| {
-                    let light_com = c.try_as::<Light>().unwrap();
-                    match *light_com.borrow() {
-                        Light::Point(_) => true,
-                        _ => false,
-                    }
-                })
-                .take(4)            // only take 4 points light.
-                .map(
-                    |c| c.clone()
-                )
-                .collect();
+        // The first one is the main light, with a default one if there is none
+        if ctx.directional_lights.is_empty() {
+            ctx.directional_lights
+                .push(Component::new(Light::new(Directional::default())));
+        }
+        ctx.directional_lights.truncate(MAX_DIRECTIONAL_LIGHTS);
     }
 
     fn gather_render_commands(
         &self,
         object: &GameObject,
         cam_pos: &Vector3<f32>,
-        update_bounds_only: bool,
         frustum_opt: &Option<Frustum>,
         render_q: &mut RenderQueueList,
         included_render_queues: &Option<BTreeSet<RenderQueue>>,
-        eng_stats: &mut Option<&mut EngineStats>,
+        culling_mask: u32,
     ) {
-        if !object.active {
+        if !object.active || object.layers & culling_mask == 0 {
             return;
         }
 
@@ -469,6 +700,10 @@ where
             // TODO: local scale only ?? should be using global scale??
             let scale = get_max_scale(&object.transform.local_scale());
 
+            let joint_matrices = object
+                .find_component::<Skeleton>()
+                .map(|(skeleton, _)| Rc::new(skeleton.joint_matrices(&m)));
+
             for surface in mesh.surfaces.iter() {
                 if let &Some(ref included) = included_render_queues {
                     if included.get(&surface.material.render_queue).is_none() {
@@ -476,95 +711,85 @@ where
                     }
                 }
 
-                if let &mut Some(ref mut stats) = eng_stats {
-                    match surface.material.render_queue {
-                        RenderQueue::Transparent => stats.total_transparent_count += 1,
-                        RenderQueue::Opaque => stats.total_opaque_count += 1,
-                        _ => (),
-                    }
-                }
+                // World bounding sphere
+                let sphere = surface.buffer.bounds().map(|bounds| {
+                    let (center, r) = bounds.local_aabb().sphere();
+                    let p = m.transform_point(Point3::from_vec(center));
+                    (p.to_vec(), r * scale)
+                });
 
                 // TODO: should use a material flag to skip
                 if let &Some(ref frustum) = frustum_opt {
                     match surface.material.render_queue {
                         RenderQueue::Skybox | RenderQueue::UI => (),
-                        _ => {
-                            let bounds = surface.buffer.bounds();
-                            if bounds.is_none() {
-                                continue;
-                            }
-
-                            let bounds = bounds.unwrap();
-                            let (center, r) = bounds.local_aabb().sphere();
-
-                            let scaled_r = r * scale;
-                            let p = m.transform_point(Point3::from_vec(center));
-
-                            if !frustum.collide_sphere(&p.to_vec(), scaled_r) {
+                        // Skinned meshes can move out of their bounds
+                        _ if joint_matrices.is_some() => (),
+                        _ => match sphere {
+                            Some((p, r)) => if !frustum.collide_sphere(&p, r) {
                                 continue;
-                            }
-
-                            if render_q.aabb.is_none() {
-                                render_q.aabb = Some(Aabb::empty());
-                            }
-
-                            render_q
-                                .aabb
-                                .as_mut()
-                                .unwrap()
-                                .merge_sphere(&p.to_vec(), scaled_r);
-                        }
-                    }
-                } else {
-                    let bounds = surface.buffer.bounds();
-                    if let Some(bounds) = bounds {
-                        let (center, r) = bounds.local_aabb().sphere();
-                        let p = m.transform_point(Point3::from_vec(center));
-
-                        if render_q.aabb.is_none() {
-                            render_q.aabb = Some(Aabb::empty());
-                        }
-
-                        render_q
-                            .aabb
-                            .as_mut()
-                            .unwrap()
-                            .merge_sphere(&p.to_vec(), r * scale);
+                            },
+                            None => continue,
+                        },
                     }
                 }
 
-                if !update_bounds_only {
-                    let q = render_q
-                        .queues
-                        .get_mut(&surface.material.render_queue)
-                        .unwrap();
+                let q = render_q
+                    .queues
+                    .get_mut(&surface.material.render_queue)
+                    .unwrap();
 
-                    let cam_dist = (cam_pos - object.transform.global().disp).magnitude();
+                let cam_dist = (cam_pos - object.transform.global().disp).magnitude();
 
-                    q.commands.push(RenderCommand {
-                        surface: surface.clone(),
-                        model_m: m,
-                        cam_distance: cam_dist,
-                    })
-                }
+                q.commands.push(RenderCommand {
+                    surface: surface.clone(),
+                    model_m: m,
+                    sphere,
+                    cam_distance: cam_dist,
+                    joint_matrices: joint_matrices.clone(),
+                })
             }
         }
     }
 
+    /// World bounds of the objects visible by the camera
     pub fn get_bounds(&self, camera: &Camera) -> Option<Aabb> {
-        let render_q = self.gather_all_render_commands(camera, true, None);
+        let mut index = self.spatial_index.borrow_mut();
+        index.update();
 
-        return render_q.aabb;
+        let queues = &camera.included_render_queues;
+        if camera.enable_frustum_culling {
+            let frustum = camera.calc_frustum(self.screen_size);
+            index.merged_bounds(|aabb| frustum.collide_aabb(aabb), queues)
+        } else {
+            index.merged_bounds(|_| true, queues)
+        }
     }
 
     fn gather_all_render_commands(
         &self,
         camera: &Camera,
-        update_bounds_only: bool,
-        mut eng_stats: Option<&mut EngineStats>,
+        eng_stats: Option<&mut EngineStats>,
     ) -> RenderQueueList {
         let mut render_q = RenderQueueList::new();
-        let objects = &self.objects;
+
+        let mut index = self.spatial_index.borrow_mut();
+        index.update();
+
+        if let Some(stats) = eng_stats {
+            let included = |q: RenderQueue| {
+                camera
+                    .included_render_queues
+                    .as_ref()
+                    .map_or(true, |included| included.contains(&q))
+            };
+
+            if included(RenderQueue::Opaque) {
+                stats.total_opaque_count += index.surface_count(RenderQueue::Opaque);
+            }
+            if included(RenderQueue::Transparent) {
+                stats.total_transparent_count += index.surface_count(RenderQueue::Transparent);
+            }
+        }
 
         let frustum = if camera.enable_frustum_culling {
             Some(camera.calc_frustum(self.screen_size))
@@ -572,54 +797,270 @@ where
             None
         };
 
+        let objects = match frustum {
+            Some(ref frustum) => index.visible(|aabb| frustum.collide_aabb(aabb)),
+            None => index.all(),
+        };
+        drop(index);
+
         for obj in objects.iter() {
-            obj.upgrade().map(|obj| {
-                if let Ok(object) = obj.try_borrow() {
-                    self.gather_render_commands(
-                        &object,
-                        &camera.eye(),
-                        update_bounds_only,
-                        &frustum,
-                        &mut render_q,
-                        &camera.included_render_queues,
-                        &mut eng_stats,
-                    )
-                }
-            });
+            if let Ok(object) = obj.try_borrow() {
+                self.gather_render_commands(
+                    &object,
+                    &camera.eye(),
+                    &frustum,
+                    &mut render_q,
+                    &camera.included_render_queues,
+                    camera.culling_mask,
+                )
+            }
         }
 
         render_q
     }
 
-    #[cfg_attr(feature = "flame_it", flame)]
-    pub fn render_pass_with_material(
-        &mut self,
+    /// Build the billboards of the visible particle systems into the transparent queue,
+    /// to be sorted by distance with the other transparent surfaces
+    fn gather_particles(&self, camera: &Camera, render_q: &mut RenderQueueList) {
+        let frustum = if camera.enable_frustum_culling {
+            Some(camera.calc_frustum(self.screen_size))
+        } else {
+            None
+        };
+
+        let cam_pos = camera.eye();
+        let q = render_q.queues.get_mut(&RenderQueue::Transparent).unwrap();
+        self.map_component::<ParticleSystem, _>(|obj, com| {
+            let object = obj.borrow();
+            if !object.active || object.layers & camera.culling_mask == 0 {
+                return true;
+            }
+
+            let mut particles = com.try_as::<ParticleSystem>().unwrap().borrow_mut();
+            let (center, r) = match particles.bounds() {
+                Some(sphere) => sphere,
+                None => return true,
+            };
+
+            if let Some(ref frustum) = frustum {
+                if !frustum.collide_sphere(&center, r) {
+                    return true;
+                }
+            }
+
+            if let Some(surface) = particles.build(&*self.asset_system, camera) {
+                q.commands.push(RenderCommand {
+                    surface,
+                    model_m: Matrix4::identity(),
+                    sphere: Some((center, r)),
+                    cam_distance: (cam_pos - center).magnitude(),
+                    joint_matrices: None,
+                });
+            }
+
+            true
+        });
+    }
+
+    /// Build the changed chunks of the tilemaps and add the visible ones to the transparent queue
+    fn gather_tilemaps(&self, camera: &Camera, render_q: &mut RenderQueueList) {
+        let frustum = if camera.enable_frustum_culling {
+            Some(camera.calc_frustum(self.screen_size))
+        } else {
+            None
+        };
+
+        let q = render_q.queues.get_mut(&RenderQueue::Transparent).unwrap();
+        self.map_component::<Tilemap, _>(|obj, com| {
+            let object = obj.borrow();
+            if !object.active || object.layers & camera.culling_mask == 0 {
+                return true;
+            }
+
+            let mut tilemap = com.try_as::<Tilemap>().unwrap().borrow_mut();
+            tilemap.update(&*self.asset_system);
+
+            let m = compute_model_m(&object);
+            let scale = get_max_scale(&object.transform.local_scale());
+
+            for (bounds, surface) in tilemap.surfaces() {
+                let (center, r) = bounds.sphere();
+                let p = m.transform_point(Point3::from_vec(center)).to_vec();
+
+                if let Some(ref frustum) = frustum {
+                    if !frustum.collide_sphere(&p, r * scale) {
+                        continue;
+                    }
+                }
+
+                q.commands.push(RenderCommand {
+                    surface,
+                    model_m: m,
+                    sphere: Some((p, r * scale)),
+                    cam_distance: 0.0,
+                    joint_matrices: None,
+                });
+            }
+
+            true
+        });
+    }
+
+    /// Batch the visible sprites into the transparent queue
+    fn gather_sprites(
+        &self,
+        ctx: &mut EngineContext,
         camera: &Camera,
-        material: Option<&Rc<Material>>,
-        clear_option: ClearOption,
-    ) -> EngineStats {
-        let mut ctx: EngineContext = EngineContext::new();
+        render_q: &mut RenderQueueList,
+    ) {
+        let frustum = if camera.enable_frustum_culling {
+            Some(camera.calc_frustum(self.screen_size))
+        } else {
+            None
+        };
+
+        let mut quads = Vec::new();
+        let mut uploaded = false;
+        self.map_component::<Sprite, _>(|obj, com| {
+            let object = obj.borrow();
+            if !object.active || object.layers & camera.culling_mask == 0 {
+                return true;
+            }
+
+            let sprite = com.try_as::<Sprite>().unwrap().borrow();
 
-        if let Some(ref rt) = camera.render_texture {
-            rt.bind_frame_buffer(&self.gl);
+            // The size of the texture is known once it is uploaded
+            if sprite.texture.size().is_none() {
+                uploaded = true;
+                if sprite.texture.prepare(&self.gl, 0).is_err() {
+                    return true;
+                }
+            }
+            let size = match sprite.texture.size() {
+                Some(size) => size,
+                None => return true,
+            };
+
+            let quad = sprite.quad(&compute_model_m(&object), size);
+            if let Some(ref frustum) = frustum {
+                let (p, r) = quad.sphere();
+                if !frustum.collide_sphere(&p, r) {
+                    return true;
+                }
+            }
+
+            quads.push(quad);
+            true
+        });
+
+        // Uploading the textures changed the bound one
+        if uploaded {
+            ctx.invalidate();
+        }
+
+        if quads.is_empty() {
+            return;
+        }
+
+        let surfaces = self.sprite_batch
+            .borrow_mut()
+            .build(&*self.asset_system, quads);
+
+        let q = render_q.queues.get_mut(&RenderQueue::Transparent).unwrap();
+        for surface in surfaces {
+            q.commands.push(RenderCommand {
+                surface,
+                model_m: Matrix4::identity(),
+                sphere: None,
+                cam_distance: 0.0,
+                joint_matrices: None,
+            });
         }
+    }
 
-        match camera.rect {
-            Some(((x, y), (w, h))) => {
-                self.gl.viewport(x, y, w, h);
+    /// Objects with a Mesh which world bounds overlap the aabb
+    pub fn query_aabb(&self, aabb: &Aabb) -> Vec<Rc<RefCell<GameObject>>> {
+        let mut index = self.spatial_index.borrow_mut();
+        index.update();
+        index.query(|b| b.intersects(aabb))
+    }
+
+    /// Objects with a Mesh which world bounds overlap the sphere
+    pub fn query_sphere(&self, center: Vector3f, r: f32) -> Vec<Rc<RefCell<GameObject>>> {
+        let mut index = self.spatial_index.borrow_mut();
+        index.update();
+        index.query(|b| b.distance2(&center) <= r * r)
+    }
+
+    /// The k objects with a Mesh nearest to p, with the distance to their bounds
+    pub fn nearest(&self, p: Vector3f, k: usize) -> Vec<(Rc<RefCell<GameObject>>, f32)> {
+        let mut index = self.spatial_index.borrow_mut();
+        index.update();
+        index.nearest(p, k)
+    }
+
+    /// Size of the viewport of a render target, None being the output of the camera
+    fn target_size(&self, camera: &Camera, target: Option<&RenderTexture>) -> (u32, u32) {
+        match target {
+            Some(rt) => rt.size().unwrap_or(self.screen_size),
+            None => camera.rect.map_or(self.screen_size, |(_, size)| size),
+        }
+    }
+
+    /// Bind the target and set its viewport, or the render texture of the camera,
+    /// if any, and its viewport when it is None
+    fn bind_target(&self, camera: &Camera, target: Option<&RenderTexture>) {
+        match target {
+            Some(rt) => {
+                rt.bind_frame_buffer(&self.gl);
+                let (w, h) = self.target_size(camera, target);
+                self.gl.viewport(0, 0, w, h);
             }
             None => {
-                self.gl
-                    .viewport(0, 0, self.screen_size.0, self.screen_size.1);
+                if let Some(ref rt) = camera.render_texture {
+                    rt.bind_frame_buffer(&self.gl);
+                }
+
+                match camera.rect {
+                    Some(((x, y), (w, h))) => {
+                        self.gl.viewport(x, y, w, h);
+                    }
+                    None => {
+                        self.gl
+                            .viewport(0, 0, self.screen_size.0, self.screen_size.1);
+                    }
+                }
             }
         }
+    }
 
-        self.clear(clear_option);
+    fn unbind_target(&self, camera: &Camera, target: Option<&RenderTexture>) {
+        match target {
+            Some(rt) => rt.unbind_frame_buffer(&self.gl),
+            None => if let Some(ref rt) = camera.render_texture {
+                rt.unbind_frame_buffer(&self.gl);
+            },
+        }
+    }
 
-        self.prepare_ctx(&mut ctx);
+    /// The deferred path needs several color attachments and glsl 300 es
+    fn use_deferred(&self, camera: &Camera) -> bool {
+        camera.rendering_path == RenderingPath::Deferred && self.gl.draw_buffers
+            && self.gl.is_webgl2
+    }
 
+    /// Gather and sort the visible surfaces
+    fn gather_scene(&self, ctx: &mut EngineContext, camera: &Camera) -> RenderQueueList {
         // gather commands
-        let mut render_q = self.gather_all_render_commands(&camera, false, Some(&mut ctx.stats));
+        let mut render_q = self.gather_all_render_commands(&camera, Some(&mut ctx.stats));
+
+        let transparent_included = camera
+            .included_render_queues
+            .as_ref()
+            .map_or(true, |included| included.contains(&RenderQueue::Transparent));
+        if transparent_included {
+            self.gather_particles(camera, &mut render_q);
+        }
 
         // Sort the opaque queue
         render_q
@@ -636,6 +1077,12 @@ where
             .unwrap()
             .sort_by_cam_distance();
 
+        // Tilemaps and sprites are drawn after the other transparent surfaces
+        if transparent_included {
+            self.gather_tilemaps(camera, &mut render_q);
+            self.gather_sprites(ctx, camera, &mut render_q);
+        }
+
         ctx.stats.surfaces_count = render_q.surface_count() as u32;
         ctx.stats.transparent_count = render_q
             .queues
@@ -650,17 +1097,309 @@ where
             .commands
             .len() as u32;
 
-        for (_, q) in render_q.queues.iter() {
-            self.render_commands(&mut ctx, &q, camera, material);
+        render_q
+    }
+
+    /// Clear the target and draw the visible surfaces of the queues passing the filter
+    /// into it, or into the output of the camera when it is None
+    fn render_scene<F>(
+        &self,
+        ctx: &mut EngineContext,
+        camera: &Camera,
+        target: Option<&RenderTexture>,
+        material: Option<&Rc<Material>>,
+        clear_option: ClearOption,
+        filter: F,
+    ) where
+        F: Fn(RenderQueue) -> bool,
+    {
+        self.bind_target(camera, target);
+
+        // Only clear the viewport of the camera, other cameras may draw beside it
+        match (target, camera.rect) {
+            (None, Some(((x, y), (w, h)))) => {
+                self.gl.enable(Flag::ScissorTest as i32);
+                self.gl.scissor(x, y, w, h);
+                self.clear_camera(camera, clear_option);
+                self.gl.disable(Flag::ScissorTest as i32);
+            }
+            _ => self.clear_camera(camera, clear_option),
+        }
+
+        self.prepare_ctx(ctx);
+        ctx.states.reversed_z = camera.reversed_z;
+
+        let mut render_q = self.gather_scene(ctx, camera);
+
+        // Passes with a replacement material are drawn forward
+        if material.is_none() && filter(RenderQueue::Opaque) && self.use_deferred(camera) {
+            let opaque = render_q.queues.get_mut(&RenderQueue::Opaque).unwrap();
+            let (deferred, forward): (Vec<_>, Vec<_>) = opaque
+                .commands
+                .drain(..)
+                .partition(|c| c.surface.material.gbuffer_program.is_some());
+            opaque.commands = forward;
+
+            if !deferred.is_empty() {
+                let q = RenderQueueState {
+                    states: opaque.states,
+                    commands: deferred,
+                };
+                self.draw_deferred(ctx, camera, target, &q);
+            }
+        }
+
+        for (queue, q) in render_q.queues.iter() {
+            if filter(*queue) {
+                self.render_commands(ctx, &q, camera, DrawMode::Forward(material));
+            }
+        }
+
+        self.unbind_target(camera, target);
+    }
+
+    /// Draw the commands into the G-buffer, then light them into the bound target
+    /// with all the lights, by batches of MAX_POINT_LIGHTS and MAX_SPOT_LIGHTS
+    fn draw_deferred(
+        &self,
+        ctx: &mut EngineContext,
+        camera: &Camera,
+        target: Option<&RenderTexture>,
+        q: &RenderQueueState,
+    ) {
+        let gl = &self.gl;
+        let size = self.target_size(camera, target);
+
+        let mut gbuffer = self.gbuffer.borrow_mut();
+        if gbuffer.as_ref().map_or(true, |g| g.size != size) {
+            *gbuffer = Some(GBuffer::new(&*self.asset_system, size));
+        }
+        let gbuffer = gbuffer.as_ref().unwrap();
+
+        // The lighting pass skips the fragments left at the cleared depth
+        gbuffer.target.bind_frame_buffer(gl);
+        gl.viewport(0, 0, size.0, size.1);
+        self.clear_camera(
+            camera,
+            ClearOption {
+                color: None,
+                clear_color: false,
+                clear_depth: true,
+                clear_stencil: false,
+            },
+        );
+
+        ctx.invalidate();
+        self.render_commands(ctx, q, camera, DrawMode::GBuffer);
+        gbuffer.target.unbind_frame_buffer(gl);
+
+        self.bind_target(camera, target);
+        ctx.invalidate();
+
+        let light = &gbuffer.light;
+        let pv = camera.projection_matrix(self.screen_size) * camera.v;
+        light.set("uInvPVMatrix", pv.invert().unwrap_or(Matrix4::identity()));
+        light.set("uViewPos", camera.eye());
+        light.set("uReversedZ", camera.reversed_z);
+
+        let batches = |count: usize, max: usize| (count + max - 1) / max;
+        let passes = batches(ctx.point_lights.len(), MAX_POINT_LIGHTS)
+            .max(batches(ctx.spot_lights.len(), MAX_SPOT_LIGHTS))
+            .max(1);
+        let range = |i: usize, count: usize, max: usize| (i * max..count.min((i + 1) * max));
+
+        for i in 0..passes {
+            let selection = LightSelection {
+                points: range(i, ctx.point_lights.len(), MAX_POINT_LIGHTS).collect(),
+                spots: range(i, ctx.spot_lights.len(), MAX_SPOT_LIGHTS).collect(),
+            };
+
+            // The directional lights are added by the first batch only
+            let directional = if i == 0 {
+                ctx.directional_lights.len()
+            } else {
+                0
+            };
+            let defines = [
+                ("UNI_DIRECTIONAL_LIGHTS", directional),
+                ("UNI_POINT_LIGHTS", selection.points.len()),
+                ("UNI_SPOT_LIGHTS", selection.spots.len()),
+            ];
+            if light.program.select_variant(&defines) {
+                ctx.invalidate();
+            }
+
+            // The next batches are added to the first one
+            if i == 1 {
+                gl.blend_func(BlendMode::One, BlendMode::One);
+            }
+
+            ctx.states.apply_defaults();
+            ctx.states.apply(&MaterialState {
+                cull: Some(CullMode::Off),
+                alpha_blending: Some(i > 0),
+                blending: None,
+                depth_write: Some(i == 0),
+                depth_test: Some(DepthTest::Always),
+            });
+            ctx.states.commit(gl);
+
+            self.draw_screen_quad(ctx, light, Some(&selection));
+        }
+
+        if passes > 1 {
+            gl.blend_func(BlendMode::SrcAlpha, BlendMode::OneMinusSrcAlpha);
+        }
+    }
+
+    /// Draw the "screen_quad" mesh buffer with the material, and the lights if any
+    fn draw_screen_quad(
+        &self,
+        ctx: &mut EngineContext,
+        material: &Rc<Material>,
+        lights: Option<&LightSelection>,
+    ) {
+        let gl = &self.gl;
+
+        match self.setup_material(ctx, material, &material.program) {
+            Ok(_) => {
+                let prog = ctx.prog.upgrade().unwrap();
+                let quad = self.asset_system.new_mesh_buffer("screen_quad");
+
+                match quad.bind(gl, &prog) {
+                    Ok(_) => {
+                        if let Some(lights) = lights {
+                            self.setup_local_lights(ctx, lights);
+                        }
+                        prog.commit(gl);
+                        quad.render(gl);
+                        ctx.stats.batch_count += 1;
+                        quad.unbind(gl);
+                    }
+                    Err(AssetError::NotReady) => (),
+                    Err(err) => panic!(format!("Failed to load mesh, reason {:?}", err)),
+                }
+            }
+            Err(AssetError::NotReady) => (),
+            Err(err) => panic!(format!("Failed to load material, reason {:?}", err)),
         }
+    }
+
+    /// Draw the "screen_quad" mesh buffer with the material into the target,
+    /// or into the output of the camera when it is None
+    fn blit(
+        &self,
+        ctx: &mut EngineContext,
+        camera: &Camera,
+        material: &Rc<Material>,
+        target: Option<&RenderTexture>,
+    ) {
+        let gl = &self.gl;
+
+        self.bind_target(camera, target);
+
+        // Binding a frame buffer changes the bound texture,
+        // and a material is drawn again with other sources
+        ctx.invalidate();
+
+        ctx.states.apply_defaults();
+        ctx.states.apply(&PostProcessStack::states());
+        ctx.states.apply(&material.states);
+        ctx.states.commit(gl);
+
+        self.draw_screen_quad(ctx, material, None);
 
-        if let Some(ref rt) = camera.render_texture {
-            rt.unbind_frame_buffer(&self.gl);
+        self.unbind_target(camera, target);
+    }
+
+    /// Render the scene into the render texture of the stack, apply the effects
+    /// to it into the output of the camera, and draw the UI over the result
+    fn render_pass_with_post_process(
+        &mut self,
+        camera: &Camera,
+        stack: &PostProcessStack,
+        clear_option: ClearOption,
+        draw_ui: bool,
+    ) -> EngineStats {
+        let size = self.target_size(camera, None);
+        let scene = stack.scene_target(size);
+
+        let mut ctx = EngineContext::new();
+        self.render_scene(
+            &mut ctx,
+            camera,
+            Some(&*scene),
+            None,
+            clear_option,
+            |q| q != RenderQueue::UI,
+        );
+
+        // The main light is needed to bind a material
+        let mut post_ctx = EngineContext::new();
+        self.prepare_ctx(&mut post_ctx);
+        stack.apply(&*self.asset_system, |material, target| {
+            self.blit(&mut post_ctx, camera, material, target);
+        });
+
+        let mut stats = ctx.stats;
+        stats.batch_count += post_ctx.stats.batch_count;
+        if draw_ui {
+            stats.batch_count += self.render_ui(camera).batch_count;
         }
+        stats
+    }
+
+    /// Draw the UI queue over the output of the camera
+    fn render_ui(&self, camera: &Camera) -> EngineStats {
+        let mut ctx = EngineContext::new();
+        let depth_only = ClearOption {
+            color: None,
+            clear_color: false,
+            clear_depth: true,
+            clear_stencil: false,
+        };
+        self.render_scene(
+            &mut ctx,
+            camera,
+            None,
+            None,
+            depth_only,
+            |q| q == RenderQueue::UI,
+        );
+
+        ctx.stats
+    }
+
+    fn render_camera(
+        &mut self,
+        camera: &Camera,
+        material: Option<&Rc<Material>>,
+        clear_option: ClearOption,
+        draw_ui: bool,
+    ) -> EngineStats {
+        // Effects are not applied to the passes drawn with a replacement material
+        if let (None, Some(stack)) = (material, camera.post_process.clone()) {
+            return self.render_pass_with_post_process(camera, &stack, clear_option, draw_ui);
+        }
+
+        let mut ctx: EngineContext = EngineContext::new();
+        self.render_scene(&mut ctx, camera, None, material, clear_option, |q| {
+            draw_ui || q != RenderQueue::UI
+        });
 
         ctx.stats
     }
 
+    #[cfg_attr(feature = "flame_it", flame)]
+    pub fn render_pass_with_material(
+        &mut self,
+        camera: &Camera,
+        material: Option<&Rc<Material>>,
+        clear_option: ClearOption,
+    ) -> EngineStats {
+        self.render_camera(camera, material, clear_option, true)
+    }
+
     #[cfg_attr(feature = "flame_it", flame)]
     pub fn render_pass(&mut self, camera: &Camera, clear_option: ClearOption) -> EngineStats {
         self.render_pass_with_material(camera, None, clear_option)
@@ -680,22 +1419,72 @@ where
         None
     }
 
+    /// The cameras of the active objects, by increasing depth
+    pub fn cameras(&self) -> Vec<Arc<Component>> {
+        let mut cameras = Vec::new();
+        self.map_component::<Camera, _>(|obj, c| {
+            if obj.borrow().active {
+                let depth = c.try_as::<Camera>().unwrap().borrow().depth;
+                cameras.push((depth, c));
+            }
+            true
+        });
+
+        // Stable, the cameras of the same depth are kept in the order they were added
+        cameras.sort_by_key(|&(depth, _)| depth);
+        cameras.into_iter().map(|(_, c)| c).collect()
+    }
+
+    /// Render all cameras by increasing depth, each one cleared with its clear option
+    /// or the given one. The UI is drawn once, by the last camera covering the screen.
     #[cfg_attr(feature = "flame_it", flame)]
     pub fn render(&mut self, clear_option: ClearOption) {
         imgui::pre_render(self);
 
-        if let Some(ref camera) = self.main_camera() {
-            self.stats =
-                self.render_pass(&camera.try_as::<Camera>().unwrap().borrow(), clear_option);
-        } else {
-            // We dont have a main camera here, just clean the screen.
+        let cameras: Vec<Camera> = self.cameras()
+            .iter()
+            .map(|c| c.try_as::<Camera>().unwrap().borrow().clone())
+            .collect();
+
+        if cameras.is_empty() {
+            // We dont have a camera here, just clean the screen.
             self.clear(clear_option);
+            return;
         }
+
+        let covers_screen = |c: &Camera| c.rect.is_none() && c.render_texture.is_none();
+
+        // The parts of the screen out of the first cameras are cleared too
+        if !covers_screen(&cameras[0]) {
+            self.gl
+                .viewport(0, 0, self.screen_size.0, self.screen_size.1);
+            self.clear(clear_option);
+        }
+
+        let ui_camera = cameras.iter().rposition(|c| covers_screen(c));
+
+        let mut stats = EngineStats::default();
+        for (i, camera) in cameras.iter().enumerate() {
+            let option = camera.clear_option.unwrap_or(clear_option);
+            stats.add(&self.render_camera(camera, None, option, ui_camera == Some(i)));
+        }
+
+        if ui_camera.is_none() {
+            let mut camera = cameras.last().unwrap().clone();
+            camera.rect = None;
+            camera.render_texture = None;
+            stats.add(&self.render_ui(&camera));
+        }
+
+        self.stats = stats;
     }
 
     pub fn new(webgl_ctx: WebGLContext, size: (u32, u32), hidpi: f32) -> Engine<A> {
         let gl = WebGLRenderingContext::new(webgl_ctx);
 
+        #[cfg(feature = "soft_gl")]
+        ::engine::render::register_soft_programs(&gl);
+
         /*=========Drawing the triangle===========*/
 
         // Clear the canvas
@@ -717,6 +1506,8 @@ where
 
         let gui_tree = SceneTree::new();
 
+        let instance_buffer = InstanceBuffer::new(&gl);
+
         Engine {
             gl: gl,
             objects: vec![],
@@ -727,6 +1518,11 @@ where
             hidpi: hidpi,
             current_camera: RefCell::new(None),
             stats: Default::default(),
+            spatial_index: RefCell::new(SpatialIndex::new()),
+            components: Default::default(),
+            instance_buffer,
+            gbuffer: RefCell::new(None),
+            sprite_batch: Default::default(),
         }
     }
 
@@ -740,6 +1536,11 @@ where
         // drop all gameobjects if there are no other references
         self.objects.retain(|obj| obj.upgrade().is_some());
 
+        // Components of dropped objects are not notified
+        for list in self.components.borrow_mut().by_type.values_mut() {
+            list.retain(|&(_, ref c)| c.upgrade().is_some());
+        }
+
         // drop camera cache if it is only by holded by ourself
         let mut cam_mut = self.current_camera.borrow_mut();
         if let Some(ref c) = *cam_mut {
@@ -775,7 +1576,13 @@ where
 
 impl<A: AssetSystem> IEngine for Engine<A> {
     fn new_game_object(&mut self, parent: &GameObject) -> Rc<RefCell<GameObject>> {
-        let go = parent.tree().new_node(parent);
+        let tree = parent.tree();
+        if self.spatial_index.borrow_mut().watch(&tree) {
+            let components = self.components.clone();
+            tree.add_watcher(move |evt, go, c| components.borrow_mut().notify(evt, go, c));
+        }
+
+        let go = tree.new_node(parent);
 
         self.objects.push(Rc::downgrade(&go));
         go
@@ -36,9 +36,12 @@ fn make_quad_mesh_data(ndc_size: (f32, f32)) -> MeshData {
         vertices: vertices,
         uvs: Some(uvs),
         normals: None,
-        indices: indices,
+        indices: indices.into(),
         tangents: None,
         bitangents: None,
+        joints: None,
+        weights: None,
+        colors: None,
     }
 }
 
@@ -3,7 +3,7 @@ use super::widgets;
 use super::widgets::Widget;
 use super::{Metric, TextAlign};
 
-use engine::MeshData;
+use engine::{MeshData, MeshIndices};
 
 struct BitmapFontData {
     hidpi: f32,
@@ -41,7 +41,7 @@ struct TextData {
 fn make_text_mesh_data(text_data: TextData) -> MeshData {
     let mut vertices = vec![];
     let mut uvs = vec![];
-    let mut indices = vec![];
+    let mut indices: Vec<u32> = vec![];
 
     let bfont = &text_data.font_data;
 
@@ -115,13 +115,18 @@ fn make_text_mesh_data(text_data: TextData) -> MeshData {
         base_y -= gh * 2.0;
     }
 
+    let nvertices = vertices.len() / 3;
+
     MeshData {
         vertices: vertices,
         uvs: Some(uvs),
         normals: None,
-        indices: indices,
+        indices: MeshIndices::with_vertex_count(indices, nvertices),
         tangents: None,
         bitangents: None,
+        joints: None,
+        weights: None,
+        colors: None,
     }
 }
 
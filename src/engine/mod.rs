@@ -1,6 +1,8 @@
 mod core;
 mod render;
 mod asset;
+mod scene;
+mod animation;
 
 pub mod imgui;
 pub mod context;
@@ -11,8 +13,11 @@ pub use self::imgui::Metric;
 
 pub use self::render::*;
 pub use self::asset::*;
-pub use self::core::{Component, ComponentBased, ComponentEvent, GameObject, SceneTree};
-pub use self::core::Aabb;
+pub use self::scene::*;
+pub use self::animation::*;
+pub use self::core::{Component, ComponentBased, ComponentEvent, GameObject, SceneTree,
+                     DEFAULT_LAYER};
+pub use self::core::{Aabb, Bvh, BvhProxy, Ray};
 
 pub use self::engine::{ClearOption, IEngine};
 
@@ -1,6 +1,7 @@
 use std::rc::Rc;
-use engine::render::{RenderQueue, RenderTexture};
+use engine::render::{PostProcessStack, RenderQueue, RenderTexture};
 use engine::core::ComponentBased;
+use engine::ClearOption;
 use std::collections::BTreeSet;
 use math::*;
 
@@ -17,6 +18,25 @@ impl Plane {
 
         Plane { n, offset }
     }
+
+    /// From the coefficients of a x + b y + c z + d >= 0, which is always true
+    /// when a, b and c are zero, like the far plane of an infinite projection
+    fn from_coefficients(v: Vector4<f32>) -> Plane {
+        let n = v.truncate();
+        let len = n.magnitude();
+
+        if len < 1e-6 {
+            return Plane {
+                n: Vector3::zero(),
+                offset: if v.w >= 0.0 { -1.0 } else { 1.0 },
+            };
+        }
+
+        Plane {
+            n: n / len,
+            offset: -v.w / len,
+        }
+    }
 }
 
 pub struct Frustum {
@@ -24,6 +44,22 @@ pub struct Frustum {
 }
 
 impl Frustum {
+    /// The planes of a projection * view matrix
+    pub fn from_matrix(m: &Matrix4<f32>) -> Frustum {
+        let (r0, r1, r2, r3) = (m.row(0), m.row(1), m.row(2), m.row(3));
+
+        Frustum {
+            planes: [
+                Plane::from_coefficients(r3 + r0),
+                Plane::from_coefficients(r3 - r0),
+                Plane::from_coefficients(r3 - r1),
+                Plane::from_coefficients(r3 + r1),
+                Plane::from_coefficients(r3 + r2),
+                Plane::from_coefficients(r3 - r2),
+            ],
+        }
+    }
+
     pub fn collide_sphere(&self, p: &Vector3<f32>, r: f32) -> bool {
         for plane in self.planes.iter() {
             // Distance = (A*x0+B*y0+C*z0+D)/Sqrt(A*A+B*B+C*C)
@@ -34,11 +70,128 @@ impl Frustum {
 
         true
     }
+
+    pub fn collide_aabb(&self, aabb: &Aabb) -> bool {
+        for plane in self.planes.iter() {
+            // The corner furthest along the normal
+            let p = Vector3::new(
+                if plane.n.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.n.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.n.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+
+            if plane.n.dot(p) - plane.offset < 0.0 {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// How a camera lights the opaque surfaces
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RenderingPath {
+    /// Each surface is lit by its nearest lights when it is drawn
+    Forward,
+    /// The opaque surfaces with a G-buffer program are drawn into a G-buffer,
+    /// then lit by all the lights. Forward where several color attachments
+    /// or glsl 300 es are not supported.
+    Deferred,
+}
+
+impl Default for RenderingPath {
+    fn default() -> RenderingPath {
+        RenderingPath::Forward
+    }
+}
+
+/// How a camera projects the view space onto the screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    Perspective {
+        /// Vertical field of view
+        fovy: Rad<f32>,
+        /// Without a far plane, zfar only bounds the shadows
+        infinite_far: bool,
+    },
+    Orthographic {
+        /// Half of the height of the view, in world units
+        size: f32,
+    },
+    /// A matrix used as is, like an off-axis projection.
+    /// The frustum culling follows it, the camera znear and zfar are not used.
+    Custom(Matrix4<f32>),
+}
+
+impl Default for Projection {
+    fn default() -> Projection {
+        Projection::perspective(Rad(3.1415 / 4.0))
+    }
+}
+
+impl Projection {
+    pub fn perspective<A: Into<Rad<f32>>>(fovy: A) -> Projection {
+        Projection::Perspective {
+            fovy: fovy.into(),
+            infinite_far: false,
+        }
+    }
+
+    pub fn orthographic(size: f32) -> Projection {
+        Projection::Orthographic { size }
+    }
+
+    /// The projection matrix for an aspect ratio and depth range
+    pub fn matrix(&self, aspect: f32, znear: f32, zfar: f32) -> Matrix4<f32> {
+        match *self {
+            Projection::Perspective {
+                fovy,
+                infinite_far: false,
+            } => PerspectiveFov {
+                fovy,
+                aspect,
+                near: znear,
+                far: zfar,
+            }.into(),
+            Projection::Perspective {
+                fovy,
+                infinite_far: true,
+            } => {
+                let f = 1.0 / (fovy.0 * 0.5).tan();
+
+                Matrix4::new(
+                    f / aspect, 0.0, 0.0, 0.0,
+                    0.0, f, 0.0, 0.0,
+                    0.0, 0.0, -1.0, -1.0,
+                    0.0, 0.0, -2.0 * znear, 0.0,
+                )
+            }
+            Projection::Orthographic { size } => {
+                ortho(-size * aspect, size * aspect, -size, size, znear, zfar)
+            }
+            Projection::Custom(m) => m,
+        }
+    }
 }
 
+#[derive(Clone)]
 pub struct Camera {
     pub v: Matrix4<f32>,
 
+    /// Cameras are rendered by increasing depth, the later ones drawing over the others
+    pub depth: i32,
+    /// Overrides the option passed to Engine::render when clearing this camera
+    pub clear_option: Option<ClearOption>,
+    /// Only the objects with one of these layers are drawn
+    pub culling_mask: u32,
+
+    pub projection: Projection,
+    /// Flips the depth, the nearer fragments having the greater depth,
+    /// and the depth tests are reversed accordingly. WebGL has no clip control,
+    /// so it does not improve the precision of the depth buffer.
+    pub reversed_z: bool,
+
     pub enable_frustum_culling: bool,
 
     /// Optional viewport of this camera,  (pos, size) in pixels
@@ -52,6 +205,11 @@ pub struct Camera {
     eye: Point3<f32>,
 
     pub render_texture: Option<Rc<RenderTexture>>,
+
+    /// Effects applied to the rendered scene
+    pub post_process: Option<Rc<PostProcessStack>>,
+
+    pub rendering_path: RenderingPath,
 }
 
 impl Default for Camera {
@@ -66,16 +224,14 @@ fn extract_forward(m: &Matrix4<f32>) -> Vector3<f32> {
     -m.row(2).truncate()
 }
 
-fn extract_up(m: &Matrix4<f32>) -> Vector3<f32> {
-    //Vector3::new(m.data[1], m.data[1 + 1 * 4], m.data[1 + 2 * 4])
-    m.row(1).truncate()
-}
-
 fn extract_right(m: &Matrix4<f32>) -> Vector3<f32> {
-    //Vector3::new(m.data[0], m.data[0 + 1 * 4], m.data[0 + 2 * 4])
     m.row(0).truncate()
 }
 
+fn extract_up(m: &Matrix4<f32>) -> Vector3<f32> {
+    m.row(1).truncate()
+}
+
 impl ComponentBased for Camera {}
 
 impl Camera {
@@ -83,6 +239,14 @@ impl Camera {
         extract_forward(&self.v)
     }
 
+    pub fn right(&self) -> Vector3<f32> {
+        extract_right(&self.v)
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        extract_up(&self.v)
+    }
+
     pub fn lookat(&mut self, eye: &Point3<f32>, target: &Point3<f32>, up: &Vector3<f32>) {
         self.v = Matrix4::look_at(*eye, *target, *up);
         self.eye = *eye;
@@ -119,22 +283,31 @@ impl Camera {
         aspect
     }
 
-    pub fn perspective(&self, screen_size: (u32, u32)) -> Matrix4<f32> {
-        use math::*;
-
+    /// The projection matrix, for the aspect ratio of the screen or the rect
+    pub fn projection_matrix(&self, screen_size: (u32, u32)) -> Matrix4<f32> {
         let aspect = self.calc_aspect(screen_size);
+        let m = self.projection.matrix(aspect, self.znear, self.zfar);
 
-        PerspectiveFov {
-            fovy: Rad(3.1415 / 4.0),
-            aspect,
-            near: self.znear,
-            far: self.zfar,
-        }.into()
+        if self.reversed_z {
+            Matrix4::from_nonuniform_scale(1.0, 1.0, -1.0) * m
+        } else {
+            m
+        }
+    }
+
+    #[deprecated(note = "use projection_matrix, which follows the projection of the camera")]
+    pub fn perspective(&self, screen_size: (u32, u32)) -> Matrix4<f32> {
+        self.projection_matrix(screen_size)
     }
 
     pub fn new() -> Camera {
         Camera {
             v: Matrix4::identity(),
+            depth: 0,
+            clear_option: None,
+            culling_mask: !0,
+            projection: Projection::default(),
+            reversed_z: false,
             eye: Point3::new(0.0, 0.0, 0.0),
             rect: None,
             znear: 0.03,
@@ -142,6 +315,8 @@ impl Camera {
             enable_frustum_culling: true,
             included_render_queues: None,
             render_texture: None,
+            post_process: None,
+            rendering_path: RenderingPath::default(),
         }
     }
 
@@ -149,49 +324,30 @@ impl Camera {
         Vector3::new(self.eye.x, self.eye.y, self.eye.z)
     }
 
-    pub fn calc_frustum(&self, screen_size: (u32, u32)) -> Frustum {
-        let forward = extract_forward(&self.v);
-        let up = extract_up(&self.v);
-        let right = extract_right(&self.v);
-        let aspect = self.calc_aspect(screen_size);
-
-        let near_center = self.eye.to_vec() + forward * self.znear;
-        let far_center = self.eye.to_vec() + forward * self.zfar;
-
-        let fovy: f32 = 3.1415 / 4.0;
+    /// Ray from the near plane through a point in pixels,
+    /// from 0 (left/top) to screen width/height (right/bottom)
+    pub fn screen_point_to_ray(&self, point: (f32, f32), screen_size: (u32, u32)) -> Ray {
+        let ((x, y), (w, h)) = self.rect.unwrap_or(((0, 0), screen_size));
 
-        let near_height = 2.0 * (fovy * 0.5).tan() * self.znear;
-        let far_height = 2.0 * (fovy * 0.5).tan() * self.zfar;
-        let near_width = near_height * aspect;
-        let far_width = far_height * aspect;
+        let ndc_x = (point.0 - x as f32) / (w as f32) * 2.0 - 1.0;
+        let ndc_y = 1.0 - (point.1 - y as f32) / (h as f32) * 2.0;
 
-        let far_top_left = far_center + up * (far_height * 0.5) - right * (far_width * 0.5);
-        let far_top_right = far_center + up * (far_height * 0.5) + right * (far_width * 0.5);
-        let far_bottom_left = far_center - up * (far_height * 0.5) - right * (far_width * 0.5);
-        let far_bottom_right = far_center - up * (far_height * 0.5) + right * (far_width * 0.5);
+        let inv = (self.projection_matrix(screen_size) * self.v)
+            .invert()
+            .unwrap_or(Matrix4::identity());
 
-        let near_top_left = near_center + up * (near_height * 0.5) - right * (near_width * 0.5);
-        let near_top_right = near_center + up * (near_height * 0.5) + right * (near_width * 0.5);
-        let near_bottom_left = near_center - up * (near_height * 0.5) - right * (near_width * 0.5);
-        let near_bottom_right = near_center - up * (near_height * 0.5) + right * (near_width * 0.5);
+        let unproject = |z: f32| {
+            let p = inv * Vector4::new(ndc_x, ndc_y, z, 1.0);
+            p.truncate() / p.w
+        };
 
-        let plane_left = Plane::from_3_points(&near_bottom_left, &far_bottom_left, &far_top_left);
-        let plane_right = Plane::from_3_points(&near_bottom_right, &near_top_right, &far_top_right);
-        let plane_top = Plane::from_3_points(&near_top_right, &near_top_left, &far_top_left);
-        let plane_bottom =
-            Plane::from_3_points(&near_bottom_left, &near_bottom_right, &far_bottom_right);
-        let plane_near = Plane::from_3_points(&near_bottom_left, &near_top_left, &near_top_right);
-        let plane_far = Plane::from_3_points(&far_bottom_left, &far_bottom_right, &far_top_right);
+        // The far plane can be at infinity
+        let near = unproject(if self.reversed_z { 1.0 } else { -1.0 });
+        Ray::new(near, unproject(0.0) - near)
+    }
 
-        Frustum {
-            planes: [
-                plane_left,
-                plane_right,
-                plane_top,
-                plane_bottom,
-                plane_near,
-                plane_far,
-            ],
-        }
+    /// The frustum of the projection, to cull the objects out of view
+    pub fn calc_frustum(&self, screen_size: (u32, u32)) -> Frustum {
+        Frustum::from_matrix(&(self.projection_matrix(screen_size) * self.v))
     }
 }
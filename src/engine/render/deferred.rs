@@ -0,0 +1,39 @@
+use engine::asset::AssetSystem;
+use engine::render::{Material, RenderTexture, TextureAttachment};
+use std::rc::Rc;
+
+/// The render target of the deferred path.
+///
+/// The opaque surfaces are drawn into it by the `gbuffer_program` of their
+/// material, then `light` draws the lit result from it, once per batch of lights.
+pub struct GBuffer {
+    pub size: (u32, u32),
+    /// Albedo, normal and material color attachments, with the depth
+    pub target: RenderTexture,
+    /// The "unrust/deferred_light" material, reading the attachments of target
+    pub light: Rc<Material>,
+}
+
+impl GBuffer {
+    pub fn new(asys: &AssetSystem, size: (u32, u32)) -> GBuffer {
+        let target = RenderTexture::new_with_attachments(
+            size.0,
+            size.1,
+            &[TextureAttachment::Color0; 3],
+        );
+
+        let light = Material::new(asys.new_program("unrust/deferred_light"));
+        light.set("uGAlbedo", target.color_texture(0));
+        light.set("uGNormal", target.color_texture(1));
+        light.set("uGMaterial", target.color_texture(2));
+        if let Some(depth) = target.depth_texture() {
+            light.set("uGDepth", depth);
+        }
+
+        GBuffer {
+            size,
+            target,
+            light: Rc::new(light),
+        }
+    }
+}
@@ -4,8 +4,22 @@ use std::rc::Rc;
 use std::cell::RefCell;
 use engine::render::{Texture, TextureAttachment};
 
+/// Number of color attachments a frame buffer can have
+pub const MAX_COLOR_ATTACHMENTS: usize = 4;
+
+const COLOR_ATTACHMENTS: [(Buffers, ColorBuffer); MAX_COLOR_ATTACHMENTS] = [
+    (Buffers::ColorAttachment0, ColorBuffer::ColorAttachment0),
+    (Buffers::ColorAttachment1, ColorBuffer::ColorAttachment1),
+    (Buffers::ColorAttachment2, ColorBuffer::ColorAttachment2),
+    (Buffers::ColorAttachment3, ColorBuffer::ColorAttachment3),
+];
+
 pub struct FrameBuffer {
     pub texture: Rc<Texture>,
+    /// Color attachments after the first one, drawn to at once with it
+    pub attachments: Vec<Rc<Texture>>,
+    /// Depth attachment next to a color texture
+    pub depth: Option<Rc<Texture>>,
     handle: RefCell<Option<WebGLFrameBuffer>>,
 }
 
@@ -13,7 +27,39 @@ impl FrameBuffer {
     pub fn new(width: u32, height: u32, attach: TextureAttachment) -> FrameBuffer {
         let texture = Texture::new_render_texture(width, height, attach);
         let handle = RefCell::new(None);
-        FrameBuffer { texture, handle }
+        FrameBuffer {
+            texture,
+            attachments: Vec::new(),
+            depth: None,
+            handle,
+        }
+    }
+
+    pub fn new_with_depth(width: u32, height: u32, attach: TextureAttachment) -> FrameBuffer {
+        let mut fb = FrameBuffer::new(width, height, attach);
+        fb.depth = Some(Texture::new_render_texture(
+            width,
+            height,
+            TextureAttachment::Depth,
+        ));
+        fb
+    }
+
+    /// Several color textures with a depth one, drawn to at once
+    /// where `draw_buffers` is supported
+    pub fn new_with_attachments(
+        width: u32,
+        height: u32,
+        attachs: &[TextureAttachment],
+    ) -> FrameBuffer {
+        assert!(!attachs.is_empty() && attachs.len() <= MAX_COLOR_ATTACHMENTS);
+
+        let mut fb = FrameBuffer::new_with_depth(width, height, attachs[0]);
+        fb.attachments = attachs[1..]
+            .iter()
+            .map(|attach| Texture::new_render_texture(width, height, *attach))
+            .collect();
+        fb
     }
 
     fn create_fb(&self, gl: &WebGLRenderingContext) {
@@ -36,6 +82,25 @@ impl FrameBuffer {
 
         gl.bind_framebuffer(Buffers::Framebuffer, &h);
         self.texture.bind_with_frame_buffer(gl, 0).unwrap();
+
+        for (i, tex) in self.attachments.iter().enumerate() {
+            tex.bind_with_frame_buffer_at(gl, 0, COLOR_ATTACHMENTS[i + 1].0)
+                .unwrap();
+        }
+
+        if !self.attachments.is_empty() {
+            let buffers: Vec<ColorBuffer> = COLOR_ATTACHMENTS[..self.attachments.len() + 1]
+                .iter()
+                .map(|&(_, c)| c)
+                .collect();
+            gl.draw_buffer(&buffers);
+        }
+
+        if let Some(ref depth) = self.depth {
+            depth
+                .bind_with_frame_buffer_at(gl, 0, Buffers::DepthAttachment)
+                .unwrap();
+        }
     }
 
     pub fn unbind(&self, gl: &WebGLRenderingContext) {
@@ -2,9 +2,16 @@ use engine::core::ComponentBased;
 use super::ShaderProgram;
 use math::*;
 
+/// Lights of each kind a program is given at most,
+/// the point and spot lights are the most relevant to each object.
+pub const MAX_DIRECTIONAL_LIGHTS: usize = 4;
+pub const MAX_POINT_LIGHTS: usize = 4;
+pub const MAX_SPOT_LIGHTS: usize = 4;
+
 pub enum Light {
     Directional(Directional),
     Point(Point),
+    Spot(Spot),
 }
 
 macro_rules! impl_light {
@@ -30,6 +37,7 @@ macro_rules! impl_light {
 impl Light {
     impl_light!(directional, directional_mut, Directional, Directional);
     impl_light!(point, point_mut, Point, Point);
+    impl_light!(spot, spot_mut, Spot, Spot);
 
     pub fn new<T>(a: T) -> Light
     where
@@ -42,6 +50,7 @@ impl Light {
         match *self {
             Light::Directional(ref mut l) => l.update(model),
             Light::Point(ref mut l) => l.update(model),
+            Light::Spot(ref mut l) => l.update(model),
         }
     }
 
@@ -49,12 +58,139 @@ impl Light {
         match *self {
             Light::Directional(ref l) => l.bind(lightname, prog),
             Light::Point(ref l) => l.bind(lightname, prog),
+            Light::Spot(ref l) => l.bind(lightname, prog),
+        }
+    }
+
+    /// Set the shadow uniforms of a point or spot light
+    pub fn bind_shadow(&self, shadowname: &str, prog: &ShaderProgram) {
+        match self.shadow() {
+            Some(shadow) => shadow.bind(shadowname, prog),
+            None => prog.set(shadowname.to_string() + ".enabled", false),
+        }
+    }
+
+    /// Shadow settings, None for directional lights which are shadowed by the ShadowPass
+    /// cascades for the main one
+    pub fn shadow(&self) -> Option<&LightShadow> {
+        match *self {
+            Light::Directional(_) => None,
+            Light::Point(ref l) => Some(&l.shadow),
+            Light::Spot(ref l) => Some(&l.shadow),
+        }
+    }
+
+    pub fn shadow_mut(&mut self) -> Option<&mut LightShadow> {
+        match *self {
+            Light::Directional(_) => None,
+            Light::Point(ref mut l) => Some(&mut l.shadow),
+            Light::Spot(ref mut l) => Some(&mut l.shadow),
+        }
+    }
+
+    /// World space position, None for directional lights
+    pub fn world_position(&self) -> Option<Vector3f> {
+        match *self {
+            Light::Directional(_) => None,
+            Light::Point(ref l) => Some(l.world_space_position),
+            Light::Spot(ref l) => Some(l.world_space_position),
+        }
+    }
+
+    /// Distance beyond which the light has no visible effect, None for directional lights
+    pub fn range(&self) -> Option<f32> {
+        match *self {
+            Light::Directional(_) => None,
+            Light::Point(ref l) => Some(attenuation_range(
+                l.constant,
+                l.linear,
+                l.quadratic,
+                &[l.diffuse, l.specular],
+            )),
+            Light::Spot(ref l) => Some(attenuation_range(
+                l.constant,
+                l.linear,
+                l.quadratic,
+                &[l.diffuse, l.specular],
+            )),
         }
     }
 }
 
+/// Distance where the attenuated brightest channel falls under 1/256
+fn attenuation_range(constant: f32, linear: f32, quadratic: f32, colors: &[Vector3f]) -> f32 {
+    let brightest = colors
+        .iter()
+        .fold(0.0f32, |m, c| m.max(c.x).max(c.y).max(c.z));
+
+    // Solve constant + linear * d + quadratic * d^2 = 256 * brightest
+    let c = constant - 256.0 * brightest;
+    if c >= 0.0 {
+        0.0
+    } else if quadratic > 0.0 {
+        (-linear + (linear * linear - 4.0 * quadratic * c).sqrt()) / (2.0 * quadratic)
+    } else if linear > 0.0 {
+        -c / linear
+    } else {
+        ::std::f32::INFINITY
+    }
+}
+
 impl ComponentBased for Light {}
 
+/// Shadow settings of a point or spot light, its shadow map is drawn by the ShadowPass
+#[derive(Clone, Debug)]
+pub struct LightShadow {
+    pub casts_shadows: bool,
+    /// Size in texels of the shadow map, of each of the 6 cube faces for a point light
+    pub resolution: u32,
+    /// World distance the shaded points are moved toward the light, against shadow acne
+    pub bias: f32,
+    /// Where the shadow map is in the atlas of the ShadowPass, None when it did not fit
+    pub tile: Option<ShadowTile>,
+}
+
+impl Default for LightShadow {
+    fn default() -> LightShadow {
+        LightShadow {
+            casts_shadows: false,
+            resolution: 256,
+            bias: 0.05,
+            tile: None,
+        }
+    }
+}
+
+/// A shadow map in a shadow atlas
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ShadowTile {
+    /// Offset and size in uv of the map, of the first cube face for a point light,
+    /// the faces being in 3 columns and 2 rows
+    pub viewport: Vector4<f32>,
+    /// From world to the clip space of a spot light
+    pub light_matrix: Matrix4f,
+    /// Near and far planes of the light projection
+    pub depth_range: Vector2f,
+}
+
+impl LightShadow {
+    fn bind(&self, shadowname: &str, prog: &ShaderProgram) {
+        let tile = match self.tile {
+            Some(ref tile) if self.casts_shadows => tile,
+            _ => {
+                prog.set(shadowname.to_string() + ".enabled", false);
+                return;
+            }
+        };
+
+        prog.set(shadowname.to_string() + ".enabled", true);
+        prog.set(shadowname.to_string() + ".viewport", tile.viewport);
+        prog.set(shadowname.to_string() + ".light_matrix", tile.light_matrix);
+        prog.set(shadowname.to_string() + ".depth_range", tile.depth_range);
+        prog.set(shadowname.to_string() + ".bias", self.bias);
+    }
+}
+
 pub struct Directional {
     pub direction: Vector3<f32>,
     pub ambient: Vector3<f32>,
@@ -118,6 +254,8 @@ pub struct Point {
     pub linear: f32,
     pub quadratic: f32,
 
+    pub shadow: LightShadow,
+
     pub world_space_position: Vector3f,
 }
 
@@ -138,6 +276,7 @@ impl Default for Point {
             constant: 1.0,
             linear: 0.022,
             quadratic: 0.0019,
+            shadow: LightShadow::default(),
         }
     }
 }
@@ -166,3 +305,96 @@ impl Point {
             .to_vec();
     }
 }
+
+/// A point light restricted to a cone
+pub struct Spot {
+    pub position: Vector3<f32>,
+    pub direction: Vector3<f32>,
+
+    /// Half angle of the fully lit cone
+    pub cut_off: Deg<f32>,
+    /// Half angle where the light has faded out
+    pub outer_cut_off: Deg<f32>,
+
+    pub ambient: Vector3<f32>,
+    pub diffuse: Vector3<f32>,
+    pub specular: Vector3<f32>,
+
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+
+    pub shadow: LightShadow,
+
+    pub world_space_position: Vector3f,
+    pub world_space_direction: Vector3f,
+}
+
+impl From<Spot> for Light {
+    fn from(w: Spot) -> Light {
+        Light::Spot(w)
+    }
+}
+
+impl Default for Spot {
+    fn default() -> Spot {
+        let down = Vector3::new(0.0, -1.0, 0.0);
+
+        Spot {
+            position: Vector3::new(0.0, 0.0, 0.0),
+            direction: down,
+            cut_off: Deg(12.5),
+            outer_cut_off: Deg(17.5),
+            ambient: Vector3::new(0.0, 0.0, 0.0),
+            diffuse: Vector3::new(0.8, 0.8, 0.8),
+            specular: Vector3::new(1.0, 1.0, 1.0),
+            constant: 1.0,
+            linear: 0.022,
+            quadratic: 0.0019,
+            shadow: LightShadow::default(),
+            world_space_position: Vector3f::zero(),
+            world_space_direction: down,
+        }
+    }
+}
+
+impl Spot {
+    fn bind(&self, lightname: &str, prog: &ShaderProgram) {
+        prog.set(
+            lightname.to_string() + ".position",
+            self.world_space_position,
+        );
+        prog.set(
+            lightname.to_string() + ".direction",
+            self.world_space_direction,
+        );
+
+        // The shader compares cosines
+        prog.set(lightname.to_string() + ".cutOff", self.cut_off.cos());
+        prog.set(
+            lightname.to_string() + ".outerCutOff",
+            self.outer_cut_off.cos(),
+        );
+
+        prog.set(lightname.to_string() + ".ambient", self.ambient);
+        prog.set(lightname.to_string() + ".diffuse", self.diffuse);
+        prog.set(lightname.to_string() + ".specular", self.specular);
+
+        prog.set(lightname.to_string() + ".constant", self.constant);
+        prog.set(lightname.to_string() + ".linear", self.linear);
+        prog.set(lightname.to_string() + ".quadratic", self.quadratic);
+
+        prog.set(lightname.to_string() + ".rate", 1.0);
+    }
+
+    fn update(&mut self, modelm: &Matrix4f) {
+        self.world_space_position = modelm
+            .transform_point(Point3::from_vec(self.position))
+            .to_vec();
+
+        let direction = modelm.transform_vector(self.direction);
+        if direction.magnitude2() > 0.0 {
+            self.world_space_direction = direction.normalize();
+        }
+    }
+}
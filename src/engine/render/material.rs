@@ -4,11 +4,11 @@ use engine::render::{RenderQueue, ShaderProgram, Texture};
 use fnv::FnvHashMap;
 use math::*;
 use std::borrow::Cow;
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
 use std::rc::Rc;
 
 #[derive(Debug, Clone)]
-pub struct TexturePtr(Rc<Texture>);
+pub struct TexturePtr(pub Rc<Texture>);
 
 impl PartialEq for TexturePtr {
     fn eq(&self, other: &Self) -> bool {
@@ -81,10 +81,27 @@ impl Default for DepthTest {
     }
 }
 
+/// How the blended surfaces are combined with the colors behind them
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Blending {
+    /// Mixed by the alpha of the surface
+    Alpha,
+    /// Added, scaled by the alpha of the surface
+    Additive,
+}
+
+impl Default for Blending {
+    fn default() -> Blending {
+        Blending::Alpha
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Default, Debug)]
 pub struct MaterialState {
     pub cull: Option<CullMode>,
     pub alpha_blending: Option<bool>,
+    /// Used when alpha_blending is on
+    pub blending: Option<Blending>,
     pub depth_write: Option<bool>,
     pub depth_test: Option<DepthTest>,
 }
@@ -94,6 +111,9 @@ pub struct Material {
     pub program: Rc<ShaderProgram>,
     pub render_queue: RenderQueue,
     pub states: MaterialState,
+    /// Draws the surface into the G-buffer of the deferred path, from the same
+    /// parameters. The surface is drawn forward when None.
+    pub gbuffer_program: Option<Rc<ShaderProgram>>,
 
     params: RefCell<MaterialParamMap>,
 }
@@ -102,6 +122,10 @@ impl PartialEq for Material {
     fn eq(&self, other: &Self) -> bool {
         Rc::ptr_eq(&self.program, &other.program) && self.render_queue == other.render_queue
             && self.states == other.states
+            && match (&self.gbuffer_program, &other.gbuffer_program) {
+                (&Some(ref a), &Some(ref b)) => Rc::ptr_eq(a, b),
+                (a, b) => a.is_none() && b.is_none(),
+            }
             && *self.params.borrow() == *other.params.borrow()
     }
 }
@@ -113,6 +137,7 @@ impl Material {
             program: program,
             params: RefCell::new(FnvHashMap::default()),
             states: MaterialState::default(),
+            gbuffer_program: None,
         };
     }
 
@@ -124,8 +149,13 @@ impl Material {
         self.params.borrow_mut().insert(name.into(), t.into());
     }
 
+    pub fn params(&self) -> Ref<MaterialParamMap> {
+        self.params.borrow()
+    }
+
     fn bind_params<F>(
         &self,
+        program: &ShaderProgram,
         params: &MaterialParamMap,
         request_tex_unit: &mut F,
         level: u32,
@@ -137,29 +167,28 @@ impl Material {
             match param {
                 &MaterialParam::Texture(ref tex) => {
                     let new_unit = request_tex_unit(&tex.0)?;
-                    self.program
-                        .set(name.clone(), (Rc::downgrade(&tex.0), new_unit));
+                    program.set(name.clone(), (Rc::downgrade(&tex.0), new_unit));
                 }
                 &MaterialParam::Bool(v) => {
-                    self.program.set(name.clone(), v);
+                    program.set(name.clone(), v);
                 }
                 &MaterialParam::Float(f) => {
-                    self.program.set(name.clone(), f);
+                    program.set(name.clone(), f);
                 }
                 &MaterialParam::Vec2(v) => {
-                    self.program.set(name.clone(), v);
+                    program.set(name.clone(), v);
                 }
                 &MaterialParam::Vec3(v) => {
-                    self.program.set(name.clone(), v);
+                    program.set(name.clone(), v);
                 }
                 &MaterialParam::Vec4(v) => {
-                    self.program.set(name.clone(), v);
+                    program.set(name.clone(), v);
                 }
                 &MaterialParam::Matrix4(v) => {
-                    self.program.set(name.clone(), v);
+                    program.set(name.clone(), v);
                 }
                 &MaterialParam::Params(ref pm) => {
-                    self.bind_params(&pm, request_tex_unit, level + 1)?;
+                    self.bind_params(program, &pm, request_tex_unit, level + 1)?;
                 }
             }
         }
@@ -167,11 +196,19 @@ impl Material {
         Ok(())
     }
 
-    pub fn bind<F>(&self, mut request_tex_unit: F) -> AssetResult<()>
+    pub fn bind<F>(&self, request_tex_unit: F) -> AssetResult<()>
+    where
+        F: FnMut(&Rc<Texture>) -> AssetResult<u32>,
+    {
+        self.bind_to(&self.program, request_tex_unit)
+    }
+
+    /// Set the parameters to another program, as the gbuffer_program
+    pub fn bind_to<F>(&self, program: &ShaderProgram, mut request_tex_unit: F) -> AssetResult<()>
     where
         F: FnMut(&Rc<Texture>) -> AssetResult<u32>,
     {
-        self.bind_params(&self.params.borrow(), &mut request_tex_unit, 0)?;
+        self.bind_params(program, &self.params.borrow(), &mut request_tex_unit, 0)?;
 
         Ok(())
     }
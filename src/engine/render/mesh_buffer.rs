@@ -9,7 +9,8 @@ use engine::render::shader_program::ShaderAttrib;
 
 use math::*;
 use std::cell::Cell;
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
 use std::f32::{MAX, MIN};
 use std::rc::Rc;
 use std::rc::Weak;
@@ -34,6 +35,9 @@ enum RebindAction {
     Normal,
     Tangent,
     Bitangent,
+    Joints,
+    Weights,
+    Color,
     Indices,
 }
 
@@ -46,7 +50,14 @@ struct MeshGLState {
     pub tb: Option<WebGLBuffer>,
     pub btb: Option<WebGLBuffer>,
 
+    pub jb: Option<WebGLBuffer>,
+    pub wb: Option<WebGLBuffer>,
+
+    pub cb: Option<WebGLBuffer>,
+
     pub ib: WebGLBuffer,
+    pub index_type: DataType,
+    pub index_count: usize,
     pub gl: WebGLRenderingContext,
 
     pub rebind_actions: Vec<RebindAction>,
@@ -84,9 +95,24 @@ impl MeshGLState {
                 data.bitangents.clone().unwrap().into_bytes(),
                 self.btb.as_mut().unwrap(),
             ),
+            RebindAction::Joints => (
+                BufferKind::Array,
+                data.joints.clone().unwrap().into_bytes(),
+                self.jb.as_mut().unwrap(),
+            ),
+            RebindAction::Weights => (
+                BufferKind::Array,
+                data.weights.clone().unwrap().into_bytes(),
+                self.wb.as_mut().unwrap(),
+            ),
+            RebindAction::Color => (
+                BufferKind::Array,
+                data.colors.clone().unwrap().into_bytes(),
+                self.cb.as_mut().unwrap(),
+            ),
             RebindAction::Indices => (
                 BufferKind::ElementArray,
-                data.indices.clone().into_bytes(),
+                data.indices.to_bytes(),
                 &mut self.ib,
             ),
         }
@@ -100,6 +126,82 @@ impl MeshGLState {
             gl.buffer_data(k, &p, DrawMode::Static);
             gl.unbind_buffer(k);
         }
+
+        self.index_type = data.indices.data_type();
+        self.index_count = data.indices.len();
+    }
+
+    fn bind_attributes(&self, gl: &WebGLRenderingContext) {
+        // Bind vertex buffer object
+        // "aVertexPosition"
+        bind_buffer(
+            gl,
+            &self.vb,
+            ShaderAttrib::Position as u32,
+            AttributeSize::Three,
+        );
+
+        // "aTextureCoord"
+        if let Some(ref uvb) = self.uvb {
+            bind_buffer(gl, uvb, ShaderAttrib::UV0 as u32, AttributeSize::Two);
+        }
+
+        // "aVertexNormal"
+        if let Some(ref nb) = self.nb {
+            bind_buffer(gl, nb, ShaderAttrib::Normal as u32, AttributeSize::Three);
+        }
+
+        // "aVertexTangent"
+        if let Some(ref tb) = self.tb {
+            bind_buffer(gl, tb, ShaderAttrib::Tangent as u32, AttributeSize::Three);
+        }
+
+        // "aVertexBitangent"
+        if let Some(ref btb) = self.btb {
+            bind_buffer(
+                gl,
+                btb,
+                ShaderAttrib::Bitangent as u32,
+                AttributeSize::Three,
+            );
+        }
+
+        // "aJoints" and "aWeights"
+        if let (&Some(ref jb), &Some(ref wb)) = (&self.jb, &self.wb) {
+            bind_buffer(gl, jb, ShaderAttrib::Joints as u32, AttributeSize::Four);
+            bind_buffer(gl, wb, ShaderAttrib::Weights as u32, AttributeSize::Four);
+        }
+
+        // "aVertexColor"
+        if let Some(ref cb) = self.cb {
+            bind_buffer(gl, cb, ShaderAttrib::Color as u32, AttributeSize::Four);
+        }
+
+        // Bind index buffer object
+        gl.bind_buffer(BufferKind::ElementArray, &self.ib);
+    }
+
+    fn draw(&self, gl: &WebGLRenderingContext) {
+        gl.draw_elements(Primitives::Triangles, self.index_count, self.index_type, 0);
+    }
+
+    fn draw_instanced(&self, gl: &WebGLRenderingContext, instances: &InstanceBuffer, count: usize) {
+        instances.bind_attributes(gl);
+
+        gl.draw_elements_instanced(
+            Primitives::Triangles,
+            self.index_count,
+            self.index_type,
+            0,
+            count,
+        );
+
+        // Leave the vertex array as a non instanced draw expects it
+        for col in 0..4 {
+            let loc = ShaderAttrib::InstanceMatrix as u32 + col;
+            gl.vertex_attrib_divisor(loc, 0);
+            gl.disable_vertex_attrib_array(loc);
+        }
     }
 }
 
@@ -110,12 +212,119 @@ impl Drop for MeshGLState {
         self.nb.as_ref().map(|b| self.gl.delete_buffer(&b));
         self.tb.as_ref().map(|b| self.gl.delete_buffer(&b));
         self.btb.as_ref().map(|b| self.gl.delete_buffer(&b));
+        self.jb.as_ref().map(|b| self.gl.delete_buffer(&b));
+        self.wb.as_ref().map(|b| self.gl.delete_buffer(&b));
+        self.cb.as_ref().map(|b| self.gl.delete_buffer(&b));
         self.gl.delete_buffer(&self.ib);
 
         self.gl.delete_vertex_array(&self.vao);
     }
 }
 
+/// Number of vertices which can be indexed by u16 indices
+pub const U16_VERTEX_LIMIT: usize = 65536;
+
+/// The index buffer of a mesh, u32 indices are only needed by meshes
+/// with more than U16_VERTEX_LIMIT vertices.
+#[derive(Debug, Clone)]
+pub enum MeshIndices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Default for MeshIndices {
+    fn default() -> MeshIndices {
+        MeshIndices::U16(Vec::new())
+    }
+}
+
+impl From<Vec<u16>> for MeshIndices {
+    fn from(v: Vec<u16>) -> MeshIndices {
+        MeshIndices::U16(v)
+    }
+}
+
+impl From<Vec<u32>> for MeshIndices {
+    fn from(v: Vec<u32>) -> MeshIndices {
+        MeshIndices::U32(v)
+    }
+}
+
+impl MeshIndices {
+    /// Choose the index width from the number of vertices of the mesh
+    pub fn with_vertex_count(indices: Vec<u32>, vertex_count: usize) -> MeshIndices {
+        if vertex_count <= U16_VERTEX_LIMIT {
+            MeshIndices::U16(indices.into_iter().map(|i| i as u16).collect())
+        } else {
+            MeshIndices::U32(indices)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            &MeshIndices::U16(ref v) => v.len(),
+            &MeshIndices::U32(ref v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_u32(&self) -> bool {
+        match self {
+            &MeshIndices::U16(_) => false,
+            &MeshIndices::U32(_) => true,
+        }
+    }
+
+    pub fn get(&self, i: usize) -> u32 {
+        match self {
+            &MeshIndices::U16(ref v) => v[i] as u32,
+            &MeshIndices::U32(ref v) => v[i],
+        }
+    }
+
+    /// Append an index, switch to u32 indices if it does not fit in u16
+    pub fn push(&mut self, i: u32) {
+        if let MeshIndices::U16(ref mut v) = *self {
+            if i < U16_VERTEX_LIMIT as u32 {
+                v.push(i as u16);
+                return;
+            }
+        }
+
+        if !self.is_u32() {
+            *self = MeshIndices::U32(self.to_u32());
+        }
+
+        if let MeshIndices::U32(ref mut v) = *self {
+            v.push(i);
+        }
+    }
+
+    pub fn to_u32(&self) -> Vec<u32> {
+        match self {
+            &MeshIndices::U16(ref v) => v.iter().map(|i| *i as u32).collect(),
+            &MeshIndices::U32(ref v) => v.clone(),
+        }
+    }
+
+    fn data_type(&self) -> DataType {
+        match self {
+            &MeshIndices::U16(_) => DataType::U16,
+            &MeshIndices::U32(_) => DataType::U32,
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            &MeshIndices::U16(ref v) => v.clone().into_bytes(),
+            &MeshIndices::U32(ref v) => v.clone().into_bytes(),
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct MeshData {
     pub vertices: Vec<f32>,
@@ -125,10 +334,81 @@ pub struct MeshData {
     pub tangents: Option<Vec<f32>>,
     pub bitangents: Option<Vec<f32>>,
 
-    pub indices: Vec<u16>,
+    /// 4 joint indices per vertex for skinning, stored as floats
+    pub joints: Option<Vec<f32>>,
+    /// 4 joint weights per vertex, which should sum up to 1
+    pub weights: Option<Vec<f32>>,
+
+    /// RGBA color per vertex
+    pub colors: Option<Vec<f32>>,
+
+    pub indices: MeshIndices,
+}
+
+fn copy_attribute(src: &Option<Vec<f32>>, dst: &mut Option<Vec<f32>>, i: usize, n: usize) {
+    if let (&Some(ref src), &mut Some(ref mut dst)) = (src, dst) {
+        dst.extend_from_slice(&src[i * n..i * n + n]);
+    }
 }
 
 impl MeshData {
+    /// Split the mesh into parts which can be indexed by u16 indices,
+    /// used when u32 index buffers are not supported.
+    pub fn split_u16(&self) -> Vec<MeshData> {
+        let empty_part = || MeshData {
+            uvs: self.uvs.as_ref().map(|_| Vec::new()),
+            normals: self.normals.as_ref().map(|_| Vec::new()),
+            tangents: self.tangents.as_ref().map(|_| Vec::new()),
+            bitangents: self.bitangents.as_ref().map(|_| Vec::new()),
+            joints: self.joints.as_ref().map(|_| Vec::new()),
+            weights: self.weights.as_ref().map(|_| Vec::new()),
+            colors: self.colors.as_ref().map(|_| Vec::new()),
+            ..MeshData::default()
+        };
+
+        let mut parts = Vec::new();
+        let mut part = empty_part();
+        let mut remap: HashMap<u32, u32> = HashMap::new();
+
+        for t in 0..self.indices.len() / 3 {
+            let tri = [
+                self.indices.get(t * 3),
+                self.indices.get(t * 3 + 1),
+                self.indices.get(t * 3 + 2),
+            ];
+
+            let new_vertices = tri.iter().filter(|i| !remap.contains_key(i)).count();
+            if remap.len() + new_vertices > U16_VERTEX_LIMIT {
+                parts.push(::std::mem::replace(&mut part, empty_part()));
+                remap.clear();
+            }
+
+            for &i in tri.iter() {
+                let next = remap.len() as u32;
+                let index = *remap.entry(i).or_insert_with(|| {
+                    let i = i as usize;
+                    part.vertices.extend_from_slice(&self.vertices[i * 3..i * 3 + 3]);
+                    copy_attribute(&self.uvs, &mut part.uvs, i, 2);
+                    copy_attribute(&self.normals, &mut part.normals, i, 3);
+                    copy_attribute(&self.tangents, &mut part.tangents, i, 3);
+                    copy_attribute(&self.bitangents, &mut part.bitangents, i, 3);
+                    copy_attribute(&self.joints, &mut part.joints, i, 4);
+                    copy_attribute(&self.weights, &mut part.weights, i, 4);
+                    copy_attribute(&self.colors, &mut part.colors, i, 4);
+                    next
+                });
+
+                part.indices.push(index);
+            }
+        }
+
+        if !part.indices.is_empty() {
+            parts.push(part);
+        }
+
+        parts
+    }
+
     pub fn compute_bound(&self) -> MeshBound {
         let mut min = Vector3::new(MAX, MAX, MAX);
         let mut max = Vector3::new(MIN, MIN, MIN);
@@ -160,7 +440,8 @@ impl MeshData {
 
 pub struct MeshBuffer {
     data: Resource<MeshData>,
-    gl_state: RefCell<Option<MeshGLState>>,
+    // One state per part, meshes are split if u32 indices are not supported
+    gl_states: RefCell<Vec<MeshGLState>>,
     bounds: Cell<Option<MeshBound>>,
 
     bound_prog: RefCell<Weak<ShaderProgram>>,
@@ -172,7 +453,7 @@ impl Asset for MeshBuffer {
     fn new_from_resource(r: Self::Resource) -> Rc<Self> {
         Rc::new(MeshBuffer {
             data: r,
-            gl_state: Default::default(),
+            gl_states: Default::default(),
             bounds: Default::default(),
             bound_prog: RefCell::new(Weak::new()),
         })
@@ -204,6 +485,54 @@ pub fn bind_buffer(
     gl.vertex_attrib_pointer(coord, asize, DataType::Float, false, 0, 0);
 }
 
+/// The model matrices of instanced draws, streamed every batch
+pub struct InstanceBuffer {
+    buffer: WebGLBuffer,
+    gl: WebGLRenderingContext,
+}
+
+impl InstanceBuffer {
+    pub fn new(gl: &WebGLRenderingContext) -> InstanceBuffer {
+        InstanceBuffer {
+            buffer: gl.create_buffer(),
+            gl: gl.clone(),
+        }
+    }
+
+    pub fn update(&self, matrices: Vec<Matrix4f>) {
+        let gl = &self.gl;
+        gl.bind_buffer(BufferKind::Array, &self.buffer);
+        gl.buffer_data(BufferKind::Array, &matrices.into_bytes(), DrawMode::Stream);
+        gl.unbind_buffer(BufferKind::Array);
+    }
+
+    /// One column-major matrix per instance
+    fn bind_attributes(&self, gl: &WebGLRenderingContext) {
+        gl.bind_buffer(BufferKind::Array, &self.buffer);
+
+        let stride = size_of::<Matrix4f>() as u32;
+        for col in 0..4 {
+            let loc = ShaderAttrib::InstanceMatrix as u32 + col;
+            gl.enable_vertex_attrib_array(loc);
+            gl.vertex_attrib_pointer(
+                loc,
+                AttributeSize::Four,
+                DataType::Float,
+                false,
+                stride,
+                col * stride / 4,
+            );
+            gl.vertex_attrib_divisor(loc, 1);
+        }
+    }
+}
+
+impl Drop for InstanceBuffer {
+    fn drop(&mut self) {
+        self.gl.delete_buffer(&self.buffer);
+    }
+}
+
 impl MeshBuffer {
     pub fn update_mesh_data(&self, mesh_data: MeshData) {
         let mut actions = Vec::new();
@@ -229,6 +558,18 @@ impl MeshBuffer {
                     actions.push(RebindAction::Bitangent);
                 });
 
+                mesh_data.joints.as_ref().map(|_| {
+                    actions.push(RebindAction::Joints);
+                });
+
+                mesh_data.weights.as_ref().map(|_| {
+                    actions.push(RebindAction::Weights);
+                });
+
+                mesh_data.colors.as_ref().map(|_| {
+                    actions.push(RebindAction::Color);
+                });
+
                 actions.push(RebindAction::Indices);
             }
         };
@@ -236,45 +577,66 @@ impl MeshBuffer {
         self.data.replace(mesh_data);
 
         // check whether the state is ready
-        match *self.gl_state.borrow_mut() {
-            None => {}
-            Some(ref mut state) => {
-                state.rebind_actions.append(&mut actions);
+        let mut states = self.gl_states.borrow_mut();
+        match states.len() {
+            0 => {}
+            1 => {
+                states[0].rebind_actions.append(&mut actions);
+                *self.bound_prog.borrow_mut() = Weak::new();
+            }
+            // A split mesh is created again from the new data
+            _ => {
+                states.clear();
                 *self.bound_prog.borrow_mut() = Weak::new();
             }
         }
     }
 
     pub fn prepare(&self, gl: &WebGLRenderingContext) -> AssetResult<()> {
-        if let Some(ref mut state) = *self.gl_state.borrow_mut() {
-            if state.rebind_actions.len() > 0 {
+        let mut states = self.gl_states.borrow_mut();
+        let need_split = |data: &MeshData| data.indices.is_u32() && !gl.element_index_uint;
+
+        if states.len() == 1 && states[0].rebind_actions.len() > 0 {
+            let data = self.data.try_borrow()?;
+
+            if need_split(&data) {
+                states.clear();
+            } else {
+                let state = &mut states[0];
                 gl.bind_vertex_array(&state.vao);
 
-                let data = self.data.try_borrow()?;
                 let rebind_actions = state.rebind_actions.drain(..).collect();
 
                 // Rebind the mesh
                 state.rebind(&rebind_actions, &data, gl);
+                return Ok(());
             }
-
+        } else if states.len() > 0 {
             return Ok(());
         }
 
         let data = self.data.try_borrow()?;
 
-        self.gl_state.replace(Some(mesh_bind_buffer(
-            &data.vertices,
-            &data.uvs,
-            &data.normals,
-            &data.tangents,
-            &data.bitangents,
-            &data.indices,
-            gl,
-        )));
+        let parts = if need_split(&data) {
+            data.split_u16()
+        } else {
+            Vec::new()
+        };
+
+        if parts.is_empty() {
+            states.push(mesh_bind_buffer(&data, gl));
+        } else {
+            states.extend(parts.iter().map(|part| mesh_bind_buffer(part, gl)));
+        }
 
         Ok(())
     }
 
+    /// The mesh data, or an error if it is not loaded yet
+    pub fn data(&self) -> AssetResult<Ref<MeshData>> {
+        self.data.try_borrow()
+    }
+
     fn compute_bounds(&self) -> Option<MeshBound> {
         let data = self.data.try_borrow().ok()?;
         Some(data.compute_bound())
@@ -296,8 +658,8 @@ impl MeshBuffer {
     pub fn bind(&self, gl: &WebGLRenderingContext, program: &Rc<ShaderProgram>) -> AssetResult<()> {
         self.prepare(gl)?;
 
-        let state_option = self.gl_state.borrow();
-        let state = state_option.as_ref().unwrap();
+        let states = self.gl_states.borrow();
+        let state = &states[0];
 
         /*======= Associating shaders to buffer objects =======*/
         gl.bind_vertex_array(&state.vao);
@@ -308,42 +670,7 @@ impl MeshBuffer {
             }
         }
 
-        // Bind vertex buffer object
-        // "aVertexPosition"
-        bind_buffer(
-            gl,
-            &state.vb,
-            ShaderAttrib::Position as u32,
-            AttributeSize::Three,
-        );
-
-        // "aTextureCoord"
-        if let Some(ref uvb) = state.uvb {
-            bind_buffer(gl, uvb, ShaderAttrib::UV0 as u32, AttributeSize::Two);
-        }
-
-        // "aVertexNormal"
-        if let Some(ref nb) = state.nb {
-            bind_buffer(gl, nb, ShaderAttrib::Normal as u32, AttributeSize::Three);
-        }
-
-        // "aVertexTangent"
-        if let Some(ref tb) = state.tb {
-            bind_buffer(gl, tb, ShaderAttrib::Tangent as u32, AttributeSize::Three);
-        }
-
-        // "aVertexBitangent"
-        if let Some(ref btb) = state.btb {
-            bind_buffer(
-                gl,
-                btb,
-                ShaderAttrib::Bitangent as u32,
-                AttributeSize::Three,
-            );
-        }
-
-        // Bind index buffer object
-        gl.bind_buffer(BufferKind::ElementArray, &state.ib);
+        state.bind_attributes(gl);
 
         *self.bound_prog.borrow_mut() = Rc::downgrade(program);
 
@@ -352,9 +679,45 @@ impl MeshBuffer {
 
     #[cfg_attr(feature = "flame_it", flame)]
     pub fn render(&self, gl: &WebGLRenderingContext) {
-        let data = self.data.try_borrow().unwrap();
+        let states = self.gl_states.borrow();
+
+        states[0].draw(gl);
+
+        if states.len() > 1 {
+            for state in states[1..].iter() {
+                gl.bind_vertex_array(&state.vao);
+                state.bind_attributes(gl);
+                state.draw(gl);
+            }
+
+            // Leave the first part bound as bind() did
+            gl.bind_vertex_array(&states[0].vao);
+            states[0].bind_attributes(gl);
+        }
+    }
+
+    /// Draw count instances of the mesh, reading their model matrices from instances.
+    /// The buffer should be bound first, as for render().
+    pub fn render_instanced(
+        &self,
+        gl: &WebGLRenderingContext,
+        instances: &InstanceBuffer,
+        count: usize,
+    ) {
+        let states = self.gl_states.borrow();
+
+        states[0].draw_instanced(gl, instances, count);
+
+        if states.len() > 1 {
+            for state in states[1..].iter() {
+                gl.bind_vertex_array(&state.vao);
+                state.bind_attributes(gl);
+                state.draw_instanced(gl, instances, count);
+            }
 
-        gl.draw_elements(Primitives::Triangles, data.indices.len(), DataType::U16, 0);
+            gl.bind_vertex_array(&states[0].vao);
+            states[0].bind_attributes(gl);
+        }
     }
 
     pub fn unbind(&self, _gl: &WebGLRenderingContext) {
@@ -384,24 +747,19 @@ fn bind_f32_array(gl: &WebGLRenderingContext, data: &Vec<f32>) -> WebGLBuffer {
     vb
 }
 
-fn mesh_bind_buffer(
-    vertices: &Vec<f32>,
-    uvs: &Option<Vec<f32>>,
-    normals: &Option<Vec<f32>>,
-    tangents: &Option<Vec<f32>>,
-    bitangents: &Option<Vec<f32>>,
-    indices: &Vec<u16>,
-    gl: &WebGLRenderingContext,
-) -> MeshGLState {
+fn mesh_bind_buffer(data: &MeshData, gl: &WebGLRenderingContext) -> MeshGLState {
     // some opengl 3.x core profile require a VAO. See issue #11
     let vao = gl.create_vertex_array();
     gl.bind_vertex_array(&vao);
 
-    let vertex_buffer = bind_f32_array(&gl, vertices);
-    let uv_buffer = uvs.as_ref().map(|ref data| bind_f32_array(gl, data));
-    let normal_buffer = normals.as_ref().map(|ref data| bind_f32_array(gl, data));
-    let tangent_buffer = tangents.as_ref().map(|ref data| bind_f32_array(gl, data));
-    let bitangent_buffer = bitangents.as_ref().map(|ref data| bind_f32_array(gl, data));
+    let vertex_buffer = bind_f32_array(&gl, &data.vertices);
+    let uv_buffer = data.uvs.as_ref().map(|ref data| bind_f32_array(gl, data));
+    let normal_buffer = data.normals.as_ref().map(|ref data| bind_f32_array(gl, data));
+    let tangent_buffer = data.tangents.as_ref().map(|ref data| bind_f32_array(gl, data));
+    let bitangent_buffer = data.bitangents.as_ref().map(|ref data| bind_f32_array(gl, data));
+    let joint_buffer = data.joints.as_ref().map(|ref data| bind_f32_array(gl, data));
+    let weight_buffer = data.weights.as_ref().map(|ref data| bind_f32_array(gl, data));
+    let color_buffer = data.colors.as_ref().map(|ref data| bind_f32_array(gl, data));
 
     // Create an empty buffer object to store Index buffer
     let index_buffer = gl.create_buffer();
@@ -410,8 +768,11 @@ fn mesh_bind_buffer(
         gl.bind_buffer(BufferKind::ElementArray, &index_buffer);
 
         // Pass the vertex data to the buffer
-        let ci = indices.clone();
-        gl.buffer_data(BufferKind::ElementArray, &ci.into_bytes(), DrawMode::Static);
+        gl.buffer_data(
+            BufferKind::ElementArray,
+            &data.indices.to_bytes(),
+            DrawMode::Static,
+        );
 
         // Unbind the buffer
         gl.unbind_buffer(BufferKind::ElementArray);
@@ -426,7 +787,14 @@ fn mesh_bind_buffer(
         tb: tangent_buffer,
         btb: bitangent_buffer,
 
+        jb: joint_buffer,
+        wb: weight_buffer,
+
+        cb: color_buffer,
+
         ib: index_buffer,
+        index_type: data.indices.data_type(),
+        index_count: data.indices.len(),
         gl: gl.clone(),
 
         rebind_actions: Vec::new(),
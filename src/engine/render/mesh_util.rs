@@ -1,5 +1,5 @@
 use math::*;
-use engine::MeshData;
+use engine::{MeshData, MeshIndices};
 
 pub trait QuadBuilder {
     fn add_quad(&mut self, ps: [Vector3f; 4]);
@@ -21,12 +21,178 @@ impl QuadBuilder for MeshData {
         add_v(&mut self.vertices, &ps[3]);
         add_v(&mut self.vertices, &ps[0]);
 
-        self.indices.push(self.indices.len() as u16);
-        self.indices.push(self.indices.len() as u16);
-        self.indices.push(self.indices.len() as u16);
+        for _ in 0..6 {
+            let i = self.indices.len() as u32;
+            self.indices.push(i);
+        }
+    }
+}
+
+#[inline]
+fn v3(s: &[f32], i: usize) -> Vector3f {
+    Vector3::new(s[i * 3], s[i * 3 + 1], s[i * 3 + 2])
+}
+
+/// Compute the tangents and bitangents of an indexed triangle list,
+/// the tangents of all triangles sharing a vertex are averaged.
+pub fn compute_vertex_tangents(
+    vertices: &[f32],
+    uvs: &[f32],
+    normals: &[f32],
+    indices: &MeshIndices,
+) -> (Vec<f32>, Vec<f32>) {
+    let n = vertices.len() / 3;
+    let mut tan_acc = vec![Vector3f::zero(); n];
+    let mut bitan_acc = vec![Vector3f::zero(); n];
+
+    for t in 0..indices.len() / 3 {
+        let (i0, i1, i2) = (
+            indices.get(t * 3) as usize,
+            indices.get(t * 3 + 1) as usize,
+            indices.get(t * 3 + 2) as usize,
+        );
+
+        let edge1 = v3(vertices, i1) - v3(vertices, i0);
+        let edge2 = v3(vertices, i2) - v3(vertices, i0);
+        let duv1 = Vector2::new(uvs[i1 * 2] - uvs[i0 * 2], uvs[i1 * 2 + 1] - uvs[i0 * 2 + 1]);
+        let duv2 = Vector2::new(uvs[i2 * 2] - uvs[i0 * 2], uvs[i2 * 2 + 1] - uvs[i0 * 2 + 1]);
+
+        let d = duv1.x * duv2.y - duv2.x * duv1.y;
+        if d.abs() < 0.000001 {
+            continue;
+        }
+
+        let r = 1.0 / d;
+        let t = (edge1 * duv2.y - edge2 * duv1.y) * r;
+        let b = (edge2 * duv1.x - edge1 * duv2.x) * r;
+
+        for &i in [i0, i1, i2].iter() {
+            tan_acc[i] += t;
+            bitan_acc[i] += b;
+        }
+    }
+
+    let mut tangents = Vec::with_capacity(n * 3);
+    let mut bitangents = Vec::with_capacity(n * 3);
+
+    for i in 0..n {
+        let normal = v3(normals, i);
+
+        // Gram-Schmidt orthogonalize
+        let mut t = tan_acc[i] - normal * normal.dot(tan_acc[i]);
+        if t.magnitude2() < 0.000001 {
+            // Pick any vector perpendicular to the normal
+            t = if normal.x.abs() < 0.9 {
+                normal.cross(Vector3::unit_x())
+            } else {
+                normal.cross(Vector3::unit_y())
+            };
+        }
+        let t = t.normalize();
+
+        let handedness = if normal.cross(t).dot(bitan_acc[i]) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        let b = normal.cross(t) * handedness;
+
+        tangents.extend_from_slice(&[t.x, t.y, t.z]);
+        bitangents.extend_from_slice(&[b.x, b.y, b.z]);
+    }
+
+    (tangents, bitangents)
+}
 
-        self.indices.push(self.indices.len() as u16);
-        self.indices.push(self.indices.len() as u16);
-        self.indices.push(self.indices.len() as u16);
+#[inline]
+fn cross2(a: Vector2f, b: Vector2f) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+/// Triangulate a planar polygon, convex ones are fanned and concave ones are
+/// ear clipped. Triangles index into `points` and keep the polygon winding.
+pub fn triangulate_polygon(points: &[Vector3f]) -> Vec<[usize; 3]> {
+    let n = points.len();
+    if n < 3 {
+        return Vec::new();
+    }
+
+    let fan = || (1..n - 1).map(|i| [0, i, i + 1]).collect();
+
+    // Newell's method, which works for concave polygons too
+    let mut normal = Vector3f::zero();
+    for i in 0..n {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        normal.x += (a.y - b.y) * (a.z + b.z);
+        normal.y += (a.z - b.z) * (a.x + b.x);
+        normal.z += (a.x - b.x) * (a.y + b.y);
     }
+
+    // Project on the axis plane the polygon faces most
+    let (ax, ay) = if normal.x.abs() >= normal.y.abs() && normal.x.abs() >= normal.z.abs() {
+        (1, 2)
+    } else if normal.y.abs() >= normal.z.abs() {
+        (2, 0)
+    } else {
+        (0, 1)
+    };
+    let p: Vec<Vector2f> = points
+        .iter()
+        .map(|v| Vector2::new(v[ax], v[ay]))
+        .collect();
+
+    let area: f32 = (0..n).map(|i| cross2(p[i], p[(i + 1) % n])).sum();
+    if area.abs() < 0.000001 {
+        // Degenerated polygon, nothing visible to keep right
+        return fan();
+    }
+
+    // Positive for convex corners whatever the winding is
+    let sign = area.signum();
+    let corner = |a: usize, b: usize, c: usize| sign * cross2(p[b] - p[a], p[c] - p[b]);
+
+    if (0..n).all(|i| corner((i + n - 1) % n, i, (i + 1) % n) >= 0.0) {
+        return fan();
+    }
+
+    let inside = |j: usize, a: usize, b: usize, c: usize| {
+        if p[j] == p[a] || p[j] == p[b] || p[j] == p[c] {
+            return false;
+        }
+
+        sign * cross2(p[b] - p[a], p[j] - p[a]) >= 0.0
+            && sign * cross2(p[c] - p[b], p[j] - p[b]) >= 0.0
+            && sign * cross2(p[a] - p[c], p[j] - p[c]) >= 0.0
+    };
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles = Vec::with_capacity(n - 2);
+
+    while remaining.len() > 3 {
+        let m = remaining.len();
+        let ear = (0..m).find(|&i| {
+            let (a, b, c) = (
+                remaining[(i + m - 1) % m],
+                remaining[i],
+                remaining[(i + 1) % m],
+            );
+
+            corner(a, b, c) > 0.0
+                && remaining
+                    .iter()
+                    .all(|&j| j == a || j == b || j == c || !inside(j, a, b, c))
+        });
+
+        // Self intersecting polygons may run out of ears, clip one anyway
+        let i = ear.unwrap_or(0);
+        triangles.push([
+            remaining[(i + m - 1) % m],
+            remaining[i],
+            remaining[(i + 1) % m],
+        ]);
+        remaining.remove(i);
+    }
+
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
 }
@@ -9,6 +9,19 @@ mod uniforms;
 mod frame_buffer;
 mod render_texture;
 mod mesh_buffer;
+mod skeleton;
+mod raycast;
+mod spatial_index;
+mod static_batch;
+mod pbr_material;
+mod post_process;
+mod deferred;
+mod sprite;
+mod sprite_batch;
+mod tilemap;
+mod particle;
+#[cfg(feature = "soft_gl")]
+mod soft_programs;
 
 #[derive(Hash, Eq, Ord, PartialOrd, PartialEq, Copy, Clone, Debug)]
 pub enum RenderQueue {
@@ -20,15 +33,32 @@ pub enum RenderQueue {
 
 pub mod mesh_util;
 
-pub use self::camera::{Camera, Frustum};
+pub use self::camera::{Camera, Frustum, Plane, Projection, RenderingPath};
 pub use self::shader::{PreprocessedShaderCode, Shader, ShaderFs, ShaderKind, ShaderKindFs,
                        ShaderKindProvider, ShaderKindVs, ShaderVs};
-pub use self::shader_program::ShaderProgram;
+pub use self::shader_program::{ShaderDefines, ShaderProgram};
 pub use self::texture::{Texture, TextureAsset, TextureAttachment, TextureFiltering, TextureImage,
                         TextureWrap};
 pub use self::mesh::{Mesh, MeshSurface};
-pub use self::mesh_buffer::{MeshBuffer, MeshData};
-pub use self::material::{CullMode, DepthTest, Material, MaterialParam, MaterialParamMap,
-                         MaterialState};
-pub use self::light::{Directional, Light, Point};
+pub use self::mesh_buffer::{InstanceBuffer, MeshBuffer, MeshData, MeshIndices, U16_VERTEX_LIMIT};
+pub use self::material::{Blending, CullMode, DepthTest, Material, MaterialParam,
+                         MaterialParamMap, MaterialState};
+pub use self::light::{Directional, Light, LightShadow, Point, ShadowTile, Spot,
+                      MAX_DIRECTIONAL_LIGHTS, MAX_POINT_LIGHTS, MAX_SPOT_LIGHTS};
 pub use self::render_texture::RenderTexture;
+pub use self::frame_buffer::MAX_COLOR_ATTACHMENTS;
+pub use self::skeleton::{Skeleton, MAX_JOINTS};
+pub use self::raycast::{raycast, RaycastFilter, RaycastHit};
+pub use self::spatial_index::SpatialIndex;
+pub use self::static_batch::StaticBatch;
+pub use self::pbr_material::{PbrMaterial, DEFAULT_ENVIRONMENT_MAP};
+pub use self::post_process::{neutral_lut, Bloom, PostProcessEffect, PostProcessStack};
+pub use self::deferred::GBuffer;
+pub use self::sprite::{Sprite, SpriteAtlas, SpriteAtlasData, SpriteFrame, SpriteQuad};
+pub use self::sprite_batch::SpriteBatch;
+pub use self::tilemap::{TileLayer, TileObject, TileObjectShape, TileProperties, Tilemap, Tileset,
+                        TILE_FLIPPED_DIAGONALLY, TILE_FLIPPED_HORIZONTALLY,
+                        TILE_FLIPPED_VERTICALLY, TILE_ID_MASK};
+pub use self::particle::{Particle, ParticleBurst, ParticleShape, ParticleSystem};
+#[cfg(feature = "soft_gl")]
+pub use self::soft_programs::register_soft_programs;
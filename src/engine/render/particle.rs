@@ -0,0 +1,529 @@
+use engine::animation::Curve;
+use engine::asset::{Asset, AssetSystem};
+use engine::core::ComponentBased;
+use engine::render::{Blending, Camera, CullMode, Material, MeshBuffer, MeshData, MeshIndices,
+                     MeshSurface, RenderQueue, Texture};
+use math::*;
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+thread_local!(static NEXT_SEED: Cell<u32> = Cell::new(0x9E37_79B9));
+
+/// Where the particles are emitted from and their direction, in the space of the emitter
+#[derive(Clone)]
+pub enum ParticleShape {
+    /// From the origin, in all directions
+    Point,
+    /// From inside the sphere, outward
+    Sphere { radius: f32 },
+    /// From a disc of radius in the xz plane, upward within angle of the y axis
+    Cone { angle: Deg<f32>, radius: f32 },
+    /// From the triangles of the mesh, along their normal
+    Mesh(Rc<MeshBuffer>),
+}
+
+/// Particles emitted at once, time seconds after the start of each cycle
+#[derive(Copy, Clone, Debug)]
+pub struct ParticleBurst {
+    pub time: f32,
+    pub count: u32,
+}
+
+/// A live particle, in world space
+#[derive(Copy, Clone, Debug)]
+pub struct Particle {
+    pub position: Vector3f,
+    pub velocity: Vector3f,
+    /// Seconds since it was emitted
+    pub age: f32,
+    pub lifetime: f32,
+    /// Size and color when emitted, scaled by the curves over its lifetime
+    pub size: f32,
+    pub color: Vector4<f32>,
+}
+
+/// Emits and simulates particles on the CPU, drawn as billboards facing the camera.
+///
+/// The particles are simulated in world space by the world every frame. The billboards
+/// of each camera are sorted back to front and built into a dynamic mesh buffer,
+/// drawn in the transparent queue.
+pub struct ParticleSystem {
+    pub texture: Rc<Texture>,
+    pub shape: ParticleShape,
+    /// Seconds of an emission cycle
+    pub duration: f32,
+    /// Start a new cycle at the end of each one, or stop emitting
+    pub looping: bool,
+    /// Particles emitted per second
+    pub rate: f32,
+    pub bursts: Vec<ParticleBurst>,
+    /// The lifetime, speed and size of the new particles are picked in (min, max)
+    pub lifetime: (f32, f32),
+    pub speed: (f32, f32),
+    pub size: (f32, f32),
+    pub color: Vector4<f32>,
+    /// World acceleration of the particles
+    pub gravity: Vector3f,
+    /// Multiplied with the color and size of the particles,
+    /// by their age from 0 when emitted to 1 when they die
+    pub color_over_lifetime: Option<Curve<Vector4<f32>>>,
+    pub size_over_lifetime: Option<Curve<f32>>,
+    pub blending: Blending,
+    /// No particle is emitted while there are so many
+    pub max_particles: usize,
+
+    particles: Vec<Particle>,
+    playing: bool,
+    time: f32,
+    /// Fraction of a particle left by the rate emission
+    pending: f32,
+    seed: u32,
+    material: Option<(Rc<Texture>, Rc<Material>)>,
+    buffer: Option<Rc<MeshBuffer>>,
+}
+
+impl ComponentBased for ParticleSystem {}
+
+impl ParticleSystem {
+    pub fn new(texture: Rc<Texture>) -> ParticleSystem {
+        // Each system gets its own random sequence
+        let seed = NEXT_SEED.with(|s| {
+            let seed = s.get();
+            s.set(seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223));
+            seed
+        });
+
+        ParticleSystem {
+            texture,
+            shape: ParticleShape::Point,
+            duration: 5.0,
+            looping: true,
+            rate: 10.0,
+            bursts: Vec::new(),
+            lifetime: (1.0, 1.0),
+            speed: (1.0, 1.0),
+            size: (0.1, 0.1),
+            color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+            gravity: Vector3::zero(),
+            color_over_lifetime: None,
+            size_over_lifetime: None,
+            blending: Blending::Alpha,
+            max_particles: 1000,
+            particles: Vec::new(),
+            playing: true,
+            time: 0.0,
+            pending: 0.0,
+            seed: seed | 1,
+            material: None,
+            buffer: None,
+        }
+    }
+
+    pub fn with_shape(mut self, shape: ParticleShape) -> ParticleSystem {
+        self.shape = shape;
+        self
+    }
+
+    pub fn with_duration(mut self, duration: f32, looping: bool) -> ParticleSystem {
+        self.duration = duration;
+        self.looping = looping;
+        self
+    }
+
+    pub fn with_rate(mut self, rate: f32) -> ParticleSystem {
+        self.rate = rate;
+        self
+    }
+
+    pub fn with_burst(mut self, time: f32, count: u32) -> ParticleSystem {
+        self.bursts.push(ParticleBurst { time, count });
+        self
+    }
+
+    pub fn with_lifetime(mut self, min: f32, max: f32) -> ParticleSystem {
+        self.lifetime = (min, max);
+        self
+    }
+
+    pub fn with_speed(mut self, min: f32, max: f32) -> ParticleSystem {
+        self.speed = (min, max);
+        self
+    }
+
+    pub fn with_size(mut self, min: f32, max: f32) -> ParticleSystem {
+        self.size = (min, max);
+        self
+    }
+
+    pub fn with_color(mut self, color: Vector4<f32>) -> ParticleSystem {
+        self.color = color;
+        self
+    }
+
+    pub fn with_gravity(mut self, gravity: Vector3f) -> ParticleSystem {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn with_color_over_lifetime(mut self, curve: Curve<Vector4<f32>>) -> ParticleSystem {
+        self.color_over_lifetime = Some(curve);
+        self
+    }
+
+    pub fn with_size_over_lifetime(mut self, curve: Curve<f32>) -> ParticleSystem {
+        self.size_over_lifetime = Some(curve);
+        self
+    }
+
+    pub fn with_blending(mut self, blending: Blending) -> ParticleSystem {
+        self.blending = blending;
+        self
+    }
+
+    pub fn with_max_particles(mut self, max_particles: usize) -> ParticleSystem {
+        self.max_particles = max_particles;
+        self
+    }
+
+    /// Set the random sequence, to emit the same particles every time
+    pub fn with_seed(mut self, seed: u32) -> ParticleSystem {
+        self.seed = seed | 1;
+        self
+    }
+
+    /// Start emitting from the start of a cycle
+    pub fn play(&mut self) {
+        self.playing = true;
+        self.time = 0.0;
+        self.pending = 0.0;
+    }
+
+    /// Stop emitting, the live particles are kept until they die
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Remove all live particles
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    /// Seconds since the start of the current cycle
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Uniform in [0, 1)
+    fn random(&mut self) -> f32 {
+        // xorshift32
+        let mut x = self.seed;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.seed = x;
+
+        (x >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn random_range(&mut self, range: (f32, f32)) -> f32 {
+        range.0 + (range.1 - range.0) * self.random()
+    }
+
+    fn random_direction(&mut self) -> Vector3f {
+        let z = self.random() * 2.0 - 1.0;
+        let a = self.random() * 2.0 * ::std::f32::consts::PI;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+
+        Vector3::new(r * a.cos(), r * a.sin(), z)
+    }
+
+    /// Triangles of the mesh of the shape with their cumulated areas,
+    /// None if it is not loaded yet or has no area
+    fn mesh_triangles(&self) -> Option<(Vec<[Vector3f; 3]>, Vec<f32>)> {
+        let buffer = match self.shape {
+            ParticleShape::Mesh(ref buffer) => buffer,
+            _ => return None,
+        };
+        let data = buffer.data().ok()?;
+
+        let v = |i: u32| {
+            let i = i as usize * 3;
+            Vector3::new(data.vertices[i], data.vertices[i + 1], data.vertices[i + 2])
+        };
+
+        let mut triangles = Vec::new();
+        let mut areas = Vec::new();
+        let mut total = 0.0;
+        for tri in data.indices.to_u32().chunks(3) {
+            if tri.len() < 3 {
+                break;
+            }
+
+            let t = [v(tri[0]), v(tri[1]), v(tri[2])];
+            total += (t[1] - t[0]).cross(t[2] - t[0]).magnitude() * 0.5;
+            triangles.push(t);
+            areas.push(total);
+        }
+
+        if total > 0.0 {
+            Some((triangles, areas))
+        } else {
+            None
+        }
+    }
+
+    /// Emit count particles now, m being the world matrix of the emitter
+    pub fn emit(&mut self, count: usize, m: &Matrix4f) {
+        let count = count.min(self.max_particles.saturating_sub(self.particles.len()));
+        if count == 0 {
+            return;
+        }
+
+        let triangles = match self.shape {
+            ParticleShape::Mesh(_) => match self.mesh_triangles() {
+                Some(triangles) => Some(triangles),
+                None => return,
+            },
+            _ => None,
+        };
+
+        for _ in 0..count {
+            let (position, direction) = match self.shape.clone() {
+                ParticleShape::Point => (Vector3::zero(), self.random_direction()),
+                ParticleShape::Sphere { radius } => {
+                    let direction = self.random_direction();
+                    let r = radius * self.random().cbrt();
+                    (direction * r, direction)
+                }
+                ParticleShape::Cone { angle, radius } => {
+                    let a = self.random() * 2.0 * ::std::f32::consts::PI;
+                    let r = radius * self.random().sqrt();
+                    let position = Vector3::new(r * a.cos(), 0.0, r * a.sin());
+
+                    // Uniform on the cap of the sphere within the angle
+                    let cos_max = Rad::from(angle).0.cos();
+                    let y = 1.0 - self.random() * (1.0 - cos_max);
+                    let s = (1.0 - y * y).max(0.0).sqrt();
+                    let b = self.random() * 2.0 * ::std::f32::consts::PI;
+                    (position, Vector3::new(s * b.cos(), y, s * b.sin()))
+                }
+                ParticleShape::Mesh(_) => {
+                    let (ref tris, ref areas) = *triangles.as_ref().unwrap();
+                    let target = self.random() * areas[areas.len() - 1];
+                    let i = areas
+                        .iter()
+                        .position(|a| *a > target)
+                        .unwrap_or(areas.len() - 1);
+                    let t = &tris[i];
+
+                    // Uniform barycentric coordinates
+                    let (mut u, mut v) = (self.random(), self.random());
+                    if u + v > 1.0 {
+                        u = 1.0 - u;
+                        v = 1.0 - v;
+                    }
+                    let position = t[0] + (t[1] - t[0]) * u + (t[2] - t[0]) * v;
+                    (position, (t[1] - t[0]).cross(t[2] - t[0]).normalize())
+                }
+            };
+
+            let direction = m.transform_vector(direction);
+            let direction = if direction.magnitude2() > 0.0 {
+                direction.normalize()
+            } else {
+                direction
+            };
+
+            let speed = self.random_range(self.speed);
+            let particle = Particle {
+                position: m.transform_point(Point3::from_vec(position)).to_vec(),
+                velocity: direction * speed,
+                age: 0.0,
+                lifetime: self.random_range(self.lifetime),
+                size: self.random_range(self.size),
+                color: self.color,
+            };
+            self.particles.push(particle);
+        }
+    }
+
+    /// Move the particles by dt seconds and emit the new ones,
+    /// m being the world matrix of the emitter
+    pub fn update(&mut self, dt: f32, m: &Matrix4f) {
+        let gravity = self.gravity;
+        for p in self.particles.iter_mut() {
+            p.age += dt;
+            p.velocity += gravity * dt;
+            p.position += p.velocity * dt;
+        }
+        self.particles.retain(|p| p.age < p.lifetime);
+
+        if !self.playing || dt <= 0.0 {
+            return;
+        }
+
+        // The bursts in [time, time + dt), through the next cycles when looping
+        let mut count = 0;
+        let mut emitting = 0.0;
+        let (mut start, mut left) = (self.time, dt);
+        loop {
+            let end = (start + left).min(self.duration);
+            count += self.bursts
+                .iter()
+                .filter(|b| b.time >= start && b.time < end)
+                .map(|b| b.count as usize)
+                .sum::<usize>();
+            emitting += end - start;
+            left -= end - start;
+
+            if left <= 0.0 || self.duration <= 0.0 {
+                self.time = end;
+                break;
+            }
+            if !self.looping {
+                self.time = self.duration;
+                self.playing = false;
+                break;
+            }
+            start = 0.0;
+        }
+
+        self.pending += self.rate * emitting;
+        let from_rate = self.pending.floor();
+        self.pending -= from_rate;
+
+        self.emit(count + from_rate as usize, m);
+    }
+
+    /// The size and color of a particle at its age
+    pub fn particle_appearance(&self, p: &Particle) -> (f32, Vector4<f32>) {
+        let t = if p.lifetime > 0.0 {
+            (p.age / p.lifetime).min(1.0)
+        } else {
+            1.0
+        };
+
+        let size = match self.size_over_lifetime {
+            Some(ref curve) => p.size * curve.evaluate(t).unwrap_or(1.0),
+            None => p.size,
+        };
+        let color = match self.color_over_lifetime {
+            Some(ref curve) => {
+                let c = curve.evaluate(t).unwrap_or(Vector4::new(1.0, 1.0, 1.0, 1.0));
+                Vector4::new(p.color.x * c.x, p.color.y * c.y, p.color.z * c.z, p.color.w * c.w)
+            }
+            None => p.color,
+        };
+
+        (size, color)
+    }
+
+    /// World bounding sphere of the live particles
+    pub fn bounds(&self) -> Option<(Vector3f, f32)> {
+        let first = self.particles.first()?;
+
+        let (mut min, mut max) = (first.position, first.position);
+        let mut size = 0.0f32;
+        for p in self.particles.iter() {
+            for i in 0..3 {
+                min[i] = min[i].min(p.position[i]);
+                max[i] = max[i].max(p.position[i]);
+            }
+            size = size.max(self.particle_appearance(p).0);
+        }
+
+        // The corners of the billboards are at most size / sqrt(2) away
+        Some(((min + max) * 0.5, (max - min).magnitude() * 0.5 + size))
+    }
+
+    fn material(&mut self, asys: &AssetSystem) -> Rc<Material> {
+        // Made again when the texture or the blending changed
+        if let Some((ref texture, ref material)) = self.material {
+            let blending = material.states.blending;
+            if Rc::ptr_eq(texture, &self.texture) && blending == Some(self.blending) {
+                return material.clone();
+            }
+        }
+
+        let mut material = Material::new(asys.new_program("unrust/sprite"));
+        material.set("uTexture", self.texture.clone());
+        material.render_queue = RenderQueue::Transparent;
+        material.states.cull = Some(CullMode::Off);
+        material.states.blending = Some(self.blending);
+
+        let material = Rc::new(material);
+        self.material = Some((self.texture.clone(), material.clone()));
+        material
+    }
+
+    /// Build the billboards facing the camera, sorted back to front,
+    /// None if there is no live particle
+    pub fn build(&mut self, asys: &AssetSystem, camera: &Camera) -> Option<Rc<MeshSurface>> {
+        if self.particles.is_empty() {
+            return None;
+        }
+
+        let (eye, forward) = (camera.eye(), camera.forward());
+        let (right, up) = (camera.right(), camera.up());
+
+        let mut order: Vec<(f32, usize)> = self.particles
+            .iter()
+            .enumerate()
+            .map(|(i, p)| ((p.position - eye).dot(forward), i))
+            .collect();
+        order.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+        let n = order.len();
+        let mut vertices = Vec::with_capacity(n * 12);
+        let mut uvs = Vec::with_capacity(n * 8);
+        let mut colors = Vec::with_capacity(n * 16);
+        let mut indices = Vec::with_capacity(n * 6);
+
+        for (k, &(_, i)) in order.iter().enumerate() {
+            let p = &self.particles[i];
+            let (size, color) = self.particle_appearance(p);
+            let (r, u) = (right * size * 0.5, up * size * 0.5);
+
+            for c in [-r - u, r - u, r + u, u - r].iter() {
+                let v = p.position + c;
+                vertices.extend_from_slice(&[v.x, v.y, v.z]);
+                colors.extend_from_slice(&[color.x, color.y, color.z, color.w]);
+            }
+            uvs.extend_from_slice(&[0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0]);
+
+            let base = (k * 4) as u32;
+            indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+
+        let data = MeshData {
+            vertices,
+            uvs: Some(uvs),
+            colors: Some(colors),
+            indices: MeshIndices::with_vertex_count(indices, n * 4),
+            ..MeshData::default()
+        };
+
+        let buffer = match self.buffer {
+            Some(ref buffer) => {
+                buffer.update_mesh_data(data);
+                buffer.clone()
+            }
+            None => MeshBuffer::new(data),
+        };
+        self.buffer = Some(buffer.clone());
+
+        Some(Rc::new(MeshSurface {
+            buffer,
+            material: self.material(asys),
+        }))
+    }
+}
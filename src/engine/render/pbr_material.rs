@@ -0,0 +1,210 @@
+use engine::asset::{AssetSystem, ObjMaterial};
+use engine::render::{CullMode, Material, RenderQueue, Texture};
+use math::*;
+use std::rc::Rc;
+
+/// The cube map used for image based lighting when none is given
+pub const DEFAULT_ENVIRONMENT_MAP: &'static str = "unrust/skybox/sky_cubemap.dds";
+
+/// Builds a Material of the metallic-roughness "unrust/pbr" program.
+///
+/// The maps left unset are replaced by textures which keep the factors as is.
+#[derive(Clone, Debug)]
+pub struct PbrMaterial {
+    base_color: Vector4<f32>,
+    metallic: f32,
+    roughness: f32,
+    emissive: Vector3f,
+    normal_scale: f32,
+    occlusion_strength: f32,
+    alpha_cutoff: f32,
+    double_sided: bool,
+
+    base_color_map: Option<Rc<Texture>>,
+    metallic_roughness_map: Option<Rc<Texture>>,
+    normal_map: Option<Rc<Texture>>,
+    occlusion_map: Option<Rc<Texture>>,
+    emissive_map: Option<Rc<Texture>>,
+
+    environment_map: Option<Rc<Texture>>,
+    environment_intensity: f32,
+}
+
+impl Default for PbrMaterial {
+    fn default() -> PbrMaterial {
+        PbrMaterial {
+            base_color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+            metallic: 1.0,
+            roughness: 1.0,
+            emissive: Vector3::zero(),
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            alpha_cutoff: 0.0,
+            double_sided: false,
+
+            base_color_map: None,
+            metallic_roughness_map: None,
+            normal_map: None,
+            occlusion_map: None,
+            emissive_map: None,
+
+            environment_map: None,
+            environment_intensity: 1.0,
+        }
+    }
+}
+
+impl PbrMaterial {
+    pub fn new() -> PbrMaterial {
+        PbrMaterial::default()
+    }
+
+    /// From the metallic-roughness parameters of a glTF material
+    pub fn from_obj_material(asys: &AssetSystem, obj_mat: &ObjMaterial) -> PbrMaterial {
+        let texture = |name: &Option<String>| name.as_ref().map(|n| asys.new_texture(n));
+        let diffuse = obj_mat.diffuse.unwrap_or(Vector3::new(1.0, 1.0, 1.0));
+
+        PbrMaterial {
+            base_color: diffuse.extend(obj_mat.alpha.unwrap_or(1.0)),
+            metallic: obj_mat.metallic.unwrap_or(1.0),
+            roughness: obj_mat.roughness.unwrap_or(1.0),
+            emissive: obj_mat.emissive.unwrap_or(Vector3::zero()),
+            alpha_cutoff: obj_mat.alpha_cutoff.unwrap_or(0.0),
+            double_sided: obj_mat.double_sided.unwrap_or(false),
+
+            base_color_map: texture(&obj_mat.diffuse_map),
+            metallic_roughness_map: texture(&obj_mat.metallic_roughness_map),
+            normal_map: texture(&obj_mat.normal_map),
+            occlusion_map: texture(&obj_mat.occlusion_map),
+            emissive_map: texture(&obj_mat.emissive_map),
+
+            ..PbrMaterial::default()
+        }
+    }
+
+    pub fn with_base_color(mut self, color: Vector4<f32>) -> PbrMaterial {
+        self.base_color = color;
+        self
+    }
+
+    pub fn with_metallic(mut self, metallic: f32) -> PbrMaterial {
+        self.metallic = metallic;
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: f32) -> PbrMaterial {
+        self.roughness = roughness;
+        self
+    }
+
+    pub fn with_emissive(mut self, emissive: Vector3f) -> PbrMaterial {
+        self.emissive = emissive;
+        self
+    }
+
+    /// Fragments with a lower alpha are discarded, 0 to disable
+    pub fn with_alpha_cutoff(mut self, cutoff: f32) -> PbrMaterial {
+        self.alpha_cutoff = cutoff;
+        self
+    }
+
+    pub fn with_double_sided(mut self, b: bool) -> PbrMaterial {
+        self.double_sided = b;
+        self
+    }
+
+    pub fn with_base_color_map(mut self, tex: Rc<Texture>) -> PbrMaterial {
+        self.base_color_map = Some(tex);
+        self
+    }
+
+    /// Roughness is read from the green channel and metallic from the blue one
+    pub fn with_metallic_roughness_map(mut self, tex: Rc<Texture>) -> PbrMaterial {
+        self.metallic_roughness_map = Some(tex);
+        self
+    }
+
+    pub fn with_normal_map(mut self, tex: Rc<Texture>, scale: f32) -> PbrMaterial {
+        self.normal_map = Some(tex);
+        self.normal_scale = scale;
+        self
+    }
+
+    pub fn with_occlusion_map(mut self, tex: Rc<Texture>, strength: f32) -> PbrMaterial {
+        self.occlusion_map = Some(tex);
+        self.occlusion_strength = strength;
+        self
+    }
+
+    pub fn with_emissive_map(mut self, tex: Rc<Texture>) -> PbrMaterial {
+        self.emissive_map = Some(tex);
+        self
+    }
+
+    /// A cube map, its mipmaps are sampled by roughness for the reflections
+    /// and the smallest one for the diffuse light
+    pub fn with_environment_map(mut self, tex: Rc<Texture>) -> PbrMaterial {
+        self.environment_map = Some(tex);
+        self
+    }
+
+    pub fn with_environment_intensity(mut self, intensity: f32) -> PbrMaterial {
+        self.environment_intensity = intensity;
+        self
+    }
+
+    pub fn build(&self, asys: &AssetSystem) -> Material {
+        let mut material = Material::new(asys.new_program("unrust/pbr"));
+        let texture = |tex: &Option<Rc<Texture>>, default: &str| match *tex {
+            Some(ref tex) => tex.clone(),
+            None => asys.new_texture(default),
+        };
+
+        material.set("uMaterial.baseColor", self.base_color);
+        material.set("uMaterial.metallic", self.metallic);
+        material.set("uMaterial.roughness", self.roughness);
+        material.set("uMaterial.emissive", self.emissive);
+        material.set("uMaterial.normalScale", self.normal_scale);
+        material.set("uMaterial.occlusionStrength", self.occlusion_strength);
+        material.set("uMaterial.alphaCutoff", self.alpha_cutoff);
+        material.set("uMaterial.hasNormalMap", self.normal_map.is_some());
+
+        material.set(
+            "uMaterial.baseColorMap",
+            texture(&self.base_color_map, "default_white"),
+        );
+        material.set(
+            "uMaterial.metallicRoughnessMap",
+            texture(&self.metallic_roughness_map, "default_white"),
+        );
+        material.set(
+            "uMaterial.normalMap",
+            texture(&self.normal_map, "default_normal_map"),
+        );
+        material.set(
+            "uMaterial.occlusionMap",
+            texture(&self.occlusion_map, "default_white"),
+        );
+        material.set(
+            "uMaterial.emissiveMap",
+            texture(&self.emissive_map, "default_white"),
+        );
+
+        material.set(
+            "uEnvironmentMap",
+            texture(&self.environment_map, DEFAULT_ENVIRONMENT_MAP),
+        );
+        material.set("uEnvironmentIntensity", self.environment_intensity);
+
+        if self.double_sided {
+            material.states.cull = Some(CullMode::Off);
+        }
+
+        // Cutout materials are drawn with the opaque ones
+        if self.base_color.w < 0.9999 && self.alpha_cutoff <= 0.0 {
+            material.render_queue = RenderQueue::Transparent;
+        }
+
+        material
+    }
+}
@@ -0,0 +1,280 @@
+use engine::asset::{Asset, AssetSystem};
+use engine::render::{CullMode, DepthTest, Material, MaterialState, RenderTexture, Texture,
+                     TextureAttachment, TextureImage};
+use image::{self, ImageBuffer};
+use math::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The bright parts of the image are blurred and added back
+pub struct Bloom {
+    pub bright: Rc<Material>,
+    pub blur: Rc<Material>,
+    pub combine: Rc<Material>,
+    /// Number of horizontal and vertical blur passes, at half resolution
+    pub iterations: u32,
+}
+
+impl Bloom {
+    pub fn new(asys: &AssetSystem) -> Bloom {
+        let bloom = Bloom {
+            bright: Rc::new(Material::new(asys.new_program("unrust/post_bloom_bright"))),
+            blur: Rc::new(Material::new(asys.new_program("unrust/post_bloom_blur"))),
+            combine: Rc::new(Material::new(asys.new_program("unrust/post_bloom_combine"))),
+            iterations: 2,
+        };
+
+        bloom.set_threshold(1.0);
+        bloom.set_intensity(1.0);
+        bloom
+    }
+
+    /// Colors brighter than the threshold bloom
+    pub fn set_threshold(&self, threshold: f32) {
+        self.bright.set("uThreshold", threshold);
+    }
+
+    pub fn set_intensity(&self, intensity: f32) {
+        self.combine.set("uIntensity", intensity);
+    }
+}
+
+/// An effect of a PostProcessStack.
+///
+/// The material of an effect draws the "screen_quad" mesh buffer,
+/// its vertex shader can include "unrust/fullscreen_quad.glsl".
+/// These uniforms are set before it is drawn:
+///
+/// * `uSource`: the result of the previous effect, or the scene
+/// * `uTexelSize`: 1 / the size of uSource in pixels
+/// * `uDepth`: the depth of the scene
+pub enum PostProcessEffect {
+    Material(Rc<Material>),
+    Bloom(Bloom),
+}
+
+fn new_effect(asys: &AssetSystem, program: &str) -> Rc<Material> {
+    Rc::new(Material::new(asys.new_program(program)))
+}
+
+impl PostProcessEffect {
+    pub fn bloom(asys: &AssetSystem) -> PostProcessEffect {
+        PostProcessEffect::Bloom(Bloom::new(asys))
+    }
+
+    /// ACES filmic tone mapping of the scene color scaled by the exposure
+    pub fn tone_mapping(asys: &AssetSystem, exposure: f32) -> PostProcessEffect {
+        let material = new_effect(asys, "unrust/post_tonemap");
+        material.set("uExposure", exposure);
+        // The shaders output colors in gamma space already
+        material.set("uGamma", 1.0);
+        PostProcessEffect::Material(material)
+    }
+
+    pub fn fxaa(asys: &AssetSystem) -> PostProcessEffect {
+        PostProcessEffect::Material(new_effect(asys, "unrust/post_fxaa"))
+    }
+
+    /// Darkens the corners, by intensity from 0 to 1
+    pub fn vignette(asys: &AssetSystem, intensity: f32) -> PostProcessEffect {
+        let material = new_effect(asys, "unrust/post_vignette");
+        material.set("uIntensity", intensity);
+        material.set("uRadius", 0.5);
+        material.set("uSmoothness", 0.5);
+        PostProcessEffect::Material(material)
+    }
+
+    /// Maps the colors through a lookup table of the given size, see `neutral_lut`
+    pub fn color_grading(asys: &AssetSystem, lut: Rc<Texture>, size: u32) -> PostProcessEffect {
+        let material = new_effect(asys, "unrust/post_color_grading");
+        material.set("uLut", lut);
+        material.set("uLutSize", size as f32);
+        PostProcessEffect::Material(material)
+    }
+
+    /// The material of a single pass effect, to change its parameters
+    pub fn material(&self) -> Option<&Rc<Material>> {
+        match self {
+            &PostProcessEffect::Material(ref m) => Some(m),
+            _ => None,
+        }
+    }
+}
+
+/// A color grading lookup table which keeps the colors as is:
+/// a strip of size slices of size x size texels, red along x, green along y,
+/// and blue from one slice to the next.
+///
+/// A color graded copy of it made in an image editor grades the same way.
+pub fn neutral_lut(size: u32) -> Rc<Texture> {
+    let max = (size - 1) as f32;
+    let value = |i: u32| (i as f32 / max * 255.0).round() as u8;
+
+    let img = ImageBuffer::from_fn(size * size, size, |x, y| {
+        image::Rgba([value(x % size), value(y), value(x / size), 0xff])
+    });
+
+    Texture::new(TextureImage::Rgba(img))
+}
+
+/// Render textures of a size
+struct Targets {
+    size: (u32, u32),
+    scene: Rc<RenderTexture>,
+    ping_pong: [Rc<RenderTexture>; 2],
+    /// Half resolution, for bloom
+    half: [Rc<RenderTexture>; 2],
+}
+
+impl Targets {
+    fn new(size: (u32, u32), format: TextureAttachment) -> Targets {
+        let (w, h) = size;
+        let half = ((w / 2).max(1), (h / 2).max(1));
+        let rt = |(w, h)| Rc::new(RenderTexture::new(w, h, format));
+
+        Targets {
+            size,
+            scene: Rc::new(RenderTexture::new_with_depth(w, h, format)),
+            ping_pong: [rt(size), rt(size)],
+            half: [rt(half), rt(half)],
+        }
+    }
+}
+
+/// Effects applied in order to the image rendered by a Camera.
+///
+/// The scene is rendered into a render texture, which each effect draws
+/// into the next one, the last effect drawing into the output of the camera.
+/// The UI is drawn afterward, without effects.
+pub struct PostProcessStack {
+    effects: Vec<PostProcessEffect>,
+    format: TextureAttachment,
+    copy: RefCell<Option<Rc<Material>>>,
+    targets: RefCell<Option<Targets>>,
+}
+
+impl Default for PostProcessStack {
+    fn default() -> PostProcessStack {
+        PostProcessStack {
+            effects: Vec::new(),
+            format: TextureAttachment::Color0HalfFloat,
+            copy: RefCell::new(None),
+            targets: RefCell::new(None),
+        }
+    }
+}
+
+impl PostProcessStack {
+    pub fn new() -> PostProcessStack {
+        PostProcessStack::default()
+    }
+
+    pub fn with_effect(mut self, effect: PostProcessEffect) -> PostProcessStack {
+        self.effects.push(effect);
+        self
+    }
+
+    /// Format of the intermediate render textures, half float by default
+    /// so that the colors brighter than 1 are kept until tone mapping.
+    pub fn with_format(mut self, format: TextureAttachment) -> PostProcessStack {
+        self.format = format;
+        self
+    }
+
+    pub fn effects(&self) -> &[PostProcessEffect] {
+        &self.effects
+    }
+
+    /// States of all post process passes, before the ones of their material
+    pub fn states() -> MaterialState {
+        MaterialState {
+            cull: Some(CullMode::Off),
+            alpha_blending: Some(false),
+            blending: None,
+            depth_write: Some(false),
+            depth_test: Some(DepthTest::Always),
+        }
+    }
+
+    /// The render texture to draw the scene into, with a depth attachment.
+    /// It is created again when the size changes.
+    pub fn scene_target(&self, size: (u32, u32)) -> Rc<RenderTexture> {
+        let mut targets = self.targets.borrow_mut();
+
+        if targets.as_ref().map_or(true, |t| t.size != size) {
+            *targets = Some(Targets::new(size, self.format));
+        }
+
+        targets.as_ref().unwrap().scene.clone()
+    }
+
+    /// Run the effects on the scene target, each pass is drawn by calling blit
+    /// with its material and target, None being the output of the camera.
+    pub fn apply<F>(&self, asys: &AssetSystem, mut blit: F)
+    where
+        F: FnMut(&Rc<Material>, Option<&RenderTexture>),
+    {
+        let targets = self.targets.borrow();
+        let targets = match *targets {
+            Some(ref t) => t,
+            None => return,
+        };
+
+        let depth = targets.scene.depth_texture();
+        let setup = |material: &Material, source: &RenderTexture| {
+            let (w, h) = source.size().unwrap_or(targets.size);
+            material.set("uSource", source.as_texture());
+            material.set("uTexelSize", Vector2::new(1.0 / w as f32, 1.0 / h as f32));
+            if let Some(ref depth) = depth {
+                material.set("uDepth", depth.clone());
+            }
+        };
+
+        if self.effects.is_empty() {
+            let mut copy = self.copy.borrow_mut();
+            let copy = copy.get_or_insert_with(|| new_effect(asys, "unrust/post_copy"));
+            setup(copy, &targets.scene);
+            blit(copy, None);
+            return;
+        }
+
+        let mut source = &targets.scene;
+        for (i, effect) in self.effects.iter().enumerate() {
+            let dst = if i + 1 == self.effects.len() {
+                None
+            } else {
+                Some(&targets.ping_pong[i % 2])
+            };
+
+            match effect {
+                &PostProcessEffect::Material(ref m) => {
+                    setup(m, source);
+                    blit(m, dst.map(|rt| &**rt));
+                }
+                &PostProcessEffect::Bloom(ref bloom) => {
+                    let half = &targets.half;
+                    setup(&bloom.bright, source);
+                    blit(&bloom.bright, Some(&*half[0]));
+
+                    for _ in 0..bloom.iterations {
+                        bloom.blur.set("uDirection", Vector2::new(1.0, 0.0));
+                        setup(&bloom.blur, &half[0]);
+                        blit(&bloom.blur, Some(&*half[1]));
+
+                        bloom.blur.set("uDirection", Vector2::new(0.0, 1.0));
+                        setup(&bloom.blur, &half[1]);
+                        blit(&bloom.blur, Some(&*half[0]));
+                    }
+
+                    bloom.combine.set("uBloom", half[0].as_texture());
+                    setup(&bloom.combine, source);
+                    blit(&bloom.combine, dst.map(|rt| &**rt));
+                }
+            }
+
+            if let Some(dst) = dst {
+                source = dst;
+            }
+        }
+    }
+}
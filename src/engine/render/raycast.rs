@@ -0,0 +1,152 @@
+use engine::core::GameObject;
+use engine::render::{Mesh, MeshSurface};
+use math::*;
+use std::cell::RefCell;
+use std::f32::MAX;
+use std::rc::Rc;
+
+pub struct RaycastHit {
+    pub game_object: Rc<RefCell<GameObject>>,
+    /// Index of the hit surface in the Mesh
+    pub surface: usize,
+    pub distance: f32,
+    pub point: Vector3f,
+    pub normal: Vector3f,
+}
+
+pub struct RaycastFilter<'a> {
+    /// Hits further than it are ignored
+    pub max_distance: f32,
+    /// Test the triangles of the mesh data, otherwise stop at the bounding boxes
+    pub triangles: bool,
+    /// Only objects for which it returns true are tested
+    pub predicate: Option<&'a Fn(&GameObject) -> bool>,
+}
+
+impl<'a> Default for RaycastFilter<'a> {
+    fn default() -> RaycastFilter<'a> {
+        RaycastFilter {
+            max_distance: MAX,
+            triangles: false,
+            predicate: None,
+        }
+    }
+}
+
+/// Distance and local normal of the nearest hit on a surface
+fn raycast_surface(ray: &Ray, surface: &MeshSurface, triangles: bool) -> Option<(f32, Vector3f)> {
+    let bounds = surface.buffer.bounds()?;
+
+    ray.intersect_sphere(Vector3::zero(), bounds.r)?;
+    let (t, axis) = ray.intersect_aabb(&bounds.aabb)?;
+
+    if !triangles {
+        let mut n = Vector3::zero();
+        n[axis] = -ray.direction[axis].signum();
+        return Some((t, n));
+    }
+
+    let data = surface.buffer.data().ok()?;
+    let v = |i: u32| {
+        let i = i as usize * 3;
+        Vector3::new(data.vertices[i], data.vertices[i + 1], data.vertices[i + 2])
+    };
+
+    let mut nearest: Option<(f32, Vector3f)> = None;
+    for i in 0..data.indices.len() / 3 {
+        let a = v(data.indices.get(i * 3));
+        let b = v(data.indices.get(i * 3 + 1));
+        let c = v(data.indices.get(i * 3 + 2));
+
+        if let Some(t) = ray.intersect_triangle(a, b, c) {
+            if nearest.map_or(true, |(nt, _)| t < nt) {
+                nearest = Some((t, (b - a).cross(c - a)));
+            }
+        }
+    }
+
+    nearest
+}
+
+fn raycast_mesh(
+    go: &Rc<RefCell<GameObject>>,
+    object: &GameObject,
+    mesh: &Mesh,
+    ray: &Ray,
+    filter: &RaycastFilter,
+    nearest: &mut Option<RaycastHit>,
+) {
+    let inv = match object.transform.as_global_matrix().invert() {
+        Some(inv) => inv,
+        None => return,
+    };
+    let local_ray = ray.transform(&inv);
+
+    for (i, surface) in mesh.surfaces.iter().enumerate() {
+        let (t, n) = match raycast_surface(&local_ray, surface, filter.triangles) {
+            Some(hit) => hit,
+            None => continue,
+        };
+
+        let max_distance = nearest
+            .as_ref()
+            .map_or(filter.max_distance, |hit| hit.distance);
+        if t > max_distance {
+            continue;
+        }
+
+        // Normals are transformed by the inverse transpose, facing the ray
+        let mut normal = inv.transpose().transform_vector(n).normalize();
+        if normal.dot(ray.direction) > 0.0 {
+            normal = -normal;
+        }
+
+        *nearest = Some(RaycastHit {
+            game_object: go.clone(),
+            surface: i,
+            distance: t,
+            point: ray.at(t),
+            normal,
+        });
+    }
+}
+
+fn raycast_object(
+    go: &Rc<RefCell<GameObject>>,
+    ray: &Ray,
+    filter: &RaycastFilter,
+    nearest: &mut Option<RaycastHit>,
+) {
+    // Skip objects borrowed by the caller
+    let object = match go.try_borrow() {
+        Ok(object) => object,
+        Err(_) => return,
+    };
+
+    if !object.active {
+        return;
+    }
+
+    if filter.predicate.map_or(true, |f| f(&*object)) {
+        if let Some((mesh, _)) = object.find_component::<Mesh>() {
+            raycast_mesh(go, &object, &mesh, ray, filter, nearest);
+        }
+    }
+
+    for child in object.childen().iter() {
+        raycast_object(child, ray, filter, nearest);
+    }
+}
+
+/// Find the nearest surface of the active meshes under root hit by the ray.
+///
+/// Objects borrowed during the call, and their children, are skipped.
+pub fn raycast(root: &GameObject, ray: &Ray, filter: &RaycastFilter) -> Option<RaycastHit> {
+    let mut nearest = None;
+
+    for child in root.childen().iter() {
+        raycast_object(child, ray, filter, &mut nearest);
+    }
+
+    nearest
+}
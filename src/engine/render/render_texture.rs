@@ -19,6 +19,21 @@ impl RenderTexture {
         RenderTexture(FrameBuffer::new(width, height, attach))
     }
 
+    /// A color render texture with a depth buffer, to render 3d scenes into
+    pub fn new_with_depth(width: u32, height: u32, attach: TextureAttachment) -> RenderTexture {
+        RenderTexture(FrameBuffer::new_with_depth(width, height, attach))
+    }
+
+    /// A render texture with several color attachments and a depth one,
+    /// see `color_texture`. Needs `draw_buffers` to be supported.
+    pub fn new_with_attachments(
+        width: u32,
+        height: u32,
+        attachs: &[TextureAttachment],
+    ) -> RenderTexture {
+        RenderTexture(FrameBuffer::new_with_attachments(width, height, attachs))
+    }
+
     pub fn bind_frame_buffer(&self, gl: &WebGLRenderingContext) {
         self.0.prepare(gl);
         self.0.bind(gl);
@@ -31,4 +46,20 @@ impl RenderTexture {
     pub fn as_texture(&self) -> Rc<Texture> {
         self.0.texture.clone()
     }
+
+    /// The texture of the color attachment i, 0 being `as_texture`
+    pub fn color_texture(&self, i: usize) -> Rc<Texture> {
+        match i {
+            0 => self.0.texture.clone(),
+            _ => self.0.attachments[i - 1].clone(),
+        }
+    }
+
+    pub fn color_count(&self) -> usize {
+        self.0.attachments.len() + 1
+    }
+
+    pub fn depth_texture(&self) -> Option<Rc<Texture>> {
+        self.0.depth.clone()
+    }
 }
@@ -36,17 +36,62 @@ impl ShaderKindProvider for ShaderKindFs {
 }
 
 #[derive(Debug)]
-pub struct PreprocessedShaderCode(String);
+pub struct PreprocessedShaderCode {
+    code: String,
+
+    // Kept to preprocess variants of the shader
+    kind: ShaderKind,
+    source: String,
+    external_files: HashMap<String, String>,
+    defaults: Vec<String>,
+}
+
+/// Names given a default value with `#ifndef NAME` in the source
+fn defaulted_names(s: &str) -> Vec<String> {
+    s.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.starts_with("#ifndef") {
+                line["#ifndef".len()..].split_whitespace().next()
+            } else {
+                None
+            }
+        })
+        .map(|name| name.to_string())
+        .collect()
+}
 
 impl PreprocessedShaderCode {
     pub fn as_string(&self) -> &String {
-        &self.0
+        &self.code
     }
 
     pub fn new(
         kind: ShaderKind,
         s: &str,
         external_files: &HashMap<String, String>,
+    ) -> Result<PreprocessedShaderCode, PreprocessError> {
+        PreprocessedShaderCode::with_predefs(kind, s, external_files, &[])
+    }
+
+    /// Whether the source gives a default to the define, which a variant can override
+    pub fn has_default(&self, name: &str) -> bool {
+        self.defaults.iter().any(|n| n == name)
+    }
+
+    /// Preprocess the source again, with the defines set before the first line
+    pub fn with_defines(
+        &self,
+        defines: &[(&'static str, usize)],
+    ) -> Result<PreprocessedShaderCode, PreprocessError> {
+        PreprocessedShaderCode::with_predefs(self.kind, &self.source, &self.external_files, defines)
+    }
+
+    fn with_predefs(
+        kind: ShaderKind,
+        s: &str,
+        external_files: &HashMap<String, String>,
+        defines: &[(&'static str, usize)],
     ) -> Result<PreprocessedShaderCode, PreprocessError> {
         let prefix = match kind {
             ShaderKind::Vertex => if !webgl::IS_GL_ES {
@@ -74,10 +119,19 @@ impl PreprocessedShaderCode {
         if webgl::IS_GL_ES {
             predefs.insert("GL_ES".to_string(), "".to_string());
         }
+        for &(name, value) in defines.iter() {
+            predefs.insert(name.to_string(), value.to_string());
+        }
 
         let processed = preprocessor::preprocess(&s, &predefs, external_files);
 
-        processed.map(|s| PreprocessedShaderCode(prefix + &s))
+        processed.map(|code| PreprocessedShaderCode {
+            code: prefix + &code,
+            kind,
+            source: s.to_string(),
+            external_files: external_files.clone(),
+            defaults: defaulted_names(s),
+        })
     }
 }
 
@@ -1,5 +1,6 @@
-use engine::asset::{Asset, AssetResult, AssetSystem, FileFuture, LoadableAsset, Resource};
-use engine::render::shader::{ShaderFs, ShaderVs};
+use engine::asset::{Asset, AssetError, AssetResult, AssetSystem, FileFuture, LoadableAsset,
+                    Resource};
+use engine::render::shader::{PreprocessedShaderCode, ShaderFs, ShaderVs};
 use engine::render::uniforms::*;
 use std::cell::RefCell;
 use std::collections::HashMap;
@@ -16,6 +17,11 @@ pub enum ShaderAttrib {
     Normal = 2,
     Tangent = 3,
     Bitangent = 4,
+    Joints = 5,
+    Weights = 6,
+    /// A mat4, using the locations 7 to 10
+    InstanceMatrix = 7,
+    Color = 11,
 }
 
 impl Asset for ShaderProgram {
@@ -24,12 +30,15 @@ impl Asset for ShaderProgram {
     fn new_from_resource((vs, fs): Self::Resource) -> Rc<ShaderProgram> {
         Rc::new(ShaderProgram {
             gl_state: RefCell::new(None),
+            variants: Default::default(),
+            defines: Default::default(),
 
             coord_map: Default::default(),
             uniform_cache: Default::default(),
 
             vs_shader: vs,
             fs_shader: fs,
+            name: RefCell::new(None),
         })
     }
 }
@@ -53,19 +62,31 @@ impl LoadableAsset for ShaderProgram {
     }
 }
 
+/// Values of the defines a variant of a program is compiled with
+pub type ShaderDefines = Vec<(&'static str, usize)>;
+
 #[derive(Debug)]
 pub struct ShaderProgramGLState {
     prog: WebGLProgram,
+    instancing: bool,
+    defines: ShaderDefines,
 }
 
 #[derive(Debug)]
 pub struct ShaderProgram {
+    /// The variant in use
     gl_state: RefCell<Option<ShaderProgramGLState>>,
+    /// The other compiled variants
+    variants: RefCell<HashMap<ShaderDefines, ShaderProgramGLState>>,
+    /// Defines of the variant to use on next bind
+    defines: RefCell<ShaderDefines>,
 
     coord_map: RefCell<HashMap<String, Option<u32>>>,
 
     vs_shader: Resource<ShaderVs>,
     fs_shader: Resource<ShaderFs>,
+    /// The name given to AssetSystem::new_program
+    name: RefCell<Option<String>>,
 
     uniform_cache: UniformCache,
 }
@@ -85,19 +106,72 @@ impl ShaderProgram {
     }
 
     fn prepare(&self, gl: &WebGLRenderingContext) -> AssetResult<()> {
-        if self.gl_state.borrow().is_some() {
-            return Ok(());
+        let defines = self.defines.borrow().clone();
+        if let Some(ref state) = *self.gl_state.borrow() {
+            if state.defines == defines {
+                return Ok(());
+            }
         }
 
         let vs = self.vs_shader.try_borrow()?;
         let fs = self.fs_shader.try_borrow()?;
 
-        let state = Some(ShaderProgramGLState::new(gl, &vs, &fs));
-        *self.gl_state.borrow_mut() = state;
+        let found = self.variants.borrow_mut().remove(&defines);
+        let state = match found {
+            Some(state) => state,
+            None => {
+                let name = self.name.borrow();
+                ShaderProgramGLState::new(gl, &vs, &fs, defines, name.as_ref().map(|s| s.as_str()))?
+            }
+        };
+
+        let old = ::std::mem::replace(&mut *self.gl_state.borrow_mut(), Some(state));
+        if let Some(old) = old {
+            self.variants.borrow_mut().insert(old.defines.clone(), old);
+
+            // The uniforms are set again on the new program
+            self.uniform_cache.reset();
+            self.coord_map.borrow_mut().clear();
+        }
 
         Ok(())
     }
 
+    pub fn set_name(&self, name: &str) {
+        *self.name.borrow_mut() = Some(name.to_string());
+    }
+
+    /// Use the variant compiled with the defines the shaders give a default to,
+    /// from the next bind. Returns true if the program has to be bound again.
+    pub fn select_variant(&self, defines: &[(&'static str, usize)]) -> bool {
+        let defines: ShaderDefines = match (self.vs_shader.try_borrow(), self.fs_shader.try_borrow())
+        {
+            (Ok(vs), Ok(fs)) => defines
+                .iter()
+                .filter(|&&(name, _)| vs.code.has_default(name) || fs.code.has_default(name))
+                .cloned()
+                .collect(),
+            _ => return false,
+        };
+
+        let changed = self.gl_state
+            .borrow()
+            .as_ref()
+            .map_or(false, |state| state.defines != defines);
+
+        *self.defines.borrow_mut() = defines;
+        changed
+    }
+
+    /// The vertex shader reads the model matrices from the aInstanceMatrix attribute
+    /// when uInstanced is set, so it can be drawn instanced.
+    pub fn supports_instancing(&self) -> bool {
+        self.gl_state
+            .borrow()
+            .as_ref()
+            .map_or(false, |state| state.instancing)
+    }
+
     pub fn attrib_loc(&self, gl: &WebGLRenderingContext, s: &str) -> Option<u32> {
         let mut m = self.coord_map.borrow_mut();
 
@@ -129,19 +203,45 @@ impl ShaderProgram {
     }
 }
 
+fn preprocess_variant(
+    code: &PreprocessedShaderCode,
+    filename: &str,
+    defines: &[(&'static str, usize)],
+) -> AssetResult<PreprocessedShaderCode> {
+    code.with_defines(defines)
+        .map_err(|e| AssetError::InvalidFormat {
+            path: filename.to_string(),
+            len: code.as_string().len(),
+            reason: format!("{:?}", e),
+        })
+}
+
 impl ShaderProgramGLState {
     pub fn new(
         gl: &WebGLRenderingContext,
         vs_unit: &ShaderVs,
         fs_unit: &ShaderFs,
-    ) -> ShaderProgramGLState {
+        defines: ShaderDefines,
+        name: Option<&str>,
+    ) -> AssetResult<ShaderProgramGLState> {
         /*================ Shaders ====================*/
 
+        let (vs_code, fs_code) = if defines.is_empty() {
+            (None, None)
+        } else {
+            (
+                Some(preprocess_variant(&vs_unit.code, &vs_unit.filename, &defines)?),
+                Some(preprocess_variant(&fs_unit.code, &fs_unit.filename, &defines)?),
+            )
+        };
+        let vs_code = vs_code.as_ref().unwrap_or(&vs_unit.code);
+        let fs_code = fs_code.as_ref().unwrap_or(&fs_unit.code);
+
         // Create a vertex shader object
         let vert_shader = gl.create_shader(WebGLShaderKind::Vertex);
 
         // Attach vertex shader source code
-        gl.shader_source(&vert_shader, &vs_unit.code.as_string());
+        gl.shader_source(&vert_shader, vs_code.as_string());
 
         // Compile the vertex shader
         uni_app::App::print(format!("Compiling shader file : {}\n", vs_unit.filename));
@@ -151,7 +251,7 @@ impl ShaderProgramGLState {
         let frag_shader = gl.create_shader(WebGLShaderKind::Fragment);
 
         // Attach fragment shader source code
-        gl.shader_source(&frag_shader, &fs_unit.code.as_string());
+        gl.shader_source(&frag_shader, fs_code.as_string());
 
         // Compile the fragmentt shader
         uni_app::App::print(format!("Compiling shader file : {}\n", fs_unit.filename));
@@ -193,14 +293,29 @@ impl ShaderProgramGLState {
             "aVertexBitangent",
             ShaderAttrib::Bitangent as _,
         );
+        gl.bind_attrib_location(&shader_program, "aJoints", ShaderAttrib::Joints as _);
+        gl.bind_attrib_location(&shader_program, "aWeights", ShaderAttrib::Weights as _);
+        gl.bind_attrib_location(
+            &shader_program,
+            "aInstanceMatrix",
+            ShaderAttrib::InstanceMatrix as _,
+        );
+        gl.bind_attrib_location(&shader_program, "aVertexColor", ShaderAttrib::Color as _);
 
         // Link both the programs
         gl.link_program(&shader_program);
 
+        // The software rasterizer finds its stand-in of the program by this name
+        if let Some(name) = name {
+            gl.label_program(&shader_program, name);
+        }
+
         let prog = ShaderProgramGLState {
             prog: shader_program,
+            instancing: vs_code.as_string().contains("aInstanceMatrix"),
+            defines,
         };
 
-        prog
+        Ok(prog)
     }
 }
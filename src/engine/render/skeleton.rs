@@ -0,0 +1,110 @@
+use engine::asset::{AssetError, AssetResult};
+use engine::core::{ComponentBased, GameObject};
+use math::*;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+/// Maximum number of joints of a skeleton, must match MAX_JOINTS in the shaders
+pub const MAX_JOINTS: usize = 32;
+
+/// Skin of the mesh on the same game object.
+///
+/// Vertices are deformed by the joints listed in their `MeshData::joints`,
+/// the joints are game objects, usually descendants of the mesh object.
+pub struct Skeleton {
+    pub joints: Vec<Weak<RefCell<GameObject>>>,
+
+    /// Transforms from mesh space to the local space of each joint in bind pose
+    pub inverse_bind_matrices: Vec<Matrix4f>,
+}
+
+impl ComponentBased for Skeleton {}
+
+fn invalid_skeleton<T>(len: usize, reason: String) -> AssetResult<T> {
+    Err(AssetError::InvalidFormat {
+        path: "<skeleton>".to_owned(),
+        len,
+        reason,
+    })
+}
+
+impl Skeleton {
+    /// Fails if there is not an inverse bind matrix per joint,
+    /// or more joints than the shaders support
+    pub fn new(
+        joints: &[Rc<RefCell<GameObject>>],
+        inverse_bind_matrices: Vec<Matrix4f>,
+    ) -> AssetResult<Skeleton> {
+        if joints.len() != inverse_bind_matrices.len() {
+            return invalid_skeleton(
+                joints.len(),
+                format!(
+                    "{} joints but {} inverse bind matrices",
+                    joints.len(),
+                    inverse_bind_matrices.len()
+                ),
+            );
+        }
+
+        if joints.len() > MAX_JOINTS {
+            return invalid_skeleton(
+                joints.len(),
+                format!(
+                    "{} joints, at most {} are supported",
+                    joints.len(),
+                    MAX_JOINTS
+                ),
+            );
+        }
+
+        Ok(Skeleton {
+            joints: joints.iter().map(|j| Rc::downgrade(j)).collect(),
+            inverse_bind_matrices,
+        })
+    }
+
+    /// Use the current transforms of the joints as bind pose
+    pub fn from_bind_pose(
+        mesh: &GameObject,
+        joints: &[Rc<RefCell<GameObject>>],
+    ) -> AssetResult<Skeleton> {
+        let mesh_m = mesh.transform.as_global_matrix();
+
+        let mut inverse_bind_matrices = Vec::with_capacity(joints.len());
+        for (i, j) in joints.iter().enumerate() {
+            let joint_m = j.borrow().transform.as_global_matrix();
+            match joint_m.inverse_transform() {
+                Some(inv) => inverse_bind_matrices.push(inv * mesh_m),
+                None => {
+                    return invalid_skeleton(joints.len(), format!("joint {} has no inverse", i))
+                }
+            }
+        }
+
+        Skeleton::new(joints, inverse_bind_matrices)
+    }
+
+    /// The skinning matrices in mesh space, for a mesh with the model matrix `model_m`.
+    ///
+    /// Only the first MAX_JOINTS joints are used when more are pushed to `joints`.
+    pub fn joint_matrices(&self, model_m: &Matrix4f) -> Vec<Matrix4f> {
+        let inv_model_m = model_m.inverse_transform().unwrap_or(Matrix4::identity());
+
+        self.joints
+            .iter()
+            .zip(self.inverse_bind_matrices.iter())
+            .take(MAX_JOINTS)
+            .map(|(joint, ibm)| {
+                // Removed or borrowed joints are left in bind pose
+                let joint_m = joint
+                    .upgrade()
+                    .and_then(|j| j.try_borrow().ok().map(|j| j.transform.as_global_matrix()));
+
+                match joint_m {
+                    Some(joint_m) => inv_model_m * joint_m * ibm,
+                    None => Matrix4::identity(),
+                }
+            })
+            .collect()
+    }
+}
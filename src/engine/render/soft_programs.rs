@@ -0,0 +1,516 @@
+// Rust stand-ins of the built-in programs, used by the software rasterizer backend.
+// They are registered by the names the programs are loaded with.
+//
+// They follow the glsl files as close as possible, except shadows are not emulated,
+// so "phong_shadow" is drawn as plain "phong". "pbr" ignores the normal maps and
+// samples its environment map without the mipmaps. "post_fxaa" is drawn as a copy.
+
+use engine::render::shader_program::ShaderAttrib;
+use engine::render::{MAX_DIRECTIONAL_LIGHTS, MAX_POINT_LIGHTS, MAX_SPOT_LIGHTS};
+use math::*;
+use webgl::{ShaderEnv, SoftProgram, WebGLRenderingContext};
+
+pub fn register_soft_programs(gl: &WebGLRenderingContext) {
+    gl.register_program("default", phong());
+    gl.register_program("phong", phong());
+    gl.register_program("unrust/phong_shadow", phong());
+    gl.register_program("unrust/pbr", pbr());
+    gl.register_program("default_ui", default_ui());
+    gl.register_program("unrust/skybox", skybox());
+    gl.register_program("unrust/sprite", sprite());
+    gl.register_program("unrust/shadow", shadow());
+    gl.register_program("unrust/local_shadow", local_shadow());
+    gl.register_program("unrust/shadow_vsm", shadow_vsm());
+
+    gl.register_program("unrust/post_copy", post(|env, uv| env.texture2d("uSource", uv)));
+    gl.register_program("unrust/post_fxaa", post(|env, uv| env.texture2d("uSource", uv)));
+    gl.register_program("unrust/post_bloom_bright", post(bloom_bright));
+    gl.register_program("unrust/post_bloom_blur", post(bloom_blur));
+    gl.register_program("unrust/post_bloom_combine", post(bloom_combine));
+    gl.register_program("unrust/post_tonemap", post(tonemap));
+    gl.register_program("unrust/post_vignette", post(vignette));
+    gl.register_program("unrust/post_color_grading", post(color_grading));
+}
+
+fn mat4(env: &ShaderEnv, name: &str) -> Matrix4f {
+    Matrix4::from(env.mat4(name))
+}
+
+fn vec3f(env: &ShaderEnv, name: &str) -> Vector3f {
+    Vector3::from(env.vec3(name))
+}
+
+fn normalize(v: Vector3f) -> Vector3f {
+    if v.magnitude2() > 0.0 {
+        v.normalize()
+    } else {
+        v
+    }
+}
+
+fn reflect(i: Vector3f, n: Vector3f) -> Vector3f {
+    i - n * (2.0 * n.dot(i))
+}
+
+fn position(attribs: &[[f32; 4]]) -> Vector4<f32> {
+    Vector4::new(attribs[0][0], attribs[0][1], attribs[0][2], 1.0)
+}
+
+fn skin_matrix(env: &ShaderEnv, attribs: &[[f32; 4]]) -> Matrix4f {
+    if env.int("uSkinned") == 0 {
+        return Matrix4::identity();
+    }
+
+    let joints = attribs[ShaderAttrib::Joints as usize];
+    let weights = attribs[ShaderAttrib::Weights as usize];
+
+    let mut m = Matrix4::zero();
+    for i in 0..4 {
+        m = m + mat4(env, &format!("uJointMatrices[{}]", joints[i] as usize)) * weights[i];
+    }
+    m
+}
+
+fn instance_matrix(attribs: &[[f32; 4]]) -> Matrix4f {
+    let col = |i: usize| Vector4::from(attribs[ShaderAttrib::InstanceMatrix as usize + i]);
+    Matrix4::from_cols(col(0), col(1), col(2), col(3))
+}
+
+/// The model matrix, from the instance attribute when drawn instanced
+fn model_matrix(env: &ShaderEnv, attribs: &[[f32; 4]]) -> Matrix4f {
+    if env.int("uInstanced") != 0 {
+        instance_matrix(attribs)
+    } else {
+        mat4(env, "uMMatrix")
+    }
+}
+
+/// Outputs the world position, normal and uv
+fn phong_vertex(env: &ShaderEnv, attribs: &[[f32; 4]], out: &mut Vec<f32>) -> [f32; 4] {
+    let skin = skin_matrix(env, attribs);
+    let pos = skin * position(attribs);
+    let normal = skin * Vector4::new(attribs[2][0], attribs[2][1], attribs[2][2], 0.0);
+
+    let instanced = env.int("uInstanced") != 0;
+    let model = model_matrix(env, attribs);
+    let frag_pos = model * pos;
+    let normal = if instanced {
+        model * normal
+    } else {
+        mat4(env, "uNMatrix") * normal
+    };
+
+    out.extend_from_slice(&[frag_pos.x, frag_pos.y, frag_pos.z]);
+    out.extend_from_slice(&[normal.x, normal.y, normal.z]);
+    out.extend_from_slice(&[attribs[1][0], attribs[1][1]]);
+
+    if instanced {
+        (mat4(env, "uPVMatrix") * model * pos).into()
+    } else {
+        (mat4(env, "uPMatrix") * mat4(env, "uMVMatrix") * pos).into()
+    }
+}
+
+fn phong() -> SoftProgram {
+    SoftProgram::new(
+        phong_vertex,
+        |env, v| {
+            let frag_pos = Vector3::new(v[0], v[1], v[2]);
+            let norm = normalize(Vector3::new(v[3], v[4], v[5]));
+            let view_dir = normalize(vec3f(env, "uViewPos") - frag_pos);
+
+            let tex = env.texture2d("uMaterial.diffuse", [v[6], v[7]]);
+            let albedo = Vector3::new(tex[0], tex[1], tex[2]);
+            let shininess = env.float("uMaterial.shininess");
+
+            let mut result: Vector3f = Vector3::zero();
+
+            // Directional Lights, the unused ones are left black
+            for i in 0..MAX_DIRECTIONAL_LIGHTS {
+                let name = |field: &str| format!("uDirectionalLights[{}].{}", i, field);
+
+                let light_dir = normalize(-vec3f(env, &name("direction")));
+                let diff = norm.dot(light_dir).max(0.0);
+                let spec = view_dir
+                    .dot(reflect(-light_dir, norm))
+                    .max(0.0)
+                    .powf(shininess);
+
+                result += vec3f(env, &name("ambient")).mul_element_wise(albedo)
+                    + vec3f(env, &name("diffuse")).mul_element_wise(albedo) * diff
+                    + vec3f(env, &name("specular")) * spec;
+            }
+
+            // Point Lights
+            for i in 0..MAX_POINT_LIGHTS {
+                let name = |field: &str| format!("uPointLights[{}].{}", i, field);
+
+                let light_pos = vec3f(env, &name("position"));
+                let light_dir = normalize(light_pos - frag_pos);
+                let diff = norm.dot(light_dir).max(0.0);
+                let spec = view_dir
+                    .dot(reflect(-light_dir, norm))
+                    .max(0.0)
+                    .powf(shininess);
+
+                let distance = (light_pos - frag_pos).magnitude();
+                let d = env.float(&name("constant")) + env.float(&name("linear")) * distance
+                    + env.float(&name("quadratic")) * (distance * distance);
+                let attenuation = 1.0 / d.max(0.001);
+
+                let ambient = vec3f(env, &name("ambient")).mul_element_wise(albedo);
+                let diffuse = vec3f(env, &name("diffuse")).mul_element_wise(albedo) * diff;
+                let specular = vec3f(env, &name("specular")) * spec;
+
+                result +=
+                    (ambient + diffuse + specular) * attenuation * env.float(&name("rate"));
+            }
+
+            // Spot Lights
+            for i in 0..MAX_SPOT_LIGHTS {
+                let name = |field: &str| format!("uSpotLights[{}].{}", i, field);
+
+                let light_pos = vec3f(env, &name("position"));
+                let light_dir = normalize(light_pos - frag_pos);
+                let diff = norm.dot(light_dir).max(0.0);
+                let spec = view_dir
+                    .dot(reflect(-light_dir, norm))
+                    .max(0.0)
+                    .powf(shininess);
+
+                let distance = (light_pos - frag_pos).magnitude();
+                let d = env.float(&name("constant")) + env.float(&name("linear")) * distance
+                    + env.float(&name("quadratic")) * (distance * distance);
+                let attenuation = 1.0 / d.max(0.001);
+
+                // Soft edge between the inner and outer cones
+                let theta = light_dir.dot(normalize(-vec3f(env, &name("direction"))));
+                let outer = env.float(&name("outerCutOff"));
+                let epsilon = (env.float(&name("cutOff")) - outer).max(0.001);
+                let intensity = ((theta - outer) / epsilon).max(0.0).min(1.0);
+
+                let ambient = vec3f(env, &name("ambient")).mul_element_wise(albedo);
+                let diffuse = vec3f(env, &name("diffuse")).mul_element_wise(albedo) * diff;
+                let specular = vec3f(env, &name("specular")) * spec;
+
+                result += (ambient + (diffuse + specular) * intensity) * attenuation
+                    * env.float(&name("rate"));
+            }
+
+            Some([result.x, result.y, result.z, 1.0])
+        },
+    )
+}
+
+fn vec4f(v: [f32; 4]) -> Vector3f {
+    Vector3::new(v[0], v[1], v[2])
+}
+
+/// The terms of the "pbr" fragment shader, for one surface point
+struct PbrSurface {
+    albedo: Vector3f,
+    metallic: f32,
+    roughness: f32,
+    f0: Vector3f,
+    n: Vector3f,
+    v: Vector3f,
+}
+
+impl PbrSurface {
+    fn fresnel(&self, cos_theta: f32) -> Vector3f {
+        let one = Vector3::new(1.0, 1.0, 1.0);
+        self.f0 + (one - self.f0) * (1.0 - cos_theta).powi(5)
+    }
+
+    /// Cook-Torrance specular with a lambertian diffuse
+    fn brdf(&self, l: Vector3f, radiance: Vector3f) -> Vector3f {
+        use std::f32::consts::PI;
+
+        let h = normalize(self.v + l);
+        let n_dot_l = self.n.dot(l).max(0.0);
+        let n_dot_v = self.n.dot(self.v).max(0.0001);
+        let n_dot_h = self.n.dot(h).max(0.0);
+
+        let f = self.fresnel(h.dot(self.v).max(0.0));
+
+        let a = self.roughness * self.roughness;
+        let a2 = a * a;
+        let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+        let d = a2 / (PI * d * d).max(0.0001);
+
+        let r = self.roughness + 1.0;
+        let k = (r * r) / 8.0;
+        let g = n_dot_v / (n_dot_v * (1.0 - k) + k) * n_dot_l / (n_dot_l * (1.0 - k) + k);
+
+        let specular = f * (d * g / (4.0 * n_dot_v * n_dot_l).max(0.0001));
+        let kd = (Vector3::new(1.0, 1.0, 1.0) - f) * (1.0 - self.metallic);
+
+        (kd.mul_element_wise(self.albedo) / PI + specular).mul_element_wise(radiance) * n_dot_l
+    }
+
+    fn image_based_lighting(&self, env: &ShaderEnv) -> Vector3f {
+        let n_dot_v = self.n.dot(self.v).max(0.0001);
+        let r = reflect(-self.v, self.n);
+
+        let irradiance = vec4f(env.texture_cube("uEnvironmentMap", self.n.into()));
+        let prefiltered = vec4f(env.texture_cube("uEnvironmentMap", r.into()));
+
+        let f = self.fresnel(n_dot_v);
+        let kd = (Vector3::new(1.0, 1.0, 1.0) - f) * (1.0 - self.metallic);
+
+        // Karis' analytical fit of the split sum lookup
+        let c0 = Vector4::new(-1.0, -0.0275, -0.572, 0.022);
+        let c1 = Vector4::new(1.0, 0.0425, 1.04, -0.04);
+        let rc = c0 * self.roughness + c1;
+        let a004 = (rc.x * rc.x).min((-9.28 * n_dot_v).exp2()) * rc.x + rc.y;
+        let (a, b) = (-1.04 * a004 + rc.z, 1.04 * a004 + rc.w);
+        let env_brdf = self.f0 * a + Vector3::new(b, b, b);
+
+        let diffuse = kd.mul_element_wise(irradiance).mul_element_wise(self.albedo);
+        let specular = prefiltered.mul_element_wise(env_brdf);
+
+        (diffuse + specular) * env.float("uEnvironmentIntensity")
+    }
+}
+
+fn attenuation(env: &ShaderEnv, name: &Fn(&str) -> String, frag_pos: Vector3f) -> f32 {
+    let distance = (vec3f(env, &name("position")) - frag_pos).magnitude();
+    let d = env.float(&name("constant")) + env.float(&name("linear")) * distance
+        + env.float(&name("quadratic")) * (distance * distance);
+    1.0 / d.max(0.001)
+}
+
+fn pbr() -> SoftProgram {
+    SoftProgram::new(
+        phong_vertex,
+        |env, v| {
+            let frag_pos = Vector3::new(v[0], v[1], v[2]);
+            let uv = [v[6], v[7]];
+
+            let tex = env.texture2d("uMaterial.baseColorMap", uv);
+            let base_color = Vector4::from(env.vec4("uMaterial.baseColor"))
+                .mul_element_wise(Vector4::from(tex));
+            if base_color.w < env.float("uMaterial.alphaCutoff") {
+                return None;
+            }
+
+            let mr = env.texture2d("uMaterial.metallicRoughnessMap", uv);
+            let albedo = base_color.truncate();
+            let metallic = (env.float("uMaterial.metallic") * mr[2]).max(0.0).min(1.0);
+
+            let s = PbrSurface {
+                albedo,
+                metallic,
+                roughness: (env.float("uMaterial.roughness") * mr[1]).max(0.04).min(1.0),
+                f0: Vector3::new(0.04, 0.04, 0.04).lerp(albedo, metallic),
+                n: normalize(Vector3::new(v[3], v[4], v[5])),
+                v: normalize(vec3f(env, "uViewPos") - frag_pos),
+            };
+
+            let mut result: Vector3f = Vector3::zero();
+
+            for i in 0..MAX_DIRECTIONAL_LIGHTS {
+                let name = |field: &str| format!("uDirectionalLights[{}].{}", i, field);
+                let l = normalize(-vec3f(env, &name("direction")));
+                result += s.brdf(l, vec3f(env, &name("diffuse")));
+            }
+
+            for i in 0..MAX_POINT_LIGHTS {
+                let name = |field: &str| format!("uPointLights[{}].{}", i, field);
+                let l = normalize(vec3f(env, &name("position")) - frag_pos);
+                let rate = attenuation(env, &name, frag_pos) * env.float(&name("rate"));
+                result += s.brdf(l, vec3f(env, &name("diffuse")) * rate);
+            }
+
+            for i in 0..MAX_SPOT_LIGHTS {
+                let name = |field: &str| format!("uSpotLights[{}].{}", i, field);
+                let l = normalize(vec3f(env, &name("position")) - frag_pos);
+
+                let theta = l.dot(normalize(-vec3f(env, &name("direction"))));
+                let outer = env.float(&name("outerCutOff"));
+                let epsilon = (env.float(&name("cutOff")) - outer).max(0.001);
+                let intensity = ((theta - outer) / epsilon).max(0.0).min(1.0);
+
+                let rate = attenuation(env, &name, frag_pos) * intensity
+                    * env.float(&name("rate"));
+                result += s.brdf(l, vec3f(env, &name("diffuse")) * rate);
+            }
+
+            let ao = env.texture2d("uMaterial.occlusionMap", uv)[0];
+            let ao = 1.0 + env.float("uMaterial.occlusionStrength") * (ao - 1.0);
+            result += s.image_based_lighting(env) * ao;
+
+            let emissive = vec4f(env.texture2d("uMaterial.emissiveMap", uv));
+            result += vec3f(env, "uMaterial.emissive").mul_element_wise(emissive);
+
+            Some([result.x, result.y, result.z, base_color.w])
+        },
+    )
+}
+
+fn default_ui() -> SoftProgram {
+    SoftProgram::new(
+        |env, attribs, out| {
+            out.extend_from_slice(&[attribs[1][0], attribs[1][1]]);
+            (mat4(env, "uMMatrix") * position(attribs)).into()
+        },
+        |env, v| Some(env.texture2d("uDiffuse", [v[0], v[1]])),
+    )
+}
+
+fn sprite() -> SoftProgram {
+    SoftProgram::new(
+        |env, attribs, out| {
+            let color = attribs[ShaderAttrib::Color as usize];
+            out.extend_from_slice(&[attribs[1][0], attribs[1][1]]);
+            out.extend_from_slice(&color);
+            (mat4(env, "uPMatrix") * mat4(env, "uMVMatrix") * position(attribs)).into()
+        },
+        |env, v| {
+            let c = env.texture2d("uTexture", [v[0], v[1]]);
+            Some([c[0] * v[2], c[1] * v[3], c[2] * v[4], c[3] * v[5]])
+        },
+    )
+}
+
+fn skybox() -> SoftProgram {
+    SoftProgram::new(
+        |env, attribs, out| {
+            out.extend_from_slice(&[attribs[0][0], attribs[0][1], attribs[0][2]]);
+
+            let pos = mat4(env, "uPVSkyboxMatrix") * position(attribs);
+            pos.into()
+        },
+        |env, v| Some(env.texture_cube("uSkybox", [v[0], v[1], v[2]])),
+    )
+}
+
+fn shadow() -> SoftProgram {
+    SoftProgram::new(
+        |env, attribs, _| {
+            let mut pos = mat4(env, "uShadowMatrix") * model_matrix(env, attribs)
+                * skin_matrix(env, attribs) * position(attribs);
+            pos.z *= pos.w;
+            pos.into()
+        },
+        |_, _| Some([1.0, 1.0, 1.0, 1.0]),
+    )
+}
+
+fn local_shadow() -> SoftProgram {
+    SoftProgram::new(
+        |env, attribs, _| {
+            let pos = mat4(env, "uShadowMatrix") * model_matrix(env, attribs)
+                * skin_matrix(env, attribs) * position(attribs);
+            pos.into()
+        },
+        |_, _| Some([1.0, 1.0, 1.0, 1.0]),
+    )
+}
+
+/// The depth and squared depth, without the derivatives
+fn shadow_vsm() -> SoftProgram {
+    SoftProgram::new(
+        |env, attribs, out| {
+            let pos = mat4(env, "uShadowMatrix") * model_matrix(env, attribs)
+                * skin_matrix(env, attribs) * position(attribs);
+            out.push(pos.z / pos.w * 0.5 + 0.5);
+            pos.into()
+        },
+        |_, v| Some([v[0], v[0] * v[0], 0.0, 1.0]),
+    )
+}
+
+/// A post process program, drawing the "screen_quad" mesh buffer as is
+fn post<F>(fragment: F) -> SoftProgram
+where
+    F: 'static + Fn(&ShaderEnv, [f32; 2]) -> [f32; 4],
+{
+    SoftProgram::new(
+        |_, attribs, out| {
+            out.extend_from_slice(&[attribs[1][0], attribs[1][1]]);
+            [attribs[0][0], attribs[0][1], 0.0, 1.0]
+        },
+        move |env, v| Some(fragment(env, [v[0], v[1]])),
+    )
+}
+
+/// uSource sampled at uv moved by (dx, dy) texels
+fn source_at(env: &ShaderEnv, uv: [f32; 2], dx: f32, dy: f32) -> Vector3f {
+    let texel = env.vec2("uTexelSize");
+    vec4f(env.texture2d("uSource", [uv[0] + dx * texel[0], uv[1] + dy * texel[1]]))
+}
+
+fn bloom_bright(env: &ShaderEnv, uv: [f32; 2]) -> [f32; 4] {
+    let c = (source_at(env, uv, -0.5, -0.5) + source_at(env, uv, -0.5, 0.5)
+        + source_at(env, uv, 0.5, -0.5) + source_at(env, uv, 0.5, 0.5)) * 0.25;
+
+    let brightness = c.x.max(c.y).max(c.z);
+    let contribution = (brightness - env.float("uThreshold")).max(0.0) / brightness.max(0.0001);
+    let c = c * contribution;
+
+    [c.x, c.y, c.z, 1.0]
+}
+
+fn bloom_blur(env: &ShaderEnv, uv: [f32; 2]) -> [f32; 4] {
+    let dir = env.vec2("uDirection");
+    let at = |offset: f32| source_at(env, uv, dir[0] * offset, dir[1] * offset);
+
+    let c = at(0.0) * 0.2270270270 + (at(1.3846153846) + at(-1.3846153846)) * 0.3162162162
+        + (at(3.2307692308) + at(-3.2307692308)) * 0.0702702703;
+
+    [c.x, c.y, c.z, 1.0]
+}
+
+fn bloom_combine(env: &ShaderEnv, uv: [f32; 2]) -> [f32; 4] {
+    let c = env.texture2d("uSource", uv);
+    let bloom = vec4f(env.texture2d("uBloom", uv)) * env.float("uIntensity");
+
+    [c[0] + bloom.x, c[1] + bloom.y, c[2] + bloom.z, c[3]]
+}
+
+fn tonemap(env: &ShaderEnv, uv: [f32; 2]) -> [f32; 4] {
+    let c = env.texture2d("uSource", uv);
+    let (exposure, gamma) = (env.float("uExposure"), env.float("uGamma"));
+
+    let aces = |x: f32| {
+        let x = x * exposure;
+        let mapped = (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+        mapped.max(0.0).min(1.0).powf(1.0 / gamma)
+    };
+
+    [aces(c[0]), aces(c[1]), aces(c[2]), c[3]]
+}
+
+fn smoothstep(e0: f32, e1: f32, x: f32) -> f32 {
+    let t = ((x - e0) / (e1 - e0)).max(0.0).min(1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn vignette(env: &ShaderEnv, uv: [f32; 2]) -> [f32; 4] {
+    let c = env.texture2d("uSource", uv);
+    let dist = Vector2::new(uv[0] - 0.5, uv[1] - 0.5).magnitude() * 1.414;
+
+    let (radius, smoothness) = (env.float("uRadius"), env.float("uSmoothness"));
+    let k = 1.0 - smoothstep(radius, radius + smoothness, dist) * env.float("uIntensity");
+
+    [c[0] * k, c[1] * k, c[2] * k, c[3]]
+}
+
+fn color_grading(env: &ShaderEnv, uv: [f32; 2]) -> [f32; 4] {
+    let c = env.texture2d("uSource", uv);
+    let n = env.float("uLutSize");
+    let clamp = |x: f32| x.max(0.0).min(1.0);
+
+    let slice = clamp(c[2]) * (n - 1.0);
+    let s0 = slice.floor();
+    let s1 = (s0 + 1.0).min(n - 1.0);
+
+    let u = (clamp(c[0]) * (n - 1.0) + 0.5) / (n * n);
+    let v = (clamp(c[1]) * (n - 1.0) + 0.5) / n;
+    let c0 = vec4f(env.texture2d("uLut", [u + s0 / n, v]));
+    let c1 = vec4f(env.texture2d("uLut", [u + s1 / n, v]));
+    let graded = c0.lerp(c1, slice - s0);
+
+    [graded.x, graded.y, graded.z, c[3]]
+}
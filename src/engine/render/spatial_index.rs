@@ -0,0 +1,362 @@
+use engine::core::internal::GameObjectUtil;
+use engine::core::{GameObject, SceneTree};
+use engine::render::{Mesh, RenderQueue, Skeleton};
+use math::*;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::rc::{Rc, Weak};
+
+/// Address of the tree and node id, ordered by creation in each tree
+type NodeKey = (usize, u64);
+
+struct Entry {
+    go: Weak<RefCell<GameObject>>,
+    bounds: Option<Aabb>,
+    /// Leaf in the bvh, for the objects which can be culled
+    proxy: Option<BvhProxy>,
+    queues: Vec<RenderQueue>,
+}
+
+/// World space bounds of the objects with a Mesh,
+/// kept up to date from the changes of the watched scene trees.
+pub struct SpatialIndex {
+    bvh: Bvh<NodeKey>,
+    entries: BTreeMap<NodeKey, Entry>,
+    /// Never culled: not loaded yet, skinned, skybox or UI
+    always: BTreeSet<NodeKey>,
+    trees: Vec<(usize, Weak<SceneTree>)>,
+    changed: Rc<RefCell<Vec<Weak<RefCell<GameObject>>>>>,
+    surface_counts: BTreeMap<RenderQueue, u32>,
+}
+
+fn tree_key(tree: &SceneTree) -> usize {
+    tree as *const SceneTree as usize
+}
+
+fn node_key(object: &GameObject) -> NodeKey {
+    (tree_key(&object.tree()), GameObjectUtil::node_id(object))
+}
+
+/// World bounds, whether it can be culled, and the queue of each surface
+fn object_bounds(object: &GameObject) -> (Option<Aabb>, bool, Vec<RenderQueue>) {
+    let mesh = match object.find_component::<Mesh>() {
+        Some((mesh, _)) => mesh,
+        None => return (None, false, Vec::new()),
+    };
+
+    let queues: Vec<RenderQueue> = mesh.surfaces
+        .iter()
+        .map(|s| s.material.render_queue)
+        .collect();
+
+    let mut local = if mesh.surfaces.is_empty() {
+        None
+    } else {
+        Some(Aabb::empty())
+    };
+
+    for surface in mesh.surfaces.iter() {
+        match surface.buffer.bounds() {
+            Some(b) => if let Some(ref mut local) = local {
+                local.merge(&b.local_aabb());
+            },
+            None => {
+                local = None;
+                break;
+            }
+        }
+    }
+
+    // Through the tree cache, so the node is clean and its next move is reported
+    let m = object.tree().get_global_matrix(GameObjectUtil::node_id(object));
+    let bounds = local.map(|local| {
+        local.corners().iter().fold(Aabb::empty(), |mut acc, p| {
+            acc.merge_point(&m.transform_point(Point3::from_vec(*p)).to_vec());
+            acc
+        })
+    });
+
+    // Skinned meshes can move out of their bounds
+    let cull = bounds.is_some() && object.find_component::<Skeleton>().is_none()
+        && queues.iter().all(|q| match *q {
+            RenderQueue::Skybox | RenderQueue::UI => false,
+            _ => true,
+        });
+
+    (bounds, cull, queues)
+}
+
+impl SpatialIndex {
+    pub fn new() -> SpatialIndex {
+        SpatialIndex {
+            bvh: Bvh::new(),
+            entries: BTreeMap::new(),
+            always: BTreeSet::new(),
+            trees: Vec::new(),
+            changed: Default::default(),
+            surface_counts: BTreeMap::new(),
+        }
+    }
+
+    /// Track the objects of the tree, return false if it is watched already
+    pub fn watch(&mut self, tree: &Rc<SceneTree>) -> bool {
+        let key = tree_key(tree);
+        if self.trees
+            .iter()
+            .any(|&(k, ref t)| k == key && t.upgrade().is_some())
+        {
+            return false;
+        }
+
+        self.remove_dropped_trees();
+        tree.track_changes();
+
+        let changed = self.changed.clone();
+        tree.add_watcher(move |_, go, c| {
+            if c.try_as::<Mesh>().is_some() || c.try_as::<Skeleton>().is_some() {
+                changed.borrow_mut().push(Rc::downgrade(go));
+            }
+        });
+
+        self.trees.push((key, Rc::downgrade(tree)));
+        true
+    }
+
+    /// Number of surfaces in the queue, culled or not
+    pub fn surface_count(&self, queue: RenderQueue) -> u32 {
+        self.surface_counts.get(&queue).cloned().unwrap_or(0)
+    }
+
+    pub fn update(&mut self) {
+        let changed: Vec<_> = self.changed.borrow_mut().drain(..).collect();
+        for go in changed {
+            // Dropped objects are removed with their node
+            if let Some(go) = go.upgrade() {
+                self.refresh(&go);
+            }
+        }
+
+        self.remove_dropped_trees();
+
+        let trees: Vec<_> = self.trees
+            .iter()
+            .filter_map(|&(t, ref tree)| tree.upgrade().map(|tree| (t, tree)))
+            .collect();
+
+        for (t, tree) in trees {
+            for id in tree.take_removed() {
+                self.remove((t, id));
+            }
+
+            for id in tree.take_moved() {
+                if let Some(go) = self.entries.get(&(t, id)).and_then(|e| e.go.upgrade()) {
+                    self.refresh(&go);
+                }
+            }
+        }
+
+        // Bounds may become known when the mesh buffers are loaded
+        let always: Vec<NodeKey> = self.always.iter().cloned().collect();
+        for key in always {
+            if let Some(go) = self.entries.get(&key).and_then(|e| e.go.upgrade()) {
+                self.refresh(&go);
+            }
+        }
+    }
+
+    fn remove_dropped_trees(&mut self) {
+        let dropped: Vec<usize> = self.trees
+            .iter()
+            .filter(|&&(_, ref tree)| tree.upgrade().is_none())
+            .map(|&(t, _)| t)
+            .collect();
+
+        for t in dropped {
+            let keys: Vec<NodeKey> = self.entries
+                .range((t, 0)..)
+                .take_while(|&(k, _)| k.0 == t)
+                .map(|(k, _)| *k)
+                .collect();
+
+            for key in keys {
+                self.remove(key);
+            }
+        }
+
+        self.trees.retain(|&(_, ref tree)| tree.upgrade().is_some());
+    }
+
+    fn refresh(&mut self, go: &Rc<RefCell<GameObject>>) {
+        let object = match go.try_borrow() {
+            Ok(object) => object,
+            Err(_) => {
+                // Try again on next update
+                self.changed.borrow_mut().push(Rc::downgrade(go));
+                return;
+            }
+        };
+
+        let key = node_key(&object);
+        if object.find_component::<Mesh>().is_none() {
+            self.remove(key);
+            return;
+        }
+
+        let (bounds, cull, queues) = object_bounds(&object);
+
+        let entry = self.entries.entry(key).or_insert_with(|| Entry {
+            go: Rc::downgrade(go),
+            bounds: None,
+            proxy: None,
+            queues: Vec::new(),
+        });
+
+        for q in entry.queues.iter() {
+            *self.surface_counts.get_mut(q).unwrap() -= 1;
+        }
+        for q in queues.iter() {
+            *self.surface_counts.entry(*q).or_insert(0) += 1;
+        }
+
+        entry.bounds = bounds;
+        entry.queues = queues;
+
+        match (entry.proxy, bounds) {
+            (Some(proxy), Some(bounds)) if cull => {
+                self.bvh.update(proxy, bounds);
+            }
+            (None, Some(bounds)) if cull => {
+                entry.proxy = Some(self.bvh.insert(bounds, key));
+                self.always.remove(&key);
+            }
+            (proxy, _) => {
+                if let Some(proxy) = proxy {
+                    self.bvh.remove(proxy);
+                }
+                entry.proxy = None;
+                self.always.insert(key);
+            }
+        }
+    }
+
+    fn remove(&mut self, key: NodeKey) {
+        if let Some(entry) = self.entries.remove(&key) {
+            if let Some(proxy) = entry.proxy {
+                self.bvh.remove(proxy);
+            }
+
+            for q in entry.queues.iter() {
+                *self.surface_counts.get_mut(q).unwrap() -= 1;
+            }
+        }
+
+        self.always.remove(&key);
+    }
+
+    fn collect(&self, mut keys: Vec<NodeKey>) -> Vec<Rc<RefCell<GameObject>>> {
+        // Keep the creation order
+        keys.sort();
+
+        keys.iter()
+            .filter_map(|k| self.entries.get(k).and_then(|e| e.go.upgrade()))
+            .collect()
+    }
+
+    fn query_keys<F>(&self, mut test: F) -> Vec<NodeKey>
+    where
+        F: FnMut(&Aabb) -> bool,
+    {
+        let mut keys = Vec::new();
+        self.bvh.query(|aabb| test(aabb), |_, key| keys.push(*key));
+
+        for key in self.always.iter() {
+            if self.entries[key].bounds.as_ref().map_or(false, |b| test(b)) {
+                keys.push(*key);
+            }
+        }
+
+        keys
+    }
+
+    /// Objects which bounds pass the test, the test is also called
+    /// with the bounds of groups of objects.
+    pub fn query<F>(&self, test: F) -> Vec<Rc<RefCell<GameObject>>>
+    where
+        F: FnMut(&Aabb) -> bool,
+    {
+        self.collect(self.query_keys(test))
+    }
+
+    /// Objects which can be visible, those without bounds are always included
+    pub fn visible<F>(&self, test: F) -> Vec<Rc<RefCell<GameObject>>>
+    where
+        F: FnMut(&Aabb) -> bool,
+    {
+        let mut keys = Vec::new();
+        self.bvh.query(test, |_, key| keys.push(*key));
+        keys.extend(self.always.iter());
+
+        self.collect(keys)
+    }
+
+    pub fn all(&self) -> Vec<Rc<RefCell<GameObject>>> {
+        self.collect(self.entries.keys().cloned().collect())
+    }
+
+    /// Merged bounds of the active objects passing the test
+    /// with a surface in one of the queues
+    pub fn merged_bounds<F>(&self, mut test: F, queues: &Option<BTreeSet<RenderQueue>>) -> Option<Aabb>
+    where
+        F: FnMut(&Aabb) -> bool,
+    {
+        let mut result: Option<Aabb> = None;
+
+        for key in self.query_keys(|aabb| test(aabb)) {
+            let entry = &self.entries[&key];
+            let go = match entry.go.upgrade() {
+                Some(go) => go,
+                None => continue,
+            };
+            let object = match go.try_borrow() {
+                Ok(object) => object,
+                Err(_) => continue,
+            };
+
+            let included = entry.queues.iter().any(|q| match *queues {
+                Some(ref included) => included.contains(q),
+                None => true,
+            });
+
+            if object.active && included {
+                let bounds = entry.bounds.unwrap();
+                result = Some(result.map_or(bounds, |r| r.union(&bounds)));
+            }
+        }
+
+        result
+    }
+
+    /// The k objects nearest to p with their distance, nearest first
+    pub fn nearest(&self, p: Vector3f, k: usize) -> Vec<(Rc<RefCell<GameObject>>, f32)> {
+        let mut found: Vec<(NodeKey, f32)> = self.bvh
+            .nearest(p, k)
+            .into_iter()
+            .map(|(proxy, d)| (*self.bvh.get(proxy), d))
+            .collect();
+
+        for key in self.always.iter() {
+            if let Some(ref b) = self.entries[key].bounds {
+                found.push((*key, b.distance2(&p).sqrt()));
+            }
+        }
+
+        found.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+        found.truncate(k);
+
+        found
+            .into_iter()
+            .filter_map(|(k, d)| self.entries[&k].go.upgrade().map(|go| (go, d)))
+            .collect()
+    }
+}
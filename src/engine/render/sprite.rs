@@ -0,0 +1,216 @@
+use engine::asset::{Asset, AssetResult, AssetSystem, FileFuture, LoadableAsset, Resource};
+use engine::core::ComponentBased;
+use engine::render::Texture;
+use math::*;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// A region of a texture drawn as a quad in the xy plane of its object.
+///
+/// Sprites are drawn in the transparent queue by the sprite batch of the engine,
+/// after the other transparent surfaces.
+#[derive(Clone, Debug)]
+pub struct Sprite {
+    pub texture: Rc<Texture>,
+    /// ((x, y), (width, height)) in pixels from the top-left corner of the texture,
+    /// the whole texture when None
+    pub rect: Option<((u32, u32), (u32, u32))>,
+    /// The point of the rect at the origin of the object,
+    /// from (0, 0) at the bottom-left to (1, 1) at the top-right
+    pub pivot: Vector2f,
+    /// Mirror the sprite around its pivot
+    pub flip_x: bool,
+    pub flip_y: bool,
+    /// Multiplied with the color of the texture
+    pub color: Vector4<f32>,
+    /// Sprites are drawn by sorting layer then by order, the lowest first
+    pub sorting_layer: i32,
+    pub order: i32,
+    /// Pixels of the texture in a world unit
+    pub pixels_per_unit: f32,
+}
+
+impl ComponentBased for Sprite {}
+
+/// A sprite in world space, ready to be batched
+pub struct SpriteQuad {
+    pub texture: Rc<Texture>,
+    /// Bottom-left, bottom-right, top-right and top-left corners
+    pub positions: [Vector3f; 4],
+    pub uvs: [Vector2f; 4],
+    pub color: Vector4<f32>,
+    pub sorting_layer: i32,
+    pub order: i32,
+}
+
+impl SpriteQuad {
+    /// World bounding sphere
+    pub fn sphere(&self) -> (Vector3f, f32) {
+        let center = self.positions
+            .iter()
+            .fold(Vector3::zero(), |acc, p| acc + *p) / 4.0;
+        let r = self.positions
+            .iter()
+            .fold(0.0f32, |r, p| r.max((p - center).magnitude()));
+
+        (center, r)
+    }
+}
+
+impl Sprite {
+    pub fn new(texture: Rc<Texture>) -> Sprite {
+        Sprite {
+            texture,
+            rect: None,
+            pivot: Vector2::new(0.5, 0.5),
+            flip_x: false,
+            flip_y: false,
+            color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+            sorting_layer: 0,
+            order: 0,
+            pixels_per_unit: 100.0,
+        }
+    }
+
+    pub fn with_rect(mut self, pos: (u32, u32), size: (u32, u32)) -> Sprite {
+        self.rect = Some((pos, size));
+        self
+    }
+
+    pub fn with_pivot(mut self, pivot: Vector2f) -> Sprite {
+        self.pivot = pivot;
+        self
+    }
+
+    pub fn with_color(mut self, color: Vector4<f32>) -> Sprite {
+        self.color = color;
+        self
+    }
+
+    pub fn with_sorting(mut self, sorting_layer: i32, order: i32) -> Sprite {
+        self.sorting_layer = sorting_layer;
+        self.order = order;
+        self
+    }
+
+    pub fn with_pixels_per_unit(mut self, pixels_per_unit: f32) -> Sprite {
+        self.pixels_per_unit = pixels_per_unit;
+        self
+    }
+
+    /// The quad of the sprite transformed by the model matrix,
+    /// tex_size being the size of its texture in pixels
+    pub fn quad(&self, m: &Matrix4f, tex_size: (u32, u32)) -> SpriteQuad {
+        let (tw, th) = (tex_size.0 as f32, tex_size.1 as f32);
+        let ((x, y), (w, h)) = self.rect.unwrap_or(((0, 0), tex_size));
+        let (x, y, w, h) = (x as f32, y as f32, w as f32, h as f32);
+
+        // Textures are flipped on load, v = 0 is the bottom of the image
+        let (mut u0, mut u1) = (x / tw, (x + w) / tw);
+        let (mut v0, mut v1) = (1.0 - (y + h) / th, 1.0 - y / th);
+
+        let (w, h) = (w / self.pixels_per_unit, h / self.pixels_per_unit);
+        let (mut x0, mut y0) = (-self.pivot.x * w, -self.pivot.y * h);
+        let (mut x1, mut y1) = (x0 + w, y0 + h);
+
+        if self.flip_x {
+            ::std::mem::swap(&mut u0, &mut u1);
+            let (a, b) = (-x1, -x0);
+            x0 = a;
+            x1 = b;
+        }
+        if self.flip_y {
+            ::std::mem::swap(&mut v0, &mut v1);
+            let (a, b) = (-y1, -y0);
+            y0 = a;
+            y1 = b;
+        }
+
+        let p = |x: f32, y: f32| m.transform_point(Point3::new(x, y, 0.0)).to_vec();
+
+        SpriteQuad {
+            texture: self.texture.clone(),
+            positions: [p(x0, y0), p(x1, y0), p(x1, y1), p(x0, y1)],
+            uvs: [
+                Vector2::new(u0, v0),
+                Vector2::new(u1, v0),
+                Vector2::new(u1, v1),
+                Vector2::new(u0, v1),
+            ],
+            color: self.color,
+            sorting_layer: self.sorting_layer,
+            order: self.order,
+        }
+    }
+}
+
+/// A named region of a sprite atlas
+#[derive(Copy, Clone, Debug)]
+pub struct SpriteFrame {
+    /// ((x, y), (width, height)) in pixels from the top-left corner of the texture
+    pub rect: ((u32, u32), (u32, u32)),
+    /// Relative to the rect, it may be outside of it when the frame is trimmed
+    pub pivot: Vector2f,
+}
+
+#[derive(Debug)]
+pub struct SpriteAtlasData {
+    pub texture: Rc<Texture>,
+    pub frames: HashMap<String, SpriteFrame>,
+}
+
+/// Sprites packed into one texture, loaded from a TexturePacker JSON file
+/// (hash or array format).
+pub struct SpriteAtlas {
+    data: Resource<SpriteAtlasData>,
+}
+
+impl Asset for SpriteAtlas {
+    type Resource = Resource<SpriteAtlasData>;
+
+    fn new_from_resource(r: Self::Resource) -> Rc<Self> {
+        Rc::new(SpriteAtlas { data: r })
+    }
+}
+
+impl LoadableAsset for SpriteAtlas {
+    fn load<T>(asys: &T, mut files: Vec<FileFuture>) -> Self::Resource
+    where
+        T: AssetSystem + Clone + 'static,
+    {
+        Self::load_resource::<SpriteAtlasData, T>(asys.clone(), files.remove(0))
+    }
+
+    fn gather<T: AssetSystem>(asys: &T, fname: &str) -> Vec<FileFuture> {
+        vec![asys.new_file(fname)]
+    }
+}
+
+impl SpriteAtlas {
+    pub fn texture(&self) -> AssetResult<Rc<Texture>> {
+        Ok(self.data.try_borrow()?.texture.clone())
+    }
+
+    /// The names of the frames, sorted
+    pub fn frame_names(&self) -> AssetResult<Vec<String>> {
+        let mut names: Vec<String> = self.data.try_borrow()?.frames.keys().cloned().collect();
+        names.sort();
+        Ok(names)
+    }
+
+    pub fn frame(&self, name: &str) -> AssetResult<Option<SpriteFrame>> {
+        Ok(self.data.try_borrow()?.frames.get(name).cloned())
+    }
+
+    /// A sprite of the named frame, or None if the atlas has no such frame
+    pub fn sprite(&self, name: &str) -> AssetResult<Option<Sprite>> {
+        let data = self.data.try_borrow()?;
+
+        Ok(data.frames.get(name).map(|frame| {
+            let (pos, size) = frame.rect;
+            Sprite::new(data.texture.clone())
+                .with_rect(pos, size)
+                .with_pivot(frame.pivot)
+        }))
+    }
+}
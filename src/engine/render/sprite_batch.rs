@@ -0,0 +1,121 @@
+use engine::asset::{Asset, AssetSystem};
+use engine::render::{CullMode, Material, MeshBuffer, MeshData, MeshIndices, MeshSurface,
+                     RenderQueue, SpriteQuad, Texture, U16_VERTEX_LIMIT};
+use std::rc::Rc;
+
+/// Packs sprite quads into a few dynamic mesh buffers, one draw per run
+/// of consecutive sprites sharing a texture.
+///
+/// The buffers and the materials are kept and reused by the next builds.
+pub struct SpriteBatch {
+    /// A new buffer is started when a batch would have more sprites
+    pub max_sprites: usize,
+    materials: Vec<(Rc<Texture>, Rc<Material>)>,
+    buffers: Vec<Rc<MeshBuffer>>,
+}
+
+impl Default for SpriteBatch {
+    fn default() -> SpriteBatch {
+        SpriteBatch {
+            // 4 vertices per sprite, drawn with u16 indices
+            max_sprites: U16_VERTEX_LIMIT / 4,
+            materials: Vec::new(),
+            buffers: Vec::new(),
+        }
+    }
+}
+
+impl SpriteBatch {
+    pub fn new() -> SpriteBatch {
+        SpriteBatch::default()
+    }
+
+    fn material(&mut self, asys: &AssetSystem, texture: &Rc<Texture>) -> Rc<Material> {
+        if let Some(&(_, ref m)) = self.materials.iter().find(|m| Rc::ptr_eq(&m.0, texture)) {
+            return m.clone();
+        }
+
+        let mut material = Material::new(asys.new_program("unrust/sprite"));
+        material.set("uTexture", texture.clone());
+        material.render_queue = RenderQueue::Transparent;
+        material.states.cull = Some(CullMode::Off);
+
+        let material = Rc::new(material);
+        self.materials.push((texture.clone(), material.clone()));
+        material
+    }
+
+    /// Sort the quads by sorting layer and order, keeping the given order of equal ones,
+    /// and build the surfaces drawing them in that order.
+    pub fn build(
+        &mut self,
+        asys: &AssetSystem,
+        mut quads: Vec<SpriteQuad>,
+    ) -> Vec<Rc<MeshSurface>> {
+        quads.sort_by_key(|q| (q.sorting_layer, q.order));
+
+        let mut runs: Vec<&[SpriteQuad]> = Vec::new();
+        let mut start = 0;
+        for i in 1..quads.len() + 1 {
+            let split = i == quads.len() || i - start >= self.max_sprites.max(1)
+                || !Rc::ptr_eq(&quads[i].texture, &quads[start].texture);
+
+            if split {
+                runs.push(&quads[start..i]);
+                start = i;
+            }
+        }
+
+        let mut surfaces = Vec::new();
+        for (i, run) in runs.into_iter().enumerate() {
+            let data = Self::mesh_data(run);
+            let buffer = match self.buffers.get(i) {
+                Some(buffer) => {
+                    buffer.update_mesh_data(data);
+                    buffer.clone()
+                }
+                None => MeshBuffer::new(data),
+            };
+            if i == self.buffers.len() {
+                self.buffers.push(buffer.clone());
+            }
+
+            surfaces.push(Rc::new(MeshSurface {
+                buffer,
+                material: self.material(asys, &run[0].texture),
+            }));
+        }
+
+        // Forget the textures which are not drawn anymore
+        self.materials
+            .retain(|&(ref t, _)| quads.iter().any(|q| Rc::ptr_eq(&q.texture, t)));
+
+        surfaces
+    }
+
+    fn mesh_data(quads: &[SpriteQuad]) -> MeshData {
+        let mut vertices = Vec::with_capacity(quads.len() * 12);
+        let mut uvs = Vec::with_capacity(quads.len() * 8);
+        let mut colors = Vec::with_capacity(quads.len() * 16);
+        let mut indices = Vec::with_capacity(quads.len() * 6);
+
+        for (i, q) in quads.iter().enumerate() {
+            for k in 0..4 {
+                vertices.extend_from_slice(&[q.positions[k].x, q.positions[k].y, q.positions[k].z]);
+                uvs.extend_from_slice(&[q.uvs[k].x, q.uvs[k].y]);
+                colors.extend_from_slice(&[q.color.x, q.color.y, q.color.z, q.color.w]);
+            }
+
+            let base = (i * 4) as u32;
+            indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+
+        MeshData {
+            vertices,
+            uvs: Some(uvs),
+            colors: Some(colors),
+            indices: MeshIndices::with_vertex_count(indices, quads.len() * 4),
+            ..MeshData::default()
+        }
+    }
+}
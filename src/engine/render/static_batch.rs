@@ -0,0 +1,254 @@
+use engine::asset::{Asset, AssetResult};
+use engine::core::{Component, GameObject};
+use engine::render::{Material, Mesh, MeshBuffer, MeshData, MeshIndices, MeshSurface, Skeleton,
+                     U16_VERTEX_LIMIT};
+use math::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Merges the meshes of static objects sharing a material into a few large buffers,
+/// so that they are drawn with a few draw calls.
+///
+/// The transforms of the objects are baked into the vertices, so the merged objects
+/// should not move relatively to the root afterwards.
+pub struct StaticBatch {
+    /// A new buffer is started when a merged one would have more vertices
+    pub max_vertices: usize,
+}
+
+impl Default for StaticBatch {
+    fn default() -> StaticBatch {
+        StaticBatch {
+            // Merged buffers can be drawn with u16 indices
+            max_vertices: U16_VERTEX_LIMIT,
+        }
+    }
+}
+
+/// An object with a Mesh to merge
+struct Source {
+    object: Option<Rc<RefCell<GameObject>>>,
+    component: Arc<Component>,
+    /// From the object to the root space
+    matrix: Matrix4f,
+    surfaces: Vec<Rc<MeshSurface>>,
+}
+
+fn collect_descendants(object: &GameObject, result: &mut Vec<Rc<RefCell<GameObject>>>) {
+    for child in object.childen() {
+        // Inactive objects are not drawn, so neither their children
+        if !child.borrow().active {
+            continue;
+        }
+
+        result.push(child.clone());
+        collect_descendants(&child.borrow(), result);
+    }
+}
+
+/// The mesh component and surfaces of an object which can be merged
+fn static_mesh(object: &GameObject) -> Option<(Arc<Component>, Vec<Rc<MeshSurface>>)> {
+    // Skinned meshes are deformed at runtime
+    if object.find_component::<Skeleton>().is_some() {
+        return None;
+    }
+
+    object
+        .find_component::<Mesh>()
+        .map(|(mesh, com)| (com.clone(), mesh.surfaces.clone()))
+}
+
+/// Append the attribute of count vertices, with n floats per vertex
+fn extend_attribute<F>(
+    dst: &mut Option<Vec<f32>>,
+    src: &Option<Vec<f32>>,
+    n: usize,
+    count: usize,
+    map: F,
+) where
+    F: Fn(&[f32]) -> Vec<f32>,
+{
+    if let Some(ref mut dst) = *dst {
+        match *src {
+            Some(ref src) => for v in src.chunks(n) {
+                dst.extend(map(v));
+            },
+            // Parts without it are zero filled
+            None => {
+                let len = dst.len();
+                dst.resize(len + n * count, 0.0);
+            }
+        }
+    }
+}
+
+impl StaticBatch {
+    /// Merge the meshes of root and its active descendants, except the skinned ones,
+    /// into a Mesh added to root. The merged meshes are removed from their objects.
+    ///
+    /// Returns NotReady without changing anything while some mesh data is not loaded.
+    pub fn build(&self, root: &mut GameObject) -> AssetResult<()> {
+        let root_inv = root
+            .transform
+            .as_global_matrix()
+            .invert()
+            .unwrap_or(Matrix4::identity());
+
+        let mut sources = Vec::new();
+        if let Some((component, surfaces)) = static_mesh(root) {
+            sources.push(Source {
+                object: None,
+                component,
+                matrix: Matrix4::identity(),
+                surfaces,
+            });
+        }
+
+        let mut descendants = Vec::new();
+        collect_descendants(root, &mut descendants);
+        for go in descendants {
+            let found = {
+                let object = go.borrow();
+                static_mesh(&object).map(|m| (m, object.transform.as_global_matrix()))
+            };
+
+            if let Some(((component, surfaces), m)) = found {
+                sources.push(Source {
+                    object: Some(go),
+                    component,
+                    matrix: root_inv * m,
+                    surfaces,
+                });
+            }
+        }
+
+        let parts: Vec<_> = sources
+            .iter()
+            .flat_map(|s| s.surfaces.iter().map(move |surface| (s.matrix, surface.clone())))
+            .collect();
+        let mesh = self.merge_surfaces(&parts)?;
+
+        for source in sources {
+            match source.object {
+                Some(go) => go.borrow_mut().remove_component(source.component),
+                None => root.remove_component(source.component),
+            }
+        }
+
+        if !mesh.surfaces.is_empty() {
+            root.add_component(mesh);
+        }
+
+        Ok(())
+    }
+
+    /// Merge surfaces, each one placed by a matrix, into a Mesh with
+    /// a few surfaces per material.
+    ///
+    /// Returns NotReady while some mesh data is not loaded.
+    pub fn merge_surfaces(&self, parts: &[(Matrix4f, Rc<MeshSurface>)]) -> AssetResult<Mesh> {
+        // Group the surfaces by material, in the order they are found
+        let mut groups: Vec<(Rc<Material>, Vec<(Matrix4f, Rc<MeshBuffer>)>)> = Vec::new();
+        for &(matrix, ref surface) in parts.iter() {
+            surface.buffer.data()?;
+
+            let part = (matrix, surface.buffer.clone());
+            match groups
+                .iter_mut()
+                .find(|g| Rc::ptr_eq(&g.0, &surface.material))
+            {
+                Some(group) => group.1.push(part),
+                None => groups.push((surface.material.clone(), vec![part])),
+            }
+        }
+
+        let mut mesh = Mesh::new();
+        for (material, parts) in groups {
+            for data in self.merge(&parts)? {
+                mesh.add_surface(MeshBuffer::new(data), material.clone());
+            }
+        }
+
+        Ok(mesh)
+    }
+
+    fn merge(&self, parts: &[(Matrix4f, Rc<MeshBuffer>)]) -> AssetResult<Vec<MeshData>> {
+        let mut result = Vec::new();
+
+        // An attribute is kept if any part has it
+        let mut has = (false, false, false, false, false);
+        for &(_, ref buffer) in parts.iter() {
+            let data = buffer.data()?;
+            has.0 |= data.uvs.is_some();
+            has.1 |= data.normals.is_some();
+            has.2 |= data.tangents.is_some();
+            has.3 |= data.bitangents.is_some();
+            has.4 |= data.colors.is_some();
+        }
+
+        let empty = || MeshData {
+            uvs: if has.0 { Some(Vec::new()) } else { None },
+            normals: if has.1 { Some(Vec::new()) } else { None },
+            tangents: if has.2 { Some(Vec::new()) } else { None },
+            bitangents: if has.3 { Some(Vec::new()) } else { None },
+            colors: if has.4 { Some(Vec::new()) } else { None },
+            ..MeshData::default()
+        };
+
+        let mut merged = empty();
+        let mut indices: Vec<u32> = Vec::new();
+
+        for &(m, ref buffer) in parts.iter() {
+            let data = buffer.data()?;
+            let count = data.vertices.len() / 3;
+            let base = merged.vertices.len() / 3;
+
+            if base > 0 && base + count > self.max_vertices {
+                merged.indices = MeshIndices::with_vertex_count(indices.split_off(0), base);
+                result.push(::std::mem::replace(&mut merged, empty()));
+            }
+            let base = merged.vertices.len() / 3;
+
+            // Normals are transformed by the inverse transpose, directions by the matrix
+            let m3 = Matrix3::from_cols(m.x.truncate(), m.y.truncate(), m.z.truncate());
+            let n3 = m3.invert().unwrap_or(m3).transpose();
+            let direction = |mat: Matrix3<f32>| {
+                move |v: &[f32]| {
+                    let v = mat * Vector3::new(v[0], v[1], v[2]);
+                    let v = if v.magnitude2() > 0.0 { v.normalize() } else { v };
+                    vec![v.x, v.y, v.z]
+                }
+            };
+
+            for v in data.vertices.chunks(3) {
+                let p = m.transform_point(Point3::new(v[0], v[1], v[2]));
+                merged.vertices.extend_from_slice(&[p.x, p.y, p.z]);
+            }
+
+            extend_attribute(&mut merged.uvs, &data.uvs, 2, count, |uv| uv.to_vec());
+            extend_attribute(&mut merged.normals, &data.normals, 3, count, direction(n3));
+            extend_attribute(&mut merged.tangents, &data.tangents, 3, count, direction(m3));
+            extend_attribute(
+                &mut merged.bitangents,
+                &data.bitangents,
+                3,
+                count,
+                direction(m3),
+            );
+            extend_attribute(&mut merged.colors, &data.colors, 4, count, |c| c.to_vec());
+
+            for i in 0..data.indices.len() {
+                indices.push(base as u32 + data.indices.get(i));
+            }
+        }
+
+        if !indices.is_empty() {
+            let count = merged.vertices.len() / 3;
+            merged.indices = MeshIndices::with_vertex_count(indices, count);
+            result.push(merged);
+        }
+
+        Ok(result)
+    }
+}
@@ -29,10 +29,14 @@ pub enum TextureImage {
     DXT5(DDS),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TextureAttachment {
     Color0,
     Depth,
+    /// Half float color, RGBA8 where float color buffers are not supported
+    Color0HalfFloat,
+    /// Float color, RGBA8 where float color buffers are not supported
+    Color0Float,
 }
 
 #[derive(Debug)]
@@ -200,19 +204,39 @@ impl Texture {
 
         if let TextureKind::RenderTexture { ref attach, .. } = self.kind {
             match attach {
-                &TextureAttachment::Color0 => {
-                    bind_to_framebuffer(gl, &state.tex, Buffers::ColorAttachment0);
-                }
                 &TextureAttachment::Depth => {
                     bind_to_framebuffer(gl, &state.tex, Buffers::DepthAttachment);
                     gl.draw_buffer(&[ColorBuffer::None]);
                 }
+                _ => {
+                    bind_to_framebuffer(gl, &state.tex, Buffers::ColorAttachment0);
+                }
             }
         }
 
         Ok(())
     }
 
+    /// Attach a render texture to the bound frame buffer at the attachment,
+    /// next to its other attachments.
+    pub fn bind_with_frame_buffer_at(
+        &self,
+        gl: &WebGLRenderingContext,
+        unit: u32,
+        attachment: Buffers,
+    ) -> AssetResult<()> {
+        self.prepare(gl, unit)?;
+
+        let state_option = self.gl_state.borrow();
+        let state = state_option.as_ref().unwrap();
+
+        gl.active_texture(unit);
+        gl.bind_texture(&state.tex);
+        bind_to_framebuffer(gl, &state.tex, attachment);
+
+        Ok(())
+    }
+
     pub fn prepare(&self, gl: &WebGLRenderingContext, unit: u32) -> AssetResult<()> {
         if self.gl_state.borrow().is_some() {
             return Ok(());
@@ -467,6 +491,15 @@ fn texture_bind_buffer(
                     force_nearest_filtering = true;
                     (PixelFormat::DepthComponent, PixelType::UnsignedShort)
                 }
+                &TextureAttachment::Color0HalfFloat if gl.color_buffer_float => {
+                    (PixelFormat::Rgba, PixelType::HalfFloat)
+                }
+                &TextureAttachment::Color0Float if gl.color_buffer_float => {
+                    // Linear filtering of float textures is an extension
+                    force_nearest_filtering = true;
+                    (PixelFormat::Rgba, PixelType::Float)
+                }
+                _ => (PixelFormat::Rgba, PixelType::UnsignedByte),
             };
 
             let tex = gl.create_texture();
@@ -0,0 +1,455 @@
+use engine::asset::{Asset, AssetSystem};
+use engine::core::{Aabb, ComponentBased};
+use engine::render::{CullMode, Material, MeshBuffer, MeshData, MeshSurface, RenderQueue,
+                     Texture};
+use math::*;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Flags in the high bits of a tile id, as in the Tiled format
+pub const TILE_FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+pub const TILE_FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+/// The tile is transposed, before the other flips
+pub const TILE_FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+/// The bits of the tile id without the flags
+pub const TILE_ID_MASK: u32 = 0x1FFF_FFFF;
+
+/// Custom properties, by name, of a map, a layer, a tile or an object
+pub type TileProperties = HashMap<String, String>;
+
+/// Tiles cut from a texture, in rows from its top-left corner
+#[derive(Clone, Debug)]
+pub struct Tileset {
+    pub name: String,
+    /// The id of its first tile in a map, 0 being no tile
+    pub first_id: u32,
+    pub texture: Rc<Texture>,
+    /// Size of the texture in pixels
+    pub image_size: (u32, u32),
+    pub tile_size: (u32, u32),
+    /// Pixels around the tiles and between them
+    pub margin: u32,
+    pub spacing: u32,
+    pub columns: u32,
+    pub tile_count: u32,
+    /// Properties of the tiles, by index in the tileset
+    pub tile_properties: HashMap<u32, TileProperties>,
+}
+
+impl Tileset {
+    /// A tileset of a texture of image_size pixels filled with tiles of tile_size
+    pub fn new(texture: Rc<Texture>, image_size: (u32, u32), tile_size: (u32, u32)) -> Tileset {
+        let columns = image_size.0 / tile_size.0.max(1);
+        let rows = image_size.1 / tile_size.1.max(1);
+
+        Tileset {
+            name: String::new(),
+            first_id: 1,
+            texture,
+            image_size,
+            tile_size,
+            margin: 0,
+            spacing: 0,
+            columns,
+            tile_count: columns * rows,
+            tile_properties: HashMap::new(),
+        }
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        let id = id & TILE_ID_MASK;
+        id >= self.first_id && id - self.first_id < self.tile_count
+    }
+
+    /// ((x, y), (width, height)) of a tile in pixels from the top-left corner of the texture
+    pub fn tile_rect(&self, id: u32) -> ((u32, u32), (u32, u32)) {
+        let index = (id & TILE_ID_MASK) - self.first_id;
+        let (col, row) = (index % self.columns.max(1), index / self.columns.max(1));
+        let (tw, th) = self.tile_size;
+
+        (
+            (
+                self.margin + col * (tw + self.spacing),
+                self.margin + row * (th + self.spacing),
+            ),
+            self.tile_size,
+        )
+    }
+
+    /// The uvs of the bottom-left, bottom-right, top-right and top-left corners
+    /// of a tile, flipped by the flags of its id
+    pub fn tile_uvs(&self, id: u32) -> [Vector2f; 4] {
+        let ((x, y), (tw, th)) = self.tile_rect(id);
+        let (x, y) = (x as f32, y as f32);
+        let (iw, ih) = (self.image_size.0 as f32, self.image_size.1 as f32);
+
+        // Textures are flipped on load, v = 1 is the top of the image
+        let (u0, u1) = (x / iw, (x + tw as f32) / iw);
+        let (v_top, v_bottom) = (1.0 - y / ih, 1.0 - (y + th as f32) / ih);
+
+        // (s, t) of the corners from the top-left of the tile, flipped to the point to sample
+        let corners = [(0.0, 1.0), (1.0, 1.0), (1.0, 0.0), (0.0, 0.0)];
+        let mut uvs = [Vector2::zero(); 4];
+        for (uv, &(s, t)) in uvs.iter_mut().zip(corners.iter()) {
+            let t = if id & TILE_FLIPPED_VERTICALLY != 0 { 1.0 - t } else { t };
+            let s = if id & TILE_FLIPPED_HORIZONTALLY != 0 { 1.0 - s } else { s };
+            let (s, t) = if id & TILE_FLIPPED_DIAGONALLY != 0 { (t, s) } else { (s, t) };
+
+            *uv = Vector2::new(u0 + s * (u1 - u0), v_top + t * (v_bottom - v_top));
+        }
+
+        uvs
+    }
+}
+
+/// A grid of tile ids, by rows from the top
+#[derive(Clone, Debug)]
+pub struct TileLayer {
+    pub name: String,
+    pub visible: bool,
+    pub opacity: f32,
+    pub properties: TileProperties,
+    tiles: Vec<u32>,
+}
+
+impl TileLayer {
+    pub fn tiles(&self) -> &[u32] {
+        &self.tiles
+    }
+}
+
+/// The shape of a TileObject, in world units relatively to its object
+#[derive(Clone, Debug, PartialEq)]
+pub enum TileObjectShape {
+    Rectangle,
+    Ellipse,
+    Point,
+    Polygon(Vec<Vector2f>),
+    Polyline(Vec<Vector2f>),
+}
+
+/// An object of an object layer of a Tiled map, added to the game object created for it
+#[derive(Clone, Debug)]
+pub struct TileObject {
+    pub id: u32,
+    pub name: String,
+    /// The type of the object in Tiled
+    pub kind: String,
+    pub layer: String,
+    /// In world units, the rectangles and ellipses extend rightward and downward
+    /// from the origin of the object as in Tiled, the tile objects upward
+    pub size: Vector2f,
+    pub shape: TileObjectShape,
+    pub properties: TileProperties,
+}
+
+impl ComponentBased for TileObject {}
+
+struct Chunk {
+    dirty: bool,
+    bounds: Aabb,
+    surfaces: Vec<Rc<MeshSurface>>,
+}
+
+/// A 2D grid of tiles in the xy plane of its object, from the origin rightward and upward.
+///
+/// The layers are split into square chunks, built into a mesh buffer per tileset,
+/// and only the chunks with changed tiles are built again. The tilemaps are drawn
+/// in the transparent queue, after the other transparent surfaces and before the sprites.
+pub struct Tilemap {
+    size: (u32, u32),
+    /// World size of a tile
+    cell_size: Vector2f,
+    chunk_size: u32,
+    tilesets: Vec<Rc<Tileset>>,
+    materials: Vec<Rc<Material>>,
+    layers: Vec<TileLayer>,
+    chunks: Vec<Vec<Chunk>>,
+    pub properties: TileProperties,
+}
+
+impl ComponentBased for Tilemap {}
+
+impl Tilemap {
+    /// A map of size tiles, without layers
+    pub fn new(size: (u32, u32), cell_size: Vector2f) -> Tilemap {
+        Tilemap {
+            size,
+            cell_size,
+            chunk_size: 16,
+            tilesets: Vec::new(),
+            materials: Vec::new(),
+            layers: Vec::new(),
+            chunks: Vec::new(),
+            properties: TileProperties::new(),
+        }
+    }
+
+    /// Width and height of the chunks in tiles, 16 by default
+    pub fn with_chunk_size(mut self, chunk_size: u32) -> Tilemap {
+        self.chunk_size = chunk_size.max(1);
+        self.chunks = (0..self.layers.len()).map(|_| self.new_chunks()).collect();
+        self
+    }
+
+    pub fn with_tileset(mut self, tileset: Rc<Tileset>) -> Tilemap {
+        self.add_tileset(tileset);
+        self
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    pub fn cell_size(&self) -> Vector2f {
+        self.cell_size
+    }
+
+    pub fn tilesets(&self) -> &[Rc<Tileset>] {
+        &self.tilesets
+    }
+
+    pub fn add_tileset(&mut self, tileset: Rc<Tileset>) {
+        self.tilesets.push(tileset);
+        self.set_all_dirty();
+    }
+
+    pub fn layers(&self) -> &[TileLayer] {
+        &self.layers
+    }
+
+    /// Add an empty layer drawn above the others, return its index
+    pub fn add_layer(&mut self, name: &str) -> usize {
+        let count = (self.size.0 * self.size.1) as usize;
+        self.add_layer_with_tiles(name, vec![0; count])
+    }
+
+    /// Add a layer of tile ids by rows from the top, which should be
+    /// width * height long, return its index
+    pub fn add_layer_with_tiles(&mut self, name: &str, mut tiles: Vec<u32>) -> usize {
+        tiles.resize((self.size.0 * self.size.1) as usize, 0);
+
+        self.layers.push(TileLayer {
+            name: name.to_owned(),
+            visible: true,
+            opacity: 1.0,
+            properties: TileProperties::new(),
+            tiles,
+        });
+        let chunks = self.new_chunks();
+        self.chunks.push(chunks);
+
+        self.layers.len() - 1
+    }
+
+    pub fn find_layer(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|l| l.name == name)
+    }
+
+    /// Change the name, the visibility, the opacity or the properties of a layer
+    pub fn layer_mut(&mut self, layer: usize) -> &mut TileLayer {
+        for chunk in self.chunks[layer].iter_mut() {
+            chunk.dirty = true;
+        }
+        &mut self.layers[layer]
+    }
+
+    /// The tile id at (x, y) from the top-left corner, 0 for none or outside of the map
+    pub fn tile(&self, layer: usize, x: u32, y: u32) -> u32 {
+        if x >= self.size.0 || y >= self.size.1 {
+            return 0;
+        }
+
+        self.layers[layer].tiles[(y * self.size.0 + x) as usize]
+    }
+
+    /// Set the tile id at (x, y) from the top-left corner, 0 to remove it
+    pub fn set_tile(&mut self, layer: usize, x: u32, y: u32, id: u32) {
+        if x >= self.size.0 || y >= self.size.1 {
+            return;
+        }
+
+        let tile = &mut self.layers[layer].tiles[(y * self.size.0 + x) as usize];
+        if *tile != id {
+            *tile = id;
+            let index = self.chunk_index(x, y);
+            self.chunks[layer][index].dirty = true;
+        }
+    }
+
+    /// The tileset of a tile id
+    pub fn tileset_of(&self, id: u32) -> Option<&Rc<Tileset>> {
+        self.tilesets.iter().rev().find(|t| t.contains(id))
+    }
+
+    /// The properties of the tile at (x, y) in its tileset
+    pub fn tile_properties(&self, layer: usize, x: u32, y: u32) -> Option<&TileProperties> {
+        let id = self.tile(layer, x, y);
+        self.tileset_of(id)
+            .and_then(|t| t.tile_properties.get(&((id & TILE_ID_MASK) - t.first_id)))
+    }
+
+    /// The tile at a point in the space of the map, as (x, y) from the top-left corner
+    pub fn cell_at(&self, p: Vector2f) -> Option<(u32, u32)> {
+        let x = (p.x / self.cell_size.x).floor();
+        let y = self.size.1 as f32 - 1.0 - (p.y / self.cell_size.y).floor();
+
+        if x < 0.0 || y < 0.0 || x >= self.size.0 as f32 || y >= self.size.1 as f32 {
+            return None;
+        }
+
+        Some((x as u32, y as u32))
+    }
+
+    /// The bottom-left corner of a tile in the space of the map
+    pub fn cell_position(&self, x: u32, y: u32) -> Vector2f {
+        Vector2::new(
+            x as f32 * self.cell_size.x,
+            (self.size.1 - 1 - y) as f32 * self.cell_size.y,
+        )
+    }
+
+    fn chunk_columns(&self) -> u32 {
+        (self.size.0 + self.chunk_size - 1) / self.chunk_size
+    }
+
+    fn chunk_index(&self, x: u32, y: u32) -> usize {
+        ((y / self.chunk_size) * self.chunk_columns() + x / self.chunk_size) as usize
+    }
+
+    fn new_chunks(&self) -> Vec<Chunk> {
+        let rows = (self.size.1 + self.chunk_size - 1) / self.chunk_size;
+        (0..self.chunk_columns() * rows)
+            .map(|_| Chunk {
+                dirty: true,
+                bounds: Aabb::empty(),
+                surfaces: Vec::new(),
+            })
+            .collect()
+    }
+
+    fn set_all_dirty(&mut self) {
+        for chunk in self.chunks.iter_mut().flat_map(|c| c.iter_mut()) {
+            chunk.dirty = true;
+        }
+    }
+
+    fn material(&mut self, asys: &AssetSystem, tileset: usize) -> Rc<Material> {
+        while self.materials.len() <= tileset {
+            let mut material = Material::new(asys.new_program("unrust/sprite"));
+            material.set("uTexture", self.tilesets[self.materials.len()].texture.clone());
+            material.render_queue = RenderQueue::Transparent;
+            material.states.cull = Some(CullMode::Off);
+            self.materials.push(Rc::new(material));
+        }
+
+        self.materials[tileset].clone()
+    }
+
+    /// Build the mesh data of the changed chunks
+    pub fn update(&mut self, asys: &AssetSystem) {
+        for layer in 0..self.layers.len() {
+            for index in 0..self.chunks[layer].len() {
+                if self.chunks[layer][index].dirty {
+                    self.build_chunk(asys, layer, index);
+                }
+            }
+        }
+    }
+
+    fn build_chunk(&mut self, asys: &AssetSystem, layer: usize, index: usize) {
+        let columns = self.chunk_columns();
+        let (cx, cy) = (index as u32 % columns, index as u32 / columns);
+        let x_end = ((cx + 1) * self.chunk_size).min(self.size.0);
+        let y_end = ((cy + 1) * self.chunk_size).min(self.size.1);
+        let opacity = self.layers[layer].opacity;
+
+        // The mesh data of each tileset used by the chunk
+        let mut parts: Vec<(usize, MeshData)> = Vec::new();
+        let mut bounds = Aabb::empty();
+
+        if self.layers[layer].visible {
+            for y in cy * self.chunk_size..y_end {
+                for x in cx * self.chunk_size..x_end {
+                    let id = self.tile(layer, x, y);
+                    let tileset = match self.tilesets.iter().rposition(|t| t.contains(id)) {
+                        Some(t) if id & TILE_ID_MASK != 0 => t,
+                        _ => continue,
+                    };
+
+                    let i = match parts.iter().position(|p| p.0 == tileset) {
+                        Some(i) => i,
+                        None => {
+                            parts.push((tileset, Self::empty_data()));
+                            parts.len() - 1
+                        }
+                    };
+                    let data = &mut parts[i].1;
+
+                    let p0 = self.cell_position(x, y);
+                    let p1 = p0 + self.cell_size;
+                    let base = (data.vertices.len() / 3) as u32;
+                    for p in [(p0.x, p0.y), (p1.x, p0.y), (p1.x, p1.y), (p0.x, p1.y)].iter() {
+                        data.vertices.extend_from_slice(&[p.0, p.1, 0.0]);
+                    }
+                    for uv in self.tilesets[tileset].tile_uvs(id).iter() {
+                        data.uvs.as_mut().unwrap().extend_from_slice(&[uv.x, uv.y]);
+                        data.colors
+                            .as_mut()
+                            .unwrap()
+                            .extend_from_slice(&[1.0, 1.0, 1.0, opacity]);
+                    }
+                    for i in [0, 1, 2, 0, 2, 3].iter() {
+                        data.indices.push(base + i);
+                    }
+
+                    bounds.merge_point(&p0.extend(0.0));
+                    bounds.merge_point(&p1.extend(0.0));
+                }
+            }
+        }
+
+        let mut old = ::std::mem::replace(&mut self.chunks[layer][index].surfaces, Vec::new());
+        let mut surfaces = Vec::new();
+        for (tileset, data) in parts.into_iter() {
+            // The buffers of the chunk are reused
+            let buffer = if old.is_empty() {
+                MeshBuffer::new(data)
+            } else {
+                let buffer = old.remove(0).buffer.clone();
+                buffer.update_mesh_data(data);
+                buffer
+            };
+
+            surfaces.push(Rc::new(MeshSurface {
+                buffer,
+                material: self.material(asys, tileset),
+            }));
+        }
+
+        let chunk = &mut self.chunks[layer][index];
+        chunk.surfaces = surfaces;
+        chunk.bounds = bounds;
+        chunk.dirty = false;
+    }
+
+    fn empty_data() -> MeshData {
+        MeshData {
+            uvs: Some(Vec::new()),
+            colors: Some(Vec::new()),
+            ..MeshData::default()
+        }
+    }
+
+    /// The surfaces of the built chunks in drawing order, with their bounds in the space of the map
+    pub fn surfaces(&self) -> Vec<(Aabb, Rc<MeshSurface>)> {
+        let mut result = Vec::new();
+        for chunk in self.chunks.iter().flat_map(|c| c.iter()) {
+            for surface in chunk.surfaces.iter() {
+                result.push((chunk.bounds, surface.clone()));
+            }
+        }
+
+        result
+    }
+}
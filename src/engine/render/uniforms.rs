@@ -155,6 +155,17 @@ impl UniformCache {
         }
     }
 
+    /// Forget the committed state and locations, when the values
+    /// have to be committed to another program
+    pub fn reset(&self) {
+        let mut pending = self.pending_entries.borrow_mut();
+        for (key, adapter) in self.uniform_entries.borrow_mut().drain() {
+            pending.entry(key).or_insert(adapter);
+        }
+
+        self.uniform_map.borrow_mut().clear();
+    }
+
     fn get_uniform(
         &self,
         gl: &WebGLRenderingContext,
@@ -0,0 +1,415 @@
+use engine::asset::{AssetResult, AssetSystem};
+use engine::render::{Blending, Camera, CullMode, DepthTest, Directional, Light, LightShadow, Material,
+                     MaterialParam, MaterialParamMap, MaterialState, Mesh, Point, Projection,
+                     RenderQueue, Spot};
+use rustc_serialize::json::{Json, Object};
+use std::collections::BTreeSet;
+use std::rc::Rc;
+use math::*;
+
+use super::registry::{SceneComponent, SceneLoadContext, SceneSaveContext};
+use super::value::*;
+
+json_enum!(CullMode, cull_to_json, cull_from_json, [Off, Back, Front, FrontAndBack]);
+json_enum!(
+    DepthTest,
+    depth_test_to_json,
+    depth_test_from_json,
+    [Never, Less, Equal, LessEqual, Greater, NotEqual, GreaterEqual, Always]
+);
+json_enum!(Blending, blending_to_json, blending_from_json, [Alpha, Additive]);
+json_enum!(
+    RenderQueue,
+    render_queue_to_json,
+    render_queue_from_json,
+    [Opaque, Skybox, Transparent, UI]
+);
+
+fn opt_to_json<T, F>(v: Option<T>, f: F) -> Json
+where
+    F: FnOnce(T) -> Json,
+{
+    v.map(f).unwrap_or(Json::Null)
+}
+
+fn save_states(states: &MaterialState) -> Json {
+    object(vec![
+        ("cull", opt_to_json(states.cull, cull_to_json)),
+        ("alpha_blending", opt_to_json(states.alpha_blending, Json::Boolean)),
+        ("blending", opt_to_json(states.blending, blending_to_json)),
+        ("depth_write", opt_to_json(states.depth_write, Json::Boolean)),
+        ("depth_test", opt_to_json(states.depth_test, depth_test_to_json)),
+    ])
+}
+
+fn load_states(data: &Json) -> AssetResult<MaterialState> {
+    Ok(MaterialState {
+        cull: load_opt(data, "cull", cull_from_json)?,
+        alpha_blending: load_opt(data, "alpha_blending", as_bool)?,
+        blending: load_opt(data, "blending", blending_from_json)?,
+        depth_write: load_opt(data, "depth_write", as_bool)?,
+        depth_test: load_opt(data, "depth_test", depth_test_from_json)?,
+    })
+}
+
+fn save_shadow(shadow: &LightShadow) -> Json {
+    object(vec![
+        ("casts_shadows", Json::Boolean(shadow.casts_shadows)),
+        ("resolution", Json::U64(shadow.resolution as u64)),
+        ("bias", Json::F64(shadow.bias as f64)),
+    ])
+}
+
+fn load_shadow(data: &Json) -> AssetResult<LightShadow> {
+    Ok(LightShadow {
+        casts_shadows: as_bool(field(data, "casts_shadows")?)?,
+        resolution: as_u64(field(data, "resolution")?)? as u32,
+        bias: as_f32(field(data, "bias")?)?,
+        tile: None,
+    })
+}
+
+fn save_projection(projection: &Projection) -> Json {
+    match *projection {
+        Projection::Perspective { fovy, infinite_far } => object(vec![
+            ("kind", Json::String("Perspective".to_owned())),
+            ("fovy", Json::F64(fovy.0 as f64)),
+            ("infinite_far", Json::Boolean(infinite_far)),
+        ]),
+        Projection::Orthographic { size } => object(vec![
+            ("kind", Json::String("Orthographic".to_owned())),
+            ("size", Json::F64(size as f64)),
+        ]),
+        Projection::Custom(ref m) => object(vec![
+            ("kind", Json::String("Custom".to_owned())),
+            ("matrix", mat4_to_json(m)),
+        ]),
+    }
+}
+
+fn load_projection(data: &Json) -> AssetResult<Projection> {
+    match as_str(field(data, "kind")?)? {
+        "Perspective" => Ok(Projection::Perspective {
+            fovy: Rad(as_f32(field(data, "fovy")?)?),
+            infinite_far: as_bool(field(data, "infinite_far")?)?,
+        }),
+        "Orthographic" => Ok(Projection::Orthographic {
+            size: as_f32(field(data, "size")?)?,
+        }),
+        "Custom" => Ok(Projection::Custom(mat4_from_json(field(data, "matrix")?)?)),
+        kind => invalid_scene(format!("unknown projection kind: {}", kind)),
+    }
+}
+
+fn save_param(param: &MaterialParam, asys: &AssetSystem) -> Option<Json> {
+    let (kind, value) = match param {
+        &MaterialParam::Texture(ref tex) => ("texture", Json::String(asys.texture_name(&tex.0)?)),
+        &MaterialParam::Float(f) => ("float", Json::F64(f as f64)),
+        &MaterialParam::Bool(b) => ("bool", Json::Boolean(b)),
+        &MaterialParam::Vec2(v) => ("vec2", floats_to_json(&[v.x, v.y])),
+        &MaterialParam::Vec3(v) => ("vec3", vec3_to_json(v)),
+        &MaterialParam::Vec4(v) => ("vec4", floats_to_json(&[v.x, v.y, v.z, v.w])),
+        &MaterialParam::Matrix4(ref m) => ("mat4", mat4_to_json(m)),
+        &MaterialParam::Params(ref pm) => ("params", save_params(pm, asys)),
+    };
+
+    Some(object(vec![(kind, value)]))
+}
+
+/// Save all params, textures which are not named assets are skipped.
+fn save_params(params: &MaterialParamMap, asys: &AssetSystem) -> Json {
+    let mut obj = Object::new();
+
+    for (name, param) in params.iter() {
+        if let Some(p) = save_param(param, asys) {
+            obj.insert(name.to_string(), p);
+        }
+    }
+
+    Json::Object(obj)
+}
+
+fn load_param(data: &Json, asys: &AssetSystem) -> AssetResult<MaterialParam> {
+    let (kind, value) = match data.as_object().and_then(|obj| obj.iter().next()) {
+        Some(kv) => kv,
+        None => return invalid_scene(format!("invalid material param: {}", data)),
+    };
+
+    Ok(match kind.as_str() {
+        "texture" => asys.new_texture(as_str(value)?).into(),
+        "float" => as_f32(value)?.into(),
+        "bool" => as_bool(value)?.into(),
+        "vec2" => {
+            let f = floats_from_json(value, 2)?;
+            Vector2::new(f[0], f[1]).into()
+        }
+        "vec3" => vec3_from_json(value)?.into(),
+        "vec4" => {
+            let f = floats_from_json(value, 4)?;
+            Vector4::new(f[0], f[1], f[2], f[3]).into()
+        }
+        "mat4" => mat4_from_json(value)?.into(),
+        "params" => load_params(value, asys)?.into(),
+        _ => return invalid_scene(format!("unknown material param type: {}", kind)),
+    })
+}
+
+fn load_params(data: &Json, asys: &AssetSystem) -> AssetResult<MaterialParamMap> {
+    let obj = match data.as_object() {
+        Some(obj) => obj,
+        None => return invalid_scene(format!("invalid material params: {}", data)),
+    };
+
+    let mut params = MaterialParamMap::default();
+    for (name, p) in obj.iter() {
+        params.insert(name.clone().into(), load_param(p, asys)?);
+    }
+
+    Ok(params)
+}
+
+/// Materials are stored with the name of their program,
+/// None if the program is not a named asset.
+pub fn save_material(material: &Material, asys: &AssetSystem) -> Option<Json> {
+    let program = asys.program_name(&material.program)?;
+
+    Some(object(vec![
+        ("program", Json::String(program)),
+        ("render_queue", render_queue_to_json(material.render_queue)),
+        ("states", save_states(&material.states)),
+        ("params", save_params(&material.params(), asys)),
+    ]))
+}
+
+pub fn load_material(data: &Json, asys: &AssetSystem) -> AssetResult<Rc<Material>> {
+    let mut material = Material::new(asys.new_program(as_str(field(data, "program")?)?));
+
+    if let Some(q) = load_opt(data, "render_queue", render_queue_from_json)? {
+        material.render_queue = q;
+    }
+    if let Some(states) = load_opt(data, "states", load_states)? {
+        material.states = states;
+    }
+    if let Some(params) = load_opt(data, "params", |p| load_params(p, asys))? {
+        for (name, p) in params.into_iter() {
+            material.set(name, p);
+        }
+    }
+
+    Ok(Rc::new(material))
+}
+
+impl SceneComponent for Mesh {
+    fn type_name() -> &'static str {
+        "Mesh"
+    }
+
+    /// Surfaces which mesh buffer or material is not a named asset are skipped.
+    fn save_scene(&self, ctx: &mut SceneSaveContext) -> Json {
+        let surfaces = self.surfaces
+            .iter()
+            .filter_map(|s| {
+                let buffer = ctx.asset_system().mesh_buffer_name(&s.buffer)?;
+                let material = ctx.material(&s.material)?;
+
+                Some(object(vec![
+                    ("buffer", Json::String(buffer)),
+                    ("material", Json::U64(material as u64)),
+                ]))
+            })
+            .collect();
+
+        object(vec![("surfaces", Json::Array(surfaces))])
+    }
+
+    fn load_scene(data: &Json, ctx: &mut SceneLoadContext) -> AssetResult<Mesh> {
+        let mut mesh = Mesh::new();
+
+        for s in as_array(field(data, "surfaces")?)?.iter() {
+            let buffer = ctx.asset_system()
+                .new_mesh_buffer(as_str(field(s, "buffer")?)?);
+            let material = ctx.material(as_u64(field(s, "material")?)? as usize)?;
+
+            mesh.add_surface(buffer, material);
+        }
+
+        Ok(mesh)
+    }
+}
+
+impl SceneComponent for Light {
+    fn type_name() -> &'static str {
+        "Light"
+    }
+
+    fn save_scene(&self, _ctx: &mut SceneSaveContext) -> Json {
+        match self {
+            &Light::Directional(ref l) => object(vec![
+                ("kind", Json::String("Directional".to_owned())),
+                ("direction", vec3_to_json(l.direction)),
+                ("ambient", vec3_to_json(l.ambient)),
+                ("diffuse", vec3_to_json(l.diffuse)),
+                ("specular", vec3_to_json(l.specular)),
+            ]),
+            &Light::Point(ref l) => object(vec![
+                ("kind", Json::String("Point".to_owned())),
+                ("position", vec3_to_json(l.position)),
+                ("ambient", vec3_to_json(l.ambient)),
+                ("diffuse", vec3_to_json(l.diffuse)),
+                ("specular", vec3_to_json(l.specular)),
+                ("constant", Json::F64(l.constant as f64)),
+                ("linear", Json::F64(l.linear as f64)),
+                ("quadratic", Json::F64(l.quadratic as f64)),
+                ("shadow", save_shadow(&l.shadow)),
+            ]),
+            &Light::Spot(ref l) => object(vec![
+                ("kind", Json::String("Spot".to_owned())),
+                ("position", vec3_to_json(l.position)),
+                ("direction", vec3_to_json(l.direction)),
+                ("cut_off", Json::F64(l.cut_off.0 as f64)),
+                ("outer_cut_off", Json::F64(l.outer_cut_off.0 as f64)),
+                ("ambient", vec3_to_json(l.ambient)),
+                ("diffuse", vec3_to_json(l.diffuse)),
+                ("specular", vec3_to_json(l.specular)),
+                ("constant", Json::F64(l.constant as f64)),
+                ("linear", Json::F64(l.linear as f64)),
+                ("quadratic", Json::F64(l.quadratic as f64)),
+                ("shadow", save_shadow(&l.shadow)),
+            ]),
+        }
+    }
+
+    fn load_scene(data: &Json, _ctx: &mut SceneLoadContext) -> AssetResult<Light> {
+        let vec3 = |key| vec3_from_json(field(data, key)?);
+        let float = |key| as_f32(field(data, key)?);
+        // Scenes saved before the shadow settings keep the default ones
+        let shadow = || load_opt(data, "shadow", load_shadow).map(|s| s.unwrap_or_default());
+
+        match as_str(field(data, "kind")?)? {
+            "Directional" => {
+                let direction = vec3("direction")?;
+
+                Ok(Light::new(Directional {
+                    direction,
+                    ambient: vec3("ambient")?,
+                    diffuse: vec3("diffuse")?,
+                    specular: vec3("specular")?,
+                    world_space_direction: direction,
+                }))
+            }
+            "Point" => Ok(Light::new(Point {
+                position: vec3("position")?,
+                ambient: vec3("ambient")?,
+                diffuse: vec3("diffuse")?,
+                specular: vec3("specular")?,
+                constant: float("constant")?,
+                linear: float("linear")?,
+                quadratic: float("quadratic")?,
+                shadow: shadow()?,
+                world_space_position: Vector3f::zero(),
+            })),
+            "Spot" => {
+                let direction = vec3("direction")?;
+
+                Ok(Light::new(Spot {
+                    position: vec3("position")?,
+                    direction,
+                    cut_off: Deg(float("cut_off")?),
+                    outer_cut_off: Deg(float("outer_cut_off")?),
+                    ambient: vec3("ambient")?,
+                    diffuse: vec3("diffuse")?,
+                    specular: vec3("specular")?,
+                    constant: float("constant")?,
+                    linear: float("linear")?,
+                    quadratic: float("quadratic")?,
+                    shadow: shadow()?,
+                    world_space_position: Vector3f::zero(),
+                    world_space_direction: direction,
+                }))
+            }
+            kind => invalid_scene(format!("unknown light kind: {}", kind)),
+        }
+    }
+}
+
+impl SceneComponent for Camera {
+    fn type_name() -> &'static str {
+        "Camera"
+    }
+
+    /// The render texture and the clear option of the camera are not stored.
+    fn save_scene(&self, _ctx: &mut SceneSaveContext) -> Json {
+        let rect = self.rect.map(|((x, y), (w, h))| {
+            Json::Array(vec![
+                Json::I64(x as i64),
+                Json::I64(y as i64),
+                Json::U64(w as u64),
+                Json::U64(h as u64),
+            ])
+        });
+
+        let queues = self.included_render_queues.as_ref().map(|queues| {
+            Json::Array(queues.iter().map(|q| render_queue_to_json(*q)).collect())
+        });
+
+        object(vec![
+            ("eye", vec3_to_json(self.eye())),
+            ("view", mat4_to_json(&self.v)),
+            ("znear", Json::F64(self.znear as f64)),
+            ("zfar", Json::F64(self.zfar as f64)),
+            ("projection", save_projection(&self.projection)),
+            ("reversed_z", Json::Boolean(self.reversed_z)),
+            ("depth", Json::I64(self.depth as i64)),
+            ("culling_mask", Json::U64(self.culling_mask as u64)),
+            ("frustum_culling", Json::Boolean(self.enable_frustum_culling)),
+            ("rect", rect.unwrap_or(Json::Null)),
+            ("render_queues", queues.unwrap_or(Json::Null)),
+        ])
+    }
+
+    fn load_scene(data: &Json, _ctx: &mut SceneLoadContext) -> AssetResult<Camera> {
+        let mut cam = Camera::new();
+
+        let eye = vec3_from_json(field(data, "eye")?)?;
+        let view = mat4_from_json(field(data, "view")?)?;
+        cam.lookat(
+            &Point3::from_vec(eye),
+            &Point3::from_vec(eye - view.row(2).truncate()),
+            &view.row(1).truncate(),
+        );
+        // lookat is not exact, keep the stored matrix
+        cam.v = view;
+
+        cam.znear = as_f32(field(data, "znear")?)?;
+        cam.zfar = as_f32(field(data, "zfar")?)?;
+        // Scenes saved before the projection settings keep the default ones
+        if let Some(projection) = load_opt(data, "projection", load_projection)? {
+            cam.projection = projection;
+        }
+        cam.reversed_z = load_opt(data, "reversed_z", as_bool)?.unwrap_or(false);
+        cam.depth = load_opt(data, "depth", as_i64)?.unwrap_or(0) as i32;
+        cam.culling_mask = load_opt(data, "culling_mask", as_u64)?.map_or(!0, |m| m as u32);
+        cam.enable_frustum_culling = as_bool(field(data, "frustum_culling")?)?;
+
+        cam.rect = load_opt(data, "rect", |r| {
+            let r = as_array(r)?;
+            if r.len() != 4 {
+                return invalid_scene(format!("invalid camera rect: {:?}", r));
+            }
+
+            Ok((
+                (as_i64(&r[0])? as i32, as_i64(&r[1])? as i32),
+                (as_u64(&r[2])? as u32, as_u64(&r[3])? as u32),
+            ))
+        })?;
+
+        cam.included_render_queues = load_opt(data, "render_queues", |queues| {
+            as_array(queues)?
+                .iter()
+                .map(render_queue_from_json)
+                .collect::<AssetResult<BTreeSet<_>>>()
+        })?;
+
+        Ok(cam)
+    }
+}
@@ -0,0 +1,8 @@
+#[macro_use]
+mod value;
+mod registry;
+mod components;
+mod scene_file;
+
+pub use self::registry::{SceneComponent, SceneLoadContext, SceneRegistry, SceneSaveContext};
+pub use self::scene_file::{load_scene, save_scene};
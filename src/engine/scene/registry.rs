@@ -0,0 +1,170 @@
+use engine::asset::{AssetResult, AssetSystem};
+use engine::core::{Component, ComponentBased};
+use engine::render::{Camera, Light, Material, Mesh, Texture};
+use rustc_serialize::json::Json;
+use std::any::TypeId;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use super::components::{load_material, save_material};
+use super::value::*;
+
+/// A component which can be stored in a scene file.
+pub trait SceneComponent: ComponentBased + Sized + 'static {
+    /// Name of the type in the scene file, must be unique in a registry
+    fn type_name() -> &'static str;
+
+    fn save_scene(&self, ctx: &mut SceneSaveContext) -> Json;
+
+    fn load_scene(data: &Json, ctx: &mut SceneLoadContext) -> AssetResult<Self>;
+}
+
+pub struct SceneSaveContext<'a> {
+    asys: &'a AssetSystem,
+    materials: Vec<Rc<Material>>,
+    materials_data: Vec<Json>,
+}
+
+impl<'a> SceneSaveContext<'a> {
+    pub fn new(asys: &'a AssetSystem) -> SceneSaveContext<'a> {
+        SceneSaveContext {
+            asys,
+            materials: Vec::new(),
+            materials_data: Vec::new(),
+        }
+    }
+
+    pub fn asset_system(&self) -> &AssetSystem {
+        self.asys
+    }
+
+    pub fn texture_name(&self, texture: &Rc<Texture>) -> Option<String> {
+        self.asys.texture_name(texture)
+    }
+
+    /// Add the material to the scene, return its index in the scene material list.
+    /// None if the material cannot be stored (e.g. its program is not a named asset)
+    pub fn material(&mut self, material: &Rc<Material>) -> Option<usize> {
+        if let Some(i) = self.materials.iter().position(|m| Rc::ptr_eq(m, material)) {
+            return Some(i);
+        }
+
+        let data = save_material(material, self.asys)?;
+        self.materials.push(material.clone());
+        self.materials_data.push(data);
+
+        Some(self.materials.len() - 1)
+    }
+
+    pub fn into_materials(self) -> Vec<Json> {
+        self.materials_data
+    }
+}
+
+pub struct SceneLoadContext<'a> {
+    asys: &'a AssetSystem,
+    materials: Vec<Rc<Material>>,
+}
+
+impl<'a> SceneLoadContext<'a> {
+    pub fn new(asys: &'a AssetSystem, materials: &[Json]) -> AssetResult<SceneLoadContext<'a>> {
+        let materials = materials
+            .iter()
+            .map(|m| load_material(m, asys))
+            .collect::<AssetResult<Vec<_>>>()?;
+
+        Ok(SceneLoadContext { asys, materials })
+    }
+
+    pub fn asset_system(&self) -> &AssetSystem {
+        self.asys
+    }
+
+    pub fn material(&self, index: usize) -> AssetResult<Rc<Material>> {
+        match self.materials.get(index) {
+            Some(m) => Ok(m.clone()),
+            None => invalid_scene(format!("material {} not found", index)),
+        }
+    }
+}
+
+type SaveHandler = fn(&Component, &mut SceneSaveContext) -> Json;
+type LoadHandler = fn(&Json, &mut SceneLoadContext) -> AssetResult<Arc<Component>>;
+
+fn save_component_type<T: SceneComponent>(c: &Component, ctx: &mut SceneSaveContext) -> Json {
+    c.try_as::<T>().unwrap().borrow().save_scene(ctx)
+}
+
+fn load_component_type<T: SceneComponent>(
+    data: &Json,
+    ctx: &mut SceneLoadContext,
+) -> AssetResult<Arc<Component>> {
+    T::load_scene(data, ctx).map(Component::new)
+}
+
+struct ComponentType {
+    name: &'static str,
+    typeid: TypeId,
+    save: SaveHandler,
+    load: LoadHandler,
+}
+
+/// All component types which would be saved to or loaded from a scene file.
+/// Components of other types are skipped when saving.
+pub struct SceneRegistry {
+    types: Vec<ComponentType>,
+}
+
+impl Default for SceneRegistry {
+    fn default() -> SceneRegistry {
+        SceneRegistry::new()
+    }
+}
+
+impl SceneRegistry {
+    pub fn new() -> SceneRegistry {
+        let mut r = SceneRegistry { types: Vec::new() };
+
+        r.register::<Mesh>();
+        r.register::<Light>();
+        r.register::<Camera>();
+        r
+    }
+
+    pub fn register<T>(&mut self)
+    where
+        T: SceneComponent,
+    {
+        self.types.retain(|t| t.name != T::type_name());
+
+        self.types.push(ComponentType {
+            name: T::type_name(),
+            typeid: TypeId::of::<T>(),
+            save: save_component_type::<T>,
+            load: load_component_type::<T>,
+        });
+    }
+
+    pub fn save_component(&self, c: &Component, ctx: &mut SceneSaveContext) -> Option<Json> {
+        let typeid = c.typeid();
+        let t = self.types.iter().find(|t| t.typeid == typeid)?;
+
+        Some(object(vec![
+            ("type", Json::String(t.name.to_owned())),
+            ("data", (t.save)(c, ctx)),
+        ]))
+    }
+
+    pub fn load_component(
+        &self,
+        data: &Json,
+        ctx: &mut SceneLoadContext,
+    ) -> AssetResult<Arc<Component>> {
+        let name = as_str(field(data, "type")?)?;
+
+        match self.types.iter().find(|t| t.name == name) {
+            Some(t) => (t.load)(field(data, "data")?, ctx),
+            None => invalid_scene(format!("unknown component type \"{}\"", name)),
+        }
+    }
+}
@@ -0,0 +1,182 @@
+// The scene file is a json document:
+//
+// {
+//   "version": 1,
+//   "materials": [ { "program": "phong", "render_queue": "Opaque", "states": {..}, "params": {..} } ],
+//   "objects": [
+//     {
+//       "active": true,
+//       "layers": 1,
+//       "position": [x, y, z],
+//       "rotation": [x, y, z, w],
+//       "scale": [x, y, z],
+//       "components": [ { "type": "Mesh", "data": {..} } ],
+//       "children": [ .. ]
+//     }
+//   ]
+// }
+//
+// Programs, textures and mesh buffers are referenced by their asset names,
+// and materials by their index in the "materials" list.
+
+use engine::asset::{AssetError, AssetResult, AssetSystem};
+use engine::core::GameObject;
+use rustc_serialize::json::Json;
+use std::cell::RefCell;
+use std::rc::Rc;
+use math::*;
+
+use super::registry::{SceneLoadContext, SceneRegistry, SceneSaveContext};
+use super::value::*;
+
+const SCENE_VERSION: u64 = 1;
+
+fn save_object(
+    go: &GameObject,
+    registry: &SceneRegistry,
+    ctx: &mut SceneSaveContext,
+) -> Json {
+    let components: Vec<Json> = go.components()
+        .iter()
+        .filter_map(|c| registry.save_component(c.as_ref(), ctx))
+        .collect();
+
+    let children: Vec<Json> = go.childen()
+        .iter()
+        .map(|child| save_object(&child.borrow(), registry, ctx))
+        .collect();
+
+    let local = go.transform.local();
+
+    object(vec![
+        ("active", Json::Boolean(go.active)),
+        ("layers", Json::U64(go.layers as u64)),
+        ("position", vec3_to_json(local.disp)),
+        ("rotation", quat_to_json(local.rot)),
+        ("scale", vec3_to_json(go.transform.local_scale())),
+        ("components", Json::Array(components)),
+        ("children", Json::Array(children)),
+    ])
+}
+
+/// Save all children of root as a scene file, but the skipped ones.
+pub fn save_scene(
+    root: &GameObject,
+    skipped: &[Rc<RefCell<GameObject>>],
+    registry: &SceneRegistry,
+    asys: &AssetSystem,
+) -> String {
+    let mut ctx = SceneSaveContext::new(asys);
+
+    let objects: Vec<Json> = root.childen()
+        .iter()
+        .filter(|go| !skipped.iter().any(|s| Rc::ptr_eq(s, go)))
+        .map(|go| save_object(&go.borrow(), registry, &mut ctx))
+        .collect();
+
+    let scene = object(vec![
+        ("version", Json::U64(SCENE_VERSION)),
+        ("materials", Json::Array(ctx.into_materials())),
+        ("objects", Json::Array(objects)),
+    ]);
+
+    scene.pretty().to_string()
+}
+
+fn load_object<F>(
+    data: &Json,
+    parent: &GameObject,
+    registry: &SceneRegistry,
+    ctx: &mut SceneLoadContext,
+    new_object: &mut F,
+) -> AssetResult<Rc<RefCell<GameObject>>>
+where
+    F: FnMut(&GameObject) -> Rc<RefCell<GameObject>>,
+{
+    let go = new_object(parent);
+
+    {
+        let mut go = go.borrow_mut();
+
+        if let Some(active) = load_opt(data, "active", as_bool)? {
+            go.active = active;
+        }
+        if let Some(layers) = load_opt(data, "layers", as_u64)? {
+            go.layers = layers as u32;
+        }
+
+        let position = load_opt(data, "position", vec3_from_json)?;
+        let rotation = load_opt(data, "rotation", quat_from_json)?;
+        go.transform.set_local(Isometry3 {
+            scale: 1.0,
+            rot: rotation.unwrap_or(Quaternion::one()),
+            disp: position.unwrap_or(Vector3::zero()),
+        });
+
+        if let Some(scale) = load_opt(data, "scale", vec3_from_json)? {
+            go.transform.set_local_scale(scale);
+        }
+    }
+
+    if let Some(components) = opt_field(data, "components") {
+        for c in as_array(components)?.iter() {
+            let c = registry.load_component(c, ctx)?;
+            go.borrow_mut().add_component(c);
+        }
+    }
+
+    if let Some(children) = opt_field(data, "children") {
+        for child in as_array(children)?.iter() {
+            load_object(child, &go.borrow(), registry, ctx, new_object)?;
+        }
+    }
+
+    Ok(go)
+}
+
+/// Load a scene file under root, return the created top level objects.
+///
+/// `new_object` creates a new game object under the given parent, the caller
+/// is responsible to keep all created objects alive.
+pub fn load_scene<F>(
+    data: &str,
+    root: &GameObject,
+    registry: &SceneRegistry,
+    asys: &AssetSystem,
+    mut new_object: F,
+) -> AssetResult<Vec<Rc<RefCell<GameObject>>>>
+where
+    F: FnMut(&GameObject) -> Rc<RefCell<GameObject>>,
+{
+    let scene = Json::from_str(data).map_err(|e| AssetError::InvalidFormat {
+        path: SCENE_PATH.to_owned(),
+        len: data.len(),
+        reason: format!("{}", e),
+    })?;
+
+    let version = as_u64(field(&scene, "version")?)?;
+    if version > SCENE_VERSION {
+        return invalid_scene(format!("unsupported scene version {}", version));
+    }
+
+    let materials: &[Json] = match opt_field(&scene, "materials") {
+        Some(m) => as_array(m)?,
+        None => &[],
+    };
+    let mut ctx = SceneLoadContext::new(asys, materials)?;
+
+    let mut objects = Vec::new();
+    if let Some(data) = opt_field(&scene, "objects") {
+        for o in as_array(data)?.iter() {
+            objects.push(load_object(
+                o,
+                root,
+                registry,
+                &mut ctx,
+                &mut new_object,
+            )?);
+        }
+    }
+
+    Ok(objects)
+}
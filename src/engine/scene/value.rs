@@ -0,0 +1,152 @@
+// Helpers to build and read the json values of a scene file
+
+use engine::asset::{AssetError, AssetResult};
+use rustc_serialize::json::{Json, Object};
+use math::*;
+
+pub const SCENE_PATH: &'static str = "<scene>";
+
+pub fn invalid_scene<T>(reason: String) -> AssetResult<T> {
+    Err(AssetError::InvalidFormat {
+        path: SCENE_PATH.to_owned(),
+        len: 0,
+        reason,
+    })
+}
+
+pub fn object(fields: Vec<(&str, Json)>) -> Json {
+    let mut obj = Object::new();
+    for (k, v) in fields.into_iter() {
+        obj.insert(k.to_owned(), v);
+    }
+
+    Json::Object(obj)
+}
+
+/// Return a field of an object, treat null as missing
+pub fn opt_field<'a>(data: &'a Json, key: &str) -> Option<&'a Json> {
+    data.find(key).and_then(|v| if v.is_null() { None } else { Some(v) })
+}
+
+pub fn field<'a>(data: &'a Json, key: &str) -> AssetResult<&'a Json> {
+    match opt_field(data, key) {
+        Some(v) => Ok(v),
+        None => invalid_scene(format!("missing field \"{}\"", key)),
+    }
+}
+
+pub fn load_opt<T, F>(data: &Json, key: &str, f: F) -> AssetResult<Option<T>>
+where
+    F: FnOnce(&Json) -> AssetResult<T>,
+{
+    match opt_field(data, key) {
+        Some(v) => f(v).map(Some),
+        None => Ok(None),
+    }
+}
+
+pub fn as_f32(v: &Json) -> AssetResult<f32> {
+    match v.as_f64() {
+        Some(f) => Ok(f as f32),
+        None => invalid_scene(format!("expect a number, found {}", v)),
+    }
+}
+
+pub fn as_u64(v: &Json) -> AssetResult<u64> {
+    match v.as_u64() {
+        Some(n) => Ok(n),
+        None => invalid_scene(format!("expect an unsigned integer, found {}", v)),
+    }
+}
+
+pub fn as_i64(v: &Json) -> AssetResult<i64> {
+    match v.as_i64() {
+        Some(n) => Ok(n),
+        None => invalid_scene(format!("expect an integer, found {}", v)),
+    }
+}
+
+pub fn as_bool(v: &Json) -> AssetResult<bool> {
+    match v.as_boolean() {
+        Some(b) => Ok(b),
+        None => invalid_scene(format!("expect a boolean, found {}", v)),
+    }
+}
+
+pub fn as_str(v: &Json) -> AssetResult<&str> {
+    match v.as_string() {
+        Some(s) => Ok(s),
+        None => invalid_scene(format!("expect a string, found {}", v)),
+    }
+}
+
+pub fn as_array(v: &Json) -> AssetResult<&Vec<Json>> {
+    match v.as_array() {
+        Some(a) => Ok(a),
+        None => invalid_scene(format!("expect an array, found {}", v)),
+    }
+}
+
+pub fn floats_to_json(v: &[f32]) -> Json {
+    Json::Array(v.iter().map(|f| Json::F64(*f as f64)).collect())
+}
+
+pub fn floats_from_json(v: &Json, len: usize) -> AssetResult<Vec<f32>> {
+    let arr = as_array(v)?;
+    if arr.len() != len {
+        return invalid_scene(format!("expect {} numbers, found {}", len, v));
+    }
+
+    arr.iter().map(as_f32).collect()
+}
+
+pub fn vec3_to_json(v: Vector3f) -> Json {
+    floats_to_json(&[v.x, v.y, v.z])
+}
+
+pub fn vec3_from_json(v: &Json) -> AssetResult<Vector3f> {
+    let f = floats_from_json(v, 3)?;
+    Ok(Vector3::new(f[0], f[1], f[2]))
+}
+
+pub fn mat4_to_json(m: &Matrix4f) -> Json {
+    let m: &[f32; 16] = m.as_ref();
+    floats_to_json(m)
+}
+
+pub fn mat4_from_json(v: &Json) -> AssetResult<Matrix4f> {
+    let f = floats_from_json(v, 16)?;
+    Ok(Matrix4::new(
+        f[0], f[1], f[2], f[3], f[4], f[5], f[6], f[7], f[8], f[9], f[10], f[11], f[12], f[13],
+        f[14], f[15],
+    ))
+}
+
+/// Quaternions are stored as [x, y, z, w]
+pub fn quat_to_json(q: Quaternion<f32>) -> Json {
+    floats_to_json(&[q.v.x, q.v.y, q.v.z, q.s])
+}
+
+pub fn quat_from_json(v: &Json) -> AssetResult<Quaternion<f32>> {
+    let f = floats_from_json(v, 4)?;
+    Ok(Quaternion::new(f[3], f[0], f[1], f[2]))
+}
+
+macro_rules! json_enum {
+    ($t:ident, $to:ident, $from:ident, [$($v:ident),*]) => {
+        pub fn $to(v: $t) -> Json {
+            let s = match v {
+                $($t::$v => stringify!($v),)*
+            };
+
+            Json::String(s.to_owned())
+        }
+
+        pub fn $from(data: &Json) -> AssetResult<$t> {
+            match data.as_string() {
+                $(Some(stringify!($v)) => Ok($t::$v),)*
+                _ => invalid_scene(format!("unknown {}: {}", stringify!($t), data)),
+            }
+        }
+    };
+}
@@ -10,7 +10,14 @@ extern crate fnv;
 extern crate futures;
 extern crate hound;
 extern crate image;
+#[cfg(feature = "physics")]
+extern crate nalgebra as na;
+#[cfg(feature = "physics")]
+extern crate ncollide;
+#[cfg(feature = "physics")]
+extern crate nphysics3d;
 extern crate obj;
+extern crate rustc_serialize;
 extern crate uni_app;
 extern crate uni_glsl;
 extern crate uni_pad;
@@ -33,7 +40,7 @@ pub mod math {
     pub use self::cgmath::prelude::*;
     pub use self::cgmath::{ortho, vec3, Decomposed, Deg, Euler, Matrix3, Matrix4, PerspectiveFov,
                            Point3, Quaternion, Rad, Vector2, Vector3, Vector4};
-    pub use engine::Aabb;
+    pub use engine::{Aabb, Bvh, BvhProxy, Ray};
 
     pub type Vector3f = Vector3<f32>;
     pub type Matrix4f = Matrix4<f32>;
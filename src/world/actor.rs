@@ -1,4 +1,6 @@
-use engine::{ComponentBased, GameObject};
+#[cfg(feature = "physics")]
+use actors::CollisionEvent;
+use engine::{Animator, ComponentBased, GameObject, ParticleSystem};
 use world::{Handle, World};
 
 pub trait Actor {
@@ -16,6 +18,22 @@ pub trait Actor {
 
     fn update(&mut self, &mut GameObject, &mut World) {}
 
+    // Called when a one-shot Animation of the GameObject finished
+    fn animation_finished_rc(&mut self, go: Handle<GameObject>, world: &mut World, name: &str) {
+        self.animation_finished(&mut go.borrow_mut(), world, name)
+    }
+
+    fn animation_finished(&mut self, &mut GameObject, &mut World, &str) {}
+
+    // Called when the Collider of the GameObject starts or stops touching another one
+    #[cfg(feature = "physics")]
+    fn collision_rc(&mut self, go: Handle<GameObject>, world: &mut World, event: &CollisionEvent) {
+        self.collision(&mut go.borrow_mut(), world, event)
+    }
+
+    #[cfg(feature = "physics")]
+    fn collision(&mut self, &mut GameObject, &mut World, &CollisionEvent) {}
+
     fn new_actor<T: Actor>(t: T) -> Box<Actor>
     where
         Self: Sized,
@@ -26,3 +44,40 @@ pub trait Actor {
 }
 
 impl ComponentBased for Box<Actor> {}
+
+/// Call f with every actor of the game object,
+/// the actors which are already borrowed (e.g. being updated) are skipped
+pub(crate) fn each_actor<F>(go: &Handle<GameObject>, mut f: F)
+where
+    F: FnMut(&mut Box<Actor>),
+{
+    let actors: Vec<_> = go.borrow()
+        .components()
+        .iter()
+        .filter(|c| c.try_as::<Box<Actor>>().is_some())
+        .cloned()
+        .collect();
+
+    for c in actors.iter() {
+        let actor = c.try_as::<Box<Actor>>().unwrap();
+        if let Ok(mut actor) = actor.try_borrow_mut() {
+            f(&mut actor);
+        }
+    }
+}
+
+// Animators are stepped by the world itself, without borrowing the owner,
+// so tracks can target the game object of the animator too.
+impl Actor for Animator {
+    fn update_rc(&mut self, _go: Handle<GameObject>, world: &mut World) {
+        self.advance(world.delta_time() as f32);
+    }
+}
+
+// Particle systems are simulated in world space, from the transform of their game object
+impl Actor for ParticleSystem {
+    fn update_rc(&mut self, go: Handle<GameObject>, world: &mut World) {
+        let m = go.borrow().transform.as_global_matrix();
+        self.update(world.delta_time() as f32, &m);
+    }
+}
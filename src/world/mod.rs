@@ -6,9 +6,14 @@ mod type_watcher;
 mod processor;
 
 pub use self::actor::Actor;
+pub(crate) use self::actor::each_actor;
 pub use self::world::{Handle, World, WorldBuilder};
 
 pub use self::processor::{Processor, ProcessorContext};
+#[cfg(feature = "physics")]
+pub(crate) use self::processor::IProcessorBuilder;
+#[cfg(feature = "physics")]
+pub(crate) use self::type_watcher::{TypeWatcherBuilder, Watcher};
 
 // Just reexport all engine modules
 pub use engine::*;
@@ -5,8 +5,8 @@ use std::sync;
 use std::sync::Arc;
 use std::marker::PhantomData;
 
-use engine::{Component, ComponentEvent, GameObject, SceneTree};
-use world::{Actor, Handle, World};
+use engine::{Animation, Component, ComponentEvent, GameObject, SceneTree};
+use world::{each_actor, Actor, Handle, World};
 
 type WeakHandle<T> = rc::Weak<RefCell<T>>;
 pub type GameObjectComponentPair = (WeakHandle<GameObject>, sync::Weak<Component>);
@@ -125,6 +125,33 @@ impl Watcher for ActorWatcher<Box<Actor>> {
     }
 }
 
+pub struct AnimationWatcher {}
+
+impl Watcher for AnimationWatcher {
+    fn is(&self, c: &Arc<Component>) -> bool {
+        c.try_as::<Animation>().is_some()
+    }
+
+    fn object_step(&self, go: &Handle<GameObject>, com: &Arc<Component>, world: &mut World) {
+        let dt = world.delta_time() as f32;
+
+        let finished = {
+            let mut animation = com.try_as::<Animation>().unwrap().borrow_mut();
+            if animation.advance(dt, &mut go.borrow_mut()) {
+                Some(animation.name.clone())
+            } else {
+                None
+            }
+        };
+
+        // Notify the actors after releasing the animation,
+        // so they could play it again or change it.
+        if let Some(name) = finished {
+            each_actor(go, |actor| actor.animation_finished_rc(go.clone(), world, &name));
+        }
+    }
+}
+
 pub struct TypeWatcherBuilder {
     object_containers: Vec<(Box<Watcher>, ObjectContainer)>,
 }
@@ -1,17 +1,19 @@
 use std::cell::{Ref, RefCell, RefMut};
 use std::ops::Deref;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 use std::sync::Arc;
 
-use engine::{AssetSystem, Camera, ClearOption, Component, ComponentBased, Engine, GameObject,
-             IEngine, SceneTree};
+use engine::{Animator, AssetResult, AssetSystem, Camera, ClearOption, Component, ComponentBased,
+             Engine, GameObject, IEngine, Material, ObjMaterial, ParticleSystem, Prefab,
+             PrefabNode, RaycastFilter, RaycastHit, SceneComponent, SceneRegistry, SceneTree};
+use math::{Aabb, Ray, Vector3f};
 use world::app_fs::AppEngine;
 
 use engine::imgui;
 use engine::SoundSystem;
 use world::fps::FPS;
 use world::processor::{IProcessorBuilder, Processor};
-use world::type_watcher::{ActorWatcher, TypeWatcher, TypeWatcherBuilder};
+use world::type_watcher::{ActorWatcher, AnimationWatcher, TypeWatcher, TypeWatcherBuilder};
 use world::Actor;
 
 use std::default::Default;
@@ -31,6 +33,10 @@ pub struct World {
     events: Rc<RefCell<Vec<AppEvent>>>,
     golist: Vec<Handle<GameObject>>,
     processor_builders: Vec<Rc<Box<IProcessorBuilder>>>,
+    /// The game object holding all processors, it is not saved in scene files
+    processor_object: Option<Handle<GameObject>>,
+    scene_registry: SceneRegistry,
+    pending_prefabs: Rc<RefCell<Vec<(Weak<RefCell<GameObject>>, Prefab)>>>,
 
     engine: AppEngine,
 
@@ -45,6 +51,7 @@ pub struct WorldBuilder<'a> {
     shown_stats: Option<bool>,
     watcher_builder: TypeWatcherBuilder,
     processor_builders: Vec<Rc<Box<IProcessorBuilder>>>,
+    scene_registry: SceneRegistry,
 }
 
 impl<'a> WorldBuilder<'a> {
@@ -56,6 +63,7 @@ impl<'a> WorldBuilder<'a> {
             headless: false,
             watcher_builder: TypeWatcherBuilder::new(),
             processor_builders: Vec::new(),
+            scene_registry: SceneRegistry::new(),
         }
     }
 
@@ -87,10 +95,17 @@ impl<'a> WorldBuilder<'a> {
         self
     }
 
+    /// Register a component type which would be stored in scene files
+    pub fn with_scene_component<T: SceneComponent>(mut self) -> WorldBuilder<'a> {
+        self.scene_registry.register::<T>();
+        self
+    }
+
     pub fn build(self) -> World {
         let size = self.size.unwrap_or((800, 600));
         let mut config = AppConfig::new(self.title, size);
         config.headless = self.headless;
+        config.gl_context = ::webgl::NEEDS_GL_CONTEXT;
 
         let app = App::new(config);
 
@@ -110,6 +125,9 @@ impl<'a> WorldBuilder<'a> {
 
         let watcher = self.watcher_builder
             .add_watcher(ActorWatcher::<Box<Actor>>::new())
+            .add_watcher(ActorWatcher::<Animator>::new())
+            .add_watcher(ActorWatcher::<ParticleSystem>::new())
+            .add_watcher(AnimationWatcher {})
             .build(main_tree.clone());
 
         let asys = engine.asset_system.clone();
@@ -124,15 +142,13 @@ impl<'a> WorldBuilder<'a> {
             fps: FPS::new(),
             events: events,
             golist: Vec::new(),
-            processor_builders: self.processor_builders.clone(),
+            processor_builders: self.processor_builders,
+            processor_object: None,
+            scene_registry: self.scene_registry,
+            pending_prefabs: Rc::new(RefCell::new(Vec::new())),
         };
 
-        // add all processor into the scenes
-        let go = w.new_game_object();
-        for builder in self.processor_builders.into_iter() {
-            go.borrow_mut().add_component(builder.new_processor());
-        }
-
+        w.add_processors();
         w
     }
 }
@@ -257,6 +273,14 @@ impl World {
             profile::dump(evt);
         }
 
+        let prefabs: Vec<_> = self.pending_prefabs.borrow_mut().drain(..).collect();
+        for (parent, prefab) in prefabs.into_iter() {
+            // Skip prefabs which parent is already removed
+            if let Some(parent) = parent.upgrade() {
+                self.instantiate_prefab(&parent, prefab);
+            }
+        }
+
         let watcher = self.watcher.clone();
         watcher.step(self);
 
@@ -283,7 +307,7 @@ impl World {
             imgui::label(
                 Native(0.0, 0.0) + Pixel(8.0, 8.0),
                 &format!(
-                    "fps: {} dt: {:04.2}[{:04.2}|{:04.2}-{:04.2}]ms\nnobj: {} actors:{} gobjs:{} sf:{} oc:[{}:{}] tc:[{}:{}]\n{}",
+                    "fps: {} dt: {:04.2}[{:04.2}|{:04.2}-{:04.2}]ms\nnobj: {} actors:{} gobjs:{} sf:{} oc:[{}:{}] tc:[{}:{}] bc:{}\n{}",
                     self.fps.fps,
                     self.fps.delta_time() * 1000.0,
                     self.fps.delta_time_stats().dt_avg * 1000.0,
@@ -295,6 +319,7 @@ impl World {
                     self.engine().stats.surfaces_count, 
                     self.engine().stats.opaque_count,self.engine().stats.total_opaque_count,
                     self.engine().stats.transparent_count, self.engine().stats.total_transparent_count,
+                    self.engine().stats.batch_count,
                     loading_stats
                 ),
             );
@@ -312,14 +337,22 @@ impl World {
     pub fn reset(&mut self) {
         self.watcher.clear();
         self.golist.clear();
+        self.pending_prefabs.borrow_mut().clear();
         self.engine.asset_system_mut().reset();
         self.main_tree.root_mut().clear_components();
 
         // add all processor back
+        self.add_processors();
+    }
+
+    /// Add all processors into the scene, on their own game object
+    fn add_processors(&mut self) {
         let go = self.new_game_object();
         for builder in self.processor_builders.iter() {
             go.borrow_mut().add_component(builder.new_processor());
         }
+
+        self.processor_object = Some(go);
     }
 
     #[cfg_attr(feature = "flame_it", flame)]
@@ -377,6 +410,133 @@ impl World {
         self.golist.retain(|ref x| !Rc::ptr_eq(&x, go));
     }
 
+    /// Find the nearest mesh surface hit by the ray, see `RaycastFilter` for options
+    pub fn raycast(&self, ray: &Ray, filter: &RaycastFilter) -> Option<RaycastHit> {
+        ::engine::raycast(&self.root(), ray, filter)
+    }
+
+    /// Game objects with a Mesh which world bounds overlap the aabb
+    pub fn query_aabb(&self, aabb: &Aabb) -> Vec<Handle<GameObject>> {
+        self.engine.query_aabb(aabb)
+    }
+
+    /// Game objects with a Mesh which world bounds overlap the sphere
+    pub fn query_sphere(&self, center: Vector3f, r: f32) -> Vec<Handle<GameObject>> {
+        self.engine.query_sphere(center, r)
+    }
+
+    /// The k game objects with a Mesh nearest to p, with their distance
+    pub fn nearest(&self, p: Vector3f, k: usize) -> Vec<(Handle<GameObject>, f32)> {
+        self.engine.nearest(p, k)
+    }
+
+    /// Save all game objects of the world as a json scene file
+    pub fn save_scene(&self) -> String {
+        let skipped: Vec<_> = self.processor_object.iter().cloned().collect();
+        ::engine::save_scene(
+            &self.root(),
+            &skipped,
+            &self.scene_registry,
+            self.asset_system(),
+        )
+    }
+
+    /// Load a json scene file into the world, return the created top level objects
+    pub fn load_scene(&mut self, data: &str) -> AssetResult<Vec<Handle<GameObject>>> {
+        let asys = (*self.engine.asset_system).clone();
+        let nobjects = self.golist.len();
+
+        let result = {
+            let engine = &mut self.engine;
+            let golist = &mut self.golist;
+
+            ::engine::load_scene(
+                data,
+                &self.main_tree.root(),
+                &self.scene_registry,
+                &asys,
+                |parent| {
+                    let go = engine.new_game_object(parent);
+                    golist.push(go.clone());
+                    go
+                },
+            )
+        };
+
+        // Remove all partially loaded objects
+        if result.is_err() {
+            self.golist.truncate(nobjects);
+        }
+
+        result
+    }
+
+    /// Load a prefab file (e.g. obj or glTF) in background, its objects are created under
+    /// parent when it is ready. Loading errors are printed.
+    pub fn load_prefab(
+        &self,
+        name: &str,
+        mh: Box<Fn(&AssetSystem, ObjMaterial) -> Rc<Material>>,
+        parent: &Handle<GameObject>,
+    ) {
+        let pending = self.pending_prefabs.clone();
+        let parent = Rc::downgrade(parent);
+        let filename = name.to_owned();
+
+        self.asset_system().new_prefab_nodes(
+            name,
+            mh,
+            Box::new(move |r: AssetResult<Prefab>| match r {
+                Ok(prefab) => pending.borrow_mut().push((parent, prefab)),
+                Err(e) => App::print(format!("Fail to load prefab {}: {:?}\n", filename, e)),
+            }),
+        );
+    }
+
+    /// Add the components of the prefab to parent and create a child object for each node,
+    /// return the created objects.
+    pub fn instantiate_prefab(
+        &mut self,
+        parent: &Handle<GameObject>,
+        prefab: Prefab,
+    ) -> Vec<Handle<GameObject>> {
+        for c in prefab.components.into_iter() {
+            parent.borrow_mut().add_component(c);
+        }
+
+        let mut objects = Vec::new();
+        for node in prefab.children.into_iter() {
+            self.instantiate_node(parent, node, &mut objects);
+        }
+
+        objects
+    }
+
+    fn instantiate_node(
+        &mut self,
+        parent: &Handle<GameObject>,
+        node: PrefabNode,
+        objects: &mut Vec<Handle<GameObject>>,
+    ) {
+        let go = self.engine.new_game_object(&parent.borrow());
+        self.golist.push(go.clone());
+        objects.push(go.clone());
+
+        {
+            let mut go = go.borrow_mut();
+            go.transform.set_local(node.transform);
+            go.transform.set_local_scale(node.scale);
+
+            for c in node.components.into_iter() {
+                go.add_component(c);
+            }
+        }
+
+        for child in node.children.into_iter() {
+            self.instantiate_node(&go, child, objects);
+        }
+    }
+
     pub fn find_component<T>(&mut self) -> Option<ComponentBorrow<T>>
     where
         T: 'static + ComponentBased,
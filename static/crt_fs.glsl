@@ -5,9 +5,8 @@
 out vec4 FragColor;
 #endif
 
-varying vec3 vColor;
-varying vec2 vTextureCoord;
-uniform sampler2D uDiffuse;
+varying vec2 vTexCoords;
+uniform sampler2D uSource;
 
 const float crtBend			= 4.8;
 const float crtOverscan		= 0.1;
@@ -29,11 +28,11 @@ vec2 crt(vec2 coord)
 }
 
 void main(void) {
-    vec2 crtCoords = crt(vTextureCoord.st);
+    vec2 crtCoords = crt(vTexCoords.st);
     if (crtCoords.x < 0.0 || crtCoords.x > 1.0 || crtCoords.y < 0.0 || crtCoords.y > 1.0) {
     	gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
     } else {
-        float coef=0.8 + abs(sin(600.0*vTextureCoord.t)) * 0.2;
-        gl_FragColor = texture2D(uDiffuse, crtCoords) * vec4(0.8,1.0*coef,0.7,1.0)  ;
+        float coef=0.8 + abs(sin(600.0*vTexCoords.t)) * 0.2;
+        gl_FragColor = texture2D(uSource, crtCoords) * vec4(0.8,1.0*coef,0.7,1.0)  ;
     }
 }
@@ -0,0 +1,139 @@
+#define USE_GLSL_300ES
+
+#define varying in
+#define gl_FragColor FragColor
+#define texture2D texture
+out vec4 FragColor;
+
+// Light counts, set by the engine for each batch of lights
+#ifndef UNI_DIRECTIONAL_LIGHTS
+#define UNI_DIRECTIONAL_LIGHTS 1
+#endif
+
+#ifndef UNI_POINT_LIGHTS
+#define UNI_POINT_LIGHTS 4
+#endif
+
+#ifndef UNI_SPOT_LIGHTS
+#define UNI_SPOT_LIGHTS 0
+#endif
+
+#include "unrust/phong_light.glsl"
+
+// The G-buffer
+uniform sampler2D uGAlbedo;
+uniform sampler2D uGNormal;
+uniform sampler2D uGMaterial;
+uniform sampler2D uGDepth;
+
+// From clip space back to the world
+uniform mat4 uInvPVMatrix;
+uniform vec3 uViewPos;
+// The far plane is at depth 0 instead of 1
+uniform bool uReversedZ;
+
+varying vec2 vTexCoords;
+
+// Lights
+#if UNI_DIRECTIONAL_LIGHTS > 0
+uniform DirectionalLight uDirectionalLights[UNI_DIRECTIONAL_LIGHTS];
+#endif
+#if UNI_POINT_LIGHTS > 0
+uniform PointLight uPointLights[UNI_POINT_LIGHTS];
+#endif
+#if UNI_SPOT_LIGHTS > 0
+uniform SpotLight uSpotLights[UNI_SPOT_LIGHTS];
+#endif
+
+// The surface of the fragment
+vec3 albedo;
+float shininess;
+
+vec3 CalcDirectionalLight(DirectionalLight light, vec3 normal, vec3 viewDir)
+{
+    vec3 lightDir = normalize(-light.direction);
+    float diff = max(dot(normal, lightDir), 0.0);
+    vec3 reflectDir = reflect(-lightDir, normal);
+    float spec = pow(max(dot(viewDir, reflectDir), 0.0), shininess);
+
+    return light.ambient * albedo + light.diffuse * diff * albedo + light.specular * spec;
+}
+
+float Attenuation(float constant, float linear, float quadratic, float distance)
+{
+    float d = constant + linear * distance + quadratic * (distance * distance);
+    return 1.0 / max(d, 0.001);
+}
+
+vec3 CalcPointLight(PointLight light, vec3 normal, vec3 fragPos, vec3 viewDir)
+{
+    vec3 lightDir = normalize(light.position - fragPos);
+    float diff = max(dot(normal, lightDir), 0.0);
+    vec3 reflectDir = reflect(-lightDir, normal);
+    float spec = pow(max(dot(viewDir, reflectDir), 0.0), shininess);
+
+    float distance = length(light.position - fragPos);
+    float attenuation = Attenuation(light.constant, light.linear, light.quadratic, distance);
+
+    vec3 color = light.ambient * albedo + light.diffuse * diff * albedo + light.specular * spec;
+    return color * attenuation * light.rate;
+}
+
+vec3 CalcSpotLight(SpotLight light, vec3 normal, vec3 fragPos, vec3 viewDir)
+{
+    vec3 lightDir = normalize(light.position - fragPos);
+    float diff = max(dot(normal, lightDir), 0.0);
+    vec3 reflectDir = reflect(-lightDir, normal);
+    float spec = pow(max(dot(viewDir, reflectDir), 0.0), shininess);
+
+    float distance = length(light.position - fragPos);
+    float attenuation = Attenuation(light.constant, light.linear, light.quadratic, distance);
+
+    // soft edge between the inner and outer cones
+    float theta = dot(lightDir, normalize(-light.direction));
+    float epsilon = max(light.cutOff - light.outerCutOff, 0.001);
+    float intensity = clamp((theta - light.outerCutOff) / epsilon, 0.0, 1.0);
+
+    vec3 ambient = light.ambient * albedo;
+    vec3 lit = light.diffuse * diff * albedo + light.specular * spec;
+    return (ambient + lit * intensity) * attenuation * light.rate;
+}
+
+void main(void) {
+    float depth = texture2D(uGDepth, vTexCoords).r;
+
+    // Nothing was drawn into the G-buffer there
+    if (uReversedZ ? depth <= 0.0 : depth >= 1.0) {
+        discard;
+    }
+
+    vec4 world = uInvPVMatrix * vec4(vec3(vTexCoords, depth) * 2.0 - 1.0, 1.0);
+    vec3 fragPos = world.xyz / world.w;
+
+    albedo = texture2D(uGAlbedo, vTexCoords).rgb;
+    shininess = texture2D(uGMaterial, vTexCoords).r * 256.0;
+
+    vec3 norm = normalize(texture2D(uGNormal, vTexCoords).xyz * 2.0 - 1.0);
+    vec3 viewDir = normalize(uViewPos - fragPos);
+    vec3 result = vec3(0.0);
+
+#if UNI_DIRECTIONAL_LIGHTS > 0
+    for(int i = 0; i < UNI_DIRECTIONAL_LIGHTS; i++)
+        result += CalcDirectionalLight(uDirectionalLights[i], norm, viewDir);
+#endif
+
+#if UNI_POINT_LIGHTS > 0
+    for(int i = 0; i < UNI_POINT_LIGHTS; i++)
+        result += CalcPointLight(uPointLights[i], norm, fragPos, viewDir);
+#endif
+
+#if UNI_SPOT_LIGHTS > 0
+    for(int i = 0; i < UNI_SPOT_LIGHTS; i++)
+        result += CalcSpotLight(uSpotLights[i], norm, fragPos, viewDir);
+#endif
+
+    gl_FragColor = vec4(result, 1.0);
+
+    // So that the forward passes are hidden by the lit surfaces
+    gl_FragDepth = depth;
+}
@@ -0,0 +1,14 @@
+#ifndef GL_ES
+#define attribute in
+#define varying out
+#endif
+
+attribute vec3 aVertexPosition;
+attribute vec2 aTextureCoord;
+varying vec2 vTexCoords;
+
+// The "screen_quad" mesh covers the whole viewport as is
+void main(void) {
+    gl_Position = vec4(aVertexPosition.xy, 0.0, 1.0);
+    vTexCoords = aTextureCoord;
+}
@@ -0,0 +1,27 @@
+#define USE_GLSL_300ES
+
+#define varying in
+#define texture2D texture
+
+// Albedo, normal and material, one per color attachment of the G-buffer.
+// A single output array starts at the first attachment.
+out vec4 GBuffer[3];
+
+struct Material {
+    sampler2D diffuse;
+    float shininess;
+};
+
+uniform Material uMaterial;
+
+varying vec3 vNormal;
+varying vec2 vTexCoords;
+
+void main(void) {
+    vec3 norm = normalize(vNormal);
+
+    GBuffer[0] = vec4(texture2D(uMaterial.diffuse, vTexCoords).rgb, 1.0);
+    GBuffer[1] = vec4(norm * 0.5 + 0.5, 1.0);
+    // The attachments have 8 bits per channel, shininess is kept up to 256
+    GBuffer[2] = vec4(uMaterial.shininess / 256.0, 0.0, 0.0, 1.0);
+}
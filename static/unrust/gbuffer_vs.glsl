@@ -0,0 +1,25 @@
+#define USE_GLSL_300ES
+
+#define attribute in
+#define varying out
+
+#include "unrust/default_uniforms.glsl"
+#include "unrust/skinning.glsl"
+#include "unrust/instancing.glsl"
+
+attribute vec3 aVertexPosition;
+attribute vec3 aVertexNormal;
+attribute vec2 aTextureCoord;
+
+varying vec3 vNormal;
+varying vec2 vTexCoords;
+
+void main(void) {
+    mat4 skin = skinMatrix();
+    vec4 pos = skin * vec4(aVertexPosition, 1.0);
+
+    vNormal = normalMatrix() * mat3(skin) * aVertexNormal;
+    vTexCoords = aTextureCoord;
+
+    gl_Position = clipPosition(pos);
+}
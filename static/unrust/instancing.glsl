@@ -0,0 +1,29 @@
+attribute mat4 aInstanceMatrix;
+
+uniform bool uInstanced;
+uniform mat4 uPVMatrix;
+
+mat4 modelMatrix() {
+    if (uInstanced) {
+        return aInstanceMatrix;
+    }
+
+    return uMMatrix;
+}
+
+// Transforms only have a uniform scale, so the model matrix keeps the normals direction
+mat3 normalMatrix() {
+    if (uInstanced) {
+        return mat3(aInstanceMatrix);
+    }
+
+    return mat3(uNMatrix);
+}
+
+vec4 clipPosition(vec4 pos) {
+    if (uInstanced) {
+        return uPVMatrix * aInstanceMatrix * pos;
+    }
+
+    return uPMatrix * uMVMatrix * pos;
+}
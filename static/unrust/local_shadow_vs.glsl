@@ -0,0 +1,16 @@
+#ifndef GL_ES
+#define attribute in
+#define varying out
+#endif
+
+#include "unrust/default_uniforms.glsl"
+#include "unrust/skinning.glsl"
+#include "unrust/instancing.glsl"
+
+attribute vec3 aVertexPosition;
+uniform mat4 uShadowMatrix;
+
+// The perspective projection of a point or spot light
+void main(void) {
+    gl_Position = uShadowMatrix * modelMatrix() * skinMatrix() * vec4(aVertexPosition, 1.0);
+}
@@ -0,0 +1,118 @@
+// Shadows of the point and spot lights, drawn by the ShadowPass into the tiles
+// of uLocalShadowMap. Needs the light counts to be defined.
+
+struct LocalShadow {
+    bool enabled;
+    // Offset and size in uv of the map, of the first cube face for a point light
+    vec4 viewport;
+    // From world to the clip space of a spot light
+    mat4 light_matrix;
+    vec2 depth_range;
+    float bias;
+};
+
+uniform sampler2D uLocalShadowMap;
+uniform float uLocalShadowTexelSize;
+
+#if UNI_POINT_LIGHTS > 0
+uniform LocalShadow uPointShadows[UNI_POINT_LIGHTS];
+#endif
+#if UNI_SPOT_LIGHTS > 0
+uniform LocalShadow uSpotShadows[UNI_SPOT_LIGHTS];
+#endif
+
+// 3x3 PCF of the map depth against depth, in a tile
+float LocalShadowPCF(vec2 tileOffset, vec2 tileScale, vec2 uv, float depth)
+{
+    vec2 minUV = tileOffset + vec2(uLocalShadowTexelSize);
+    vec2 maxUV = tileOffset + tileScale - vec2(uLocalShadowTexelSize);
+    float shadow = 0.0;
+
+    for(int x = -1; x <= 1; ++x)
+    {
+        for(int y = -1; y <= 1; ++y)
+        {
+            vec2 offset = vec2(x, y) * uLocalShadowTexelSize;
+            vec2 p = clamp(tileOffset + uv * tileScale + offset, minUV, maxUV);
+            shadow += float(depth > texture2D(uLocalShadowMap, p).r);
+        }
+    }
+
+    return 1.0 - shadow / 9.0;
+}
+
+// The cube faces, as drawn by the ShadowPass: direction and up vector
+void CubeFace(vec3 d, out int face, out vec3 forward, out vec3 up)
+{
+    vec3 a = abs(d);
+
+    if (a.x >= a.y && a.x >= a.z) {
+        face = d.x > 0.0 ? 0 : 1;
+        forward = vec3(sign(d.x), 0.0, 0.0);
+        up = vec3(0.0, -1.0, 0.0);
+    } else if (a.y >= a.z) {
+        face = d.y > 0.0 ? 2 : 3;
+        forward = vec3(0.0, sign(d.y), 0.0);
+        up = vec3(0.0, 0.0, sign(d.y));
+    } else {
+        face = d.z > 0.0 ? 4 : 5;
+        forward = vec3(0.0, 0.0, sign(d.z));
+        up = vec3(0.0, -1.0, 0.0);
+    }
+}
+
+float PointShadowCalculation(LocalShadow shadow, vec3 lightPos, vec3 fragPos)
+{
+    if (!shadow.enabled) {
+        return 1.0;
+    }
+
+    vec3 d = fragPos - lightPos;
+    d -= normalize(d) * shadow.bias;
+
+    int face;
+    vec3 forward;
+    vec3 up;
+    CubeFace(d, face, forward, up);
+
+    // The view and 90 degrees projection of the face
+    vec3 s = normalize(cross(forward, up));
+    vec3 u = cross(s, forward);
+    float w = dot(forward, d);
+    vec2 uv = vec2(dot(s, d), dot(u, d)) / w * 0.5 + 0.5;
+
+    float n = shadow.depth_range.x;
+    float f = shadow.depth_range.y;
+    float depth = ((f + n) / (f - n) - 2.0 * f * n / ((f - n) * w)) * 0.5 + 0.5;
+
+    // Beyond the far plane nothing was drawn
+    if (depth > 1.0) {
+        return 1.0;
+    }
+
+    // 3 columns and 2 rows of faces
+    vec2 tileOffset = shadow.viewport.xy +
+        vec2(float(face - 3 * (face / 3)), float(face / 3)) * shadow.viewport.zw;
+
+    return LocalShadowPCF(tileOffset, shadow.viewport.zw, uv, depth);
+}
+
+float SpotShadowCalculation(LocalShadow shadow, vec3 lightPos, vec3 fragPos)
+{
+    if (!shadow.enabled) {
+        return 1.0;
+    }
+
+    vec3 d = fragPos - lightPos;
+    vec4 pos = shadow.light_matrix * vec4(fragPos - normalize(d) * shadow.bias, 1.0);
+    if (pos.w <= 0.0) {
+        return 1.0;
+    }
+
+    vec3 projCoords = pos.xyz / pos.w * 0.5 + 0.5;
+    if (any(lessThan(projCoords, vec3(0.0))) || any(greaterThan(projCoords, vec3(1.0)))) {
+        return 1.0;
+    }
+
+    return LocalShadowPCF(shadow.viewport.xy, shadow.viewport.zw, projCoords.xy, projCoords.z);
+}
@@ -0,0 +1,240 @@
+#define USE_GLSL_300ES
+
+#define gl_FragColor FragColor
+#define texture2D texture
+out vec4 FragColor;
+
+// Light counts, set by the engine for each variant of the program
+#ifndef UNI_DIRECTIONAL_LIGHTS
+#define UNI_DIRECTIONAL_LIGHTS 1
+#endif
+
+#ifndef UNI_POINT_LIGHTS
+#define UNI_POINT_LIGHTS 4
+#endif
+
+#ifndef UNI_SPOT_LIGHTS
+#define UNI_SPOT_LIGHTS 0
+#endif
+
+#include "unrust/phong_light.glsl"
+#include "unrust/shadow_utils.glsl"
+
+// Metallic-roughness material, as in glTF 2.0
+struct Material {
+    vec4 baseColor;
+    float metallic;
+    float roughness;
+    vec3 emissive;
+    float normalScale;
+    float occlusionStrength;
+    // Fragments with a lower alpha are discarded
+    float alphaCutoff;
+
+    sampler2D baseColorMap;
+    // Roughness in the green channel, metallic in the blue one
+    sampler2D metallicRoughnessMap;
+    sampler2D normalMap;
+    sampler2D occlusionMap;
+    sampler2D emissiveMap;
+    bool hasNormalMap;
+};
+
+uniform vec3 uViewPos;
+uniform Material uMaterial;
+
+// Image based lighting, from the mipmaps of an environment cube map
+uniform samplerCube uEnvironmentMap;
+uniform float uEnvironmentIntensity;
+
+in vec3 vFragPos;
+in vec3 vNormal;
+in vec3 vTangent;
+in vec3 vBitangent;
+in vec2 vTexCoords;
+
+// Lights
+uniform DirectionalLight uDirectionalLights[UNI_DIRECTIONAL_LIGHTS];
+#if UNI_POINT_LIGHTS > 0
+uniform PointLight uPointLights[UNI_POINT_LIGHTS];
+#endif
+#if UNI_SPOT_LIGHTS > 0
+uniform SpotLight uSpotLights[UNI_SPOT_LIGHTS];
+#endif
+
+const float PI = 3.14159265359;
+
+struct Surface {
+    vec3 albedo;
+    float metallic;
+    float roughness;
+    vec3 F0;
+    vec3 N;
+    vec3 V;
+};
+
+vec3 SurfaceNormal();
+vec3 BRDF(Surface s, vec3 L, vec3 radiance);
+vec3 ImageBasedLighting(Surface s);
+float Attenuation(float constant, float linear, float quadratic, vec3 position);
+
+void main(void) {
+    vec4 baseColor = uMaterial.baseColor * texture2D(uMaterial.baseColorMap, vTexCoords);
+    if (baseColor.a < uMaterial.alphaCutoff) {
+        discard;
+    }
+
+    vec4 mr = texture2D(uMaterial.metallicRoughnessMap, vTexCoords);
+
+    Surface s;
+    s.albedo = baseColor.rgb;
+    s.metallic = clamp(uMaterial.metallic * mr.b, 0.0, 1.0);
+    s.roughness = clamp(uMaterial.roughness * mr.g, 0.04, 1.0);
+    // Dielectrics reflect 4% of the light, metals reflect with their base color
+    s.F0 = mix(vec3(0.04), s.albedo, s.metallic);
+    s.N = SurfaceNormal();
+    s.V = normalize(uViewPos - vFragPos);
+
+    vec3 result = vec3(0.0);
+
+    // Directional Lights, only the main one casts shadows
+    for(int i = 0; i < UNI_DIRECTIONAL_LIGHTS; i++) {
+        vec3 L = normalize(-uDirectionalLights[i].direction);
+        float shadow = i == 0 ? ShadowCalculation(vFragPos, s.N, s.N, L) : 1.0;
+        result += BRDF(s, L, uDirectionalLights[i].diffuse) * shadow;
+    }
+
+    // Point Lights
+#if UNI_POINT_LIGHTS > 0
+    for(int i = 0; i < UNI_POINT_LIGHTS; i++) {
+        PointLight light = uPointLights[i];
+        vec3 L = normalize(light.position - vFragPos);
+        float attenuation = Attenuation(light.constant, light.linear, light.quadratic, light.position);
+        result += BRDF(s, L, light.diffuse * attenuation * light.rate);
+    }
+#endif
+
+    // Spot Lights
+#if UNI_SPOT_LIGHTS > 0
+    for(int i = 0; i < UNI_SPOT_LIGHTS; i++) {
+        SpotLight light = uSpotLights[i];
+        vec3 L = normalize(light.position - vFragPos);
+        float attenuation = Attenuation(light.constant, light.linear, light.quadratic, light.position);
+
+        // soft edge between the inner and outer cones
+        float theta = dot(L, normalize(-light.direction));
+        float epsilon = max(light.cutOff - light.outerCutOff, 0.001);
+        float intensity = clamp((theta - light.outerCutOff) / epsilon, 0.0, 1.0);
+
+        result += BRDF(s, L, light.diffuse * attenuation * intensity * light.rate);
+    }
+#endif
+
+    // Ambient light, reduced in the occluded parts
+    float ao = texture2D(uMaterial.occlusionMap, vTexCoords).r;
+    ao = 1.0 + uMaterial.occlusionStrength * (ao - 1.0);
+    result += ImageBasedLighting(s) * ao;
+
+    result += uMaterial.emissive * texture2D(uMaterial.emissiveMap, vTexCoords).rgb;
+
+    gl_FragColor = vec4(result, baseColor.a);
+}
+
+vec3 SurfaceNormal()
+{
+    vec3 N = normalize(vNormal);
+    if (!uMaterial.hasNormalMap || dot(vTangent, vTangent) == 0.0) {
+        return N;
+    }
+
+    vec3 n = texture2D(uMaterial.normalMap, vTexCoords).rgb * 2.0 - 1.0;
+    n.xy *= uMaterial.normalScale;
+
+    mat3 TBN = mat3(normalize(vTangent), normalize(vBitangent), N);
+    return normalize(TBN * n);
+}
+
+float Attenuation(float constant, float linear, float quadratic, vec3 position)
+{
+    float distance = length(position - vFragPos);
+    float d = (constant + linear * distance + quadratic * (distance * distance));
+    return 1.0 / max(d, 0.001);
+}
+
+// Trowbridge-Reitz GGX normal distribution
+float DistributionGGX(float NdotH, float roughness)
+{
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = NdotH * NdotH * (a2 - 1.0) + 1.0;
+
+    return a2 / max(PI * d * d, 0.0001);
+}
+
+// Smith's method with Schlick-GGX
+float GeometrySmith(float NdotV, float NdotL, float roughness)
+{
+    float r = roughness + 1.0;
+    float k = (r * r) / 8.0;
+
+    float ggxV = NdotV / (NdotV * (1.0 - k) + k);
+    float ggxL = NdotL / (NdotL * (1.0 - k) + k);
+
+    return ggxV * ggxL;
+}
+
+vec3 FresnelSchlick(float cosTheta, vec3 F0)
+{
+    return F0 + (1.0 - F0) * pow(1.0 - cosTheta, 5.0);
+}
+
+// Cook-Torrance specular with a lambertian diffuse
+vec3 BRDF(Surface s, vec3 L, vec3 radiance)
+{
+    vec3 H = normalize(s.V + L);
+    float NdotL = max(dot(s.N, L), 0.0);
+    float NdotV = max(dot(s.N, s.V), 0.0001);
+    float NdotH = max(dot(s.N, H), 0.0);
+
+    vec3 F = FresnelSchlick(max(dot(H, s.V), 0.0), s.F0);
+    float D = DistributionGGX(NdotH, s.roughness);
+    float G = GeometrySmith(NdotV, NdotL, s.roughness);
+
+    vec3 specular = D * G * F / max(4.0 * NdotV * NdotL, 0.0001);
+    vec3 kD = (vec3(1.0) - F) * (1.0 - s.metallic);
+
+    return (kD * s.albedo / PI + specular) * radiance * NdotL;
+}
+
+// Analytical fit of the split sum BRDF lookup, by Karis
+vec3 EnvBRDFApprox(vec3 F0, float roughness, float NdotV)
+{
+    const vec4 c0 = vec4(-1.0, -0.0275, -0.572, 0.022);
+    const vec4 c1 = vec4(1.0, 0.0425, 1.04, -0.04);
+
+    vec4 r = roughness * c0 + c1;
+    float a004 = min(r.x * r.x, exp2(-9.28 * NdotV)) * r.x + r.y;
+    vec2 AB = vec2(-1.04, 1.04) * a004 + r.zw;
+
+    return F0 * AB.x + AB.y;
+}
+
+vec3 ImageBasedLighting(Surface s)
+{
+    float NdotV = max(dot(s.N, s.V), 0.0001);
+    vec3 R = reflect(-s.V, s.N);
+
+    // The mipmaps stand for the convolutions of the environment:
+    // the smallest one for the irradiance, the others by roughness
+    float levels = log2(float(textureSize(uEnvironmentMap, 0).x));
+    vec3 irradiance = textureLod(uEnvironmentMap, s.N, levels).rgb;
+    vec3 prefiltered = textureLod(uEnvironmentMap, R, s.roughness * levels).rgb;
+
+    vec3 F = s.F0 + (max(vec3(1.0 - s.roughness), s.F0) - s.F0) * pow(1.0 - NdotV, 5.0);
+    vec3 kD = (vec3(1.0) - F) * (1.0 - s.metallic);
+
+    vec3 diffuse = kD * irradiance * s.albedo;
+    vec3 specular = prefiltered * EnvBRDFApprox(s.F0, s.roughness, NdotV);
+
+    return (diffuse + specular) * uEnvironmentIntensity;
+}
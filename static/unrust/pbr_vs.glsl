@@ -0,0 +1,35 @@
+#define USE_GLSL_300ES
+
+#define attribute in
+#define varying out
+
+#include "unrust/default_uniforms.glsl"
+#include "unrust/skinning.glsl"
+#include "unrust/instancing.glsl"
+
+attribute vec3 aVertexPosition;
+attribute vec3 aVertexNormal;
+attribute vec3 aVertexTangent;
+attribute vec3 aVertexBitangent;
+attribute vec2 aTextureCoord;
+
+varying vec3 vFragPos;
+varying vec3 vNormal;
+varying vec3 vTangent;
+varying vec3 vBitangent;
+varying vec2 vTexCoords;
+
+void main(void) {
+    mat4 skin = skinMatrix();
+    vec4 pos = skin * vec4(aVertexPosition, 1.0);
+    mat3 model = mat3(modelMatrix()) * mat3(skin);
+
+    vFragPos = vec3(modelMatrix() * pos);
+
+    vNormal = normalMatrix() * mat3(skin) * aVertexNormal;
+    vTangent = model * aVertexTangent;
+    vBitangent = model * aVertexBitangent;
+    vTexCoords = aTextureCoord;
+
+    gl_Position = clipPosition(pos);
+}
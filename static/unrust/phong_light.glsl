@@ -17,5 +17,23 @@ struct PointLight {
     vec3 diffuse;
     vec3 specular;
 
+    float rate;
+};
+
+struct SpotLight {
+    vec3 position;
+    vec3 direction;
+
+    float cutOff;
+    float outerCutOff;
+
+    float constant;
+    float linear;
+    float quadratic;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+
     float rate;
 };
\ No newline at end of file
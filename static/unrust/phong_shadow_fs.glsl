@@ -4,10 +4,22 @@
 #define texture2D texture
 out vec4 FragColor;
 
+// Light counts, set by the engine for each variant of the program
+#ifndef UNI_DIRECTIONAL_LIGHTS
+#define UNI_DIRECTIONAL_LIGHTS 1
+#endif
+
+#ifndef UNI_POINT_LIGHTS
 #define UNI_POINT_LIGHTS 4
+#endif
+
+#ifndef UNI_SPOT_LIGHTS
+#define UNI_SPOT_LIGHTS 0
+#endif
 
 #include "unrust/phong_light.glsl"
 #include "unrust/shadow_utils.glsl"
+#include "unrust/local_shadows.glsl"
 
 struct Material {
     sampler2D diffuse;
@@ -22,27 +34,47 @@ in vec2 vTexCoords;
 in vec3 vNormal;       
 
 // Lights
-uniform DirectionalLight uDirectionalLight;
+uniform DirectionalLight uDirectionalLights[UNI_DIRECTIONAL_LIGHTS];
+#if UNI_POINT_LIGHTS > 0
 uniform PointLight uPointLights[UNI_POINT_LIGHTS];
+#endif
+#if UNI_SPOT_LIGHTS > 0
+uniform SpotLight uSpotLights[UNI_SPOT_LIGHTS];
+#endif
 
-vec3 CalcDirectionalLight(DirectionalLight light, vec3 normal, vec3 viewDir);
-vec3 CalcPointLight(PointLight light, vec3 normal, vec3 fragPos, vec3 viewDir);
+vec3 CalcDirectionalLight(DirectionalLight light, vec3 normal, vec3 viewDir, bool shadowed);
+vec3 CalcPointLight(PointLight light, vec3 normal, vec3 fragPos, vec3 viewDir, float shadow);
+vec3 CalcSpotLight(SpotLight light, vec3 normal, vec3 fragPos, vec3 viewDir, float shadow);
 
 void main(void) {
     vec3 norm = normalize(vNormal);
     vec3 viewDir = normalize(uViewPos - vFragPos);
+    vec3 result = vec3(0.0);
 
-    // Directional Light
-    vec3 result = CalcDirectionalLight(uDirectionalLight, norm, viewDir);
+    // Directional Lights, only the main one casts shadows
+    for(int i = 0; i < UNI_DIRECTIONAL_LIGHTS; i++)
+        result += CalcDirectionalLight(uDirectionalLights[i], norm, viewDir, i == 0);
     
     // Point Lights
-    for(int i = 0; i < UNI_POINT_LIGHTS; i++)
-        result += CalcPointLight(uPointLights[i], norm, vFragPos, viewDir);
+#if UNI_POINT_LIGHTS > 0
+    for(int i = 0; i < UNI_POINT_LIGHTS; i++) {
+        float shadow = PointShadowCalculation(uPointShadows[i], uPointLights[i].position, vFragPos);
+        result += CalcPointLight(uPointLights[i], norm, vFragPos, viewDir, shadow);
+    }
+#endif
+
+    // Spot Lights
+#if UNI_SPOT_LIGHTS > 0
+    for(int i = 0; i < UNI_SPOT_LIGHTS; i++) {
+        float shadow = SpotShadowCalculation(uSpotShadows[i], uSpotLights[i].position, vFragPos);
+        result += CalcSpotLight(uSpotLights[i], norm, vFragPos, viewDir, shadow);
+    }
+#endif
 
     gl_FragColor = vec4(result, 1.0);           
 }
 
-vec3 CalcDirectionalLight(DirectionalLight light, vec3 normal, vec3 viewDir)
+vec3 CalcDirectionalLight(DirectionalLight light, vec3 normal, vec3 viewDir, bool shadowed)
 {
     // diffuse
     vec3 ambient = light.ambient * vec3(texture2D(uMaterial.diffuse, vTexCoords));
@@ -56,12 +88,12 @@ vec3 CalcDirectionalLight(DirectionalLight light, vec3 normal, vec3 viewDir)
     float spec = pow(max(dot(viewDir, reflectDir), 0.0), uMaterial.shininess);
     vec3 specular = light.specular * spec; 
 
-    float shadow = ShadowCalculation(vFragPos, normal, normal, lightDir);
+    float shadow = shadowed ? ShadowCalculation(vFragPos, normal, normal, lightDir) : 1.0;
 
     return ambient + (diffuse + specular) * shadow;
 }
 
-vec3 CalcPointLight(PointLight light, vec3 normal, vec3 fragPos, vec3 viewDir)
+vec3 CalcPointLight(PointLight light, vec3 normal, vec3 fragPos, vec3 viewDir, float shadow)
 {
     vec3 lightDir = normalize(light.position - fragPos);
     
@@ -82,8 +114,40 @@ vec3 CalcPointLight(PointLight light, vec3 normal, vec3 fragPos, vec3 viewDir)
     vec3 specular = light.specular * spec;
     
     ambient *= attenuation;
-    diffuse *= attenuation;
-    specular *= attenuation;
+    diffuse *= attenuation * shadow;
+    specular *= attenuation * shadow;
     
     return (ambient + diffuse + specular) * light.rate;        
 }
+
+vec3 CalcSpotLight(SpotLight light, vec3 normal, vec3 fragPos, vec3 viewDir, float shadow)
+{
+    vec3 lightDir = normalize(light.position - fragPos);
+
+    // diffuse shading
+    float diff = max(dot(normal, lightDir), 0.0);
+    // specular shading
+    vec3 reflectDir = reflect(-lightDir, normal);
+    float spec = pow(max(dot(viewDir, reflectDir), 0.0), uMaterial.shininess);
+
+    // attenuation
+    float distance = length(light.position - fragPos);
+    float d = (light.constant + light.linear * distance + light.quadratic * (distance * distance));
+    float attenuation = 1.0 / max(d, 0.001);
+
+    // soft edge between the inner and outer cones
+    float theta = dot(lightDir, normalize(-light.direction));
+    float epsilon = max(light.cutOff - light.outerCutOff, 0.001);
+    float intensity = clamp((theta - light.outerCutOff) / epsilon, 0.0, 1.0);
+
+    // combine results
+    vec3 ambient = light.ambient * vec3(texture2D(uMaterial.diffuse, vTexCoords));
+    vec3 diffuse = light.diffuse * diff * vec3(texture2D(uMaterial.diffuse, vTexCoords));
+    vec3 specular = light.specular * spec;
+
+    ambient *= attenuation;
+    diffuse *= attenuation * intensity * shadow;
+    specular *= attenuation * intensity * shadow;
+
+    return (ambient + diffuse + specular) * light.rate;
+}
@@ -4,6 +4,8 @@
 #define varying out
 
 #include "unrust/default_uniforms.glsl"
+#include "unrust/skinning.glsl"
+#include "unrust/instancing.glsl"
 
 attribute vec3 aVertexPosition;
 attribute vec3 aVertexNormal;
@@ -14,10 +16,13 @@ varying vec3 vNormal;
 varying vec2 vTexCoords;
 
 void main(void) {
-    vFragPos = vec3(uMMatrix * vec4(aVertexPosition, 1.0));            
+    mat4 skin = skinMatrix();
+    vec4 pos = skin * vec4(aVertexPosition, 1.0);
+
+    vFragPos = vec3(modelMatrix() * pos);            
     
-    vNormal = mat3(uNMatrix) * aVertexNormal;
+    vNormal = normalMatrix() * mat3(skin) * aVertexNormal;
     vTexCoords = aTextureCoord;
     
-    gl_Position = uPMatrix * uMVMatrix * vec4(aVertexPosition, 1.0);
+    gl_Position = clipPosition(pos);
 }
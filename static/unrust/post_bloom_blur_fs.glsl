@@ -0,0 +1,19 @@
+#include "unrust/post_header.glsl"
+
+// (1, 0) for the horizontal pass, (0, 1) for the vertical one
+uniform vec2 uDirection;
+
+void main()
+{
+    // 9 taps gaussian, with 5 linearly filtered samples
+    vec2 off1 = 1.3846153846 * uDirection * uTexelSize;
+    vec2 off2 = 3.2307692308 * uDirection * uTexelSize;
+
+    vec3 c = texture2D(uSource, vTexCoords).rgb * 0.2270270270;
+    c += texture2D(uSource, vTexCoords + off1).rgb * 0.3162162162;
+    c += texture2D(uSource, vTexCoords - off1).rgb * 0.3162162162;
+    c += texture2D(uSource, vTexCoords + off2).rgb * 0.0702702703;
+    c += texture2D(uSource, vTexCoords - off2).rgb * 0.0702702703;
+
+    gl_FragColor = vec4(c, 1.0);
+}
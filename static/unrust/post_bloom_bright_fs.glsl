@@ -0,0 +1,20 @@
+#include "unrust/post_header.glsl"
+
+uniform float uThreshold;
+
+void main()
+{
+    // Downsampled by averaging 4 texels around
+    vec3 d = vec3(-0.5, 0.5, 0.0);
+    vec3 c = texture2D(uSource, vTexCoords + d.xx * uTexelSize).rgb;
+    c += texture2D(uSource, vTexCoords + d.xy * uTexelSize).rgb;
+    c += texture2D(uSource, vTexCoords + d.yx * uTexelSize).rgb;
+    c += texture2D(uSource, vTexCoords + d.yy * uTexelSize).rgb;
+    c *= 0.25;
+
+    // Only the part brighter than the threshold is kept
+    float brightness = max(c.r, max(c.g, c.b));
+    float contribution = max(brightness - uThreshold, 0.0) / max(brightness, 0.0001);
+
+    gl_FragColor = vec4(c * contribution, 1.0);
+}
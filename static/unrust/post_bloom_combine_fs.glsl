@@ -0,0 +1,12 @@
+#include "unrust/post_header.glsl"
+
+uniform sampler2D uBloom;
+uniform float uIntensity;
+
+void main()
+{
+    vec4 c = texture2D(uSource, vTexCoords);
+    vec3 bloom = texture2D(uBloom, vTexCoords).rgb;
+
+    gl_FragColor = vec4(c.rgb + bloom * uIntensity, c.a);
+}
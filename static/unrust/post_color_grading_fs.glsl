@@ -0,0 +1,27 @@
+#include "unrust/post_header.glsl"
+
+// A strip of uLutSize slices of uLutSize x uLutSize, blue by slice
+uniform sampler2D uLut;
+uniform float uLutSize;
+
+vec3 lookup(vec3 c)
+{
+    float n = uLutSize;
+    float slice = c.b * (n - 1.0);
+    float s0 = floor(slice);
+    float s1 = min(s0 + 1.0, n - 1.0);
+
+    // Sample at the texel centers, from the first mipmap
+    vec2 uv = (c.rg * (n - 1.0) + 0.5) / vec2(n * n, n);
+    vec3 c0 = texture2D(uLut, uv + vec2(s0 / n, 0.0), -16.0).rgb;
+    vec3 c1 = texture2D(uLut, uv + vec2(s1 / n, 0.0), -16.0).rgb;
+
+    return mix(c0, c1, slice - s0);
+}
+
+void main()
+{
+    vec4 c = texture2D(uSource, vTexCoords);
+
+    gl_FragColor = vec4(lookup(clamp(c.rgb, 0.0, 1.0)), c.a);
+}
@@ -0,0 +1 @@
+#include "unrust/fullscreen_quad.glsl"
@@ -0,0 +1,6 @@
+#include "unrust/post_header.glsl"
+
+void main()
+{
+    gl_FragColor = texture2D(uSource, vTexCoords);
+}
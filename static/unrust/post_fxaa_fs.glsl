@@ -0,0 +1,48 @@
+#include "unrust/post_header.glsl"
+
+#define FXAA_REDUCE_MIN (1.0 / 128.0)
+#define FXAA_REDUCE_MUL (1.0 / 8.0)
+#define FXAA_SPAN_MAX 8.0
+
+// FXAA of a LDR source, along the edge found from the luma of the corners
+void main()
+{
+    vec3 rgbNW = texture2D(uSource, vTexCoords + vec2(-1.0, -1.0) * uTexelSize).rgb;
+    vec3 rgbNE = texture2D(uSource, vTexCoords + vec2(1.0, -1.0) * uTexelSize).rgb;
+    vec3 rgbSW = texture2D(uSource, vTexCoords + vec2(-1.0, 1.0) * uTexelSize).rgb;
+    vec3 rgbSE = texture2D(uSource, vTexCoords + vec2(1.0, 1.0) * uTexelSize).rgb;
+    vec4 texColor = texture2D(uSource, vTexCoords);
+    vec3 rgbM = texColor.rgb;
+
+    vec3 luma = vec3(0.299, 0.587, 0.114);
+    float lumaNW = dot(rgbNW, luma);
+    float lumaNE = dot(rgbNE, luma);
+    float lumaSW = dot(rgbSW, luma);
+    float lumaSE = dot(rgbSE, luma);
+    float lumaM = dot(rgbM, luma);
+    float lumaMin = min(lumaM, min(min(lumaNW, lumaNE), min(lumaSW, lumaSE)));
+    float lumaMax = max(lumaM, max(max(lumaNW, lumaNE), max(lumaSW, lumaSE)));
+
+    vec2 dir;
+    dir.x = -((lumaNW + lumaNE) - (lumaSW + lumaSE));
+    dir.y = ((lumaNW + lumaSW) - (lumaNE + lumaSE));
+
+    float dirReduce = max((lumaNW + lumaNE + lumaSW + lumaSE) * (0.25 * FXAA_REDUCE_MUL),
+                          FXAA_REDUCE_MIN);
+    float rcpDirMin = 1.0 / (min(abs(dir.x), abs(dir.y)) + dirReduce);
+    dir = min(vec2(FXAA_SPAN_MAX), max(vec2(-FXAA_SPAN_MAX), dir * rcpDirMin)) * uTexelSize;
+
+    vec3 rgbA = 0.5 * (
+        texture2D(uSource, vTexCoords + dir * (1.0 / 3.0 - 0.5)).rgb +
+        texture2D(uSource, vTexCoords + dir * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 rgbB = rgbA * 0.5 + 0.25 * (
+        texture2D(uSource, vTexCoords + dir * -0.5).rgb +
+        texture2D(uSource, vTexCoords + dir * 0.5).rgb);
+
+    float lumaB = dot(rgbB, luma);
+    if ((lumaB < lumaMin) || (lumaB > lumaMax)) {
+        gl_FragColor = vec4(rgbA, texColor.a);
+    } else {
+        gl_FragColor = vec4(rgbB, texColor.a);
+    }
+}
@@ -0,0 +1,13 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+#define texture2D texture
+out vec4 FragColor;
+#endif
+
+varying vec2 vTexCoords;
+
+// The result of the previous pass, or the scene
+uniform sampler2D uSource;
+// 1 / size of the source in pixels
+uniform vec2 uTexelSize;
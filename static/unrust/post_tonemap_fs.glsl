@@ -0,0 +1,24 @@
+#include "unrust/post_header.glsl"
+
+uniform float uExposure;
+// 2.2 when the output is not converted to srgb afterward
+uniform float uGamma;
+
+// ACES filmic curve fitted by Krzysztof Narkowicz
+vec3 aces(vec3 x)
+{
+    const float a = 2.51;
+    const float b = 0.03;
+    const float c = 2.43;
+    const float d = 0.59;
+    const float e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+
+void main()
+{
+    vec4 c = texture2D(uSource, vTexCoords);
+    vec3 mapped = aces(c.rgb * uExposure);
+
+    gl_FragColor = vec4(pow(mapped, vec3(1.0 / uGamma)), c.a);
+}
@@ -0,0 +1,15 @@
+#include "unrust/post_header.glsl"
+
+uniform float uIntensity;
+// Distance from the center where the darkening starts, 1 at the corners
+uniform float uRadius;
+uniform float uSmoothness;
+
+void main()
+{
+    vec4 c = texture2D(uSource, vTexCoords);
+    float dist = length(vTexCoords - 0.5) * 1.414;
+    float vignette = smoothstep(uRadius, uRadius + uSmoothness, dist);
+
+    gl_FragColor = vec4(c.rgb * (1.0 - vignette * uIntensity), c.a);
+}
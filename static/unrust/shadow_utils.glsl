@@ -1,14 +1,59 @@
 #include "unrust/shadow_map.glsl"
 
+// Filter modes, as ShadowFilter in shadow_pass.rs
+#define SHADOW_FILTER_HARD 0
+#define SHADOW_FILTER_PCF3X3 1
+#define SHADOW_FILTER_PCF5X5 2
+#define SHADOW_FILTER_POISSON 3
+#define SHADOW_FILTER_PCSS 4
+#define SHADOW_FILTER_VSM 5
+
 uniform bool uShadowEnabled;
 uniform ShadowMap uShadowMap[4];
 uniform sampler2D uShadowMapTexture;
+// Depth and squared depth, for variance shadow maps
+uniform sampler2D uShadowMomentsTexture;
+
+uniform float uShadowFilter;
+// Radius of the Poisson filter and size of the light for PCSS, in texels
+uniform float uShadowSoftness;
+uniform float uShadowCascadeCount;
+// Part of a cascade blended with the next one, from 0 to 1
+uniform float uShadowBlendBand;
+// Near and far planes of the camera
+uniform vec2 uShadowCameraRange;
+
+const vec2 POISSON_DISK[16] = vec2[](
+    vec2(-0.94201624, -0.39906216),
+    vec2(0.94558609, -0.76890725),
+    vec2(-0.09418410, -0.92938870),
+    vec2(0.34495938, 0.29387760),
+    vec2(-0.91588581, 0.45771432),
+    vec2(-0.81544232, -0.87912464),
+    vec2(-0.38277543, 0.27676845),
+    vec2(0.97484398, 0.75648379),
+    vec2(0.44323325, -0.97511554),
+    vec2(0.53742981, -0.47373420),
+    vec2(-0.26496911, -0.41893023),
+    vec2(0.79197514, 0.19090188),
+    vec2(-0.24188840, 0.99706507),
+    vec2(-0.81409955, 0.91437590),
+    vec2(0.19984126, 0.78641367),
+    vec2(0.14383161, -0.14100790)
+);
 
 float ndc_z() {
     return ((2.0 * gl_FragCoord.z - gl_DepthRange.near - gl_DepthRange.far) /
     (gl_DepthRange.far - gl_DepthRange.near));
 }
 
+// Distance to the camera plane of a ndc depth
+float linear_z(float ndc) {
+    float n = uShadowCameraRange.x;
+    float f = uShadowCameraRange.y;
+    return 2.0 * f * n / ((f + n) - ndc * (f - n));
+}
+
 vec2 get_shadow_offsets(vec3 N, vec3 L) {
     float cos_alpha = clamp(dot(N, L), 0.0, 1.0);
     float offset_scale_N = sqrt(1.0 - cos_alpha*cos_alpha); // sin(acos(L·N))
@@ -22,7 +67,7 @@ vec3 LightSpacePosition(int index, vec3 worldPos, vec3 worldNormal, vec2 bias_of
 
     vec4 posLightSpace = uShadowMap[index].light_matrix * vec4(worldPos + worldNormal * normal_bias * bias_offset.x, 1.0);
     vec3 projCoordsNDC = posLightSpace.xyz / posLightSpace.w;
-    
+
     // transform ndc to range [0,1]
     return projCoordsNDC * 0.5 + 0.5;
 }
@@ -31,8 +76,130 @@ int ShadowIndex() {
     float nz = ndc_z();
     int i3 = 3 * int(nz > uShadowMap[3].range.x);
     int i2 = max(i3, 2 * int(nz > uShadowMap[2].range.x));
-    
-    return max(i2, 1 * int(nz > uShadowMap[1].range.x));
+    int i1 = max(i2, 1 * int(nz > uShadowMap[1].range.x));
+
+    return min(i1, int(uShadowCascadeCount + 0.5) - 1);
+}
+
+bool InShadowMap(vec2 uv) {
+    return uv.x >= 0.0 && uv.x <= 1.0 && uv.y >= 0.0 && uv.y <= 1.0;
+}
+
+// Coordinates in the texture of the map of a cascade
+vec2 ShadowMapUV(int index, vec2 uv) {
+    return uShadowMap[index].viewport_offset + uv * uShadowMap[index].viewport_scale;
+}
+
+// 1 when the point is behind the map at uv, 0 outside of the map
+float ShadowCompare(int index, vec2 uv, float depth) {
+    float mapDepth = texture2D(uShadowMapTexture, ShadowMapUV(index, uv)).r;
+    return float(InShadowMap(uv)) * float(depth > mapDepth);
+}
+
+float ShadowPCF(int index, vec3 projCoords, float bias, float texelSize, int radius) {
+    float shadow = 0.0;
+
+    for(int x = -2; x <= 2; ++x)
+    {
+        for(int y = -2; y <= 2; ++y)
+        {
+            if (abs(x) > radius || abs(y) > radius) {
+                continue;
+            }
+
+            vec2 offset = vec2(x, y) * texelSize;
+            shadow += ShadowCompare(index, projCoords.xy + offset, projCoords.z - bias);
+        }
+    }
+
+    float n = float(2 * radius + 1);
+    return shadow / (n * n);
+}
+
+float ShadowPoisson(int index, vec3 projCoords, float bias, float radius) {
+    float shadow = 0.0;
+
+    for(int i = 0; i < 16; ++i)
+    {
+        vec2 offset = POISSON_DISK[i] * radius;
+        shadow += ShadowCompare(index, projCoords.xy + offset, projCoords.z - bias);
+    }
+
+    return shadow / 16.0;
+}
+
+// Percentage closer soft shadows: the filter grows with the distance to the blockers
+float ShadowPCSS(int index, vec3 projCoords, float bias, float texelSize) {
+    float lightSize = uShadowSoftness * texelSize;
+    float receiver = projCoords.z - bias;
+
+    float blockers = 0.0;
+    float blockerDepth = 0.0;
+    for(int i = 0; i < 16; ++i)
+    {
+        vec2 uv = projCoords.xy + POISSON_DISK[i] * lightSize;
+        float mapDepth = texture2D(uShadowMapTexture, ShadowMapUV(index, uv)).r;
+
+        if (InShadowMap(uv) && mapDepth < receiver) {
+            blockers += 1.0;
+            blockerDepth += mapDepth;
+        }
+    }
+
+    if (blockers == 0.0) {
+        return 0.0;
+    }
+
+    // The depth of a directional light map is linear
+    blockerDepth /= blockers;
+    float penumbra = (receiver - blockerDepth) / max(blockerDepth, 0.0001) * lightSize;
+
+    return ShadowPoisson(index, projCoords, bias, clamp(penumbra, texelSize, lightSize));
+}
+
+// Variance shadow maps: the upper bound of the lit part given by the moments
+float ShadowVSM(int index, vec3 projCoords) {
+    if (!InShadowMap(projCoords.xy)) {
+        return 0.0;
+    }
+
+    vec2 moments = texture2D(uShadowMomentsTexture, ShadowMapUV(index, projCoords.xy)).rg;
+    if (projCoords.z <= moments.x) {
+        return 0.0;
+    }
+
+    float variance = max(moments.y - moments.x * moments.x, 0.00002);
+    float d = projCoords.z - moments.x;
+    float lit = variance / (variance + d * d);
+
+    // Cut the tail of the bound, which bleeds light
+    lit = clamp((lit - 0.2) / 0.8, 0.0, 1.0);
+    return 1.0 - lit;
+}
+
+float CascadeShadow(int index, vec3 worldPos, vec3 worldNormal, vec2 bias_offset)
+{
+    float constant_bias = 0.5;
+    float slope_bias = 3.0;
+
+    vec3 projCoords = LightSpacePosition(index, worldPos, worldNormal, bias_offset);
+    float texelSize = uShadowMap[index].tex_size;
+    float bias = constant_bias * texelSize * (constant_bias + slope_bias * bias_offset.y);
+
+    int mode = int(uShadowFilter + 0.5);
+    if (mode == SHADOW_FILTER_HARD) {
+        return ShadowCompare(index, projCoords.xy, projCoords.z - bias);
+    } else if (mode == SHADOW_FILTER_PCF5X5) {
+        return ShadowPCF(index, projCoords, bias, texelSize, 2);
+    } else if (mode == SHADOW_FILTER_POISSON) {
+        return ShadowPoisson(index, projCoords, bias, uShadowSoftness * texelSize);
+    } else if (mode == SHADOW_FILTER_PCSS) {
+        return ShadowPCSS(index, projCoords, bias, texelSize);
+    } else if (mode == SHADOW_FILTER_VSM) {
+        return ShadowVSM(index, projCoords);
+    }
+
+    return ShadowPCF(index, projCoords, bias, texelSize, 1);
 }
 
 float ShadowCalculation(vec3 worldPos, vec3 worldNormal, vec3 normal, vec3 lightDir)
@@ -40,41 +207,23 @@ float ShadowCalculation(vec3 worldPos, vec3 worldNormal, vec3 normal, vec3 light
     if (!uShadowEnabled) {
         return 1.0;
     }
-    
-    float constant_bias = 0.5;
-    float slope_bias = 3.0;
-    float normal_bias = 0.02;
 
-    vec2 bias_offset = get_shadow_offsets(normal, lightDir);    
+    vec2 bias_offset = get_shadow_offsets(normal, lightDir);
     int index = ShadowIndex();
-            
-    vec3 projCoords = LightSpacePosition(index, worldPos, worldNormal, bias_offset);
-    float currentDepth = projCoords.z;    
-    float texelSize = uShadowMap[index].tex_size;
+    float shadow = CascadeShadow(index, worldPos, worldNormal, bias_offset);
 
-    float shadow = 0.0;
-    float bias = constant_bias * texelSize * (constant_bias + slope_bias * bias_offset.y);
+    // Blend with the next cascade at the end of this one, to hide the seam
+    if (index + 1 < int(uShadowCascadeCount + 0.5) && uShadowBlendBand > 0.0) {
+        float near = linear_z(uShadowMap[index].range.x);
+        float far = linear_z(uShadowMap[index].range.y);
+        float band = (far - near) * uShadowBlendBand;
+        float t = clamp((linear_z(ndc_z()) - (far - band)) / band, 0.0, 1.0);
 
-    for(int x = -1; x <= 1; ++x)
-    {
-        for(int y = -1; y <= 1; ++y)
-        {
-            vec2 offset = vec2(x, y)* texelSize;
-            vec2 boundProj = projCoords.xy + offset;
-            vec2 adjBoundProj = uShadowMap[index].viewport_offset + boundProj * uShadowMap[index].viewport_scale;
-
-            float pcfDepth = texture2D(uShadowMapTexture, adjBoundProj).r;
-            float partShadow = float(currentDepth - bias > pcfDepth);
-            
-            partShadow = float(boundProj.x >= 0.0 && boundProj.x <= 1.0 &&
-                boundProj.y >= 0.0 && boundProj.y <= 1.0) * partShadow;
-            
-            shadow += partShadow;
-        }       
+        if (t > 0.0) {
+            float next = CascadeShadow(index + 1, worldPos, worldNormal, bias_offset);
+            shadow = mix(shadow, next, t);
+        }
     }
-    
-    shadow /= 9.0;
 
     return (1.0 - shadow);
 }
-
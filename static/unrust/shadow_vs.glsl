@@ -4,12 +4,14 @@
 #endif
 
 #include "unrust/default_uniforms.glsl"
+#include "unrust/skinning.glsl"
+#include "unrust/instancing.glsl"
 
 attribute vec3 aVertexPosition;
 uniform mat4 uShadowMatrix;            
 
 void main(void) {
-    vec4 pos = uShadowMatrix * uMMatrix * vec4(aVertexPosition, 1.0);    
+    vec4 pos = uShadowMatrix * modelMatrix() * skinMatrix() * vec4(aVertexPosition, 1.0);    
     pos.z *= pos.w;
     gl_Position = pos;
-}
\ No newline at end of file
+}
\ No newline at end of file
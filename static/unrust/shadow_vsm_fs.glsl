@@ -0,0 +1,14 @@
+#define USE_GLSL_300ES
+
+#define gl_FragColor FragColor
+out vec4 FragColor;
+
+// The depth and squared depth, blurred by the filtering of the texture
+void main()
+{
+    float depth = gl_FragCoord.z;
+    float dx = dFdx(depth);
+    float dy = dFdy(depth);
+
+    gl_FragColor = vec4(depth, depth * depth + 0.25 * (dx * dx + dy * dy), 0.0, 1.0);
+}
@@ -0,0 +1,17 @@
+#define USE_GLSL_300ES
+
+#define attribute in
+#define varying out
+
+#include "unrust/default_uniforms.glsl"
+#include "unrust/skinning.glsl"
+#include "unrust/instancing.glsl"
+
+attribute vec3 aVertexPosition;
+uniform mat4 uShadowMatrix;
+
+void main(void) {
+    vec4 pos = uShadowMatrix * modelMatrix() * skinMatrix() * vec4(aVertexPosition, 1.0);
+    pos.z *= pos.w;
+    gl_Position = pos;
+}
@@ -0,0 +1,18 @@
+#define MAX_JOINTS 32
+
+attribute vec4 aJoints;
+attribute vec4 aWeights;
+
+uniform bool uSkinned;
+uniform mat4 uJointMatrices[MAX_JOINTS];
+
+mat4 skinMatrix() {
+    if (!uSkinned) {
+        return mat4(1.0);
+    }
+
+    return aWeights.x * uJointMatrices[int(aJoints.x)] +
+        aWeights.y * uJointMatrices[int(aJoints.y)] +
+        aWeights.z * uJointMatrices[int(aJoints.z)] +
+        aWeights.w * uJointMatrices[int(aJoints.w)];
+}
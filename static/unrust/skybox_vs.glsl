@@ -3,6 +3,7 @@
 #define varying out
 #endif
 
+// Puts the vertices at the far plane
 uniform mat4 uPVSkyboxMatrix;
 
 attribute vec3 aVertexPosition;
@@ -11,5 +12,5 @@ varying vec3 vTexCoords;
 void main()
 {
     vTexCoords = aVertexPosition;
-    gl_Position = (uPVSkyboxMatrix * vec4(aVertexPosition, 1.0)).xyww;
-}         
\ No newline at end of file
+    gl_Position = uPVSkyboxMatrix * vec4(aVertexPosition, 1.0);
+}         
\ No newline at end of file
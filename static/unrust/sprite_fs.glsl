@@ -0,0 +1,15 @@
+#ifndef GL_ES
+#define varying in
+#define gl_FragColor FragColor
+#define texture2D texture
+out vec4 FragColor;
+#endif
+
+varying vec2 vTexCoords;
+varying vec4 vColor;
+uniform sampler2D uTexture;
+
+void main()
+{
+    gl_FragColor = texture2D(uTexture, vTexCoords) * vColor;
+}
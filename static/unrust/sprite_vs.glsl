@@ -0,0 +1,21 @@
+#ifndef GL_ES
+#define attribute in
+#define varying out
+#endif
+
+#include "unrust/default_uniforms.glsl"
+
+// The vertices of the sprite batches are in world space
+attribute vec3 aVertexPosition;
+attribute vec2 aTextureCoord;
+attribute vec4 aVertexColor;
+
+varying vec2 vTexCoords;
+varying vec4 vColor;
+
+void main()
+{
+    vTexCoords = aTextureCoord;
+    vColor = aVertexColor;
+    gl_Position = uPMatrix * uMVMatrix * vec4(aVertexPosition, 1.0);
+}
@@ -0,0 +1,251 @@
+extern crate unrust;
+
+mod common;
+
+use common::{assert_near_within, new_world};
+use std::cell::RefCell;
+use std::rc::Rc;
+use unrust::engine::{Animation, AnimationChannel, AnimationClip, AnimationTrack, Animator, Curve,
+                     GameObject, Interpolation, Keyframes, Material, MaterialParam, Skeleton,
+                     WrapMode, MAX_JOINTS};
+use unrust::math::*;
+use unrust::world::{Actor, Handle, World};
+
+fn new_rig(world: &mut World) -> (Handle<GameObject>, Handle<GameObject>) {
+    let root = world.new_game_object();
+    let joint = world.new_game_object();
+    root.borrow().add_child(&joint.borrow());
+
+    let mut clip = AnimationClip::new("wave");
+    let translation = AnimationTrack::new(
+        &joint,
+        vec![0.0, 1.0],
+        Keyframes::Translation(vec![Vector3::zero(), Vector3::new(2.0, 0.0, 0.0)]),
+    );
+    let rotation = AnimationTrack::new(
+        &joint,
+        vec![0.0, 1.0, 2.0],
+        Keyframes::Rotation(vec![
+            Quaternion::one(),
+            Quaternion::from_angle_z(Deg(90.0)),
+            Quaternion::from_angle_z(Deg(180.0)),
+        ]),
+    );
+    clip.add_track(translation.unwrap());
+    clip.add_track(rotation.unwrap());
+
+    let mut animator = Animator::new();
+    animator.looping = false;
+    animator.add_clip(clip);
+    assert!(animator.play("wave"));
+
+    let skeleton = Skeleton::from_bind_pose(&root.borrow(), &[joint.clone()]).unwrap();
+
+    {
+        let mut go = root.borrow_mut();
+        go.add_component(animator);
+        go.add_component(skeleton);
+    }
+
+    (root, joint)
+}
+
+fn advance(root: &Handle<GameObject>, dt: f32) -> (bool, f32) {
+    let go = root.borrow();
+    let (mut animator, _) = go.find_component_mut::<Animator>().unwrap();
+    animator.advance(dt);
+
+    (animator.is_playing(), animator.time())
+}
+
+fn joint_matrix(root: &Handle<GameObject>) -> Matrix4f {
+    let go = root.borrow();
+    let (skeleton, _) = go.find_component::<Skeleton>().unwrap();
+    skeleton.joint_matrices(&go.transform.as_global_matrix())[0]
+}
+
+#[test]
+fn test_animator_drives_joints() {
+    let mut world = new_world("Animation");
+    let (root, joint) = new_rig(&mut world);
+
+    advance(&root, 0.5);
+    assert_near_within(
+        joint.borrow().transform.local().disp,
+        Vector3::new(1.0, 0.0, 0.0),
+        0.0001,
+    );
+
+    advance(&root, 1.0);
+    let rot = joint.borrow().transform.local().rot;
+    assert_near_within(
+        rot.rotate_vector(Vector3::unit_x()),
+        Vector3::new(-1.0, 1.0, 0.0).normalize(),
+        0.0001,
+    );
+
+    // One shot clips stop at their last key
+    assert_eq!(advance(&root, 1.0), (false, 2.0));
+    assert_near_within(
+        joint.borrow().transform.local().disp,
+        Vector3::new(2.0, 0.0, 0.0),
+        0.0001,
+    );
+}
+
+#[test]
+fn test_skeleton_joint_matrices() {
+    let mut world = new_world("Animation");
+    let (root, joint) = new_rig(&mut world);
+
+    assert_eq!(joint_matrix(&root), Matrix4::identity());
+
+    joint.borrow_mut().transform.set_local(Isometry3 {
+        scale: 1.0,
+        rot: Quaternion::one(),
+        disp: Vector3::new(0.0, 3.0, 0.0),
+    });
+
+    assert_near_within(
+        joint_matrix(&root).w.truncate(),
+        Vector3::new(0.0, 3.0, 0.0),
+        0.0001,
+    );
+}
+
+#[test]
+fn test_invalid_skeletons() {
+    let mut world = new_world("Animation");
+    let joints: Vec<_> = (0..MAX_JOINTS + 1).map(|_| world.new_game_object()).collect();
+
+    assert!(Skeleton::new(&joints[..2], vec![Matrix4::identity()]).is_err());
+    assert!(Skeleton::new(&joints, vec![Matrix4::identity(); MAX_JOINTS + 1]).is_err());
+    assert!(Skeleton::new(&joints[1..], vec![Matrix4::identity(); MAX_JOINTS]).is_ok());
+}
+
+#[test]
+fn test_invalid_track() {
+    let mut world = new_world("Animation");
+    let joint = world.new_game_object();
+
+    let track = AnimationTrack::new(
+        &joint,
+        vec![0.0, 1.0, 2.0],
+        Keyframes::Scale(vec![Vector3::new(1.0, 1.0, 1.0)]),
+    );
+    assert!(track.is_err());
+}
+
+#[test]
+fn test_borrowed_target_skipped() {
+    let mut world = new_world("Animation");
+    let joint = world.new_game_object();
+
+    let track = AnimationTrack::new(
+        &joint,
+        vec![0.0, 1.0],
+        Keyframes::Translation(vec![Vector3::zero(), Vector3::new(2.0, 0.0, 0.0)]),
+    );
+    let track = track.unwrap();
+
+    {
+        let _borrowed = joint.borrow_mut();
+        track.sample(1.0);
+    }
+    assert_eq!(joint.borrow().transform.local().disp, Vector3::zero());
+
+    track.sample(1.0);
+    assert_eq!(
+        joint.borrow().transform.local().disp,
+        Vector3::new(2.0, 0.0, 0.0)
+    );
+}
+
+#[test]
+fn test_curve_interpolation() {
+    let mut curve = Curve::new();
+    curve.add_key(1.0, 10.0, Interpolation::Step);
+    curve.add_key(0.0, 0.0, Interpolation::CubicBezier(0.42, 0.0, 0.58, 1.0));
+    curve.add_key(2.0, 20.0, Interpolation::Linear);
+
+    assert_eq!(curve.duration(), 2.0);
+    assert_eq!(curve.evaluate(-1.0), Some(0.0));
+    assert!((curve.evaluate(0.5).unwrap() - 5.0).abs() < 0.001);
+    assert!(curve.evaluate(0.25).unwrap() < 2.5);
+    assert_eq!(curve.evaluate(1.5), Some(10.0));
+    assert_eq!(curve.evaluate(3.0), Some(20.0));
+}
+
+#[test]
+fn test_animation_ping_pong() {
+    let mut world = new_world("Animation");
+    let go = world.new_game_object();
+    let mut animation = Animation::new("bob");
+    animation.wrap_mode = WrapMode::PingPong;
+    animation.add_channel(AnimationChannel::Position(Curve::linear(&[
+        (0.0, Vector3::zero()),
+        (1.0, Vector3::unit_y()),
+    ])));
+    animation.play();
+
+    let mut y = || {
+        let mut go = go.borrow_mut();
+        assert!(!animation.advance(0.5, &mut go));
+        go.transform.local().disp.y
+    };
+
+    assert_eq!(y(), 0.5);
+    assert_eq!(y(), 1.0);
+    assert_eq!(y(), 0.5);
+    assert_eq!(y(), 0.0);
+    assert_eq!(y(), 0.5);
+}
+
+struct Fader {
+    finished: Rc<RefCell<Vec<String>>>,
+}
+
+impl Actor for Fader {
+    fn animation_finished(&mut self, _go: &mut GameObject, _world: &mut World, name: &str) {
+        self.finished.borrow_mut().push(name.to_string());
+    }
+}
+
+#[test]
+fn test_animation_finished_callback() {
+    let mut world = new_world("Animation");
+    let material = Rc::new(Material::new(world.asset_system().new_program("phong")));
+    let finished = Rc::new(RefCell::new(Vec::new()));
+
+    let mut animation = Animation::new("fade");
+    animation.add_channel(AnimationChannel::Float(
+        material.clone(),
+        "uAlpha".into(),
+        Curve::linear(&[(0.0, 0.25)]),
+    ));
+    animation.add_channel(AnimationChannel::Scale(Curve::linear(&[
+        (0.0, Vector3::new(2.0, 2.0, 2.0)),
+    ])));
+    animation.play();
+
+    let go = world.new_game_object();
+    {
+        let mut go = go.borrow_mut();
+        go.add_component(animation);
+        go.add_component(Fader::new_actor(Fader {
+            finished: finished.clone(),
+        }));
+    }
+
+    // A zero length one-shot animation finishes at its first step
+    assert!(world.poll_events());
+    assert_eq!(*finished.borrow(), vec!["fade".to_string()]);
+    assert_eq!(go.borrow().transform.local_scale(), Vector3::new(2.0, 2.0, 2.0));
+    match material.params().get("uAlpha") {
+        Some(&MaterialParam::Float(v)) => assert_eq!(v, 0.25),
+        _ => panic!("uAlpha is not set"),
+    }
+
+    assert!(world.poll_events());
+    assert_eq!(finished.borrow().len(), 1);
+}
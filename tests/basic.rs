@@ -126,7 +126,15 @@ impl<'a> PartialEq for Image<'a> {
     }
 }
 
+// The software rasterizer does not produce the same pixels as a GPU
+#[cfg(not(feature = "soft_gl"))]
+const GOLDEN_FILE: &'static str = "basic_golden.png";
+#[cfg(feature = "soft_gl")]
+const GOLDEN_FILE: &'static str = "basic_soft_golden.png";
+
 #[test]
+// The mock backend never produces any pixel
+#[cfg_attr(feature = "mock_gl", ignore)]
 fn test_basic() {
     let mut world = WorldBuilder::new("Headless")
         .with_headless(true)
@@ -151,12 +159,18 @@ fn test_basic() {
     let mut golden_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     golden_dir.push("tests");
     golden_dir.push("resources");
-    let golden_path = golden_dir.join("basic_golden.png");
+    let golden_path = golden_dir.join(GOLDEN_FILE);
     let img = img.expect("Cannot capture frame buffer");
 
     if is_golden() {
         img.save(golden_path).expect("Cannot save to file");
     } else {
+        assert!(
+            golden_path.exists(),
+            "{} is missing, record it with UNRUST_TEST_GOLDEN=1",
+            golden_path.display()
+        );
+
         let golden = image::open(golden_path).unwrap();
         // For test fail
         // if Image(&golden.to_rgba()) != Image(&img) {
@@ -0,0 +1,103 @@
+extern crate unrust;
+
+mod common;
+
+use common::assert_near;
+use unrust::engine::{Camera, Projection};
+use unrust::math::*;
+
+const SCREEN: (u32, u32) = (320, 240);
+
+fn new_camera(projection: Projection) -> Camera {
+    let mut camera = Camera::new();
+    camera.projection = projection;
+    camera.znear = 0.1;
+    camera.zfar = 100.0;
+    camera.lookat(
+        &Point3::new(0.0, 0.0, 10.0),
+        &Point3::new(0.0, 0.0, 0.0),
+        &Vector3::unit_y(),
+    );
+    camera
+}
+
+fn ndc_depth(camera: &Camera, distance: f32) -> f32 {
+    let p = camera.projection_matrix(SCREEN) * Vector4::new(0.0, 0.0, -distance, 1.0);
+    p.z / p.w
+}
+
+#[test]
+fn test_perspective_fov() {
+    let camera = new_camera(Projection::perspective(Deg(90.0)));
+    let frustum = camera.calc_frustum(SCREEN);
+
+    // Half of the view height is the distance at 90 degrees
+    assert!(frustum.collide_sphere(&Vector3::new(0.0, 9.5, 0.0), 0.01));
+    assert!(!frustum.collide_sphere(&Vector3::new(0.0, 10.5, 0.0), 0.01));
+    assert!(!frustum.collide_sphere(&Vector3::new(0.0, 0.0, -95.0), 0.01));
+
+    assert!((ndc_depth(&camera, 0.1) + 1.0).abs() < 0.001);
+    assert!((ndc_depth(&camera, 100.0) - 1.0).abs() < 0.001);
+}
+
+#[test]
+fn test_orthographic() {
+    let camera = new_camera(Projection::orthographic(5.0));
+    let frustum = camera.calc_frustum(SCREEN);
+
+    // The width follows the aspect ratio
+    assert!(frustum.collide_sphere(&Vector3::new(6.5, 0.0, -50.0), 0.01));
+    assert!(!frustum.collide_sphere(&Vector3::new(0.0, 5.5, 0.0), 0.01));
+
+    // The rays are parallel
+    let ray = camera.screen_point_to_ray((0.0, 0.0), SCREEN);
+    assert_near(ray.direction, Vector3::new(0.0, 0.0, -1.0));
+    assert_near(ray.origin, Vector3::new(-5.0 * 320.0 / 240.0, 5.0, 9.9));
+}
+
+#[test]
+fn test_infinite_far_and_reversed_z() {
+    let mut camera = new_camera(Projection::Perspective {
+        fovy: Deg(60.0).into(),
+        infinite_far: true,
+    });
+    let frustum = camera.calc_frustum(SCREEN);
+    assert!(frustum.collide_sphere(&Vector3::new(0.0, 0.0, -10000.0), 1.0));
+    assert!(ndc_depth(&camera, 10000.0) < 1.0);
+
+    camera.reversed_z = true;
+    assert!((ndc_depth(&camera, 0.1) - 1.0).abs() < 0.001);
+    assert!(ndc_depth(&camera, 10.0) > ndc_depth(&camera, 20.0));
+
+    let ray = camera.screen_point_to_ray((160.0, 120.0), SCREEN);
+    assert_near(ray.origin, Vector3::new(0.0, 0.0, 9.9));
+    assert_near(ray.direction, Vector3::new(0.0, 0.0, -1.0));
+}
+
+#[test]
+fn test_custom_projection() {
+    let camera = new_camera(Projection::Custom(ortho(0.0, 10.0, 0.0, 10.0, 0.0, 20.0)));
+    let frustum = camera.calc_frustum(SCREEN);
+
+    // Off-axis, only the upper right quarter is in view
+    assert!(frustum.collide_sphere(&Vector3::new(5.0, 5.0, 0.0), 0.01));
+    assert!(!frustum.collide_sphere(&Vector3::new(-5.0, 5.0, 0.0), 0.01));
+    assert!(!frustum.collide_sphere(&Vector3::new(5.0, 5.0, -15.0), 0.01));
+}
+
+#[test]
+#[allow(deprecated)]
+fn test_projection_matrices() {
+    let camera = new_camera(Projection::orthographic(5.0));
+    let aspect = 320.0 / 240.0;
+
+    assert_eq!(
+        camera.projection_matrix(SCREEN),
+        Projection::orthographic(5.0).matrix(aspect, 0.1, 100.0)
+    );
+    assert_eq!(
+        camera.projection_matrix(SCREEN),
+        ortho(-5.0 * aspect, 5.0 * aspect, -5.0, 5.0, 0.1, 100.0)
+    );
+    assert_eq!(camera.perspective(SCREEN), camera.projection_matrix(SCREEN));
+}
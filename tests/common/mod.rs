@@ -0,0 +1,102 @@
+// Helpers shared by the integration tests, each test crate uses a part of them
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use unrust::engine::{AssetResult, AssetSystem, GameObject, Material, Mesh, ObjMaterial, Prefab};
+use unrust::math::*;
+use unrust::world::{Handle, World, WorldBuilder};
+
+/// A headless world builder, to add processors to the world
+pub fn world_builder(title: &str) -> WorldBuilder {
+    WorldBuilder::new(title)
+        .with_headless(true)
+        .with_size((320, 240))
+}
+
+pub fn new_world(title: &str) -> World {
+    world_builder(title).build()
+}
+
+pub fn at(pos: Vector3f) -> Isometry3<f32> {
+    Isometry3 {
+        scale: 1.0,
+        rot: Quaternion::one(),
+        disp: pos,
+    }
+}
+
+pub fn phong_material(world: &World) -> Rc<Material> {
+    Rc::new(Material::new(world.asset_system().new_program("phong")))
+}
+
+/// A game object with a Mesh of the named buffer, at pos in world space
+pub fn add_mesh_with(
+    world: &mut World,
+    buffer: &str,
+    material: &Rc<Material>,
+    pos: Vector3f,
+) -> Handle<GameObject> {
+    let mut mesh = Mesh::new();
+    mesh.add_surface(world.asset_system().new_mesh_buffer(buffer), material.clone());
+
+    let go = world.new_game_object();
+    {
+        let mut go = go.borrow_mut();
+        go.add_component(mesh);
+        go.transform.set_global(at(pos));
+    }
+
+    go
+}
+
+/// A game object with a Mesh of the named buffer and its own phong material
+pub fn add_mesh(world: &mut World, buffer: &str, pos: Vector3f) -> Handle<GameObject> {
+    let material = phong_material(world);
+    add_mesh_with(world, buffer, &material, pos)
+}
+
+/// Make go a child of parent, keeping its transform in world space
+pub fn attach(parent: &Handle<GameObject>, go: &Handle<GameObject>) {
+    let global = go.borrow().transform.global();
+    parent.borrow().add_child(&go.borrow());
+    go.borrow_mut().transform.set_global(global);
+}
+
+fn default_material(asys: &AssetSystem, _: ObjMaterial) -> Rc<Material> {
+    Rc::new(Material::new(asys.new_program("default")))
+}
+
+/// Load a prefab with AssetSystem::new_prefab, or new_prefab_nodes to keep the glTF nodes,
+/// and poll the world until it is loaded
+pub fn wait_prefab(world: &mut World, name: &str, keep_nodes: bool) -> AssetResult<Prefab> {
+    let loaded = Rc::new(RefCell::new(None));
+    {
+        let loaded = loaded.clone();
+        let f = Box::new(move |r: AssetResult<Prefab>| *loaded.borrow_mut() = Some(r));
+        let asys = world.asset_system();
+        if keep_nodes {
+            asys.new_prefab_nodes(name, Box::new(default_material), f);
+        } else {
+            asys.new_prefab(name, Box::new(default_material), f);
+        }
+    }
+
+    for _ in 0..10 {
+        if loaded.borrow().is_some() {
+            break;
+        }
+        assert!(world.poll_events());
+    }
+
+    let result = loaded.borrow_mut().take();
+    result.expect("The prefab is not loaded")
+}
+
+pub fn assert_near_within(a: Vector3f, b: Vector3f, epsilon: f32) {
+    assert!((a - b).magnitude() < epsilon, "{:?} != {:?}", a, b);
+}
+
+pub fn assert_near(a: Vector3f, b: Vector3f) {
+    assert_near_within(a, b, 0.001);
+}
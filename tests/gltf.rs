@@ -0,0 +1,88 @@
+extern crate unrust;
+
+mod common;
+
+use common::{new_world, wait_prefab};
+use std::rc::Rc;
+use unrust::engine::{AssetSystem, Material, Mesh, ObjMaterial};
+use unrust::math::*;
+
+fn build_material(asys: &AssetSystem, obj_mat: ObjMaterial) -> Rc<Material> {
+    let material = Material::new(asys.new_program("default"));
+    material.set("uMaterial.diffuse", asys.new_texture("default_white"));
+    material.set(
+        "uMaterial.shininess",
+        obj_mat.shininess.unwrap_or(32.0),
+    );
+
+    Rc::new(material)
+}
+
+#[test]
+fn test_gltf_hierarchy() {
+    let mut world = new_world("glTF");
+    let parent = world.new_game_object();
+
+    world.load_prefab(
+        "gltf_test_model.gltf",
+        Box::new(build_material),
+        &parent,
+    );
+
+    for _ in 0..10 {
+        if parent.borrow().childen().len() > 0 {
+            break;
+        }
+        assert!(world.poll_events());
+    }
+
+    let body = parent.borrow().childen();
+    assert_eq!(body.len(), 1);
+
+    let body = body[0].borrow();
+    assert_eq!(body.transform.local().disp, Vector3::new(0.0, 1.0, 0.0));
+    assert_eq!(body.find_component::<Mesh>().unwrap().0.surfaces.len(), 1);
+
+    let children = body.childen();
+    assert_eq!(children.len(), 2);
+
+    let arm = children[0].borrow();
+    assert_eq!(arm.transform.local().disp, Vector3::new(2.0, 0.0, 0.0));
+    assert_eq!(arm.transform.local_scale(), Vector3::new(0.5, 0.5, 0.5));
+    assert!(arm.find_component::<Mesh>().is_some());
+
+    // Nodes without mesh are kept as empty objects
+    let pivot = children[1].borrow();
+    assert_eq!(pivot.transform.local().disp, Vector3::new(0.0, 0.0, -3.0));
+    assert!(pivot.find_component::<Mesh>().is_none());
+}
+
+#[test]
+fn test_gltf_new_prefab_flattened() {
+    let mut world = new_world("glTF");
+
+    let nodes = wait_prefab(&mut world, "gltf_test_model.gltf", true).unwrap();
+    let flat = wait_prefab(&mut world, "gltf_test_model.gltf", false).unwrap();
+
+    // The meshes of body and arm share a material, they are merged into one surface
+    assert!(flat.children.is_empty());
+    assert_eq!(flat.components.len(), 1);
+    let mesh = flat.components[0].try_as::<Mesh>().unwrap().borrow();
+    assert_eq!(mesh.surfaces.len(), 1);
+
+    let body = nodes.children[0].components[0]
+        .try_as::<Mesh>()
+        .unwrap()
+        .borrow();
+    let source = body.surfaces[0].buffer.data().unwrap();
+    let merged = mesh.surfaces[0].buffer.data().unwrap();
+    assert_eq!(merged.vertices.len(), source.vertices.len() * 2);
+
+    // The body is translated by its node
+    for (a, b) in source.vertices.chunks(3).zip(merged.vertices.chunks(3)) {
+        assert_eq!(
+            Vector3::new(a[0], a[1] + 1.0, a[2]),
+            Vector3::new(b[0], b[1], b[2])
+        );
+    }
+}
@@ -0,0 +1,863 @@
+#![cfg(feature = "mock_gl")]
+
+extern crate unrust;
+extern crate webgl;
+
+mod common;
+
+use common::add_mesh_with;
+use std::collections::HashMap;
+use std::rc::Rc;
+use unrust::actors::{FirstPersonCamera, ShadowFilter, ShadowPass};
+use unrust::engine::{Asset, AssetSystem, Blending, Directional, GameObject, Light, Material,
+                     Mesh, MeshBuffer, MeshData, MeshIndices, ObjMaterial, ParticleSystem,
+                     PbrMaterial, Point, PostProcessEffect, PostProcessStack, Projection,
+                     RenderQueue, RenderingPath, Skeleton, Spot, Sprite, Tilemap, Tileset,
+                     U16_VERTEX_LIMIT};
+use unrust::math::*;
+use unrust::world::{Handle, World, WorldBuilder};
+use webgl::{BlendMode, BufferKind, ColorBuffer, DataType, GLCommand, PixelType, UniformValue};
+
+fn new_cube(world: &mut World, tex: &str, queue: RenderQueue) -> Handle<GameObject> {
+    let material = {
+        let db = world.asset_system();
+        let mut material = Material::new(db.new_program("default"));
+        material.render_queue = queue;
+        material.set("uMaterial.diffuse", db.new_texture(tex));
+        material.set("uMaterial.shininess", 32.0);
+        Rc::new(material)
+    };
+
+    add_mesh_with(world, "cube", &material, Vector3::zero())
+}
+
+fn new_world() -> World {
+    let mut world = WorldBuilder::new("MockGL")
+        .with_headless(true)
+        .with_size((640, 480))
+        .with_processor::<FirstPersonCamera>()
+        .build();
+
+    {
+        let go = world.new_game_object();
+        go.borrow_mut()
+            .add_component(Light::new(Directional::default()));
+    }
+
+    // Add the transparent one first, the engine should sort it out.
+    new_cube(&mut world, "default_green", RenderQueue::Transparent);
+    new_cube(&mut world, "default_red", RenderQueue::Opaque);
+
+    // Warm up, the camera and all assets get ready in the first frames
+    for _ in 0..3 {
+        assert!(world.poll_events());
+    }
+
+    {
+        let fpc = world.find_component::<FirstPersonCamera>().unwrap();
+        fpc.borrow_mut().eye = Vector3::new(0.0, 0.0, -9.0);
+        fpc.borrow_mut().update_camera();
+    }
+
+    world
+}
+
+#[test]
+fn test_opaque_before_transparent() {
+    let mut world = new_world();
+
+    world.engine().gl.clear_commands();
+    assert!(world.poll_events());
+
+    let calls = world.engine().gl.draw_calls();
+    let last_opaque = calls.iter().rposition(|c| !c.blend);
+    let first_transparent = calls.iter().position(|c| c.blend);
+
+    assert!(last_opaque.is_some(), "No opaque draw call: {:?}", calls);
+    assert!(
+        first_transparent.is_some(),
+        "No transparent draw call: {:?}",
+        calls
+    );
+    assert!(last_opaque < first_transparent);
+}
+
+#[test]
+fn test_texture_uploaded_once() {
+    let mut world = new_world();
+
+    for _ in 0..10 {
+        assert!(world.poll_events());
+    }
+
+    let mut uploads = HashMap::new();
+    for cmd in world.engine().gl.commands() {
+        if let GLCommand::TexImage2D {
+            texture,
+            target,
+            level,
+            ..
+        } = cmd
+        {
+            *uploads.entry((texture, target as u32, level)).or_insert(0) += 1;
+        }
+    }
+
+    assert!(!uploads.is_empty());
+    for (key, count) in uploads {
+        assert_eq!(count, 1, "Texture {:?} is uploaded {} times", key, count);
+    }
+}
+
+fn new_large_mesh(world: &mut World) -> usize {
+    let n = U16_VERTEX_LIMIT + 1000;
+    let mut data = MeshData::default();
+    let mut indices = Vec::new();
+
+    for i in 0..n {
+        let (x, y) = ((i % 256) as f32 / 256.0, (i / 256) as f32 / 256.0);
+        data.vertices.extend_from_slice(&[x, y, 0.0]);
+    }
+    for i in 0..(n - 2) as u32 {
+        indices.extend_from_slice(&[i, i + 1, i + 2]);
+    }
+    data.indices = MeshIndices::with_vertex_count(indices, n);
+    let count = data.indices.len();
+
+    let mut mesh = Mesh::new();
+    {
+        let db = world.asset_system();
+        let material = Material::new(db.new_program("default"));
+        material.set("uMaterial.diffuse", db.new_texture("default_white"));
+        mesh.add_surface(MeshBuffer::new(data), material);
+    }
+
+    let go = world.new_game_object();
+    go.borrow_mut().add_component(mesh);
+
+    count
+}
+
+/// Index count and type of all draw calls larger than a cube
+fn large_element_draws(world: &World) -> Vec<(usize, DataType)> {
+    world
+        .engine()
+        .gl
+        .commands()
+        .into_iter()
+        .filter_map(|cmd| match cmd {
+            GLCommand::DrawElements { count, kind, .. } if count > 36 => Some((count, kind)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_large_mesh_u32_indices() {
+    let mut world = new_world();
+    let count = new_large_mesh(&mut world);
+
+    world.engine().gl.clear_commands();
+    assert!(world.poll_events());
+
+    assert_eq!(large_element_draws(&world), vec![(count, DataType::U32)]);
+}
+
+#[test]
+fn test_large_mesh_split_without_u32_indices() {
+    let mut world = new_world();
+    world.engine_mut().gl.common.element_index_uint = false;
+    let count = new_large_mesh(&mut world);
+
+    world.engine().gl.clear_commands();
+    assert!(world.poll_events());
+
+    let draws = large_element_draws(&world);
+    assert!(draws.len() > 1, "Mesh is not split: {:?}", draws);
+    assert!(draws.iter().all(|&(_, kind)| kind == DataType::U16));
+    assert_eq!(draws.iter().map(|&(c, _)| c).sum::<usize>(), count);
+}
+
+fn build_obj_material(asys: &AssetSystem, _: ObjMaterial) -> Rc<Material> {
+    let material = Material::new(asys.new_program("default"));
+    material.set("uMaterial.diffuse", asys.new_texture("default_white"));
+    Rc::new(material)
+}
+
+#[test]
+fn test_obj_vertices_welded() {
+    let mut world = new_world();
+    let parent = world.new_game_object();
+    world.load_prefab("weld_test_cube.obj", Box::new(build_obj_material), &parent);
+
+    for _ in 0..10 {
+        world.engine().gl.clear_commands();
+        assert!(world.poll_events());
+        if parent.borrow().childen().len() > 0 {
+            break;
+        }
+    }
+
+    let mut array_buffers = Vec::new();
+    let mut element_buffers = Vec::new();
+    for cmd in world.engine().gl.commands() {
+        match cmd {
+            GLCommand::BufferData {
+                kind: BufferKind::Array,
+                len,
+                ..
+            } => array_buffers.push(len),
+            GLCommand::BufferData {
+                kind: BufferKind::ElementArray,
+                len,
+                ..
+            } => element_buffers.push(len),
+            _ => {}
+        }
+    }
+
+    // 8 welded corners with positions and generated normals, 36 u16 indices
+    assert_eq!(array_buffers, vec![8 * 12, 8 * 12]);
+    assert_eq!(element_buffers, vec![36 * 2]);
+}
+
+#[test]
+fn test_skinned_mesh_uniforms() {
+    let mut world = new_world();
+
+    let data = MeshData {
+        vertices: vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
+        joints: Some(vec![0.0; 12]),
+        weights: Some(vec![
+            1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0,
+        ]),
+        indices: vec![0u16, 1, 2].into(),
+        ..MeshData::default()
+    };
+
+    let mut mesh = Mesh::new();
+    {
+        let db = world.asset_system();
+        let material = Material::new(db.new_program("default"));
+        material.set("uMaterial.diffuse", db.new_texture("default_white"));
+        mesh.add_surface(MeshBuffer::new(data), material);
+    }
+
+    let go = world.new_game_object();
+    let joint = world.new_game_object();
+    go.borrow().add_child(&joint.borrow());
+
+    let skeleton = Skeleton::from_bind_pose(&go.borrow(), &[joint.clone()]).unwrap();
+    go.borrow_mut().add_component(mesh);
+    go.borrow_mut().add_component(skeleton);
+    assert!(world.poll_events());
+
+    joint.borrow_mut().transform.set_local(Isometry3 {
+        scale: 1.0,
+        rot: Quaternion::one(),
+        disp: Vector3::new(0.0, 2.0, 0.0),
+    });
+
+    world.engine().gl.clear_commands();
+    assert!(world.poll_events());
+
+    let commands = world.engine().gl.commands();
+    let uniform = |name: &str| {
+        commands
+            .iter()
+            .filter_map(|cmd| match *cmd {
+                GLCommand::Uniform {
+                    name: ref n,
+                    ref value,
+                    ..
+                } if n == name => Some(value.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert!(uniform("uSkinned").contains(&UniformValue::I32(1)));
+
+    let joint_m = match uniform("uJointMatrices[0]").last() {
+        Some(&UniformValue::Matrix4(m)) => m,
+        other => panic!("No joint matrix: {:?}", other),
+    };
+    assert_eq!(joint_m[3], [0.0, 2.0, 0.0, 1.0]);
+}
+
+#[test]
+fn test_shared_meshes_drawn_instanced() {
+    let mut world = new_world();
+    let n = 20;
+
+    let (buffer, material) = {
+        let db = world.asset_system();
+        let material = Rc::new(Material::new(db.new_program("default")));
+        material.set("uMaterial.diffuse", db.new_texture("default_white"));
+        (db.new_mesh_buffer("cube"), material)
+    };
+
+    for i in 0..n {
+        let mut mesh = Mesh::new();
+        mesh.add_surface(buffer.clone(), material.clone());
+
+        let go = world.new_game_object();
+        let mut go = go.borrow_mut();
+        go.add_component(mesh);
+        go.transform.set_global(Isometry3 {
+            scale: 0.1,
+            rot: Quaternion::one(),
+            disp: Vector3::new(i as f32 * 0.1 - 1.0, 0.0, 0.0),
+        });
+    }
+    assert!(world.poll_events());
+
+    world.engine().gl.clear_commands();
+    assert!(world.poll_events());
+
+    let instanced = |world: &World| {
+        world
+            .engine()
+            .gl
+            .commands()
+            .into_iter()
+            .filter_map(|cmd| match cmd {
+                GLCommand::DrawElementsInstanced { instances, .. } => Some(instances),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(instanced(&world), vec![n]);
+    let calls = world.engine().gl.draw_calls();
+    assert_eq!(world.engine().stats.batch_count as usize, calls.len());
+    assert!(calls.len() < n);
+
+    // Without the extension, every object is drawn on its own
+    world.engine_mut().gl.common.instanced_arrays = false;
+    world.engine().gl.clear_commands();
+    assert!(world.poll_events());
+
+    assert!(instanced(&world).is_empty());
+    let calls = world.engine().gl.draw_calls();
+    assert_eq!(world.engine().stats.batch_count as usize, calls.len());
+    assert!(calls.len() > n);
+}
+
+#[test]
+fn test_lights_selected_per_object() {
+    let mut world = new_world();
+
+    let add_light = |world: &mut World, light: Light| {
+        let go = world.new_game_object();
+        go.borrow_mut().add_component(light);
+    };
+
+    add_light(&mut world, Light::new(Directional::default()));
+    for x in [11.0, 3.0, 9.0, 5.0, 7.0].iter() {
+        add_light(
+            &mut world,
+            Light::new(Point {
+                position: Vector3::new(*x, 0.0, 0.0),
+                ..Point::default()
+            }),
+        );
+    }
+
+    // Near, but too short ranged to reach the cubes
+    add_light(
+        &mut world,
+        Light::new(Point {
+            position: Vector3::new(4.0, 0.0, 0.0),
+            quadratic: 100.0,
+            ..Point::default()
+        }),
+    );
+
+    add_light(
+        &mut world,
+        Light::new(Spot {
+            position: Vector3::new(0.0, 5.0, 0.0),
+            ..Spot::default()
+        }),
+    );
+
+    world.engine().gl.clear_commands();
+    assert!(world.poll_events());
+
+    let commands = world.engine().gl.commands();
+    let uniform = |name: &str| {
+        commands
+            .iter()
+            .filter_map(|cmd| match *cmd {
+                GLCommand::Uniform {
+                    name: ref n,
+                    ref value,
+                    ..
+                } if n == name => Some(value.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // The 4 nearest point lights in range
+    let mut xs: Vec<f32> = (0..4)
+        .flat_map(|i| uniform(&format!("uPointLights[{}].position", i)))
+        .filter_map(|v| match v {
+            UniformValue::Vec3((x, _, _)) => Some(x),
+            _ => None,
+        })
+        .collect();
+    xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    xs.dedup();
+    assert_eq!(xs, vec![3.0, 5.0, 7.0, 9.0]);
+
+    let cut_off = Deg(12.5f32).cos();
+    assert!(uniform("uSpotLights[0].cutOff").contains(&UniformValue::F32(cut_off)));
+    assert!(!uniform("uDirectionalLights[1].diffuse").is_empty());
+
+    // A variant of the program is compiled for these light counts
+    let programs = commands
+        .iter()
+        .filter(|cmd| match **cmd {
+            GLCommand::LabelProgram(_, ref label) => label == "default",
+            _ => false,
+        })
+        .count();
+    assert!(programs > 0);
+}
+
+#[test]
+fn test_pbr_material_drawn() {
+    let mut world = new_world();
+
+    let mut mesh = Mesh::new();
+    {
+        let db = world.asset_system();
+        let material = PbrMaterial::new().with_roughness(0.5).build(db);
+        mesh.add_surface(db.new_mesh_buffer("cube"), material);
+    }
+    let go = world.new_game_object();
+    go.borrow_mut().add_component(mesh);
+
+    world.engine().gl.clear_commands();
+    for _ in 0..3 {
+        assert!(world.poll_events());
+    }
+
+    let commands = world.engine().gl.commands();
+    assert!(commands.iter().any(|cmd| match *cmd {
+        GLCommand::LabelProgram(_, ref label) => label == "unrust/pbr",
+        _ => false,
+    }));
+
+    // The environment cube map is bound for the lighting
+    assert!(commands.iter().any(|cmd| match *cmd {
+        GLCommand::Uniform { ref name, .. } => name == "uEnvironmentMap",
+        _ => false,
+    }));
+}
+
+#[test]
+fn test_post_process_stack() {
+    let mut world = new_world();
+
+    let stack = {
+        let db = world.asset_system();
+        PostProcessStack::new()
+            .with_effect(PostProcessEffect::bloom(db))
+            .with_effect(PostProcessEffect::tone_mapping(db, 1.5))
+    };
+    world.current_camera().unwrap().borrow_mut().post_process = Some(Rc::new(stack));
+
+    // Let the post process programs load
+    world.engine().gl.clear_commands();
+    for _ in 0..3 {
+        assert!(world.poll_events());
+    }
+
+    let commands = world.engine().gl.commands();
+    assert!(commands.iter().any(|cmd| match *cmd {
+        GLCommand::TexImage2D { kind, .. } => kind == PixelType::HalfFloat,
+        _ => false,
+    }));
+
+    let program = |name: &str| {
+        commands
+            .iter()
+            .filter_map(|cmd| match *cmd {
+                GLCommand::LabelProgram(p, ref label) if label == name => Some(p),
+                _ => None,
+            })
+            .next()
+    };
+    let (bright, tonemap) = (
+        program("unrust/post_bloom_bright"),
+        program("unrust/post_tonemap"),
+    );
+    assert!(bright.is_some() && tonemap.is_some());
+
+    world.engine().gl.clear_commands();
+    assert!(world.poll_events());
+
+    let gl = &world.engine().gl;
+    let calls = gl.draw_calls();
+    let post = calls
+        .iter()
+        .position(|c| Some(c.program) == bright)
+        .unwrap();
+
+    // The scene is drawn into a render texture, the last pass onto the screen
+    assert!(post > 0);
+    assert!(calls[..post].iter().all(|c| c.framebuffer != 0));
+    assert!(calls[post..].iter().all(|c| !c.blend && !c.depth_mask));
+
+    let last = calls.last().unwrap();
+    assert_eq!(Some(last.program), tonemap);
+    assert_eq!(last.framebuffer, 0);
+
+    // Bright pass, 2 blur iterations of 2 passes and the combine pass
+    assert_eq!(calls.len() - post, 7);
+    assert_eq!(
+        gl.uniform_value(tonemap.unwrap(), "uExposure"),
+        Some(UniformValue::F32(1.5))
+    );
+}
+
+#[test]
+fn test_deferred_path() {
+    let mut world = new_world();
+    world.current_camera().unwrap().borrow_mut().rendering_path = RenderingPath::Deferred;
+
+    let mut mesh = Mesh::new();
+    {
+        let db = world.asset_system();
+        let mut material = Material::new(db.new_program("phong"));
+        material.gbuffer_program = Some(db.new_program("unrust/gbuffer"));
+        material.set("uMaterial.diffuse", db.new_texture("default_white"));
+        material.set("uMaterial.shininess", 32.0);
+        mesh.add_surface(db.new_mesh_buffer("cube"), material);
+    }
+    let go = world.new_game_object();
+    go.borrow_mut().add_component(mesh);
+
+    // More point lights than a forward draw can have
+    for i in 0..6 {
+        let go = world.new_game_object();
+        go.borrow_mut().add_component(Light::new(Point {
+            position: Vector3::new(i as f32, 2.0, 0.0),
+            ..Point::default()
+        }));
+    }
+
+    world.engine().gl.clear_commands();
+    for _ in 0..3 {
+        assert!(world.poll_events());
+    }
+
+    let commands = world.engine().gl.commands();
+    assert!(commands.iter().any(|cmd| match *cmd {
+        GLCommand::DrawBuffer(ref buffers) => {
+            buffers.len() == 3 && buffers[2] == ColorBuffer::ColorAttachment2
+        }
+        _ => false,
+    }));
+
+    let programs = |name: &str| {
+        commands
+            .iter()
+            .filter_map(|cmd| match *cmd {
+                GLCommand::LabelProgram(p, ref label) if label == name => Some(p),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    let (gbuffer, light) = (
+        programs("unrust/gbuffer"),
+        programs("unrust/deferred_light"),
+    );
+    assert!(!gbuffer.is_empty() && !light.is_empty());
+
+    world.engine().gl.clear_commands();
+    assert!(world.poll_events());
+
+    let calls = world.engine().gl.draw_calls();
+    let gbuffer_calls: Vec<_> = calls
+        .iter()
+        .filter(|c| gbuffer.contains(&c.program))
+        .collect();
+    assert_eq!(gbuffer_calls.len(), 1);
+    assert!(gbuffer_calls[0].framebuffer != 0);
+
+    // All the lights in two batches, the second one added to the first
+    let light_calls: Vec<_> = calls
+        .iter()
+        .filter(|c| light.contains(&c.program))
+        .collect();
+    assert_eq!(light_calls.len(), 2);
+    assert!(light_calls.iter().all(|c| c.framebuffer == 0));
+    assert!(!light_calls[0].blend && light_calls[1].blend);
+
+    // The other surfaces are drawn forward
+    assert!(calls.len() > 3);
+}
+
+#[test]
+fn test_vsm_shadow_filter() {
+    let mut world = WorldBuilder::new("MockGL")
+        .with_headless(true)
+        .with_size((640, 480))
+        .with_processor::<FirstPersonCamera>()
+        .with_processor::<ShadowPass>()
+        .build();
+
+    {
+        let go = world.new_game_object();
+        go.borrow_mut()
+            .add_component(Light::new(Directional::default()));
+    }
+    new_cube(&mut world, "default_red", RenderQueue::Opaque);
+
+    {
+        let shadow_pass = world.find_component::<ShadowPass>().unwrap();
+        let mut shadow_pass = shadow_pass.borrow_mut();
+        shadow_pass.set_filter(ShadowFilter::Vsm);
+        shadow_pass.set_cascade_count(2);
+        shadow_pass.set_split_lambda(0.5);
+    }
+
+    world.engine().gl.clear_commands();
+    for _ in 0..3 {
+        assert!(world.poll_events());
+    }
+
+    let vsm: Vec<_> = world
+        .engine()
+        .gl
+        .commands()
+        .iter()
+        .filter_map(|cmd| match *cmd {
+            GLCommand::LabelProgram(p, ref label) if label == "unrust/shadow_vsm" => Some(p),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(vsm.len(), 1);
+
+    world.engine().gl.clear_commands();
+    assert!(world.poll_events());
+
+    // The cube is drawn once in each cascade, into the moments texture
+    let calls = world.engine().gl.draw_calls();
+    let vsm_calls: Vec<_> = calls.iter().filter(|c| c.program == vsm[0]).collect();
+    assert_eq!(vsm_calls.len(), 2);
+    assert!(vsm_calls.iter().all(|c| c.framebuffer != 0));
+}
+
+#[test]
+fn test_split_screen_cameras() {
+    let mut world = new_world();
+    world.current_camera().unwrap().borrow_mut().rect = Some(((0, 0), (320, 480)));
+
+    // Drawn after the first one, with the objects of layer 2 only
+    {
+        let mut camera = world.current_camera().unwrap().borrow().clone();
+        camera.rect = Some(((320, 0), (320, 480)));
+        camera.depth = 1;
+        camera.culling_mask = 2;
+
+        let go = world.new_game_object();
+        go.borrow_mut().add_component(camera);
+    }
+
+    let cube = new_cube(&mut world, "default_white", RenderQueue::Opaque);
+    cube.borrow_mut().layers = 2;
+
+    for _ in 0..3 {
+        assert!(world.poll_events());
+    }
+
+    world.engine().gl.clear_commands();
+    assert!(world.poll_events());
+
+    let mut viewport = None;
+    let mut scissors = Vec::new();
+    let mut draws = HashMap::new();
+    for cmd in world.engine().gl.commands() {
+        match cmd {
+            GLCommand::Viewport(x, y, w, h) => viewport = Some((x, y, w, h)),
+            GLCommand::Scissor(x, y, w, h) => scissors.push((x, y, w, h)),
+            GLCommand::DrawElements { .. } | GLCommand::DrawElementsInstanced { .. } => {
+                *draws.entry(viewport.unwrap()).or_insert(0) += 1;
+            }
+            _ => (),
+        }
+    }
+
+    // Each camera only clears its own viewport
+    assert_eq!(scissors, vec![(0, 0, 320, 480), (320, 0, 320, 480)]);
+    assert_eq!(draws.get(&(0, 0, 320, 480)), Some(&3));
+    assert_eq!(draws.get(&(320, 0, 320, 480)), Some(&1));
+}
+
+#[test]
+fn test_sprites_batched() {
+    let mut world = new_world();
+    world.current_camera().unwrap().borrow_mut().projection = Projection::orthographic(5.0);
+
+    let (white, red) = {
+        let db = world.asset_system();
+        (db.new_texture("default_white"), db.new_texture("default_red"))
+    };
+
+    for i in 0..1000 {
+        let go = world.new_game_object();
+        let mut go = go.borrow_mut();
+        go.add_component(Sprite::new(white.clone()).with_pixels_per_unit(8.0));
+        go.transform.set_global(Isometry3 {
+            scale: 1.0,
+            rot: Quaternion::one(),
+            disp: Vector3::new((i % 40) as f32 * 0.2 - 4.0, 0.0, 0.0),
+        });
+    }
+
+    // Drawn last by its sorting layer
+    {
+        let go = world.new_game_object();
+        go.borrow_mut()
+            .add_component(Sprite::new(red.clone()).with_sorting(1, 0));
+    }
+
+    // Culled
+    {
+        let go = world.new_game_object();
+        let mut go = go.borrow_mut();
+        go.add_component(Sprite::new(red.clone()));
+        go.transform.set_global(Isometry3 {
+            scale: 1.0,
+            rot: Quaternion::one(),
+            disp: Vector3::new(100.0, 0.0, 0.0),
+        });
+    }
+
+    world.engine().gl.clear_commands();
+    assert!(world.poll_events());
+
+    // The cubes are drawn with 36 indices
+    let sprite_draws: Vec<_> = world
+        .engine()
+        .gl
+        .draw_calls()
+        .into_iter()
+        .filter(|c| c.count != 36)
+        .collect();
+
+    assert_eq!(sprite_draws.len(), 2, "{:?}", sprite_draws);
+    assert_eq!(sprite_draws[0].count, 1000 * 6);
+    assert_eq!(sprite_draws[1].count, 6);
+    assert!(sprite_draws.iter().all(|c| c.blend && !c.cull_face));
+}
+
+#[test]
+fn test_tilemap_chunks_drawn() {
+    let mut world = new_world();
+    world.current_camera().unwrap().borrow_mut().projection = Projection::orthographic(5.0);
+
+    let tileset = {
+        let texture = world.asset_system().new_texture("default_white");
+        Rc::new(Tileset::new(texture, (16, 16), (4, 4)))
+    };
+
+    let mut tilemap = Tilemap::new((24, 4), Vector2::new(0.5, 0.5))
+        .with_chunk_size(8)
+        .with_tileset(tileset);
+    let layer = tilemap.add_layer("ground");
+    for y in 0..4 {
+        for x in 0..24 {
+            tilemap.set_tile(layer, x, y, 1 + (x + y) % 16);
+        }
+    }
+
+    let go = world.new_game_object();
+    go.borrow_mut().add_component(tilemap);
+    go.borrow_mut().transform.set_global(Isometry3 {
+        scale: 1.0,
+        rot: Quaternion::one(),
+        disp: Vector3::new(-6.0, -1.0, 0.0),
+    });
+
+    world.engine().gl.clear_commands();
+    assert!(world.poll_events());
+
+    // A draw per chunk of 8 * 4 tiles, the cubes are drawn with 36 indices
+    let tile_draws: Vec<_> = world
+        .engine()
+        .gl
+        .draw_calls()
+        .into_iter()
+        .filter(|c| c.count != 36)
+        .collect();
+    assert_eq!(tile_draws.len(), 3, "{:?}", tile_draws);
+    assert!(tile_draws.iter().all(|c| c.count == 32 * 6));
+    assert!(tile_draws.iter().all(|c| c.blend && !c.cull_face));
+
+    {
+        let go = go.borrow();
+        let (mut tilemap, _) = go.find_component_mut::<Tilemap>().unwrap();
+        tilemap.set_tile(layer, 20, 3, 0);
+    }
+
+    world.engine().gl.clear_commands();
+    assert!(world.poll_events());
+
+    // Only the indices of the changed chunk are uploaded again
+    let uploads: Vec<_> = world
+        .engine()
+        .gl
+        .commands()
+        .into_iter()
+        .filter_map(|cmd| match cmd {
+            GLCommand::BufferData {
+                kind: BufferKind::ElementArray,
+                len,
+                ..
+            } => Some(len),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(uploads, vec![31 * 6 * 2]);
+}
+
+#[test]
+fn test_particles_drawn_additive() {
+    let mut world = new_world();
+
+    let ps = ParticleSystem::new(world.asset_system().new_texture("default_white"))
+        .with_rate(0.0)
+        .with_burst(0.0, 50)
+        .with_lifetime(100.0, 100.0)
+        .with_speed(0.0, 0.0)
+        .with_blending(Blending::Additive);
+    {
+        let go = world.new_game_object();
+        go.borrow_mut().add_component(ps);
+    }
+
+    // Emitted in the first frame
+    assert!(world.poll_events());
+    world.engine().gl.clear_commands();
+    assert!(world.poll_events());
+
+    let particle_draws: Vec<_> = world
+        .engine()
+        .gl
+        .draw_calls()
+        .into_iter()
+        .filter(|c| c.count != 36)
+        .collect();
+    assert_eq!(particle_draws.len(), 1, "{:?}", particle_draws);
+    assert_eq!(particle_draws[0].count, 50 * 6);
+    assert!(particle_draws[0].blend && !particle_draws[0].cull_face);
+
+    let commands = world.engine().gl.commands();
+    assert!(commands.contains(&GLCommand::BlendFunc(BlendMode::SrcAlpha, BlendMode::One)));
+}
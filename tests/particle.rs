@@ -0,0 +1,247 @@
+extern crate unrust;
+
+mod common;
+
+use common::{assert_near, new_world, world_builder};
+use std::rc::Rc;
+use unrust::actors::FirstPersonCamera;
+use unrust::engine::{Camera, Curve, MeshBuffer, MeshData, ParticleShape, ParticleSystem};
+use unrust::math::*;
+use unrust::world::World;
+
+fn new_system(world: &World) -> ParticleSystem {
+    ParticleSystem::new(world.asset_system().new_texture("default_white"))
+        .with_rate(0.0)
+        .with_lifetime(10.0, 10.0)
+        .with_seed(7)
+}
+
+#[test]
+fn test_bursts_through_cycles() {
+    let world = new_world("Particle");
+    let mut ps = new_system(&world)
+        .with_duration(1.0, true)
+        .with_burst(0.5, 10);
+    let m = Matrix4::identity();
+
+    ps.update(0.4, &m);
+    assert_eq!(ps.particles().len(), 0);
+    ps.update(0.2, &m);
+    assert_eq!(ps.particles().len(), 10);
+
+    // From 0.6 to the end of the cycle, then to 0.6 of the next one
+    ps.update(1.0, &m);
+    assert_eq!(ps.particles().len(), 20);
+    assert!((ps.time() - 0.6).abs() < 0.001);
+}
+
+#[test]
+fn test_rate_and_duration() {
+    let world = new_world("Particle");
+    let mut ps = new_system(&world)
+        .with_duration(2.0, false)
+        .with_rate(10.0);
+    let m = Matrix4::identity();
+
+    // The fractions of particles are kept for the next frames
+    for _ in 0..4 {
+        ps.update(0.25, &m);
+    }
+    assert_eq!(ps.particles().len(), 10);
+
+    // Not looping, the emission stops at the end of the duration
+    ps.update(5.0, &m);
+    assert_eq!(ps.particles().len(), 20);
+    assert!(!ps.is_playing());
+
+    ps.play();
+    ps.update(0.5, &m);
+    assert_eq!(ps.particles().len(), 25);
+}
+
+#[test]
+fn test_max_particles() {
+    let world = new_world("Particle");
+    let mut ps = new_system(&world).with_max_particles(8);
+
+    ps.emit(5, &Matrix4::identity());
+    ps.emit(5, &Matrix4::identity());
+    assert_eq!(ps.particles().len(), 8);
+
+    ps.clear();
+    assert_eq!(ps.particles().len(), 0);
+}
+
+#[test]
+fn test_gravity_and_lifetime() {
+    let world = new_world("Particle");
+    let mut ps = new_system(&world)
+        .with_lifetime(1.0, 1.0)
+        .with_speed(0.0, 0.0)
+        .with_gravity(Vector3::new(0.0, -10.0, 0.0));
+    ps.stop();
+
+    let m = Matrix4::from_translation(Vector3::new(1.0, 2.0, 3.0));
+    ps.emit(1, &m);
+    assert_eq!(ps.particles()[0].position, Vector3::new(1.0, 2.0, 3.0));
+
+    ps.update(0.5, &m);
+    let p = ps.particles()[0];
+    assert_near(p.velocity, Vector3::new(0.0, -5.0, 0.0));
+    assert_near(p.position, Vector3::new(1.0, -0.5, 3.0));
+
+    ps.update(0.6, &m);
+    assert_eq!(ps.particles().len(), 0);
+}
+
+#[test]
+fn test_shapes() {
+    let world = new_world("Particle");
+    let m = Matrix4::identity();
+
+    let mut ps = new_system(&world).with_shape(ParticleShape::Sphere { radius: 2.0 });
+    ps.emit(50, &m);
+    for p in ps.particles() {
+        assert!(p.position.magnitude() <= 2.0);
+        assert!(p.position.dot(p.velocity) >= 0.0);
+    }
+
+    let mut ps = new_system(&world).with_shape(ParticleShape::Cone {
+        angle: Deg(30.0),
+        radius: 0.5,
+    });
+    ps.emit(50, &m);
+    for p in ps.particles() {
+        assert!(p.position.y == 0.0 && p.position.magnitude() <= 0.5);
+        assert!(p.velocity.normalize().y >= Rad::from(Deg(30.0f32)).0.cos() - 0.001);
+    }
+
+    // A quad facing +z
+    let data = MeshData {
+        vertices: vec![
+            0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0,
+        ],
+        indices: vec![0u16, 1, 2, 0, 2, 3].into(),
+        ..MeshData::default()
+    };
+    let mut ps = new_system(&world).with_shape(ParticleShape::Mesh(MeshBuffer::new(data)));
+    ps.emit(50, &m);
+    assert_eq!(ps.particles().len(), 50);
+    for p in ps.particles() {
+        assert!(p.position.x >= 0.0 && p.position.x <= 1.0);
+        assert!(p.position.y >= 0.0 && p.position.y <= 1.0);
+        assert_near(p.velocity.normalize(), Vector3::unit_z());
+    }
+}
+
+#[test]
+fn test_seeded_systems_are_equal() {
+    let world = new_world("Particle");
+    let mut a = new_system(&world).with_shape(ParticleShape::Sphere { radius: 1.0 });
+    let mut b = new_system(&world).with_shape(ParticleShape::Sphere { radius: 1.0 });
+
+    a.emit(10, &Matrix4::identity());
+    b.emit(10, &Matrix4::identity());
+    for (pa, pb) in a.particles().iter().zip(b.particles()) {
+        assert_eq!(pa.position, pb.position);
+        assert_eq!(pa.velocity, pb.velocity);
+    }
+}
+
+#[test]
+fn test_over_lifetime_curves() {
+    let world = new_world("Particle");
+    let mut ps = new_system(&world)
+        .with_lifetime(2.0, 2.0)
+        .with_size(1.0, 1.0)
+        .with_color(Vector4::new(1.0, 0.5, 1.0, 1.0))
+        .with_size_over_lifetime(Curve::linear(&[(0.0, 1.0), (1.0, 0.0)]))
+        .with_color_over_lifetime(Curve::linear(&[
+            (0.0, Vector4::new(1.0, 1.0, 1.0, 1.0)),
+            (1.0, Vector4::new(1.0, 1.0, 0.0, 0.0)),
+        ]));
+
+    ps.emit(1, &Matrix4::identity());
+    ps.update(0.5, &Matrix4::identity());
+
+    let (size, color) = ps.particle_appearance(&ps.particles()[0]);
+    assert_eq!(size, 0.75);
+    assert_eq!(color, Vector4::new(1.0, 0.5, 0.75, 0.75));
+}
+
+#[test]
+fn test_billboards_sorted_back_to_front() {
+    let world = new_world("Particle");
+    let mut camera = Camera::new();
+    camera.lookat(
+        &Point3::new(0.0, 0.0, 10.0),
+        &Point3::new(0.0, 0.0, 0.0),
+        &Vector3::unit_y(),
+    );
+
+    let mut ps = new_system(&world).with_speed(0.0, 0.0).with_size(2.0, 2.0);
+    for z in [1.0, -3.0, 2.0].iter() {
+        ps.emit(1, &Matrix4::from_translation(Vector3::new(0.0, 0.0, *z)));
+    }
+
+    let surface = ps.build(world.asset_system(), &camera).unwrap();
+    {
+        let data = surface.buffer.data().unwrap();
+        assert_eq!(data.vertices.len(), 3 * 4 * 3);
+        assert_eq!(data.indices.len(), 3 * 6);
+
+        // The farthest first, each quad facing the camera
+        let zs: Vec<f32> = data.vertices.chunks(12).map(|q| q[2]).collect();
+        assert_eq!(zs, vec![-3.0, 1.0, 2.0]);
+        assert_eq!(&data.vertices[..6], &[-1.0, -1.0, -3.0, 1.0, -1.0, -3.0]);
+    }
+
+    // The buffer is kept and updated in the next builds
+    ps.clear();
+    assert!(ps.build(world.asset_system(), &camera).is_none());
+    ps.emit(1, &Matrix4::identity());
+    let rebuilt = ps.build(world.asset_system(), &camera).unwrap();
+    assert!(Rc::ptr_eq(&surface.buffer, &rebuilt.buffer));
+    assert!(Rc::ptr_eq(&surface.material, &rebuilt.material));
+}
+
+#[test]
+fn test_non_finite_particles_are_built() {
+    let world = new_world("Particle");
+    let mut camera = Camera::new();
+    camera.lookat(
+        &Point3::new(0.0, 0.0, 10.0),
+        &Point3::new(0.0, 0.0, 0.0),
+        &Vector3::unit_y(),
+    );
+
+    let mut ps = new_system(&world).with_speed(0.0, 0.0);
+    for z in [1.0, ::std::f32::NAN, 2.0].iter() {
+        ps.emit(1, &Matrix4::from_translation(Vector3::new(0.0, 0.0, *z)));
+    }
+
+    let surface = ps.build(world.asset_system(), &camera).unwrap();
+    assert_eq!(surface.buffer.data().unwrap().indices.len(), 3 * 6);
+}
+
+#[test]
+fn test_non_finite_particles_are_rendered() {
+    let mut world = world_builder("Particle")
+        .with_processor::<FirstPersonCamera>()
+        .build();
+
+    // Two systems in the transparent queue, one of them at a non-finite distance
+    let mut objects = Vec::new();
+    for x in [0.0, ::std::f32::NAN].iter() {
+        let mut ps = new_system(&world).with_speed(0.0, 0.0);
+        ps.emit(1, &Matrix4::from_translation(Vector3::new(*x, 0.0, 0.0)));
+
+        let go = world.new_game_object();
+        go.borrow_mut().add_component(ps);
+        objects.push(go);
+    }
+
+    for _ in 0..3 {
+        assert!(world.poll_events());
+    }
+}
@@ -0,0 +1,97 @@
+extern crate unrust;
+
+mod common;
+
+use common::new_world;
+use unrust::engine::{AssetSystem, CullMode, Material, MaterialParam, ObjMaterial, PbrMaterial,
+                     RenderQueue, DEFAULT_ENVIRONMENT_MAP};
+use unrust::math::*;
+
+fn float(material: &Material, name: &str) -> f32 {
+    match material.params().get(name) {
+        Some(&MaterialParam::Float(v)) => v,
+        p => panic!("{} is not a float: {:?}", name, p),
+    }
+}
+
+fn texture_name(asys: &AssetSystem, material: &Material, name: &str) -> String {
+    match material.params().get(name) {
+        Some(&MaterialParam::Texture(ref tex)) => asys.texture_name(&tex.0).unwrap(),
+        p => panic!("{} is not a texture: {:?}", name, p),
+    }
+}
+
+#[test]
+fn test_default_maps() {
+    let world = new_world("Pbr");
+    let asys = world.asset_system();
+
+    let material = PbrMaterial::new()
+        .with_metallic(0.0)
+        .with_roughness(0.5)
+        .build(asys);
+
+    assert_eq!(asys.program_name(&material.program).unwrap(), "unrust/pbr");
+    assert_eq!(float(&material, "uMaterial.metallic"), 0.0);
+    assert_eq!(float(&material, "uMaterial.roughness"), 0.5);
+    assert_eq!(float(&material, "uEnvironmentIntensity"), 1.0);
+    assert_eq!(
+        material.params().get("uMaterial.hasNormalMap"),
+        Some(&MaterialParam::Bool(false))
+    );
+
+    let tex = |name: &str| texture_name(asys, &material, name);
+    assert_eq!(tex("uMaterial.baseColorMap"), "default_white");
+    assert_eq!(tex("uMaterial.metallicRoughnessMap"), "default_white");
+    assert_eq!(tex("uMaterial.normalMap"), "default_normal_map");
+    assert_eq!(tex("uEnvironmentMap"), DEFAULT_ENVIRONMENT_MAP);
+
+    assert_eq!(material.render_queue, RenderQueue::Opaque);
+    assert_eq!(material.states.cull, None);
+}
+
+#[test]
+fn test_from_obj_material() {
+    let world = new_world("Pbr");
+    let asys = world.asset_system();
+
+    let obj_mat = ObjMaterial {
+        diffuse: Some(Vector3::new(1.0, 0.5, 0.25)),
+        alpha: Some(0.5),
+        metallic: Some(0.25),
+        roughness: Some(0.75),
+        double_sided: Some(true),
+        normal_map: Some("default_blue".to_owned()),
+        occlusion_map: Some("default_red".to_owned()),
+        ..ObjMaterial::default()
+    };
+
+    let material = PbrMaterial::from_obj_material(asys, &obj_mat)
+        .with_environment_intensity(0.5)
+        .build(asys);
+
+    assert_eq!(
+        material.params().get("uMaterial.baseColor"),
+        Some(&MaterialParam::Vec4(Vector4::new(1.0, 0.5, 0.25, 0.5)))
+    );
+    assert_eq!(float(&material, "uMaterial.metallic"), 0.25);
+    assert_eq!(float(&material, "uMaterial.roughness"), 0.75);
+    assert_eq!(float(&material, "uEnvironmentIntensity"), 0.5);
+    assert_eq!(
+        material.params().get("uMaterial.hasNormalMap"),
+        Some(&MaterialParam::Bool(true))
+    );
+
+    let tex = |name: &str| texture_name(asys, &material, name);
+    assert_eq!(tex("uMaterial.normalMap"), "default_blue");
+    assert_eq!(tex("uMaterial.occlusionMap"), "default_red");
+
+    assert_eq!(material.render_queue, RenderQueue::Transparent);
+    assert_eq!(material.states.cull, Some(CullMode::Off));
+
+    // Alpha tested materials stay opaque
+    let material = PbrMaterial::from_obj_material(asys, &obj_mat)
+        .with_alpha_cutoff(0.5)
+        .build(asys);
+    assert_eq!(material.render_queue, RenderQueue::Opaque);
+}
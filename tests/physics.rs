@@ -0,0 +1,139 @@
+#![cfg(feature = "physics")]
+
+extern crate unrust;
+
+mod common;
+
+use common::world_builder;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+use unrust::actors::{Collider, CollisionEvent, Physics, RigidBody};
+use unrust::engine::GameObject;
+use unrust::math::*;
+use unrust::world::{Actor, Handle, World};
+
+fn new_world() -> World {
+    world_builder("Physics").with_processor::<Physics>().build()
+}
+
+fn new_object(world: &mut World, pos: Vector3f, collider: Collider) -> Handle<GameObject> {
+    let go = world.new_game_object();
+    {
+        let mut go = go.borrow_mut();
+        go.transform.set_global(Isometry3 {
+            scale: 1.0,
+            rot: Quaternion::one(),
+            disp: pos,
+        });
+        go.add_component(collider);
+    }
+
+    go
+}
+
+fn step(world: &mut World) {
+    thread::sleep(Duration::from_millis(10));
+    assert!(world.poll_events());
+}
+
+struct Contacts {
+    started: Rc<RefCell<u32>>,
+}
+
+impl Actor for Contacts {
+    fn collision(&mut self, _go: &mut GameObject, _world: &mut World, event: &CollisionEvent) {
+        if let &CollisionEvent::Started(_) = event {
+            *self.started.borrow_mut() += 1;
+        }
+    }
+}
+
+#[test]
+fn test_rigid_body_falls() {
+    let mut world = new_world();
+    let ball = new_object(&mut world, Vector3::new(0.0, 10.0, 0.0), Collider::sphere(0.5));
+    ball.borrow_mut().add_component(RigidBody::new(1.0));
+
+    for _ in 0..10 {
+        step(&mut world);
+    }
+
+    let go = ball.borrow();
+    assert!(go.transform.global().disp.y < 10.0);
+
+    let (body, _) = go.find_component::<RigidBody>().unwrap();
+    assert!(body.velocity().y < 0.0);
+}
+
+#[test]
+fn test_static_collider_stays() {
+    let mut world = new_world();
+    let ground = new_object(
+        &mut world,
+        Vector3::new(0.0, -1.0, 0.0),
+        Collider::cuboid(Vector3::new(10.0, 1.0, 10.0)),
+    );
+
+    for _ in 0..5 {
+        step(&mut world);
+    }
+
+    assert_eq!(ground.borrow().transform.global().disp, Vector3::new(0.0, -1.0, 0.0));
+}
+
+#[test]
+fn test_collision_events() {
+    let mut world = new_world();
+    let started = Rc::new(RefCell::new(0));
+
+    let ground = new_object(
+        &mut world,
+        Vector3::new(0.0, -1.0, 0.0),
+        Collider::cuboid(Vector3::new(10.0, 1.0, 10.0)),
+    );
+    ground.borrow_mut().add_component(Contacts::new_actor(Contacts {
+        started: started.clone(),
+    }));
+
+    // Starts slightly inside the ground
+    let ball = new_object(&mut world, Vector3::new(0.0, 0.45, 0.0), Collider::sphere(0.5));
+    {
+        let mut go = ball.borrow_mut();
+        go.add_component(RigidBody::new(1.0));
+        go.add_component(Contacts::new_actor(Contacts {
+            started: started.clone(),
+        }));
+    }
+
+    for _ in 0..20 {
+        step(&mut world);
+        if *started.borrow() > 0 {
+            break;
+        }
+    }
+
+    // Both sides are notified once
+    assert_eq!(*started.borrow(), 2);
+}
+
+#[test]
+fn test_world_gravity() {
+    let mut world = new_world();
+    world
+        .find_component::<Physics>()
+        .unwrap()
+        .borrow()
+        .set_gravity(Vector3::new(0.0, 9.81, 0.0));
+
+    let ball = new_object(&mut world, Vector3::new(0.0, 10.0, 0.0), Collider::sphere(0.5));
+    ball.borrow_mut().add_component(RigidBody::new(1.0));
+
+    for _ in 0..10 {
+        step(&mut world);
+    }
+
+    // The processor of the world is the simulated one
+    assert!(ball.borrow().transform.global().disp.y > 10.0);
+}
@@ -0,0 +1,94 @@
+extern crate unrust;
+
+mod common;
+
+use common::{add_mesh, assert_near, new_world};
+use unrust::engine::{Asset, Camera, GameObject, Material, Mesh, MeshBuffer, MeshData,
+                     RaycastFilter};
+use unrust::math::*;
+
+fn camera_ray(point: (f32, f32)) -> Ray {
+    let mut camera = Camera::new();
+    camera.lookat(
+        &Point3::new(0.0, 0.0, 10.0),
+        &Point3::new(0.0, 0.0, 0.0),
+        &Vector3::unit_y(),
+    );
+
+    camera.screen_point_to_ray(point, (320, 240))
+}
+
+#[test]
+fn test_screen_point_to_ray() {
+    let ray = camera_ray((160.0, 120.0));
+    assert_near(ray.origin, Vector3::new(0.0, 0.0, 9.97));
+    assert_near(ray.direction, Vector3::new(0.0, 0.0, -1.0));
+
+    // Top left corner of the screen
+    let ray = camera_ray((0.0, 0.0));
+    assert!(ray.direction.x < 0.0 && ray.direction.y > 0.0);
+}
+
+#[test]
+fn test_raycast_nearest_object() {
+    let mut world = new_world("Raycast");
+    let far = add_mesh(&mut world, "cube", Vector3::new(0.0, 0.0, -5.0));
+    let near = add_mesh(&mut world, "cube", Vector3::new(0.0, 0.0, 0.0));
+    add_mesh(&mut world, "cube", Vector3::new(5.0, 0.0, 0.0));
+
+    let ray = camera_ray((160.0, 120.0));
+    let hit = world.raycast(&ray, &RaycastFilter::default()).unwrap();
+
+    assert!(::std::rc::Rc::ptr_eq(&hit.game_object, &near));
+    assert_eq!(hit.surface, 0);
+    assert!((hit.distance - 8.97).abs() < 0.001);
+    assert_near(hit.point, Vector3::new(0.0, 0.0, 1.0));
+    assert_near(hit.normal, Vector3::new(0.0, 0.0, 1.0));
+
+    let skip_near = |go: &GameObject| go.transform.global().disp.z < -1.0;
+    let filter = RaycastFilter {
+        predicate: Some(&skip_near),
+        ..Default::default()
+    };
+    let hit = world.raycast(&ray, &filter).unwrap();
+    assert!(::std::rc::Rc::ptr_eq(&hit.game_object, &far));
+
+    let filter = RaycastFilter {
+        max_distance: 5.0,
+        ..Default::default()
+    };
+    assert!(world.raycast(&ray, &filter).is_none());
+}
+
+#[test]
+fn test_raycast_triangles() {
+    let mut world = new_world("Raycast");
+
+    let mut data = MeshData::default();
+    data.vertices = vec![-1.0, -1.0, 0.0, 1.0, -1.0, 0.0, -1.0, 1.0, 0.0];
+    data.indices = vec![0u16, 1, 2].into();
+
+    let mut mesh = Mesh::new();
+    mesh.add_surface(
+        MeshBuffer::new(data),
+        Material::new(world.asset_system().new_program("phong")),
+    );
+    let go = world.new_game_object();
+    go.borrow_mut().add_component(mesh);
+
+    let filter = RaycastFilter {
+        triangles: true,
+        ..Default::default()
+    };
+
+    // Inside the bounds but outside the triangle
+    let ray = Ray::new(Vector3::new(0.5, 0.5, 5.0), Vector3::new(0.0, 0.0, -1.0));
+    assert!(world.raycast(&ray, &RaycastFilter::default()).is_some());
+    assert!(world.raycast(&ray, &filter).is_none());
+
+    let ray = Ray::new(Vector3::new(-0.5, -0.5, -5.0), Vector3::new(0.0, 0.0, 1.0));
+    let hit = world.raycast(&ray, &filter).unwrap();
+    assert!((hit.distance - 5.0).abs() < 0.001);
+    assert_near(hit.point, Vector3::new(-0.5, -0.5, 0.0));
+    assert_near(hit.normal, Vector3::new(0.0, 0.0, -1.0));
+}
@@ -0,0 +1,112 @@
+extern crate unrust;
+
+mod common;
+
+use common::new_world;
+use std::rc::Rc;
+use unrust::engine::{Directional, Light, Material, Mesh, Point};
+use unrust::math::*;
+use unrust::world::World;
+
+fn build_scene(world: &mut World) {
+    {
+        let go = world.new_game_object();
+        go.borrow_mut()
+            .add_component(Light::new(Directional::default()));
+    }
+
+    let mut mesh = Mesh::new();
+    {
+        let db = world.asset_system();
+        let material = Material::new(db.new_program("phong"));
+        material.set("uMaterial.diffuse", db.new_texture("default_red"));
+        material.set("uMaterial.shininess", 32.0);
+
+        let material = Rc::new(material);
+        mesh.add_surface(db.new_mesh_buffer("cube"), material.clone());
+        mesh.add_surface(db.new_mesh_buffer("plane"), material);
+    }
+
+    let parent = world.new_game_object();
+    {
+        let mut go = parent.borrow_mut();
+        go.add_component(mesh);
+        go.active = false;
+        go.transform.set_local(Isometry3 {
+            scale: 1.0,
+            rot: Quaternion::from_angle_y(Deg(30.0)),
+            disp: Vector3::new(1.0, 2.0, 3.0),
+        });
+        go.transform.set_local_scale(Vector3::new(2.0, 2.0, 2.0));
+    }
+
+    let child = world.new_game_object();
+    child.borrow_mut().add_component(Light::new(Point::default()));
+    parent.borrow().add_child(&child.borrow());
+}
+
+#[test]
+fn test_scene_round_trip() {
+    let mut world = new_world("Scene");
+    build_scene(&mut world);
+
+    let saved = world.save_scene();
+
+    let mut loaded = new_world("Scene");
+    let objects = loaded.load_scene(&saved).expect("Cannot load scene");
+    assert_eq!(objects.len(), 2);
+
+    let parent = &objects[1];
+    assert!(!parent.borrow().active);
+    assert_eq!(parent.borrow().childen().len(), 1);
+    assert_eq!(
+        parent.borrow().transform.local_scale(),
+        Vector3::new(2.0, 2.0, 2.0)
+    );
+
+    {
+        let go = parent.borrow();
+        let (mesh, _) = go.find_component::<Mesh>().unwrap();
+        assert_eq!(mesh.surfaces.len(), 2);
+        assert!(Rc::ptr_eq(
+            &mesh.surfaces[0].material,
+            &mesh.surfaces[1].material
+        ));
+    }
+
+    assert_eq!(loaded.save_scene(), saved);
+}
+
+#[test]
+fn test_empty_objects_saved() {
+    let mut world = new_world("Scene");
+    let marker = world.new_game_object();
+    marker.borrow_mut().transform.set_local(Isometry3 {
+        scale: 1.0,
+        rot: Quaternion::one(),
+        disp: Vector3::new(4.0, 5.0, 6.0),
+    });
+
+    let saved = world.save_scene();
+
+    // The object holding the processors is not saved
+    let mut loaded = new_world("Scene");
+    let objects = loaded.load_scene(&saved).expect("Cannot load scene");
+    assert_eq!(objects.len(), 1);
+    assert_eq!(
+        objects[0].borrow().transform.local().disp,
+        Vector3::new(4.0, 5.0, 6.0)
+    );
+}
+
+#[test]
+fn test_scene_invalid() {
+    let mut world = new_world("Scene");
+
+    assert!(world.load_scene("{").is_err());
+    assert!(
+        world
+            .load_scene(r#"{"version": 1, "objects": [{"components": [{"type": "Foo", "data": {}}]}]}"#)
+            .is_err()
+    );
+}
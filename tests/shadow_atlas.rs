@@ -0,0 +1,25 @@
+extern crate unrust;
+
+use unrust::actors::ShadowAtlas;
+
+#[test]
+fn test_allocate_in_rows() {
+    let mut atlas = ShadowAtlas::new(1024);
+
+    // The cube faces of a point light, then a spot light next to them
+    assert_eq!(atlas.allocate(768, 512), Some((0, 0)));
+    assert_eq!(atlas.allocate(256, 256), Some((768, 0)));
+
+    // The first row is full
+    assert_eq!(atlas.allocate(256, 256), Some((0, 512)));
+    assert_eq!(atlas.allocate(512, 256), Some((256, 512)));
+
+    // Too high for the rows and the space left
+    assert_eq!(atlas.allocate(768, 512), None);
+    assert_eq!(atlas.allocate(256, 256), Some((768, 512)));
+    assert_eq!(atlas.allocate(256, 256), Some((0, 768)));
+
+    atlas.clear();
+    assert_eq!(atlas.allocate(2048, 16), None);
+    assert_eq!(atlas.allocate(1024, 1024), Some((0, 0)));
+}
@@ -0,0 +1,85 @@
+extern crate unrust;
+
+mod common;
+
+use common::{add_mesh, at, new_world};
+use std::rc::Rc;
+use unrust::engine::GameObject;
+use unrust::math::*;
+use unrust::world::Handle;
+
+fn contains(list: &[Handle<GameObject>], go: &Handle<GameObject>) -> bool {
+    list.iter().any(|x| Rc::ptr_eq(x, go))
+}
+
+fn around(p: Vector3f) -> Aabb {
+    Aabb {
+        min: p - Vector3::new(0.5, 0.5, 0.5),
+        max: p + Vector3::new(0.5, 0.5, 0.5),
+    }
+}
+
+#[test]
+fn test_query_aabb_and_sphere() {
+    let mut world = new_world("Spatial");
+    let a = add_mesh(&mut world, "cube", Vector3::new(0.0, 0.0, 0.0));
+    let b = add_mesh(&mut world, "cube", Vector3::new(10.0, 0.0, 0.0));
+
+    let found = world.query_aabb(&around(Vector3::new(1.2, 0.0, 0.0)));
+    assert_eq!(found.len(), 1);
+    assert!(contains(&found, &a));
+
+    let found = world.query_sphere(Vector3::new(5.0, 0.0, 0.0), 4.5);
+    assert_eq!(found.len(), 2);
+    assert!(contains(&found, &a) && contains(&found, &b));
+
+    // Objects without a Mesh are not indexed
+    world.new_game_object();
+    assert_eq!(world.query_aabb(&around(Vector3::zero())).len(), 1);
+
+    world.remove_game_object(&b);
+    drop(b);
+    assert_eq!(world.query_sphere(Vector3::new(5.0, 0.0, 0.0), 4.5).len(), 1);
+}
+
+#[test]
+fn test_nearest() {
+    let mut world = new_world("Spatial");
+    let far = add_mesh(&mut world, "cube", Vector3::new(-20.0, 0.0, 0.0));
+    let near = add_mesh(&mut world, "cube", Vector3::new(5.0, 0.0, 0.0));
+    add_mesh(&mut world, "cube", Vector3::new(0.0, 10.0, 0.0));
+
+    let found = world.nearest(Vector3::new(8.0, 0.0, 0.0), 2);
+    assert_eq!(found.len(), 2);
+    assert!(Rc::ptr_eq(&found[0].0, &near));
+    assert!((found[0].1 - 2.0).abs() < 0.001);
+    assert!(found[0].1 <= found[1].1);
+
+    let found = world.nearest(Vector3::new(8.0, 0.0, 0.0), 10);
+    assert_eq!(found.len(), 3);
+    assert!(Rc::ptr_eq(&found[2].0, &far));
+}
+
+#[test]
+fn test_index_follows_parent() {
+    let mut world = new_world("Spatial");
+    let parent = world.new_game_object();
+    let child = world.new_game_object();
+    let cube = add_mesh(&mut world, "cube", Vector3::zero());
+
+    parent.borrow().add_child(&child.borrow());
+    child.borrow().add_child(&cube.borrow());
+    assert_eq!(world.query_aabb(&around(Vector3::zero())).len(), 1);
+
+    parent
+        .borrow_mut()
+        .transform
+        .set_global(at(Vector3::new(0.0, 0.0, 30.0)));
+
+    let m = cube.borrow().transform.as_global_matrix();
+    assert_eq!(m.w.truncate(), Vector3::new(0.0, 0.0, 30.0));
+
+    assert!(world.query_aabb(&around(Vector3::zero())).is_empty());
+    let found = world.query_aabb(&around(Vector3::new(0.0, 0.0, 30.0)));
+    assert!(contains(&found, &cube));
+}
@@ -0,0 +1,107 @@
+extern crate unrust;
+
+mod common;
+
+use common::{assert_near, new_world};
+use std::rc::Rc;
+use unrust::engine::{AssetError, Sprite, SpriteBatch, SpriteQuad, Texture};
+use unrust::math::*;
+
+fn new_quad(texture: &Rc<Texture>, sorting_layer: i32, order: i32) -> SpriteQuad {
+    let mut quad = Sprite::new(texture.clone()).quad(&Matrix4::identity(), (4, 4));
+    quad.sorting_layer = sorting_layer;
+    quad.order = order;
+    quad
+}
+
+#[test]
+fn test_sprite_quad() {
+    let world = new_world("Sprite");
+    let sprite = Sprite::new(world.asset_system().new_texture("default_white"))
+        .with_rect((0, 0), (32, 16))
+        .with_pixels_per_unit(16.0);
+
+    let quad = sprite.quad(&Matrix4::from_translation(Vector3::new(0.0, 0.0, 1.0)), (64, 64));
+    assert_near(quad.positions[0], Vector3::new(-1.0, -0.5, 1.0));
+    assert_near(quad.positions[2], Vector3::new(1.0, 0.5, 1.0));
+
+    // The rect is from the top of the image, which is at v = 1
+    assert_eq!(quad.uvs[0], Vector2::new(0.0, 0.75));
+    assert_eq!(quad.uvs[2], Vector2::new(0.5, 1.0));
+
+    // Flipped around the pivot
+    let mut sprite = sprite.with_pivot(Vector2::new(0.0, 0.0));
+    sprite.flip_x = true;
+    let quad = sprite.quad(&Matrix4::identity(), (64, 64));
+    assert_near(quad.positions[0], Vector3::new(-2.0, 0.0, 0.0));
+    assert_near(quad.positions[2], Vector3::new(0.0, 1.0, 0.0));
+    assert_eq!(quad.uvs[0], Vector2::new(0.5, 0.75));
+    assert_eq!(quad.uvs[2], Vector2::new(0.0, 1.0));
+}
+
+#[test]
+fn test_sprite_atlas_frames() {
+    let mut world = new_world("Sprite");
+    let atlas = world
+        .asset_system()
+        .new_sprite_atlas("sprite_atlas_test.json");
+
+    let mut names = Err(AssetError::NotReady);
+    for _ in 0..10 {
+        names = atlas.frame_names();
+        match names {
+            Err(AssetError::NotReady) => assert!(world.poll_events()),
+            _ => break,
+        }
+    }
+    assert_eq!(names.unwrap(), vec!["idle.png", "run.png"]);
+
+    // The pivots are from the bottom-left corner of the frames
+    let idle = atlas.frame("idle.png").unwrap().unwrap();
+    assert_eq!(idle.rect, ((0, 0), (32, 32)));
+    assert_eq!(idle.pivot, Vector2::new(0.5, 0.0));
+
+    // The pivot of a trimmed frame stays at the same point of the untrimmed sprite
+    let run = atlas.sprite("run.png").unwrap().unwrap();
+    assert_eq!(run.rect, Some(((32, 0), (16, 32))));
+    assert_eq!(run.pivot, Vector2::new(0.75, 0.5));
+    assert!(Rc::ptr_eq(
+        &run.texture,
+        &world.asset_system().new_texture("tex_a.png")
+    ));
+
+    assert!(atlas.sprite("jump.png").unwrap().is_none());
+}
+
+#[test]
+fn test_batch_sorted_by_texture_runs() {
+    let world = new_world("Sprite");
+    let asys = world.asset_system();
+    let (a, b) = (asys.new_texture("default_red"), asys.new_texture("default_blue"));
+
+    let mut quads = Vec::new();
+    for i in 0..100 {
+        quads.push(new_quad(&a, 0, i));
+    }
+    // Drawn between the sprites of a by their order
+    quads.push(new_quad(&b, 0, 50));
+    quads.push(new_quad(&b, 1, 0));
+
+    let mut batch = SpriteBatch::new();
+    let surfaces = batch.build(asys, quads);
+
+    let counts: Vec<usize> = surfaces
+        .iter()
+        .map(|s| s.buffer.data().unwrap().vertices.len() / 12)
+        .collect();
+    assert_eq!(counts, vec![51, 1, 49, 1]);
+    assert!(Rc::ptr_eq(&surfaces[0].material, &surfaces[2].material));
+
+    // The buffers are reused by the next build
+    let buffer = surfaces[0].buffer.clone();
+    batch.max_sprites = 10;
+    let surfaces = batch.build(asys, (0..25).map(|i| new_quad(&a, 0, i)).collect());
+    assert_eq!(surfaces.len(), 3);
+    assert!(Rc::ptr_eq(&surfaces[0].buffer, &buffer));
+    assert_eq!(surfaces[2].buffer.data().unwrap().indices.len(), 5 * 6);
+}
@@ -0,0 +1,97 @@
+extern crate unrust;
+
+mod common;
+
+use common::{add_mesh_with, assert_near, at, attach, new_world, phong_material};
+use std::rc::Rc;
+use unrust::engine::{Mesh, StaticBatch};
+use unrust::math::*;
+
+#[test]
+fn test_merge_by_material() {
+    let mut world = new_world("StaticBatch");
+    let root = world.new_game_object();
+    root.borrow_mut()
+        .transform
+        .set_global(at(Vector3::new(0.0, 10.0, 0.0)));
+
+    let (a, b) = (phong_material(&world), phong_material(&world));
+    let first = add_mesh_with(&mut world, "cube", &a, Vector3::new(-5.0, 10.0, 0.0));
+    attach(&root, &first);
+    let second = add_mesh_with(&mut world, "cube", &b, Vector3::new(0.0, 10.0, 0.0));
+    attach(&root, &second);
+    let third = add_mesh_with(&mut world, "cube", &a, Vector3::new(5.0, 10.0, 0.0));
+    attach(&first, &third);
+
+    StaticBatch::default()
+        .build(&mut root.borrow_mut())
+        .unwrap();
+
+    for child in root.borrow().childen() {
+        assert!(child.borrow().find_component::<Mesh>().is_none());
+    }
+    assert!(first.borrow().childen()[0]
+        .borrow()
+        .find_component::<Mesh>()
+        .is_none());
+
+    let root = root.borrow();
+    let (mesh, _) = root.find_component::<Mesh>().unwrap();
+    assert_eq!(mesh.surfaces.len(), 2);
+    assert!(Rc::ptr_eq(&mesh.surfaces[0].material, &a));
+    assert!(Rc::ptr_eq(&mesh.surfaces[1].material, &b));
+
+    // The transforms are baked, relatively to the root
+    let data = mesh.surfaces[0].buffer.data().unwrap();
+    assert_eq!(data.vertices.len(), 2 * 24 * 3);
+    assert_eq!(data.indices.len(), 2 * 36);
+    assert!(!data.indices.is_u32());
+
+    let aabb = mesh.surfaces[0].buffer.bounds().unwrap().aabb;
+    assert_near(aabb.min, Vector3::new(-6.0, -1.0, -1.0));
+    assert_near(aabb.max, Vector3::new(6.0, 1.0, 1.0));
+    assert_eq!(data.normals.as_ref().unwrap().len(), data.vertices.len());
+}
+
+#[test]
+fn test_max_vertices() {
+    let mut world = new_world("StaticBatch");
+    let root = world.new_game_object();
+    let material = phong_material(&world);
+
+    for i in 0..5 {
+        let pos = Vector3::new(i as f32 * 3.0, 0.0, 0.0);
+        let cube = add_mesh_with(&mut world, "cube", &material, pos);
+        attach(&root, &cube);
+    }
+
+    let batch = StaticBatch { max_vertices: 50 };
+    batch.build(&mut root.borrow_mut()).unwrap();
+
+    let root = root.borrow();
+    let (mesh, _) = root.find_component::<Mesh>().unwrap();
+
+    // 2 cubes of 24 vertices in each buffer
+    let counts: Vec<usize> = mesh.surfaces
+        .iter()
+        .map(|s| s.buffer.data().unwrap().vertices.len() / 3)
+        .collect();
+    assert_eq!(counts, vec![48, 48, 24]);
+}
+
+#[test]
+fn test_batch_is_queried_at_root() {
+    let mut world = new_world("StaticBatch");
+    let root = world.new_game_object();
+    let material = phong_material(&world);
+    let cube = add_mesh_with(&mut world, "cube", &material, Vector3::new(20.0, 0.0, 0.0));
+    attach(&root, &cube);
+
+    StaticBatch::default()
+        .build(&mut root.borrow_mut())
+        .unwrap();
+
+    let found = world.query_sphere(Vector3::new(20.0, 0.0, 0.0), 0.5);
+    assert_eq!(found.len(), 1);
+    assert!(Rc::ptr_eq(&found[0], &root));
+}
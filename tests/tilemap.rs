@@ -0,0 +1,158 @@
+extern crate unrust;
+
+mod common;
+
+use common::new_world;
+use std::rc::Rc;
+use unrust::engine::{AssetSystem, Material, ObjMaterial, Sprite, TileObject, TileObjectShape,
+                     Tilemap, Tileset, TILE_FLIPPED_DIAGONALLY, TILE_FLIPPED_HORIZONTALLY};
+use unrust::math::*;
+use unrust::world::World;
+
+fn build_material(asys: &AssetSystem, _: ObjMaterial) -> Rc<Material> {
+    Rc::new(Material::new(asys.new_program("default")))
+}
+
+fn new_tileset(world: &World) -> Rc<Tileset> {
+    let texture = world.asset_system().new_texture("default_white");
+    Rc::new(Tileset::new(texture, (64, 64), (16, 16)))
+}
+
+#[test]
+fn test_tileset_uvs() {
+    let world = new_world("Tilemap");
+    let tileset = new_tileset(&world);
+
+    assert_eq!(tileset.columns, 4);
+    assert_eq!(tileset.tile_count, 16);
+    assert_eq!(tileset.tile_rect(6), ((16, 16), (16, 16)));
+
+    // The rows are from the top of the image, which is at v = 1
+    let uvs = tileset.tile_uvs(1);
+    assert_eq!(uvs[0], Vector2::new(0.0, 0.75));
+    assert_eq!(uvs[2], Vector2::new(0.25, 1.0));
+
+    let uvs = tileset.tile_uvs(1 | TILE_FLIPPED_HORIZONTALLY);
+    assert_eq!(uvs[0], Vector2::new(0.25, 0.75));
+    assert_eq!(uvs[2], Vector2::new(0.0, 1.0));
+
+    // Transposed, the bottom-left corner shows the top-right one
+    let uvs = tileset.tile_uvs(1 | TILE_FLIPPED_DIAGONALLY);
+    assert_eq!(uvs[0], Vector2::new(0.25, 1.0));
+    assert_eq!(uvs[2], Vector2::new(0.0, 0.75));
+}
+
+#[test]
+fn test_tilemap_cells() {
+    let world = new_world("Tilemap");
+    let tilemap = Tilemap::new((4, 3), Vector2::new(1.0, 0.5)).with_tileset(new_tileset(&world));
+
+    // Row 0 is at the top
+    assert_eq!(tilemap.cell_position(0, 0), Vector2::new(0.0, 1.0));
+    assert_eq!(tilemap.cell_at(Vector2::new(0.5, 1.2)), Some((0, 0)));
+    assert_eq!(tilemap.cell_at(Vector2::new(3.5, 0.2)), Some((3, 2)));
+    assert_eq!(tilemap.cell_at(Vector2::new(4.5, 0.2)), None);
+}
+
+#[test]
+fn test_only_dirty_chunks_rebuilt() {
+    let world = new_world("Tilemap");
+    let mut tilemap = Tilemap::new((20, 3), Vector2::new(1.0, 1.0))
+        .with_chunk_size(8)
+        .with_tileset(new_tileset(&world));
+
+    let layer = tilemap.add_layer("ground");
+    tilemap.set_tile(layer, 0, 0, 1);
+    tilemap.set_tile(layer, 19, 2, 2);
+    tilemap.update(world.asset_system());
+
+    // Empty chunks have no surface
+    let surfaces = tilemap.surfaces();
+    assert_eq!(surfaces.len(), 2);
+    assert_eq!(surfaces[0].0.min, Vector3::new(0.0, 2.0, 0.0));
+    assert_eq!(surfaces[1].0.max, Vector3::new(20.0, 1.0, 0.0));
+
+    tilemap.set_tile(layer, 1, 0, 3);
+    tilemap.update(world.asset_system());
+
+    // The buffer of the changed chunk is updated, the other one is kept as is
+    let rebuilt = tilemap.surfaces();
+    assert!(!Rc::ptr_eq(&surfaces[0].1, &rebuilt[0].1));
+    assert!(Rc::ptr_eq(&surfaces[0].1.buffer, &rebuilt[0].1.buffer));
+    assert!(Rc::ptr_eq(&surfaces[1].1, &rebuilt[1].1));
+    assert_eq!(rebuilt[0].1.buffer.data().unwrap().vertices.len(), 2 * 4 * 3);
+}
+
+fn load_map(name: &str) {
+    let mut world = new_world("Tilemap");
+    let parent = world.new_game_object();
+    world.load_prefab(name, Box::new(build_material), &parent);
+
+    for _ in 0..10 {
+        if parent.borrow().childen().len() > 0 {
+            break;
+        }
+        assert!(world.poll_events());
+    }
+
+    let parent = parent.borrow();
+    let (tilemap, _) = parent.find_component::<Tilemap>().unwrap();
+    assert_eq!(tilemap.size(), (4, 3));
+    assert_eq!(tilemap.properties["music"], "level1");
+    assert_eq!(tilemap.tilesets()[0].name, "terrain");
+    assert!(Rc::ptr_eq(
+        &tilemap.tilesets()[0].texture,
+        &world.asset_system().new_texture("tex_a.png")
+    ));
+
+    // The layer of the group is flattened with its opacity
+    let layers = tilemap.layers();
+    assert_eq!(layers.len(), 2);
+    assert_eq!(layers[0].tiles()[5], 6 | TILE_FLIPPED_HORIZONTALLY);
+    assert_eq!(layers[1].name, "deco");
+    assert_eq!(layers[1].opacity, 0.5);
+    assert_eq!(layers[1].tiles()[6], 6);
+    assert_eq!(layers[1].properties["parallax"], "0.5");
+    assert_eq!(tilemap.tile_properties(0, 1, 0).unwrap()["solid"], "true");
+
+    let objects = parent.childen();
+    assert_eq!(objects.len(), 3);
+
+    // A map tile is a world unit, from the bottom-left corner of the map
+    let player = objects[0].borrow();
+    assert_eq!(player.transform.local().disp, Vector3::new(1.0, 1.0, 0.0));
+    let (object, _) = player.find_component::<TileObject>().unwrap();
+    assert_eq!(object.name, "player");
+    assert_eq!(object.kind, "spawn");
+    assert_eq!(object.layer, "spawns");
+    assert_eq!(object.shape, TileObjectShape::Point);
+    assert_eq!(object.properties["health"], "3");
+
+    let chest = objects[1].borrow();
+    assert_eq!(chest.transform.local().disp, Vector3::new(2.0, 0.0, 0.0));
+    let (sprite, _) = chest.find_component::<Sprite>().unwrap();
+    assert_eq!(sprite.rect, Some(((16, 0), (16, 16))));
+    assert_eq!(sprite.pixels_per_unit, 16.0);
+
+    let zone = objects[2].borrow();
+    assert_eq!(zone.transform.local().disp, Vector3::new(0.0, 3.0, 0.0));
+    let (object, _) = zone.find_component::<TileObject>().unwrap();
+    assert_eq!(
+        object.shape,
+        TileObjectShape::Polygon(vec![
+            Vector2::new(0.0, 0.0),
+            Vector2::new(1.0, 0.0),
+            Vector2::new(1.0, -1.0),
+        ])
+    );
+}
+
+#[test]
+fn test_load_tmx() {
+    load_map("tilemap_test.tmx");
+}
+
+#[test]
+fn test_load_tiled_json() {
+    load_map("tilemap_test.json");
+}
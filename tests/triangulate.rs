@@ -0,0 +1,68 @@
+extern crate unrust;
+
+mod common;
+
+use common::{new_world, wait_prefab};
+use unrust::engine::mesh_util::triangulate_polygon;
+use unrust::engine::AssetError;
+use unrust::math::*;
+
+/// Signed area of the triangles in the xy plane
+fn area(points: &[Vector3f], triangles: &[[usize; 3]]) -> f32 {
+    triangles
+        .iter()
+        .map(|t| {
+            let (a, b, c) = (points[t[0]], points[t[1]], points[t[2]]);
+            (b - a).cross(c - a).z * 0.5
+        })
+        .sum()
+}
+
+#[test]
+fn test_triangulate_convex() {
+    let points: Vec<Vector3f> = (0..6)
+        .map(|i| {
+            let a = Rad::full_turn() * (i as f32 / 6.0);
+            Vector3::new(a.cos(), a.sin(), 0.0)
+        })
+        .collect();
+
+    let triangles = triangulate_polygon(&points);
+    assert_eq!(triangles, vec![[0, 1, 2], [0, 2, 3], [0, 3, 4], [0, 4, 5]]);
+}
+
+#[test]
+fn test_triangulate_concave() {
+    // L shape with area 3, a fan from the first vertex would leave the polygon
+    let mut points = vec![
+        Vector3::new(0.0, 0.0, 0.0),
+        Vector3::new(2.0, 0.0, 0.0),
+        Vector3::new(2.0, 1.0, 0.0),
+        Vector3::new(1.0, 1.0, 0.0),
+        Vector3::new(1.0, 2.0, 0.0),
+        Vector3::new(0.0, 2.0, 0.0),
+    ];
+
+    let triangles = triangulate_polygon(&points);
+    assert_eq!(triangles.len(), 4);
+    assert_eq!(area(&points, &triangles), 3.0);
+
+    // Winding is kept, so all triangles face the other way
+    points.reverse();
+    let triangles = triangulate_polygon(&points);
+    assert_eq!(triangles.len(), 4);
+    assert_eq!(area(&points, &triangles), -3.0);
+}
+
+#[test]
+fn test_malformed_obj_faces() {
+    let mut world = new_world("Triangulate");
+
+    for name in ["invalid_face_test.obj", "invalid_index_test.obj"].iter() {
+        match wait_prefab(&mut world, name, false) {
+            Err(AssetError::InvalidFormat { ref path, .. }) => assert!(path.ends_with(name)),
+            Err(e) => panic!("{} gives {:?}", name, e),
+            Ok(_) => panic!("{} is loaded", name),
+        }
+    }
+}
@@ -38,6 +38,8 @@ pub struct AppConfig {
     pub vsync: bool,
     pub headless: bool,
     pub show_cursor: bool,
+    /// Create a GL context for the app, only honored for headless apps
+    pub gl_context: bool,
 }
 
 impl AppConfig {
@@ -48,6 +50,7 @@ impl AppConfig {
             vsync: true,
             headless: false,
             show_cursor: true,
+            gl_context: true,
         }
     }
 }
@@ -17,6 +17,7 @@ use super::events;
 enum WindowContext {
     Normal(glutin::GlWindow),
     Headless(glutin::HeadlessContext),
+    Offscreen,
 }
 
 impl WindowContext {
@@ -34,10 +35,11 @@ impl WindowContext {
         }
     }
 
-    fn context(&self) -> &glutin::GlContext {
+    fn context(&self) -> Option<&glutin::GlContext> {
         match self {
-            &WindowContext::Normal(ref w) => w,
-            &WindowContext::Headless(ref w) => w,
+            &WindowContext::Normal(ref w) => Some(w),
+            &WindowContext::Headless(ref w) => Some(w),
+            &WindowContext::Offscreen => None,
         }
     }
 
@@ -46,13 +48,14 @@ impl WindowContext {
         match self {
             &WindowContext::Normal(ref w) => w.swap_buffers(),
             &WindowContext::Headless(_) => Ok(()),
+            &WindowContext::Offscreen => Ok(()),
         }
     }
 }
 
 pub struct App {
     window: WindowContext,
-    events_loop: glutin::EventsLoop,
+    events_loop: Option<glutin::EventsLoop>,
     exiting: bool,
     pub events: Rc<RefCell<Vec<AppEvent>>>,
 }
@@ -122,6 +125,17 @@ fn translate_event(e: glutin::Event) -> Option<AppEvent> {
 impl App {
     pub fn new(config: AppConfig) -> App {
         use glutin::*;
+        // Without a GL context there is nothing to present to,
+        // so we do not even need a connection to the windowing system.
+        if config.headless && !config.gl_context {
+            return App {
+                window: WindowContext::Offscreen,
+                events_loop: None,
+                exiting: false,
+                events: Rc::new(RefCell::new(Vec::new())),
+            };
+        }
+
         let events_loop = glutin::EventsLoop::new();
         let gl_req = GlRequest::GlThenGles {
             opengl_version: (3, 2),
@@ -155,12 +169,12 @@ impl App {
         };
 
         unsafe {
-            window.context().make_current().unwrap();
+            window.context().unwrap().make_current().unwrap();
         }
 
         App {
             window: window,
-            events_loop,
+            events_loop: Some(events_loop),
             exiting: false,
             events: Rc::new(RefCell::new(Vec::new())),
         }
@@ -185,7 +199,10 @@ impl App {
     }
 
     pub fn get_proc_address(&self, name: &str) -> *const c_void {
-        self.window.context().get_proc_address(name) as *const c_void
+        match self.window.context() {
+            Some(ctx) => ctx.get_proc_address(name) as *const c_void,
+            None => ::std::ptr::null(),
+        }
     }
 
     pub fn canvas<'p>(&'p self) -> Box<'p + FnMut(&str) -> *const c_void> {
@@ -196,13 +213,18 @@ impl App {
         use glutin::*;
         let mut running = true;
 
-        let (window, events_loop, events) = (&self.window, &mut self.events_loop, &mut self.events);
+        let (window, events_loop, events) = match self.events_loop {
+            Some(ref mut events_loop) => (&self.window, events_loop, &mut self.events),
+            None => return running,
+        };
 
         events_loop.poll_events(|event| {
             match event {
                 glutin::Event::WindowEvent { ref event, .. } => match event {
                     &glutin::WindowEvent::Closed => running = false,
-                    &glutin::WindowEvent::Resized(w, h) => window.context().resize(w, h),
+                    &glutin::WindowEvent::Resized(w, h) => {
+                        window.context().map(|ctx| ctx.resize(w, h));
+                    }
                     &glutin::WindowEvent::KeyboardInput { input, .. } => {
                         // issue tracked in https://github.com/tomaka/winit/issues/41
                         // Right now we handle it manually.
@@ -5,7 +5,7 @@
 */
 
 /// Constants passed to WebGLRenderingContext.vertexAttribPointer()
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum AttributeSize {
     One = 1,
     Two = 2,
@@ -14,7 +14,7 @@ pub enum AttributeSize {
 }
 
 /// Constants passed to WebGLRenderingContext.createShader()
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ShaderKind {
     /// Passed to createShader to define a fragment shader.
     Fragment = 0x8B30,
@@ -23,7 +23,7 @@ pub enum ShaderKind {
 }
 
 /// Constants passed to WebGLRenderingContext.createShader()
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ShaderParameter {
     /// Passed to getShaderParamter to get the status of the compilation. Returns false if the shader was not compiled. You can then query getShaderInfoLog to find the exact error
     CompileStatus = 0x8B81,
@@ -62,13 +62,13 @@ pub enum ShaderParameter {
 }
 
 /// Passed to bindBuffer or bufferData to specify the type of buffer being used.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BufferKind {
     Array = 0x8892,
     ElementArray = 0x8893,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum DrawMode {
     /// Passed to bufferData as a hint about whether the contents of the buffer are likely to be used often and not change often.
     Static = 0x88E4,
@@ -78,7 +78,7 @@ pub enum DrawMode {
     Stream = 0x88E0,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BufferParameter {
     /// Passed to getBufferParameter to get a buffer's size.
     Size = 0x8764,
@@ -86,7 +86,7 @@ pub enum BufferParameter {
     Usage = 0x8765,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum DataType {
     I8 = 0x1400,
     U8 = 0x1401,
@@ -97,7 +97,7 @@ pub enum DataType {
     Float = 0x1406,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Flag {
     /// Passed to enable/disable to turn on/off blending. Can also be used with getParameter to find the current blending method.
     Blend = 0x0BE2,
@@ -117,7 +117,7 @@ pub enum Flag {
     StencilTest = 0x0B90,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BufferBit {
     /// Passed to clear to clear the current depth buffer.
     Depth = 0x00000100,
@@ -128,7 +128,7 @@ pub enum BufferBit {
 }
 
 /// Passed to drawElements or drawArrays to draw primitives.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Primitives {
     /// Passed to drawElements or drawArrays to draw single points.
     Points = 0x0000,
@@ -147,7 +147,7 @@ pub enum Primitives {
 }
 
 /// Constants passed to WebGLRenderingContext.blendFunc() or WebGLRenderingContext.blendFuncSeparate() to specify the blending mode (for both, RBG and alpha, or separately).
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BlendMode {
     /// Passed to blendFunc or blendFuncSeparate to turn off a component.
     Zero = 0,
@@ -184,7 +184,7 @@ pub enum BlendMode {
 /// Constants passed to WebGLRenderingContext.blendEquation()
 /// or WebGLRenderingContext.blendEquationSeparate() to control
 /// how the blending is calculated (for both, RBG and alpha, or separately).
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BlendEquation {
     /// Passed to blendEquation or blendEquationSeparate to set an addition blend function.
     FuncAdd = 0x8006,
@@ -195,7 +195,7 @@ pub enum BlendEquation {
 }
 
 /// Constants passed to WebGLRenderingContext.getParameter() to specify what information to return.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Parameter {
     /// Passed to getParameter to get the current RGB blend function. same as BlendEquationRgb
     BlendEquation = 0x8009,
@@ -330,7 +330,7 @@ pub enum Parameter {
 }
 
 /// Constants passed to WebGLRenderingContext.getVertexAttrib().
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum VertexAttrib {
     /// Passed to getVertexAttrib to read back the current vertex attribute.
     Current = 0x8626,
@@ -351,7 +351,7 @@ pub enum VertexAttrib {
 }
 
 /// Constants passed to WebGLRenderingContext.cullFace().
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Culling {
     /// Passed to enable/disable to turn on/off culling. Can also be used with getParameter to find the current culling method.
     CullFace = 0x0B44,
@@ -364,7 +364,7 @@ pub enum Culling {
 }
 
 /// Constants returned from WebGLRenderingContext.getError().
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Error {
     /// Returned from getError.
     NoError = 0,
@@ -381,7 +381,7 @@ pub enum Error {
 }
 
 /// Constants passed to WebGLRenderingContext.frontFace().
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FrontFaceDirection {
     /// Passed to frontFace to specify the front face of a polygon is drawn in the clockwise direction
     CW = 0x0900,
@@ -390,7 +390,7 @@ pub enum FrontFaceDirection {
 }
 
 /// Constants passed to WebGLRenderingContext.depthFunc().
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum DepthTest {
     /// Passed to depthFunction or stencilFunction to specify depth or stencil tests will never pass. i.e. Nothing will be drawn.
     Never = 0x0200,
@@ -411,7 +411,7 @@ pub enum DepthTest {
 }
 
 /// Constants passed to WebGLRenderingContext.stencilFunc().
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum StencilTest {
     /// Passed to depthFunction or stencilFunction to specify depth or stencil tests will never pass. i.e. Nothing will be drawn.
     Never = 0x0200,
@@ -432,7 +432,7 @@ pub enum StencilTest {
 }
 
 /// Constants passed to WebGLRenderingContext.stencilOp().
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum StencilAction {
     ///
     Keep = 0x1E00,
@@ -450,7 +450,7 @@ pub enum StencilAction {
     DecrWrap = 0x8508,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PixelType {
     ///
     UnsignedByte = 0x1401,
@@ -471,9 +471,11 @@ pub enum PixelType {
 
     ///
     Float = 0x1406,
+    /// WebGL2 only
+    HalfFloat = 0x140B,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PixelFormat {
     ///
     DepthComponent = 0x1902,
@@ -490,7 +492,7 @@ pub enum PixelFormat {
 }
 
 /// Constants passed to WebGLRenderingContext.hint()
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Hint {
     /// There is no preference for this behavior.
     DontCare = 0x1100,
@@ -503,7 +505,7 @@ pub enum Hint {
 }
 
 /// WebGLRenderingContext.texParameter[fi]() or WebGLRenderingContext.bindTexture() "target" parameter
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TextureKind {
     ///
     Texture2d = 0x0DE1,
@@ -512,7 +514,7 @@ pub enum TextureKind {
 }
 
 /// WebGLRenderingContext.texParameter[fi]() "pname" parameter
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TextureParameter {
     ///
     TextureMagFilter = 0x2800,
@@ -530,7 +532,7 @@ pub enum TextureParameter {
 }
 
 /// WebGLRenderingContext.texImage2D() "target" parameter
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TextureBindPoint {
     ///
     Texture2d = 0x0DE1,
@@ -549,7 +551,7 @@ pub enum TextureBindPoint {
 }
 
 /// WebGLRenderingContext.texParameter[fi]() "param" parameter
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TextureMagFilter {
     ///
     Nearest = 0x2600,
@@ -558,7 +560,7 @@ pub enum TextureMagFilter {
 }
 
 /// WebGLRenderingContext.texParameter[fi]() "param" parameter
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TextureMinFilter {
     ///
     Nearest = 0x2600,
@@ -586,7 +588,7 @@ pub enum TextureWrap {
 }
 
 /// Constants passed to WebGLRenderingContext.hint()
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Buffers {
     ///
     Framebuffer = 0x8D40,
@@ -635,6 +637,12 @@ pub enum Buffers {
     ///
     ColorAttachment0 = 0x8CE0,
     ///
+    ColorAttachment1 = 0x8CE1,
+    ///
+    ColorAttachment2 = 0x8CE2,
+    ///
+    ColorAttachment3 = 0x8CE3,
+    ///
     DepthAttachment = 0x8D00,
     ///
     StencilAttachment = 0x8D20,
@@ -663,7 +671,7 @@ pub enum Buffers {
 }
 
 /// Constants passed to WebGLRenderingContext.hint()
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PixelStorageMode {
     ///
     UnpackFlipYWebgl = 0x9240,
@@ -680,7 +688,7 @@ pub enum PixelStorageMode {
 }
 
 ///
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ShaderPrecision {
     ///
     LowFloat = 0x8DF0,
@@ -697,7 +705,7 @@ pub enum ShaderPrecision {
 }
 
 /// Constants passed to WebGLRenderingContext.hint()
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum UniformType {
     ///
     FloatVec2 = 0x8B50,
@@ -732,7 +740,7 @@ pub enum UniformType {
 }
 
 ///
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TextureCompression {
     /// A DXT1-compressed image in an RGB image format.
     RgbDxt1 = 0x83F0,
@@ -748,7 +756,7 @@ pub enum TextureCompression {
 }
 
 ///
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ColorBuffer {
     None = 0,
     Back = 0x0405,
@@ -4,7 +4,10 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(feature = "mock", feature = "soft"))]
+compile_error!("The \"mock\" and \"soft\" backends can not be enabled at the same time");
+
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "mock"), not(feature = "soft")))]
 extern crate gl;
 
 #[cfg(target_arch = "wasm32")]
@@ -15,21 +18,42 @@ extern crate stdweb;
 #[path = "webgl.rs"]
 pub mod webgl;
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "mock"), not(feature = "soft")))]
 #[path = "webgl_native.rs"]
 mod webgl;
 
+#[cfg(all(not(target_arch = "wasm32"), feature = "mock"))]
+#[path = "webgl_mock.rs"]
+mod webgl;
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "soft"))]
+#[path = "webgl_soft.rs"]
+mod webgl;
+
 #[cfg(not(target_arch = "wasm32"))]
 pub const IS_GL_ES: bool = false;
 
 #[cfg(target_arch = "wasm32")]
 pub const IS_GL_ES: bool = true;
 
+/// Whether the backend needs a real GL context (and so a window) to run
+#[cfg(any(target_arch = "wasm32", all(not(feature = "mock"), not(feature = "soft"))))]
+pub const NEEDS_GL_CONTEXT: bool = true;
+
+#[cfg(all(not(target_arch = "wasm32"), any(feature = "mock", feature = "soft")))]
+pub const NEEDS_GL_CONTEXT: bool = false;
+
 mod glenum;
 
 pub use glenum::*;
 pub use webgl::WebGLContext;
 
+#[cfg(all(not(target_arch = "wasm32"), feature = "mock"))]
+pub use webgl::{DrawCall, GLCommand, UniformValue};
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "soft"))]
+pub use webgl::{FragmentShader, ShaderEnv, SoftProgram, UniformValue, Vec4, VertexShader};
+
 pub mod common {
     use std::ops::Deref;
 
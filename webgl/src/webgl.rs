@@ -11,6 +11,16 @@ pub type Reference = i32;
 pub struct GLContext {
     pub reference: Reference,
     pub is_webgl2: bool,
+    /// 32-bit index buffers are supported (OES_element_index_uint on WebGL1)
+    pub element_index_uint: bool,
+    /// Instanced draws are supported (ANGLE_instanced_arrays on WebGL1)
+    pub instanced_arrays: bool,
+    /// Float and half float color attachments can be rendered to
+    /// (EXT_color_buffer_float on WebGL2, never on WebGL1)
+    pub color_buffer_float: bool,
+    /// Several color attachments can be drawn to at once
+    /// (WEBGL_draw_buffers on WebGL1)
+    pub draw_buffers: bool,
 }
 
 pub type WebGLContext<'a> = &'a CanvasElement;
@@ -87,12 +97,22 @@ impl GLContext {
             }
 
             var ext = gl.getExtension("WEBGL_depth_texture");
+            var uint_ext = gl.getExtension("OES_element_index_uint");
+            var instanced_ext = version == 2 ? null : gl.getExtension("ANGLE_instanced_arrays");
+            var float_ext = version == 2 ? gl.getExtension("EXT_color_buffer_float") : null;
+            var draw_buffers_ext = version == 2 ? null : gl.getExtension("WEBGL_draw_buffers");
 
             // Create gl related objects
             if( !Module.gl) {
                 Module.gl = {};
                 Module.gl.counter = 1;
                 Module.gl.version = version;
+                Module.gl.element_index_uint = version == 2 || !!uint_ext;
+                Module.gl.instanced_arrays = version == 2 || !!instanced_ext;
+                Module.gl.instanced_ext = instanced_ext;
+                Module.gl.color_buffer_float = !!float_ext;
+                Module.gl.draw_buffers = version == 2 || !!draw_buffers_ext;
+                Module.gl.draw_buffers_ext = draw_buffers_ext;
 
                 Module.gl.matrix4x4 = new Float32Array([
                     1.0, 0,   0,   0,
@@ -125,10 +145,26 @@ impl GLContext {
         };
 
         let version: u32 = js!( return Module.gl.version; ).try_into().unwrap();
+        let element_index_uint: bool = js!( return Module.gl.element_index_uint; )
+            .try_into()
+            .unwrap();
+        let instanced_arrays: bool = js!( return Module.gl.instanced_arrays; )
+            .try_into()
+            .unwrap();
+        let color_buffer_float: bool = js!( return Module.gl.color_buffer_float; )
+            .try_into()
+            .unwrap();
+        let draw_buffers: bool = js!( return Module.gl.draw_buffers; )
+            .try_into()
+            .unwrap();
 
         GLContext {
             reference: gl.try_into().unwrap(),
             is_webgl2: version == 2,
+            element_index_uint,
+            instanced_arrays,
+            color_buffer_float,
+            draw_buffers,
         }
     }
 
@@ -242,6 +278,9 @@ impl GLContext {
         };
     }
 
+    /// Give the program a name, only used by the non-GPU backends
+    pub fn label_program(&self, _program: &WebGLProgram, _label: &str) {}
+
     pub fn use_program(&self, program: &WebGLProgram) {
         self.log("use_program");
         js! {
@@ -337,6 +376,28 @@ impl GLContext {
         };
     }
 
+    pub fn disable_vertex_attrib_array(&self, location: u32) {
+        self.log("disable_vertex_attrib_array");
+        js! {
+            @(no_return)
+            var ctx = Module.gl.get(@{&self.reference});
+            ctx.disableVertexAttribArray(@{location})
+        };
+    }
+
+    pub fn vertex_attrib_divisor(&self, location: u32, divisor: u32) {
+        self.log("vertex_attrib_divisor");
+        js! {
+            @(no_return)
+            var ctx = Module.gl.get(@{&self.reference});
+            if (ctx.vertexAttribDivisor) {
+                ctx.vertexAttribDivisor(@{location},@{divisor});
+            } else {
+                Module.gl.instanced_ext.vertexAttribDivisorANGLE(@{location},@{divisor});
+            }
+        };
+    }
+
     pub fn clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
         self.log("clear_color");
 
@@ -425,6 +486,17 @@ impl GLContext {
         };
     }
 
+    pub fn scissor(&self, x: i32, y: i32, width: u32, height: u32) {
+        self.log("scissor");
+        let params = js! { return [@{x},@{y},@{width},@{height}] };
+        js! {
+            @(no_return)
+            var ctx = Module.gl.get(@{&self.reference});
+            var p = @{params};
+            ctx.scissor(p[0],p[1],p[2],p[3]);
+        };
+    }
+
     pub fn draw_elements(&self, mode: Primitives, count: usize, kind: DataType, offset: u32) {
         self.log("draw_elemnts");
         js_raw!({
@@ -433,6 +505,27 @@ impl GLContext {
         }, [self.reference, mode as i32, count as i32, kind as i32, offset as i32 ]);
     }
 
+    pub fn draw_elements_instanced(
+        &self,
+        mode: Primitives,
+        count: usize,
+        kind: DataType,
+        offset: u32,
+        instances: usize,
+    ) {
+        self.log("draw_elements_instanced");
+        js! {
+            @(no_return)
+            var ctx = Module.gl.get(@{&self.reference});
+            var args = [@{mode as i32},@{count as i32},@{kind as i32},@{offset as i32},@{instances as i32}];
+            if (ctx.drawElementsInstanced) {
+                ctx.drawElementsInstanced(args[0],args[1],args[2],args[3],args[4]);
+            } else {
+                Module.gl.instanced_ext.drawElementsInstancedANGLE(args[0],args[1],args[2],args[3],args[4]);
+            }
+        };
+    }
+
     pub fn draw_arrays(&self, mode: Primitives, count: usize) {
         self.log("draw_arrays");
         js! {
@@ -516,7 +609,8 @@ impl GLContext {
         pixels: &[u8],
     ) {
         self.log("tex_img2d");
-        let params1 = js! { return [@{target as u32},@{level as u32},@{format as u32}] };
+        let params1 =
+            js! { return [@{target as u32},@{level as u32},@{internal_format(format, kind)}] };
         let params2 =
             js! { return [@{width as u32},@{height as u32},@{format as u32},@{kind as u32}] };
 
@@ -535,15 +629,15 @@ impl GLContext {
                 var p = @{params1}.concat(@{params2});
                 var ctx = Module.gl.get(@{&self.reference});
 
-                ctx.texImage2D(p[0],p[1], p[2] ,p[3],p[4],0,p[2],p[6],@{TypedArray::from(pixels)});
+                ctx.texImage2D(p[0],p[1], p[2] ,p[3],p[4],0,p[5],p[6],@{TypedArray::from(pixels)});
             };
         } else {
             js!{
                 var p = @{params1}.concat(@{params2});
                 var ctx = Module.gl.get(@{&self.reference});
 
-                var internal_fmt =  @{format as u32};
-                var fmt = internal_fmt;
+                var internal_fmt = p[2];
+                var fmt = p[5];
                 if ( @{is_depth}) {
                     internal_fmt =  ctx.DEPTH_COMPONENT16;
                 }
@@ -952,7 +1046,11 @@ impl GLContext {
             @(no_return)
 
             var ctx = Module.gl.get(@{self.reference});
-            ctx.drawBuffers(@{color_enums});
+            if (Module.gl.version == 2) {
+                ctx.drawBuffers(@{color_enums});
+            } else if (Module.gl.draw_buffers_ext) {
+                Module.gl.draw_buffers_ext.drawBuffersWEBGL(@{color_enums});
+            }
         };
     }
 
@@ -1003,3 +1101,14 @@ impl GLContext {
         }
     }
 }
+
+/// Float textures need a sized internal format
+fn internal_format(format: PixelFormat, kind: PixelType) -> u32 {
+    match (format, kind) {
+        (PixelFormat::Rgba, PixelType::Float) => 0x8814,     // RGBA32F
+        (PixelFormat::Rgba, PixelType::HalfFloat) => 0x881A, // RGBA16F
+        (PixelFormat::Rgb, PixelType::Float) => 0x8815,      // RGB32F
+        (PixelFormat::Rgb, PixelType::HalfFloat) => 0x881B,  // RGB16F
+        _ => format as u32,
+    }
+}
@@ -0,0 +1,891 @@
+// A recording backend which does not talk to any GPU.
+//
+// Every call is appended to a command log and all object handles are fake,
+// so the engine can run (and be inspected) inside a plain `cargo test`.
+
+use glenum::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::os::raw::c_void;
+use std::rc::Rc;
+
+use common::*;
+
+pub type Reference = u32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UniformValue {
+    Matrix4([[f32; 4]; 4]),
+    Matrix3([[f32; 3]; 3]),
+    Matrix2([[f32; 2]; 2]),
+    I32(i32),
+    F32(f32),
+    Vec2((f32, f32)),
+    Vec3((f32, f32, f32)),
+    Vec4((f32, f32, f32, f32)),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GLCommand {
+    CreateBuffer(Reference),
+    DeleteBuffer(Reference),
+    BindBuffer(BufferKind, Reference),
+    UnbindBuffer(BufferKind),
+    BufferData {
+        kind: BufferKind,
+        buffer: Reference,
+        len: usize,
+        usage: DrawMode,
+    },
+    BufferSubData {
+        kind: BufferKind,
+        buffer: Reference,
+        offset: u32,
+        len: usize,
+    },
+
+    CreateShader(ShaderKind, Reference),
+    ShaderSource(Reference, String),
+    CompileShader(Reference),
+    CreateProgram(Reference),
+    AttachShader(Reference, Reference),
+    BindAttribLocation(Reference, String, u32),
+    LinkProgram(Reference),
+    LabelProgram(Reference, String),
+    UseProgram(Reference),
+
+    VertexAttribPointer {
+        location: u32,
+        size: AttributeSize,
+        kind: DataType,
+        normalized: bool,
+        stride: u32,
+        offset: u32,
+    },
+    EnableVertexAttribArray(u32),
+    DisableVertexAttribArray(u32),
+    VertexAttribDivisor(u32, u32),
+
+    ClearColor(f32, f32, f32, f32),
+    Enable(i32),
+    Disable(i32),
+    CullFace(Culling),
+    DepthMask(bool),
+    DepthFunc(DepthTest),
+    ClearDepth(f32),
+    Clear(BufferBit),
+    Viewport(i32, i32, u32, u32),
+    Scissor(i32, i32, u32, u32),
+
+    DrawElements {
+        mode: Primitives,
+        count: usize,
+        kind: DataType,
+        offset: u32,
+    },
+    DrawElementsInstanced {
+        mode: Primitives,
+        count: usize,
+        kind: DataType,
+        offset: u32,
+        instances: usize,
+    },
+    DrawArrays {
+        mode: Primitives,
+        count: usize,
+    },
+    ReadPixels {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    PixelStorei(PixelStorageMode, i32),
+
+    CreateTexture(Reference),
+    DeleteTexture(Reference),
+    TexImage2D {
+        texture: Reference,
+        target: TextureBindPoint,
+        level: u8,
+        width: u16,
+        height: u16,
+        format: PixelFormat,
+        kind: PixelType,
+        len: usize,
+    },
+    TexSubImage2D {
+        texture: Reference,
+        target: TextureBindPoint,
+        level: u8,
+        xoffset: u16,
+        yoffset: u16,
+        width: u16,
+        height: u16,
+        len: usize,
+    },
+    CompressedTexImage2D {
+        texture: Reference,
+        target: TextureBindPoint,
+        level: u8,
+        compression: TextureCompression,
+        width: u16,
+        height: u16,
+        len: usize,
+    },
+    GenerateMipmap,
+    GenerateMipmapCube,
+    ActiveTexture(u32),
+    BindTexture(Reference),
+    UnbindTexture,
+    BindTextureCube(Reference),
+    UnbindTextureCube,
+    TexParameteri(TextureKind, TextureParameter, i32),
+    TexParameterf(TextureKind, TextureParameter, f32),
+
+    BlendEquation(BlendEquation),
+    BlendFunc(BlendMode, BlendMode),
+    BlendColor(f32, f32, f32, f32),
+
+    Uniform {
+        program: Reference,
+        name: String,
+        value: UniformValue,
+    },
+
+    CreateVertexArray(Reference),
+    DeleteVertexArray(Reference),
+    BindVertexArray(Reference),
+    UnbindVertexArray,
+
+    DrawBuffer(Vec<ColorBuffer>),
+    CreateFramebuffer(Reference),
+    DeleteFramebuffer(Reference),
+    BindFramebuffer(Buffers, Reference),
+    FramebufferTexture2D {
+        attachment: Buffers,
+        textarget: TextureBindPoint,
+        texture: Reference,
+        level: i32,
+    },
+    UnbindFramebuffer(Buffers),
+}
+
+/// Snapshot of the bound state at the time of a draw call
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrawCall {
+    pub program: Reference,
+    pub vertex_array: Reference,
+    pub framebuffer: Reference,
+    /// (unit, texture) pairs bound when the draw call was issued
+    pub textures: Vec<(u32, Reference)>,
+    pub count: usize,
+    /// 1 unless the draw call is instanced
+    pub instances: usize,
+
+    pub blend: bool,
+    pub depth_test: bool,
+    pub cull_face: bool,
+    pub depth_mask: bool,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    counter: Reference,
+    commands: Vec<GLCommand>,
+    draw_calls: Vec<DrawCall>,
+
+    program: Reference,
+    vertex_array: Reference,
+    framebuffer: Reference,
+    array_buffer: Reference,
+    element_buffer: Reference,
+    active_unit: u32,
+    textures: HashMap<u32, Reference>,
+
+    enabled: Vec<i32>,
+    depth_mask: bool,
+
+    attrib_locations: HashMap<(Reference, String), u32>,
+    uniform_locations: HashMap<(Reference, String), Reference>,
+    uniforms: HashMap<(Reference, String), UniformValue>,
+}
+
+impl MockState {
+    fn next_handle(&mut self) -> Reference {
+        self.counter += 1;
+        self.counter
+    }
+
+    fn is_enabled(&self, flag: i32) -> bool {
+        self.enabled.contains(&flag)
+    }
+
+    fn bound_texture(&self) -> Reference {
+        self.textures.get(&self.active_unit).cloned().unwrap_or(0)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GLContext {
+    pub reference: Reference,
+    pub is_webgl2: bool,
+    /// 32-bit index buffers are supported (OES_element_index_uint on WebGL1)
+    pub element_index_uint: bool,
+    /// Instanced draws are supported (ANGLE_instanced_arrays on WebGL1)
+    pub instanced_arrays: bool,
+    /// Float and half float color attachments can be rendered to
+    /// (EXT_color_buffer_float on WebGL2, never on WebGL1)
+    pub color_buffer_float: bool,
+    /// Several color attachments can be drawn to at once
+    /// (WEBGL_draw_buffers on WebGL1)
+    pub draw_buffers: bool,
+    state: Rc<RefCell<MockState>>,
+}
+
+impl PartialEq for GLContext {
+    fn eq(&self, other: &GLContext) -> bool {
+        self.reference == other.reference && Rc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+pub type WebGLContext<'p> = Box<'p + for<'a> FnMut(&'a str) -> *const c_void>;
+
+impl WebGLRenderingContext {
+    pub fn new<'p>(_loadfn: WebGLContext<'p>) -> WebGLRenderingContext {
+        WebGLRenderingContext {
+            common: GLContext::new(),
+        }
+    }
+}
+
+impl GLContext {
+    pub fn new() -> GLContext {
+        let mut state = MockState::default();
+        state.depth_mask = true;
+
+        GLContext {
+            reference: 0,
+            is_webgl2: true,
+            element_index_uint: true,
+            instanced_arrays: true,
+            color_buffer_float: true,
+            draw_buffers: true,
+            state: Rc::new(RefCell::new(state)),
+        }
+    }
+
+    pub fn print<T: Into<String>>(msg: T) {
+        print!("{}", msg.into());
+    }
+
+    fn record(&self, cmd: GLCommand) {
+        self.state.borrow_mut().commands.push(cmd);
+    }
+
+    fn new_handle(&self) -> Reference {
+        self.state.borrow_mut().next_handle()
+    }
+
+    /// All commands recorded since the context was created (or last cleared)
+    pub fn commands(&self) -> Vec<GLCommand> {
+        self.state.borrow().commands.clone()
+    }
+
+    /// All draw calls recorded since the context was created (or last cleared)
+    pub fn draw_calls(&self) -> Vec<DrawCall> {
+        self.state.borrow().draw_calls.clone()
+    }
+
+    /// Forget all recorded commands and draw calls, the bound state is kept.
+    pub fn clear_commands(&self) {
+        let mut state = self.state.borrow_mut();
+        state.commands.clear();
+        state.draw_calls.clear();
+    }
+
+    /// The last value uploaded to the uniform `name` of `program`
+    pub fn uniform_value(&self, program: Reference, name: &str) -> Option<UniformValue> {
+        self.state
+            .borrow()
+            .uniforms
+            .get(&(program, name.to_string()))
+            .cloned()
+    }
+
+    pub fn create_buffer(&self) -> WebGLBuffer {
+        let buffer = WebGLBuffer(self.new_handle());
+        self.record(GLCommand::CreateBuffer(buffer.0));
+        buffer
+    }
+
+    pub fn delete_buffer(&self, buffer: &WebGLBuffer) {
+        self.record(GLCommand::DeleteBuffer(buffer.0));
+    }
+
+    pub fn bind_buffer(&self, kind: BufferKind, buffer: &WebGLBuffer) {
+        {
+            let mut state = self.state.borrow_mut();
+            match kind {
+                BufferKind::Array => state.array_buffer = buffer.0,
+                BufferKind::ElementArray => state.element_buffer = buffer.0,
+            }
+        }
+        self.record(GLCommand::BindBuffer(kind, buffer.0));
+    }
+
+    fn bound_buffer(&self, kind: BufferKind) -> Reference {
+        let state = self.state.borrow();
+        match kind {
+            BufferKind::Array => state.array_buffer,
+            BufferKind::ElementArray => state.element_buffer,
+        }
+    }
+
+    pub fn buffer_data(&self, kind: BufferKind, data: &[u8], draw: DrawMode) {
+        let buffer = self.bound_buffer(kind);
+        self.record(GLCommand::BufferData {
+            kind,
+            buffer,
+            len: data.len(),
+            usage: draw,
+        });
+    }
+
+    pub fn buffer_sub_data(&self, kind: BufferKind, offset: u32, data: &[u8]) {
+        let buffer = self.bound_buffer(kind);
+        self.record(GLCommand::BufferSubData {
+            kind,
+            buffer,
+            offset,
+            len: data.len(),
+        });
+    }
+
+    pub fn unbind_buffer(&self, kind: BufferKind) {
+        {
+            let mut state = self.state.borrow_mut();
+            match kind {
+                BufferKind::Array => state.array_buffer = 0,
+                BufferKind::ElementArray => state.element_buffer = 0,
+            }
+        }
+        self.record(GLCommand::UnbindBuffer(kind));
+    }
+
+    pub fn create_shader(&self, kind: ShaderKind) -> WebGLShader {
+        let shader = WebGLShader(self.new_handle());
+        self.record(GLCommand::CreateShader(kind, shader.0));
+        shader
+    }
+
+    pub fn shader_source(&self, shader: &WebGLShader, source: &str) {
+        self.record(GLCommand::ShaderSource(shader.0, source.to_string()));
+    }
+
+    pub fn compile_shader(&self, shader: &WebGLShader) {
+        self.record(GLCommand::CompileShader(shader.0));
+    }
+
+    pub fn create_program(&self) -> WebGLProgram {
+        let program = WebGLProgram(self.new_handle());
+        self.record(GLCommand::CreateProgram(program.0));
+        program
+    }
+
+    pub fn link_program(&self, program: &WebGLProgram) {
+        self.record(GLCommand::LinkProgram(program.0));
+    }
+
+    pub fn label_program(&self, program: &WebGLProgram, label: &str) {
+        self.record(GLCommand::LabelProgram(program.0, label.to_string()));
+    }
+
+    pub fn use_program(&self, program: &WebGLProgram) {
+        self.state.borrow_mut().program = program.0;
+        self.record(GLCommand::UseProgram(program.0));
+    }
+
+    pub fn attach_shader(&self, program: &WebGLProgram, shader: &WebGLShader) {
+        self.record(GLCommand::AttachShader(program.0, shader.0));
+    }
+
+    pub fn bind_attrib_location(&self, program: &WebGLProgram, name: &str, loc: u32) {
+        self.state
+            .borrow_mut()
+            .attrib_locations
+            .insert((program.0, name.to_string()), loc);
+        self.record(GLCommand::BindAttribLocation(
+            program.0,
+            name.to_string(),
+            loc,
+        ));
+    }
+
+    pub fn get_attrib_location(&self, program: &WebGLProgram, name: &str) -> Option<u32> {
+        self.state
+            .borrow()
+            .attrib_locations
+            .get(&(program.0, name.to_string()))
+            .cloned()
+    }
+
+    pub fn get_uniform_location(
+        &self,
+        program: &WebGLProgram,
+        name: &str,
+    ) -> Option<WebGLUniformLocation> {
+        let mut state = self.state.borrow_mut();
+        let key = (program.0, name.to_string());
+
+        let reference = match state.uniform_locations.get(&key) {
+            Some(r) => *r,
+            None => {
+                let r = state.next_handle();
+                state.uniform_locations.insert(key, r);
+                r
+            }
+        };
+
+        Some(WebGLUniformLocation {
+            reference,
+            name: name.into(),
+        })
+    }
+
+    pub fn vertex_attrib_pointer(
+        &self,
+        location: u32,
+        size: AttributeSize,
+        kind: DataType,
+        normalized: bool,
+        stride: u32,
+        offset: u32,
+    ) {
+        self.record(GLCommand::VertexAttribPointer {
+            location,
+            size,
+            kind,
+            normalized,
+            stride,
+            offset,
+        });
+    }
+
+    pub fn enable_vertex_attrib_array(&self, location: u32) {
+        self.record(GLCommand::EnableVertexAttribArray(location));
+    }
+
+    pub fn disable_vertex_attrib_array(&self, location: u32) {
+        self.record(GLCommand::DisableVertexAttribArray(location));
+    }
+
+    pub fn vertex_attrib_divisor(&self, location: u32, divisor: u32) {
+        self.record(GLCommand::VertexAttribDivisor(location, divisor));
+    }
+
+    pub fn clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.record(GLCommand::ClearColor(r, g, b, a));
+    }
+
+    pub fn enable(&self, flag: i32) {
+        {
+            let mut state = self.state.borrow_mut();
+            if !state.is_enabled(flag) {
+                state.enabled.push(flag);
+            }
+        }
+        self.record(GLCommand::Enable(flag));
+    }
+
+    pub fn disable(&self, flag: i32) {
+        self.state.borrow_mut().enabled.retain(|f| *f != flag);
+        self.record(GLCommand::Disable(flag));
+    }
+
+    pub fn cull_face(&self, flag: Culling) {
+        self.record(GLCommand::CullFace(flag));
+    }
+
+    pub fn depth_mask(&self, b: bool) {
+        self.state.borrow_mut().depth_mask = b;
+        self.record(GLCommand::DepthMask(b));
+    }
+
+    pub fn depth_func(&self, d: DepthTest) {
+        self.record(GLCommand::DepthFunc(d));
+    }
+
+    pub fn clear_depth(&self, value: f32) {
+        self.record(GLCommand::ClearDepth(value));
+    }
+
+    pub fn clear(&self, bit: BufferBit) {
+        self.record(GLCommand::Clear(bit));
+    }
+
+    pub fn viewport(&self, x: i32, y: i32, width: u32, height: u32) {
+        self.record(GLCommand::Viewport(x, y, width, height));
+    }
+
+    pub fn scissor(&self, x: i32, y: i32, width: u32, height: u32) {
+        self.record(GLCommand::Scissor(x, y, width, height));
+    }
+
+    fn record_draw_call(&self, count: usize, instances: usize) {
+        let mut state = self.state.borrow_mut();
+
+        let mut textures: Vec<(u32, Reference)> = state
+            .textures
+            .iter()
+            .filter(|&(_, t)| *t != 0)
+            .map(|(u, t)| (*u, *t))
+            .collect();
+        textures.sort();
+
+        let call = DrawCall {
+            program: state.program,
+            vertex_array: state.vertex_array,
+            framebuffer: state.framebuffer,
+            textures,
+            count,
+            instances,
+            blend: state.is_enabled(Flag::Blend as i32),
+            depth_test: state.is_enabled(Flag::DepthTest as i32),
+            cull_face: state.is_enabled(Culling::CullFace as i32),
+            depth_mask: state.depth_mask,
+        };
+
+        state.draw_calls.push(call);
+    }
+
+    pub fn draw_elements(&self, mode: Primitives, count: usize, kind: DataType, offset: u32) {
+        self.record_draw_call(count, 1);
+        self.record(GLCommand::DrawElements {
+            mode,
+            count,
+            kind,
+            offset,
+        });
+    }
+
+    pub fn draw_elements_instanced(
+        &self,
+        mode: Primitives,
+        count: usize,
+        kind: DataType,
+        offset: u32,
+        instances: usize,
+    ) {
+        self.record_draw_call(count, instances);
+        self.record(GLCommand::DrawElementsInstanced {
+            mode,
+            count,
+            kind,
+            offset,
+            instances,
+        });
+    }
+
+    pub fn draw_arrays(&self, mode: Primitives, count: usize) {
+        self.record_draw_call(count, 1);
+        self.record(GLCommand::DrawArrays { mode, count });
+    }
+
+    pub fn read_pixels(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        _format: PixelFormat,
+        _kind: PixelType,
+        data: &mut [u8],
+    ) {
+        // There is no frame buffer at all, just give back a black image.
+        for d in data.iter_mut() {
+            *d = 0;
+        }
+
+        self.record(GLCommand::ReadPixels {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    pub fn pixel_storei(&self, storage: PixelStorageMode, value: i32) {
+        self.record(GLCommand::PixelStorei(storage, value));
+    }
+
+    pub fn tex_image2d(
+        &self,
+        target: TextureBindPoint,
+        level: u8,
+        width: u16,
+        height: u16,
+        format: PixelFormat,
+        kind: PixelType,
+        pixels: &[u8],
+    ) {
+        let texture = self.state.borrow().bound_texture();
+        self.record(GLCommand::TexImage2D {
+            texture,
+            target,
+            level,
+            width,
+            height,
+            format,
+            kind,
+            len: pixels.len(),
+        });
+    }
+
+    pub fn tex_sub_image2d(
+        &self,
+        target: TextureBindPoint,
+        level: u8,
+        xoffset: u16,
+        yoffset: u16,
+        width: u16,
+        height: u16,
+        _format: PixelFormat,
+        _kind: PixelType,
+        pixels: &[u8],
+    ) {
+        let texture = self.state.borrow().bound_texture();
+        self.record(GLCommand::TexSubImage2D {
+            texture,
+            target,
+            level,
+            xoffset,
+            yoffset,
+            width,
+            height,
+            len: pixels.len(),
+        });
+    }
+
+    pub fn compressed_tex_image2d(
+        &self,
+        target: TextureBindPoint,
+        level: u8,
+        compression: TextureCompression,
+        width: u16,
+        height: u16,
+        data: &[u8],
+    ) {
+        let texture = self.state.borrow().bound_texture();
+        self.record(GLCommand::CompressedTexImage2D {
+            texture,
+            target,
+            level,
+            compression,
+            width,
+            height,
+            len: data.len(),
+        });
+    }
+
+    pub fn get_program_parameter(&self, _program: &WebGLProgram, pname: ShaderParameter) -> i32 {
+        match pname {
+            ShaderParameter::LinkStatus
+            | ShaderParameter::CompileStatus
+            | ShaderParameter::ValidateStatus => 1,
+            _ => 0,
+        }
+    }
+
+    pub fn create_texture(&self) -> WebGLTexture {
+        let texture = WebGLTexture(self.new_handle());
+        self.record(GLCommand::CreateTexture(texture.0));
+        texture
+    }
+
+    pub fn delete_texture(&self, texture: &WebGLTexture) {
+        self.state
+            .borrow_mut()
+            .textures
+            .retain(|_, t| *t != texture.0);
+        self.record(GLCommand::DeleteTexture(texture.0));
+    }
+
+    pub fn generate_mipmap(&self) {
+        self.record(GLCommand::GenerateMipmap);
+    }
+
+    pub fn generate_mipmap_cube(&self) {
+        self.record(GLCommand::GenerateMipmapCube);
+    }
+
+    pub fn active_texture(&self, active: u32) {
+        self.state.borrow_mut().active_unit = active;
+        self.record(GLCommand::ActiveTexture(active));
+    }
+
+    pub fn bind_texture(&self, texture: &WebGLTexture) {
+        {
+            let mut state = self.state.borrow_mut();
+            let unit = state.active_unit;
+            state.textures.insert(unit, texture.0);
+        }
+        self.record(GLCommand::BindTexture(texture.0));
+    }
+
+    pub fn unbind_texture(&self) {
+        {
+            let mut state = self.state.borrow_mut();
+            let unit = state.active_unit;
+            state.textures.remove(&unit);
+        }
+        self.record(GLCommand::UnbindTexture);
+    }
+
+    pub fn bind_texture_cube(&self, texture: &WebGLTexture) {
+        {
+            let mut state = self.state.borrow_mut();
+            let unit = state.active_unit;
+            state.textures.insert(unit, texture.0);
+        }
+        self.record(GLCommand::BindTextureCube(texture.0));
+    }
+
+    pub fn unbind_texture_cube(&self) {
+        {
+            let mut state = self.state.borrow_mut();
+            let unit = state.active_unit;
+            state.textures.remove(&unit);
+        }
+        self.record(GLCommand::UnbindTextureCube);
+    }
+
+    pub fn blend_equation(&self, eq: BlendEquation) {
+        self.record(GLCommand::BlendEquation(eq));
+    }
+
+    pub fn blend_func(&self, b1: BlendMode, b2: BlendMode) {
+        self.record(GLCommand::BlendFunc(b1, b2));
+    }
+
+    pub fn blend_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.record(GLCommand::BlendColor(r, g, b, a));
+    }
+
+    fn set_uniform(&self, location: &WebGLUniformLocation, value: UniformValue) {
+        let program = {
+            let mut state = self.state.borrow_mut();
+            let program = state.program;
+            state
+                .uniforms
+                .insert((program, location.name.clone()), value.clone());
+            program
+        };
+
+        self.record(GLCommand::Uniform {
+            program,
+            name: location.name.clone(),
+            value,
+        });
+    }
+
+    pub fn uniform_matrix_4fv(&self, location: &WebGLUniformLocation, value: &[[f32; 4]; 4]) {
+        self.set_uniform(location, UniformValue::Matrix4(*value));
+    }
+
+    pub fn uniform_matrix_3fv(&self, location: &WebGLUniformLocation, value: &[[f32; 3]; 3]) {
+        self.set_uniform(location, UniformValue::Matrix3(*value));
+    }
+
+    pub fn uniform_matrix_2fv(&self, location: &WebGLUniformLocation, value: &[[f32; 2]; 2]) {
+        self.set_uniform(location, UniformValue::Matrix2(*value));
+    }
+
+    pub fn uniform_1i(&self, location: &WebGLUniformLocation, value: i32) {
+        self.set_uniform(location, UniformValue::I32(value));
+    }
+
+    pub fn uniform_1f(&self, location: &WebGLUniformLocation, value: f32) {
+        self.set_uniform(location, UniformValue::F32(value));
+    }
+
+    pub fn uniform_2f(&self, location: &WebGLUniformLocation, value: (f32, f32)) {
+        self.set_uniform(location, UniformValue::Vec2(value));
+    }
+
+    pub fn uniform_3f(&self, location: &WebGLUniformLocation, value: (f32, f32, f32)) {
+        self.set_uniform(location, UniformValue::Vec3(value));
+    }
+
+    pub fn uniform_4f(&self, location: &WebGLUniformLocation, value: (f32, f32, f32, f32)) {
+        self.set_uniform(location, UniformValue::Vec4(value));
+    }
+
+    pub fn tex_parameteri(&self, kind: TextureKind, pname: TextureParameter, param: i32) {
+        self.record(GLCommand::TexParameteri(kind, pname, param));
+    }
+
+    pub fn tex_parameterfv(&self, kind: TextureKind, pname: TextureParameter, param: f32) {
+        self.record(GLCommand::TexParameterf(kind, pname, param));
+    }
+
+    pub fn create_vertex_array(&self) -> WebGLVertexArray {
+        let vao = WebGLVertexArray(self.new_handle());
+        self.record(GLCommand::CreateVertexArray(vao.0));
+        vao
+    }
+
+    pub fn delete_vertex_array(&self, vao: &WebGLVertexArray) {
+        self.record(GLCommand::DeleteVertexArray(vao.0));
+    }
+
+    pub fn bind_vertex_array(&self, vao: &WebGLVertexArray) {
+        self.state.borrow_mut().vertex_array = vao.0;
+        self.record(GLCommand::BindVertexArray(vao.0));
+    }
+
+    pub fn unbind_vertex_array(&self, _vao: &WebGLVertexArray) {
+        self.state.borrow_mut().vertex_array = 0;
+        self.record(GLCommand::UnbindVertexArray);
+    }
+
+    pub fn draw_buffer(&self, buffers: &[ColorBuffer]) {
+        self.record(GLCommand::DrawBuffer(buffers.to_vec()));
+    }
+
+    pub fn create_framebuffer(&self) -> WebGLFrameBuffer {
+        let fb = WebGLFrameBuffer(self.new_handle());
+        self.record(GLCommand::CreateFramebuffer(fb.0));
+        fb
+    }
+
+    pub fn delete_framebuffer(&self, fb: &WebGLFrameBuffer) {
+        self.record(GLCommand::DeleteFramebuffer(fb.0));
+    }
+
+    pub fn bind_framebuffer(&self, buffer: Buffers, fb: &WebGLFrameBuffer) {
+        self.state.borrow_mut().framebuffer = fb.0;
+        self.record(GLCommand::BindFramebuffer(buffer, fb.0));
+    }
+
+    pub fn framebuffer_texture2d(
+        &self,
+        _target: Buffers,
+        attachment: Buffers,
+        textarget: TextureBindPoint,
+        texture: &WebGLTexture,
+        level: i32,
+    ) {
+        self.record(GLCommand::FramebufferTexture2D {
+            attachment,
+            textarget,
+            texture: texture.0,
+            level,
+        });
+    }
+
+    pub fn unbind_framebuffer(&self, buffer: Buffers) {
+        self.state.borrow_mut().framebuffer = 0;
+        self.record(GLCommand::UnbindFramebuffer(buffer));
+    }
+}
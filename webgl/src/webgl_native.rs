@@ -15,6 +15,16 @@ pub type Reference = u32;
 pub struct GLContext {
     pub reference: Reference,
     pub is_webgl2: bool,
+    /// 32-bit index buffers are supported (OES_element_index_uint on WebGL1)
+    pub element_index_uint: bool,
+    /// Instanced draws are supported (ANGLE_instanced_arrays on WebGL1)
+    pub instanced_arrays: bool,
+    /// Float and half float color attachments can be rendered to
+    /// (EXT_color_buffer_float on WebGL2, never on WebGL1)
+    pub color_buffer_float: bool,
+    /// Several color attachments can be drawn to at once
+    /// (WEBGL_draw_buffers on WebGL1)
+    pub draw_buffers: bool,
 }
 
 pub fn check_gl_error(msg: &str) {
@@ -74,6 +84,10 @@ impl GLContext {
         GLContext {
             reference: 0,
             is_webgl2: true,
+            element_index_uint: true,
+            instanced_arrays: true,
+            color_buffer_float: true,
+            draw_buffers: true,
         }
     }
 
@@ -207,6 +221,9 @@ impl GLContext {
         check_gl_error("link_program");
     }
 
+    /// Give the program a name, only used by the non-GPU backends
+    pub fn label_program(&self, _program: &WebGLProgram, _label: &str) {}
+
     pub fn use_program(&self, program: &WebGLProgram) {
         unsafe {
             gl::UseProgram(program.0);
@@ -293,6 +310,20 @@ impl GLContext {
         check_gl_error("enable_vertex_attrib_array");
     }
 
+    pub fn disable_vertex_attrib_array(&self, location: u32) {
+        unsafe {
+            gl::DisableVertexAttribArray(location as _);
+        }
+        check_gl_error("disable_vertex_attrib_array");
+    }
+
+    pub fn vertex_attrib_divisor(&self, location: u32, divisor: u32) {
+        unsafe {
+            gl::VertexAttribDivisor(location as _, divisor as _);
+        }
+        check_gl_error("vertex_attrib_divisor");
+    }
+
     pub fn clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
         unsafe {
             gl::ClearColor(r, g, b, a);
@@ -357,6 +388,13 @@ impl GLContext {
         check_gl_error("viewport");
     }
 
+    pub fn scissor(&self, x: i32, y: i32, width: u32, height: u32) {
+        unsafe {
+            gl::Scissor(x, y, width as _, height as _);
+        };
+        check_gl_error("scissor");
+    }
+
     pub fn draw_elements(&self, mode: Primitives, count: usize, kind: DataType, offset: u32) {
         unsafe {
             gl::DrawElements(mode as _, count as _, kind as _, offset as _);
@@ -364,6 +402,26 @@ impl GLContext {
         check_gl_error("draw_elements");
     }
 
+    pub fn draw_elements_instanced(
+        &self,
+        mode: Primitives,
+        count: usize,
+        kind: DataType,
+        offset: u32,
+        instances: usize,
+    ) {
+        unsafe {
+            gl::DrawElementsInstanced(
+                mode as _,
+                count as _,
+                kind as _,
+                offset as _,
+                instances as _,
+            );
+        };
+        check_gl_error("draw_elements_instanced");
+    }
+
     pub fn draw_arrays(&self, mode: Primitives, count: usize) {
         unsafe {
             gl::DrawArrays(mode as _, 0, count as _);
@@ -425,7 +483,7 @@ impl GLContext {
             gl::TexImage2D(
                 target as _,
                 level as _,
-                format as _, // internal format
+                internal_format(format, kind) as _,
                 width as _,
                 height as _,
                 0,
@@ -764,10 +822,9 @@ impl GLContext {
     }
 
     pub fn draw_buffer(&self, buffers: &[ColorBuffer]) {
+        let values: Vec<u32> = buffers.iter().map(|c| *c as u32).collect();
         unsafe {
-            for value in buffers {
-                gl::DrawBuffer(*value as _);
-            }
+            gl::DrawBuffers(values.len() as _, values.as_ptr());
         }
         check_gl_error("draw_buffer");
     }
@@ -825,3 +882,14 @@ impl GLContext {
         check_gl_error("unbind_framebuffer");
     }
 }
+
+/// Float textures need a sized internal format
+fn internal_format(format: PixelFormat, kind: PixelType) -> u32 {
+    match (format, kind) {
+        (PixelFormat::Rgba, PixelType::Float) => 0x8814,     // RGBA32F
+        (PixelFormat::Rgba, PixelType::HalfFloat) => 0x881A, // RGBA16F
+        (PixelFormat::Rgb, PixelType::Float) => 0x8815,      // RGB32F
+        (PixelFormat::Rgb, PixelType::HalfFloat) => 0x881B,  // RGB16F
+        _ => format as u32,
+    }
+}
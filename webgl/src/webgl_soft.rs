@@ -0,0 +1,1993 @@
+// A software rasterizer backend.
+//
+// It implements the subset of the WebGL API the engine uses on the CPU, so real
+// images can be rendered on machines without a GPU. GLSL is not supported,
+// programs are matched by their label against Rust closures registered with
+// `GLContext::register_program`.
+
+use glenum::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+use std::os::raw::c_void;
+use std::rc::Rc;
+
+use common::*;
+
+pub type Reference = u32;
+
+pub type Vec4 = [f32; 4];
+
+const MAX_VERTEX_ATTRIBS: usize = 16;
+
+const IDENTITY: [[f32; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UniformValue {
+    Matrix4([[f32; 4]; 4]),
+    Matrix3([[f32; 3]; 3]),
+    Matrix2([[f32; 2]; 2]),
+    I32(i32),
+    F32(f32),
+    Vec2((f32, f32)),
+    Vec3((f32, f32, f32)),
+    Vec4((f32, f32, f32, f32)),
+}
+
+/// What a soft program can read while running:
+/// the uniforms of the program and the bound textures
+pub struct ShaderEnv<'a> {
+    uniforms: &'a HashMap<String, UniformValue>,
+    textures: &'a HashMap<Reference, SoftTexture>,
+    units: &'a HashMap<u32, Reference>,
+}
+
+impl<'a> ShaderEnv<'a> {
+    pub fn uniform(&self, name: &str) -> Option<&UniformValue> {
+        self.uniforms.get(name)
+    }
+
+    pub fn mat4(&self, name: &str) -> [[f32; 4]; 4] {
+        match self.uniform(name) {
+            Some(&UniformValue::Matrix4(m)) => m,
+            _ => IDENTITY,
+        }
+    }
+
+    pub fn vec2(&self, name: &str) -> [f32; 2] {
+        match self.uniform(name) {
+            Some(&UniformValue::Vec2(v)) => [v.0, v.1],
+            _ => [0.0; 2],
+        }
+    }
+
+    pub fn vec3(&self, name: &str) -> [f32; 3] {
+        match self.uniform(name) {
+            Some(&UniformValue::Vec3(v)) => [v.0, v.1, v.2],
+            _ => [0.0; 3],
+        }
+    }
+
+    pub fn vec4(&self, name: &str) -> Vec4 {
+        match self.uniform(name) {
+            Some(&UniformValue::Vec4(v)) => [v.0, v.1, v.2, v.3],
+            _ => [0.0; 4],
+        }
+    }
+
+    pub fn float(&self, name: &str) -> f32 {
+        match self.uniform(name) {
+            Some(&UniformValue::F32(f)) => f,
+            Some(&UniformValue::I32(i)) => i as f32,
+            _ => 0.0,
+        }
+    }
+
+    pub fn int(&self, name: &str) -> i32 {
+        match self.uniform(name) {
+            Some(&UniformValue::I32(i)) => i,
+            _ => 0,
+        }
+    }
+
+    /// Sample the 2d texture bound to the sampler uniform `name`
+    pub fn texture2d(&self, name: &str, uv: [f32; 2]) -> Vec4 {
+        match self.sampler(name) {
+            Some(tex) => tex.sample(0, uv[0], uv[1]),
+            None => [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    /// Sample the cube map bound to the sampler uniform `name`
+    pub fn texture_cube(&self, name: &str, dir: [f32; 3]) -> Vec4 {
+        match self.sampler(name) {
+            Some(tex) => tex.sample_cube(dir),
+            None => [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    fn sampler(&self, name: &str) -> Option<&SoftTexture> {
+        let unit = self.int(name) as u32;
+        self.units.get(&unit).and_then(|t| self.textures.get(t))
+    }
+}
+
+pub type VertexShader = Box<Fn(&ShaderEnv, &[Vec4], &mut Vec<f32>) -> Vec4>;
+pub type FragmentShader = Box<Fn(&ShaderEnv, &[f32]) -> Option<Vec4>>;
+
+/// A Rust stand-in for a GLSL program.
+///
+/// The vertex closure gets the attributes indexed by location and pushes its varyings,
+/// then returns the clip space position. The fragment closure gets the interpolated
+/// varyings and returns the color, or None to discard the fragment.
+pub struct SoftProgram {
+    pub vertex: VertexShader,
+    pub fragment: FragmentShader,
+}
+
+impl SoftProgram {
+    pub fn new<V, F>(vertex: V, fragment: F) -> SoftProgram
+    where
+        V: 'static + Fn(&ShaderEnv, &[Vec4], &mut Vec<f32>) -> Vec4,
+        F: 'static + Fn(&ShaderEnv, &[f32]) -> Option<Vec4>,
+    {
+        SoftProgram {
+            vertex: Box::new(vertex),
+            fragment: Box::new(fragment),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Image {
+    width: u32,
+    height: u32,
+    data: Vec<Vec4>,
+    /// Float images keep the values written out of [0, 1]
+    float: bool,
+}
+
+impl Image {
+    fn new(width: u32, height: u32, fill: Vec4) -> Image {
+        Image {
+            width,
+            height,
+            data: vec![fill; (width * height) as usize],
+            float: false,
+        }
+    }
+
+    fn fetch(&self, x: u32, y: u32) -> Vec4 {
+        self.data[(y * self.width + x) as usize]
+    }
+
+    /// Set the pixels in the box to c, all of them without a box
+    fn fill(&mut self, rect: Option<(i32, i32, u32, u32)>, c: Vec4) {
+        let (x, y, w, h) = rect.unwrap_or((0, 0, self.width, self.height));
+        let x0 = x.max(0).min(self.width as i32) as u32;
+        let y0 = y.max(0).min(self.height as i32) as u32;
+        let x1 = (x + w as i32).max(0).min(self.width as i32) as u32;
+        let y1 = (y + h as i32).max(0).min(self.height as i32) as u32;
+
+        for py in y0..y1 {
+            for px in x0..x1 {
+                self.data[(py * self.width + px) as usize] = c;
+            }
+        }
+    }
+
+    /// The color stored for c, clamped unless the image is a float one
+    fn store(&self, c: Vec4) -> Vec4 {
+        if self.float {
+            return c;
+        }
+
+        [
+            c[0].max(0.0).min(1.0),
+            c[1].max(0.0).min(1.0),
+            c[2].max(0.0).min(1.0),
+            c[3].max(0.0).min(1.0),
+        ]
+    }
+}
+
+#[derive(Debug)]
+struct SoftTexture {
+    // 1 face for 2d texture, 6 faces for cube map
+    faces: Vec<Image>,
+    mag_filter: i32,
+    wrap_s: i32,
+    wrap_t: i32,
+}
+
+impl SoftTexture {
+    fn new() -> SoftTexture {
+        SoftTexture {
+            faces: vec![Image::default()],
+            mag_filter: TextureMagFilter::Linear as i32,
+            wrap_s: TextureWrap::Repeat as i32,
+            wrap_t: TextureWrap::Repeat as i32,
+        }
+    }
+
+    fn face_mut(&mut self, face: usize) -> &mut Image {
+        if self.faces.len() <= face {
+            self.faces.resize(face + 1, Image::default());
+        }
+
+        &mut self.faces[face]
+    }
+
+    fn sample(&self, face: usize, s: f32, t: f32) -> Vec4 {
+        self.sample_with(face, s, t, self.wrap_s, self.wrap_t)
+    }
+
+    fn sample_with(&self, face: usize, s: f32, t: f32, wrap_s: i32, wrap_t: i32) -> Vec4 {
+        let img = match self.faces.get(face) {
+            Some(img) if img.width > 0 && img.height > 0 => img,
+            _ => return [0.0, 0.0, 0.0, 1.0],
+        };
+
+        let (w, h) = (img.width as f32, img.height as f32);
+
+        if self.mag_filter != TextureMagFilter::Linear as i32 {
+            let x = wrap(wrap_s, (s * w).floor() as i32, img.width);
+            let y = wrap(wrap_t, (t * h).floor() as i32, img.height);
+            return img.fetch(x, y);
+        }
+
+        let u = s * w - 0.5;
+        let v = t * h - 0.5;
+        let (x0, y0) = (u.floor(), v.floor());
+        let (fx, fy) = (u - x0, v - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+
+        let xs = [wrap(wrap_s, x0, img.width), wrap(wrap_s, x0 + 1, img.width)];
+        let ys = [wrap(wrap_t, y0, img.height), wrap(wrap_t, y0 + 1, img.height)];
+
+        let top = lerp4(img.fetch(xs[0], ys[0]), img.fetch(xs[1], ys[0]), fx);
+        let bottom = lerp4(img.fetch(xs[0], ys[1]), img.fetch(xs[1], ys[1]), fx);
+        lerp4(top, bottom, fy)
+    }
+
+    fn sample_cube(&self, dir: [f32; 3]) -> Vec4 {
+        let (x, y, z) = (dir[0], dir[1], dir[2]);
+        let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+
+        // See table 3.21 in the OpenGL ES 2.0 spec
+        let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+            if x >= 0.0 {
+                (0, -z, -y, ax)
+            } else {
+                (1, z, -y, ax)
+            }
+        } else if ay >= az {
+            if y >= 0.0 {
+                (2, x, z, ay)
+            } else {
+                (3, x, -z, ay)
+            }
+        } else {
+            if z >= 0.0 {
+                (4, x, -y, az)
+            } else {
+                (5, -x, -y, az)
+            }
+        };
+
+        if ma == 0.0 {
+            return [0.0, 0.0, 0.0, 1.0];
+        }
+
+        let s = (sc / ma + 1.0) * 0.5;
+        let t = (tc / ma + 1.0) * 0.5;
+        let clamp = TextureWrap::ClampToEdge as i32;
+
+        self.sample_with(face, s, t, clamp, clamp)
+    }
+}
+
+fn wrap(mode: i32, i: i32, size: u32) -> u32 {
+    let n = size as i32;
+
+    let i = if mode == TextureWrap::ClampToEdge as i32 {
+        i.max(0).min(n - 1)
+    } else if mode == TextureWrap::MirroredRepeat as i32 {
+        let period = 2 * n;
+        let m = ((i % period) + period) % period;
+        if m < n {
+            m
+        } else {
+            period - 1 - m
+        }
+    } else {
+        ((i % n) + n) % n
+    };
+
+    i as u32
+}
+
+fn lerp4(a: Vec4, b: Vec4, t: f32) -> Vec4 {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+        a[3] + (b[3] - a[3]) * t,
+    ]
+}
+
+fn face_index(target: TextureBindPoint) -> usize {
+    match target {
+        TextureBindPoint::Texture2d => 0,
+        TextureBindPoint::TextureCubeMapPositiveX => 0,
+        TextureBindPoint::TextureCubeMapNegativeX => 1,
+        TextureBindPoint::TextureCubeMapPositiveY => 2,
+        TextureBindPoint::TextureCubeMapNegativeY => 3,
+        TextureBindPoint::TextureCubeMapPositiveZ => 4,
+        TextureBindPoint::TextureCubeMapNegativeZ => 5,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct AttribPointer {
+    buffer: Reference,
+    size: usize,
+    kind: DataType,
+    normalized: bool,
+    stride: u32,
+    offset: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+struct VertexArray {
+    attribs: [Option<AttribPointer>; MAX_VERTEX_ATTRIBS],
+    enabled: [bool; MAX_VERTEX_ATTRIBS],
+    divisors: [u32; MAX_VERTEX_ATTRIBS],
+    element_buffer: Reference,
+}
+
+#[derive(Debug, Default)]
+struct ProgramState {
+    label: Option<String>,
+    attribs: HashMap<String, u32>,
+    uniforms: HashMap<String, UniformValue>,
+}
+
+#[derive(Debug)]
+struct SoftFrameBuffer {
+    color: Option<(Reference, usize)>,
+    depth: Option<(Reference, usize)>,
+    draw_color: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Attachment {
+    ScreenColor,
+    ScreenDepth,
+    Texture(Reference, usize),
+}
+
+struct SoftState {
+    counter: Reference,
+
+    buffers: HashMap<Reference, Vec<u8>>,
+    array_buffer: Reference,
+
+    vertex_arrays: HashMap<Reference, VertexArray>,
+    vertex_array: Reference,
+
+    programs: HashMap<Reference, ProgramState>,
+    program: Reference,
+    registry: HashMap<String, Rc<SoftProgram>>,
+    missing: Vec<String>,
+
+    textures: HashMap<Reference, SoftTexture>,
+    units: HashMap<u32, Reference>,
+    active_unit: u32,
+
+    framebuffers: HashMap<Reference, SoftFrameBuffer>,
+    framebuffer: Reference,
+    screen_color: Image,
+    screen_depth: Image,
+
+    viewport: (i32, i32, u32, u32),
+    scissor: (i32, i32, u32, u32),
+    scissor_test: bool,
+    clear_color: Vec4,
+    clear_depth: f32,
+
+    blend: bool,
+    depth_test: bool,
+    cull: bool,
+    cull_mode: Culling,
+    depth_mask: bool,
+    depth_func: DepthTest,
+    blend_src: BlendMode,
+    blend_dst: BlendMode,
+    blend_equation: BlendEquation,
+    blend_color: Vec4,
+
+    unpack_alignment: usize,
+    pack_alignment: usize,
+}
+
+impl SoftState {
+    fn new() -> SoftState {
+        let mut vertex_arrays = HashMap::new();
+        vertex_arrays.insert(0, VertexArray::default());
+
+        SoftState {
+            counter: 0,
+            buffers: HashMap::new(),
+            array_buffer: 0,
+            vertex_arrays,
+            vertex_array: 0,
+            programs: HashMap::new(),
+            program: 0,
+            registry: HashMap::new(),
+            missing: Vec::new(),
+            textures: HashMap::new(),
+            units: HashMap::new(),
+            active_unit: 0,
+            framebuffers: HashMap::new(),
+            framebuffer: 0,
+            screen_color: Image::default(),
+            screen_depth: Image::default(),
+            viewport: (0, 0, 0, 0),
+            scissor: (0, 0, 0, 0),
+            scissor_test: false,
+            clear_color: [0.0, 0.0, 0.0, 0.0],
+            clear_depth: 1.0,
+            blend: false,
+            depth_test: false,
+            cull: false,
+            cull_mode: Culling::Back,
+            depth_mask: true,
+            depth_func: DepthTest::Less,
+            blend_src: BlendMode::One,
+            blend_dst: BlendMode::Zero,
+            blend_equation: BlendEquation::FuncAdd,
+            blend_color: [0.0, 0.0, 0.0, 0.0],
+            unpack_alignment: 4,
+            pack_alignment: 4,
+        }
+    }
+
+    fn next_handle(&mut self) -> Reference {
+        self.counter += 1;
+        self.counter
+    }
+
+    /// The box pixels are written in, when the scissor test is enabled
+    fn scissor_box(&self) -> Option<(i32, i32, u32, u32)> {
+        if self.scissor_test {
+            Some(self.scissor)
+        } else {
+            None
+        }
+    }
+
+    fn bound_texture(&self) -> Option<Reference> {
+        self.units.get(&self.active_unit).cloned()
+    }
+
+    fn vao_mut(&mut self) -> &mut VertexArray {
+        self.vertex_arrays
+            .entry(self.vertex_array)
+            .or_insert_with(Default::default)
+    }
+
+    fn bound_buffer(&self, kind: BufferKind) -> Reference {
+        match kind {
+            BufferKind::Array => self.array_buffer,
+            BufferKind::ElementArray => self
+                .vertex_arrays
+                .get(&self.vertex_array)
+                .map(|vao| vao.element_buffer)
+                .unwrap_or(0),
+        }
+    }
+
+    /// Returns the (color, depth) attachments of the bound frame buffer
+    fn attachments(&self) -> (Option<Attachment>, Option<Attachment>) {
+        if self.framebuffer == 0 {
+            return (Some(Attachment::ScreenColor), Some(Attachment::ScreenDepth));
+        }
+
+        match self.framebuffers.get(&self.framebuffer) {
+            Some(fb) => {
+                let color = if fb.draw_color { fb.color } else { None };
+                (
+                    color.map(|(t, f)| Attachment::Texture(t, f)),
+                    fb.depth.map(|(t, f)| Attachment::Texture(t, f)),
+                )
+            }
+            None => (None, None),
+        }
+    }
+
+    fn image_mut(&mut self, attachment: Attachment) -> Option<&mut Image> {
+        match attachment {
+            Attachment::ScreenColor => Some(&mut self.screen_color),
+            Attachment::ScreenDepth => Some(&mut self.screen_depth),
+            Attachment::Texture(t, face) => self.textures.get_mut(&t).map(|t| t.face_mut(face)),
+        }
+    }
+
+    fn target_mut(&mut self, attachment: Option<Attachment>) -> Option<&mut Image> {
+        match attachment {
+            Some(a) => self.image_mut(a),
+            None => None,
+        }
+    }
+
+    fn take_image(&mut self, attachment: Option<Attachment>) -> Option<Image> {
+        self.target_mut(attachment)
+            .map(|img| mem::replace(img, Image::default()))
+    }
+
+    fn put_image(&mut self, attachment: Option<Attachment>, img: Option<Image>) {
+        if let (Some(a), Some(img)) = (attachment, img) {
+            if let Some(slot) = self.image_mut(a) {
+                *slot = img;
+            }
+        }
+    }
+}
+
+pub struct GLContext {
+    pub reference: Reference,
+    pub is_webgl2: bool,
+    /// 32-bit index buffers are supported (OES_element_index_uint on WebGL1)
+    pub element_index_uint: bool,
+    /// Instanced draws are supported (ANGLE_instanced_arrays on WebGL1)
+    pub instanced_arrays: bool,
+    /// Float and half float color attachments can be rendered to
+    /// (EXT_color_buffer_float on WebGL2, never on WebGL1)
+    pub color_buffer_float: bool,
+    /// Several color attachments can be drawn to at once
+    /// (WEBGL_draw_buffers on WebGL1)
+    pub draw_buffers: bool,
+    state: Rc<RefCell<SoftState>>,
+}
+
+impl fmt::Debug for GLContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GLContext")
+            .field("reference", &self.reference)
+            .field("is_webgl2", &self.is_webgl2)
+            .field("element_index_uint", &self.element_index_uint)
+            .field("instanced_arrays", &self.instanced_arrays)
+            .field("color_buffer_float", &self.color_buffer_float)
+            .field("draw_buffers", &self.draw_buffers)
+            .finish()
+    }
+}
+
+impl Clone for GLContext {
+    fn clone(&self) -> GLContext {
+        GLContext {
+            reference: self.reference,
+            is_webgl2: self.is_webgl2,
+            element_index_uint: self.element_index_uint,
+            instanced_arrays: self.instanced_arrays,
+            color_buffer_float: self.color_buffer_float,
+            draw_buffers: self.draw_buffers,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl PartialEq for GLContext {
+    fn eq(&self, other: &GLContext) -> bool {
+        self.reference == other.reference && Rc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+pub type WebGLContext<'p> = Box<'p + for<'a> FnMut(&'a str) -> *const c_void>;
+
+impl WebGLRenderingContext {
+    pub fn new<'p>(_loadfn: WebGLContext<'p>) -> WebGLRenderingContext {
+        WebGLRenderingContext {
+            common: GLContext::new(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct ClipVertex {
+    pos: Vec4,
+    varyings: Vec<f32>,
+}
+
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    z: f32,
+    inv_w: f32,
+    // varyings divided by w
+    varyings: Vec<f32>,
+}
+
+/// The fixed function states used while rasterizing
+struct RasterState {
+    viewport: (i32, i32, u32, u32),
+    scissor: Option<(i32, i32, u32, u32)>,
+    blend: bool,
+    depth_test: bool,
+    depth_mask: bool,
+    depth_func: DepthTest,
+    cull: Option<Culling>,
+    blend_src: BlendMode,
+    blend_dst: BlendMode,
+    blend_equation: BlendEquation,
+    blend_color: Vec4,
+}
+
+struct Targets {
+    color: Option<Image>,
+    depth: Option<Image>,
+}
+
+impl Targets {
+    fn size(&self) -> Option<(u32, u32)> {
+        self.color
+            .as_ref()
+            .or(self.depth.as_ref())
+            .map(|img| (img.width, img.height))
+    }
+}
+
+impl GLContext {
+    pub fn new() -> GLContext {
+        GLContext {
+            reference: 0,
+            is_webgl2: true,
+            element_index_uint: true,
+            instanced_arrays: true,
+            color_buffer_float: true,
+            // Only the first color attachment is rasterized
+            draw_buffers: false,
+            state: Rc::new(RefCell::new(SoftState::new())),
+        }
+    }
+
+    pub fn print<T: Into<String>>(msg: T) {
+        print!("{}", msg.into());
+    }
+
+    /// Register the stand-in of all programs labeled `label`
+    pub fn register_program(&self, label: &str, program: SoftProgram) {
+        self.state
+            .borrow_mut()
+            .registry
+            .insert(label.to_string(), Rc::new(program));
+    }
+
+    pub fn label_program(&self, program: &WebGLProgram, label: &str) {
+        let mut state = self.state.borrow_mut();
+        if let Some(p) = state.programs.get_mut(&program.0) {
+            p.label = Some(label.to_string());
+        }
+    }
+
+    pub fn create_buffer(&self) -> WebGLBuffer {
+        let mut state = self.state.borrow_mut();
+        let handle = state.next_handle();
+        state.buffers.insert(handle, Vec::new());
+        WebGLBuffer(handle)
+    }
+
+    pub fn delete_buffer(&self, buffer: &WebGLBuffer) {
+        self.state.borrow_mut().buffers.remove(&buffer.0);
+    }
+
+    pub fn bind_buffer(&self, kind: BufferKind, buffer: &WebGLBuffer) {
+        let mut state = self.state.borrow_mut();
+        match kind {
+            BufferKind::Array => state.array_buffer = buffer.0,
+            BufferKind::ElementArray => state.vao_mut().element_buffer = buffer.0,
+        }
+    }
+
+    pub fn buffer_data(&self, kind: BufferKind, data: &[u8], _draw: DrawMode) {
+        let mut state = self.state.borrow_mut();
+        let buffer = state.bound_buffer(kind);
+        state.buffers.insert(buffer, data.to_vec());
+    }
+
+    pub fn buffer_sub_data(&self, kind: BufferKind, offset: u32, data: &[u8]) {
+        let mut state = self.state.borrow_mut();
+        let buffer = state.bound_buffer(kind);
+
+        if let Some(buf) = state.buffers.get_mut(&buffer) {
+            let offset = offset as usize;
+            if buf.len() < offset + data.len() {
+                buf.resize(offset + data.len(), 0);
+            }
+            buf[offset..offset + data.len()].copy_from_slice(data);
+        }
+    }
+
+    pub fn unbind_buffer(&self, kind: BufferKind) {
+        let mut state = self.state.borrow_mut();
+        match kind {
+            BufferKind::Array => state.array_buffer = 0,
+            BufferKind::ElementArray => state.vao_mut().element_buffer = 0,
+        }
+    }
+
+    pub fn create_shader(&self, _kind: ShaderKind) -> WebGLShader {
+        WebGLShader(self.state.borrow_mut().next_handle())
+    }
+
+    pub fn shader_source(&self, _shader: &WebGLShader, _source: &str) {}
+
+    pub fn compile_shader(&self, _shader: &WebGLShader) {}
+
+    pub fn create_program(&self) -> WebGLProgram {
+        let mut state = self.state.borrow_mut();
+        let handle = state.next_handle();
+        state.programs.insert(handle, ProgramState::default());
+        WebGLProgram(handle)
+    }
+
+    pub fn link_program(&self, _program: &WebGLProgram) {}
+
+    pub fn use_program(&self, program: &WebGLProgram) {
+        self.state.borrow_mut().program = program.0;
+    }
+
+    pub fn attach_shader(&self, _program: &WebGLProgram, _shader: &WebGLShader) {}
+
+    pub fn bind_attrib_location(&self, program: &WebGLProgram, name: &str, loc: u32) {
+        let mut state = self.state.borrow_mut();
+        if let Some(p) = state.programs.get_mut(&program.0) {
+            p.attribs.insert(name.to_string(), loc);
+        }
+    }
+
+    pub fn get_attrib_location(&self, program: &WebGLProgram, name: &str) -> Option<u32> {
+        self.state
+            .borrow()
+            .programs
+            .get(&program.0)
+            .and_then(|p| p.attribs.get(name).cloned())
+    }
+
+    pub fn get_uniform_location(
+        &self,
+        program: &WebGLProgram,
+        name: &str,
+    ) -> Option<WebGLUniformLocation> {
+        Some(WebGLUniformLocation {
+            reference: program.0,
+            name: name.into(),
+        })
+    }
+
+    pub fn vertex_attrib_pointer(
+        &self,
+        location: u32,
+        size: AttributeSize,
+        kind: DataType,
+        normalized: bool,
+        stride: u32,
+        offset: u32,
+    ) {
+        let mut state = self.state.borrow_mut();
+        let buffer = state.array_buffer;
+
+        if let Some(slot) = state.vao_mut().attribs.get_mut(location as usize) {
+            *slot = Some(AttribPointer {
+                buffer,
+                size: size as usize,
+                kind,
+                normalized,
+                stride,
+                offset,
+            });
+        }
+    }
+
+    pub fn enable_vertex_attrib_array(&self, location: u32) {
+        let mut state = self.state.borrow_mut();
+        if let Some(enabled) = state.vao_mut().enabled.get_mut(location as usize) {
+            *enabled = true;
+        }
+    }
+
+    pub fn disable_vertex_attrib_array(&self, location: u32) {
+        let mut state = self.state.borrow_mut();
+        if let Some(enabled) = state.vao_mut().enabled.get_mut(location as usize) {
+            *enabled = false;
+        }
+    }
+
+    pub fn vertex_attrib_divisor(&self, location: u32, divisor: u32) {
+        let mut state = self.state.borrow_mut();
+        if let Some(slot) = state.vao_mut().divisors.get_mut(location as usize) {
+            *slot = divisor;
+        }
+    }
+
+    pub fn clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.state.borrow_mut().clear_color = [r, g, b, a];
+    }
+
+    pub fn enable(&self, flag: i32) {
+        self.set_flag(flag, true);
+    }
+
+    pub fn disable(&self, flag: i32) {
+        self.set_flag(flag, false);
+    }
+
+    fn set_flag(&self, flag: i32, b: bool) {
+        let mut state = self.state.borrow_mut();
+
+        if flag == Flag::Blend as i32 {
+            state.blend = b;
+        } else if flag == Flag::DepthTest as i32 {
+            state.depth_test = b;
+        } else if flag == Culling::CullFace as i32 {
+            state.cull = b;
+        } else if flag == Flag::ScissorTest as i32 {
+            state.scissor_test = b;
+        }
+    }
+
+    pub fn cull_face(&self, flag: Culling) {
+        self.state.borrow_mut().cull_mode = flag;
+    }
+
+    pub fn depth_mask(&self, b: bool) {
+        self.state.borrow_mut().depth_mask = b;
+    }
+
+    pub fn depth_func(&self, d: DepthTest) {
+        self.state.borrow_mut().depth_func = d;
+    }
+
+    pub fn clear_depth(&self, value: f32) {
+        self.state.borrow_mut().clear_depth = value;
+    }
+
+    pub fn clear(&self, bit: BufferBit) {
+        let mut state = self.state.borrow_mut();
+        let (color, depth) = state.attachments();
+        let scissor = state.scissor_box();
+
+        match bit {
+            BufferBit::Color => {
+                let c = state.clear_color;
+                if let Some(img) = state.target_mut(color) {
+                    let c = img.store(c);
+                    img.fill(scissor, c);
+                }
+            }
+            BufferBit::Depth => {
+                if !state.depth_mask {
+                    return;
+                }
+
+                let d = state.clear_depth;
+                if let Some(img) = state.target_mut(depth) {
+                    img.fill(scissor, [d, d, d, 1.0]);
+                }
+            }
+            BufferBit::Stencil => (),
+        }
+    }
+
+    pub fn scissor(&self, x: i32, y: i32, width: u32, height: u32) {
+        self.state.borrow_mut().scissor = (x, y, width, height);
+    }
+
+    pub fn viewport(&self, x: i32, y: i32, width: u32, height: u32) {
+        let mut state = self.state.borrow_mut();
+        state.viewport = (x, y, width, height);
+
+        // The size of the default frame buffer follows the viewport
+        if state.framebuffer == 0 {
+            let w = (x.max(0) as u32 + width).max(state.screen_color.width);
+            let h = (y.max(0) as u32 + height).max(state.screen_color.height);
+
+            if w != state.screen_color.width || h != state.screen_color.height {
+                let (c, d) = (state.clear_color, state.clear_depth);
+                state.screen_color = Image::new(w, h, c);
+                state.screen_depth = Image::new(w, h, [d, d, d, 1.0]);
+            }
+        }
+    }
+
+    pub fn draw_elements(&self, mode: Primitives, count: usize, kind: DataType, offset: u32) {
+        self.draw_elements_instanced(mode, count, kind, offset, 1);
+    }
+
+    pub fn draw_elements_instanced(
+        &self,
+        mode: Primitives,
+        count: usize,
+        kind: DataType,
+        offset: u32,
+        instances: usize,
+    ) {
+        let indices = {
+            let state = self.state.borrow();
+            let buffer = state.bound_buffer(BufferKind::ElementArray);
+            let data = match state.buffers.get(&buffer) {
+                Some(data) => data,
+                None => return,
+            };
+
+            let size = data_type_size(kind);
+            let offset = offset as usize;
+
+            (0..count)
+                .map(|i| offset + i * size)
+                .take_while(|at| at + size <= data.len())
+                .map(|at| match kind {
+                    DataType::U8 | DataType::I8 => data[at] as u32,
+                    DataType::U16 | DataType::I16 => read_u16(data, at) as u32,
+                    _ => read_u32(data, at),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        self.draw(mode, &indices, instances);
+    }
+
+    pub fn draw_arrays(&self, mode: Primitives, count: usize) {
+        let indices: Vec<u32> = (0..count as u32).collect();
+        self.draw(mode, &indices, 1);
+    }
+
+    fn draw(&self, mode: Primitives, indices: &[u32], instances: usize) {
+        let triangles: Vec<[u32; 3]> = match mode {
+            Primitives::Triangles => indices
+                .chunks(3)
+                .filter(|c| c.len() == 3)
+                .map(|c| [c[0], c[1], c[2]])
+                .collect(),
+            Primitives::TriangleStrip => (2..indices.len())
+                .map(|i| {
+                    if i % 2 == 0 {
+                        [indices[i - 2], indices[i - 1], indices[i]]
+                    } else {
+                        [indices[i - 1], indices[i - 2], indices[i]]
+                    }
+                })
+                .collect(),
+            Primitives::TriangleFan => (2..indices.len())
+                .map(|i| [indices[0], indices[i - 1], indices[i]])
+                .collect(),
+            _ => {
+                GLContext::print(format!("Soft GL: {:?} is not supported.\n", mode));
+                return;
+            }
+        };
+
+        let mut state = self.state.borrow_mut();
+
+        let label = state
+            .programs
+            .get(&state.program)
+            .and_then(|p| p.label.clone())
+            .unwrap_or_default();
+
+        let found = state.registry.get(&label).cloned();
+        let soft_program = match found {
+            Some(p) => p,
+            None => {
+                if !state.missing.contains(&label) {
+                    GLContext::print(format!(
+                        "Soft GL: no soft program registered for \"{}\", skipped.\n",
+                        label
+                    ));
+                    state.missing.push(label);
+                }
+                return;
+            }
+        };
+
+        // Take the render targets out, so that we can sample the other textures meanwhile
+        let (color_attach, depth_attach) = state.attachments();
+        let mut targets = Targets {
+            color: state.take_image(color_attach),
+            depth: state.take_image(depth_attach),
+        };
+
+        let raster = RasterState {
+            viewport: state.viewport,
+            scissor: state.scissor_box(),
+            blend: state.blend,
+            depth_test: state.depth_test,
+            depth_mask: state.depth_mask,
+            depth_func: state.depth_func,
+            cull: if state.cull {
+                Some(state.cull_mode)
+            } else {
+                None
+            },
+            blend_src: state.blend_src,
+            blend_dst: state.blend_dst,
+            blend_equation: state.blend_equation,
+            blend_color: state.blend_color,
+        };
+
+        {
+            let empty = HashMap::new();
+            let uniforms = state
+                .programs
+                .get(&state.program)
+                .map(|p| &p.uniforms)
+                .unwrap_or(&empty);
+
+            let env = ShaderEnv {
+                uniforms,
+                textures: &state.textures,
+                units: &state.units,
+            };
+
+            let vao = state
+                .vertex_arrays
+                .get(&state.vertex_array)
+                .cloned()
+                .unwrap_or_default();
+
+            for instance in 0..instances as u32 {
+                let mut cache: HashMap<u32, ClipVertex> = HashMap::new();
+                let mut run_vertex = |index: u32| -> ClipVertex {
+                    cache
+                        .entry(index)
+                        .or_insert_with(|| {
+                            let mut attribs = [[0.0, 0.0, 0.0, 1.0]; MAX_VERTEX_ATTRIBS];
+                            for loc in 0..MAX_VERTEX_ATTRIBS {
+                                if !vao.enabled[loc] {
+                                    continue;
+                                }
+                                // Instanced attributes advance once per divisor instances
+                                let at = match vao.divisors[loc] {
+                                    0 => index,
+                                    d => instance / d,
+                                };
+                                if let Some(ref ptr) = vao.attribs[loc] {
+                                    attribs[loc] = fetch_attrib(&state.buffers, ptr, at);
+                                }
+                            }
+
+                            let mut varyings = Vec::new();
+                            let pos = (soft_program.vertex)(&env, &attribs[..], &mut varyings);
+                            ClipVertex { pos, varyings }
+                        })
+                        .clone()
+                };
+
+                for tri in triangles.iter() {
+                    let poly = vec![run_vertex(tri[0]), run_vertex(tri[1]), run_vertex(tri[2])];
+
+                    // Clip against the near and far plane in the clip space
+                    let poly = clip_polygon(poly, |p| p[3] - 1e-6);
+                    let poly = clip_polygon(poly, |p| p[2] + p[3]);
+                    let poly = clip_polygon(poly, |p| p[3] - p[2]);
+
+                    if poly.len() < 3 {
+                        continue;
+                    }
+
+                    let verts: Vec<ScreenVertex> = poly.iter()
+                        .map(|v| to_screen(v, raster.viewport))
+                        .collect();
+
+                    for i in 1..verts.len() - 1 {
+                        rasterize(
+                            &raster,
+                            &env,
+                            &soft_program,
+                            [&verts[0], &verts[i], &verts[i + 1]],
+                            &mut targets,
+                        );
+                    }
+                }
+            }
+        }
+
+        state.put_image(color_attach, targets.color);
+        state.put_image(depth_attach, targets.depth);
+    }
+
+    pub fn read_pixels(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        format: PixelFormat,
+        kind: PixelType,
+        data: &mut [u8],
+    ) {
+        let mut state = self.state.borrow_mut();
+        let (color, _) = state.attachments();
+        let alignment = state.pack_alignment;
+
+        let img = match state.target_mut(color) {
+            Some(img) => img,
+            None => return,
+        };
+
+        let channels = match format {
+            PixelFormat::Rgba => 4,
+            PixelFormat::Rgb => 3,
+            _ => 1,
+        };
+        let csize = match kind {
+            PixelType::Float => 4,
+            _ => 1,
+        };
+        let row_size = align(width as usize * channels * csize, alignment);
+
+        for row in 0..height {
+            for col in 0..width {
+                let (px, py) = (x + col, y + row);
+                let c = if px < img.width && py < img.height {
+                    img.fetch(px, py)
+                } else {
+                    [0.0; 4]
+                };
+
+                for ch in 0..channels {
+                    let at = row as usize * row_size + (col as usize * channels + ch) * csize;
+                    if at + csize > data.len() {
+                        return;
+                    }
+
+                    let v = if channels == 1 { c[3] } else { c[ch] };
+                    match kind {
+                        PixelType::Float => {
+                            let bits = v.to_bits();
+                            for b in 0..4 {
+                                data[at + b] = (bits >> (8 * b)) as u8;
+                            }
+                        }
+                        _ => data[at] = (v.max(0.0).min(1.0) * 255.0).round() as u8,
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn pixel_storei(&self, storage: PixelStorageMode, value: i32) {
+        let mut state = self.state.borrow_mut();
+        match storage {
+            PixelStorageMode::PackAlignment => state.pack_alignment = value.max(1) as usize,
+            PixelStorageMode::UnpackAlignment => state.unpack_alignment = value.max(1) as usize,
+            _ => (),
+        }
+    }
+
+    pub fn tex_image2d(
+        &self,
+        target: TextureBindPoint,
+        level: u8,
+        width: u16,
+        height: u16,
+        format: PixelFormat,
+        kind: PixelType,
+        pixels: &[u8],
+    ) {
+        // We do not sample mipmaps, only the base level is kept
+        if level != 0 {
+            return;
+        }
+
+        let mut state = self.state.borrow_mut();
+        let alignment = state.unpack_alignment;
+
+        let mut img = if pixels.len() == 0 {
+            Image::new(width as u32, height as u32, [0.0; 4])
+        } else {
+            Image {
+                width: width as u32,
+                height: height as u32,
+                data: decode_pixels(
+                    format,
+                    kind,
+                    width as usize,
+                    height as usize,
+                    pixels,
+                    alignment,
+                ),
+                float: false,
+            }
+        };
+
+        img.float = match kind {
+            PixelType::Float | PixelType::HalfFloat => true,
+            _ => false,
+        };
+
+        if let Some(tex) = state.bound_texture() {
+            if let Some(tex) = state.textures.get_mut(&tex) {
+                *tex.face_mut(face_index(target)) = img;
+            }
+        }
+    }
+
+    pub fn tex_sub_image2d(
+        &self,
+        target: TextureBindPoint,
+        level: u8,
+        xoffset: u16,
+        yoffset: u16,
+        width: u16,
+        height: u16,
+        format: PixelFormat,
+        kind: PixelType,
+        pixels: &[u8],
+    ) {
+        if level != 0 {
+            return;
+        }
+
+        let mut state = self.state.borrow_mut();
+        let alignment = state.unpack_alignment;
+        let data = decode_pixels(
+            format,
+            kind,
+            width as usize,
+            height as usize,
+            pixels,
+            alignment,
+        );
+
+        let tex = match state.bound_texture() {
+            Some(tex) => tex,
+            None => return,
+        };
+
+        if let Some(tex) = state.textures.get_mut(&tex) {
+            let img = tex.face_mut(face_index(target));
+
+            for y in 0..height as u32 {
+                for x in 0..width as u32 {
+                    let (px, py) = (x + xoffset as u32, y + yoffset as u32);
+                    if px < img.width && py < img.height {
+                        img.data[(py * img.width + px) as usize] =
+                            data[(y * width as u32 + x) as usize];
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn compressed_tex_image2d(
+        &self,
+        target: TextureBindPoint,
+        level: u8,
+        compression: TextureCompression,
+        width: u16,
+        height: u16,
+        data: &[u8],
+    ) {
+        if level != 0 {
+            return;
+        }
+
+        let mut state = self.state.borrow_mut();
+        let img = Image {
+            width: width as u32,
+            height: height as u32,
+            data: decode_dxt(compression, width as usize, height as usize, data),
+            float: false,
+        };
+
+        if let Some(tex) = state.bound_texture() {
+            if let Some(tex) = state.textures.get_mut(&tex) {
+                *tex.face_mut(face_index(target)) = img;
+            }
+        }
+    }
+
+    pub fn get_program_parameter(&self, _program: &WebGLProgram, pname: ShaderParameter) -> i32 {
+        match pname {
+            ShaderParameter::LinkStatus
+            | ShaderParameter::CompileStatus
+            | ShaderParameter::ValidateStatus => 1,
+            _ => 0,
+        }
+    }
+
+    pub fn create_texture(&self) -> WebGLTexture {
+        let mut state = self.state.borrow_mut();
+        let handle = state.next_handle();
+        state.textures.insert(handle, SoftTexture::new());
+        WebGLTexture(handle)
+    }
+
+    pub fn delete_texture(&self, texture: &WebGLTexture) {
+        let mut state = self.state.borrow_mut();
+        state.textures.remove(&texture.0);
+        state.units.retain(|_, t| *t != texture.0);
+    }
+
+    pub fn generate_mipmap(&self) {}
+
+    pub fn generate_mipmap_cube(&self) {}
+
+    pub fn active_texture(&self, active: u32) {
+        self.state.borrow_mut().active_unit = active;
+    }
+
+    pub fn bind_texture(&self, texture: &WebGLTexture) {
+        let mut state = self.state.borrow_mut();
+        let unit = state.active_unit;
+        state.units.insert(unit, texture.0);
+    }
+
+    pub fn unbind_texture(&self) {
+        let mut state = self.state.borrow_mut();
+        let unit = state.active_unit;
+        state.units.remove(&unit);
+    }
+
+    pub fn bind_texture_cube(&self, texture: &WebGLTexture) {
+        self.bind_texture(texture);
+    }
+
+    pub fn unbind_texture_cube(&self) {
+        self.unbind_texture();
+    }
+
+    pub fn blend_equation(&self, eq: BlendEquation) {
+        self.state.borrow_mut().blend_equation = eq;
+    }
+
+    pub fn blend_func(&self, b1: BlendMode, b2: BlendMode) {
+        let mut state = self.state.borrow_mut();
+        state.blend_src = b1;
+        state.blend_dst = b2;
+    }
+
+    pub fn blend_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.state.borrow_mut().blend_color = [r, g, b, a];
+    }
+
+    fn set_uniform(&self, location: &WebGLUniformLocation, value: UniformValue) {
+        let mut state = self.state.borrow_mut();
+        let program = state.program;
+
+        if let Some(p) = state.programs.get_mut(&program) {
+            p.uniforms.insert(location.name.clone(), value);
+        }
+    }
+
+    pub fn uniform_matrix_4fv(&self, location: &WebGLUniformLocation, value: &[[f32; 4]; 4]) {
+        self.set_uniform(location, UniformValue::Matrix4(*value));
+    }
+
+    pub fn uniform_matrix_3fv(&self, location: &WebGLUniformLocation, value: &[[f32; 3]; 3]) {
+        self.set_uniform(location, UniformValue::Matrix3(*value));
+    }
+
+    pub fn uniform_matrix_2fv(&self, location: &WebGLUniformLocation, value: &[[f32; 2]; 2]) {
+        self.set_uniform(location, UniformValue::Matrix2(*value));
+    }
+
+    pub fn uniform_1i(&self, location: &WebGLUniformLocation, value: i32) {
+        self.set_uniform(location, UniformValue::I32(value));
+    }
+
+    pub fn uniform_1f(&self, location: &WebGLUniformLocation, value: f32) {
+        self.set_uniform(location, UniformValue::F32(value));
+    }
+
+    pub fn uniform_2f(&self, location: &WebGLUniformLocation, value: (f32, f32)) {
+        self.set_uniform(location, UniformValue::Vec2(value));
+    }
+
+    pub fn uniform_3f(&self, location: &WebGLUniformLocation, value: (f32, f32, f32)) {
+        self.set_uniform(location, UniformValue::Vec3(value));
+    }
+
+    pub fn uniform_4f(&self, location: &WebGLUniformLocation, value: (f32, f32, f32, f32)) {
+        self.set_uniform(location, UniformValue::Vec4(value));
+    }
+
+    pub fn tex_parameteri(&self, _kind: TextureKind, pname: TextureParameter, param: i32) {
+        let mut state = self.state.borrow_mut();
+        let tex = match state.bound_texture() {
+            Some(tex) => tex,
+            None => return,
+        };
+
+        if let Some(tex) = state.textures.get_mut(&tex) {
+            match pname {
+                TextureParameter::TextureMagFilter => tex.mag_filter = param,
+                TextureParameter::TextureWrapS => tex.wrap_s = param,
+                TextureParameter::TextureWrapT => tex.wrap_t = param,
+                _ => (),
+            }
+        }
+    }
+
+    pub fn tex_parameterfv(&self, _kind: TextureKind, _pname: TextureParameter, _param: f32) {}
+
+    pub fn create_vertex_array(&self) -> WebGLVertexArray {
+        let mut state = self.state.borrow_mut();
+        let handle = state.next_handle();
+        state.vertex_arrays.insert(handle, VertexArray::default());
+        WebGLVertexArray(handle)
+    }
+
+    pub fn delete_vertex_array(&self, vao: &WebGLVertexArray) {
+        self.state.borrow_mut().vertex_arrays.remove(&vao.0);
+    }
+
+    pub fn bind_vertex_array(&self, vao: &WebGLVertexArray) {
+        self.state.borrow_mut().vertex_array = vao.0;
+    }
+
+    pub fn unbind_vertex_array(&self, _vao: &WebGLVertexArray) {
+        self.state.borrow_mut().vertex_array = 0;
+    }
+
+    pub fn draw_buffer(&self, buffers: &[ColorBuffer]) {
+        let mut state = self.state.borrow_mut();
+        let current = state.framebuffer;
+
+        if let Some(fb) = state.framebuffers.get_mut(&current) {
+            fb.draw_color = buffers.iter().any(|b| *b != ColorBuffer::None);
+        }
+    }
+
+    pub fn create_framebuffer(&self) -> WebGLFrameBuffer {
+        let mut state = self.state.borrow_mut();
+        let handle = state.next_handle();
+        state.framebuffers.insert(
+            handle,
+            SoftFrameBuffer {
+                color: None,
+                depth: None,
+                draw_color: true,
+            },
+        );
+        WebGLFrameBuffer(handle)
+    }
+
+    pub fn delete_framebuffer(&self, fb: &WebGLFrameBuffer) {
+        self.state.borrow_mut().framebuffers.remove(&fb.0);
+    }
+
+    pub fn bind_framebuffer(&self, _buffer: Buffers, fb: &WebGLFrameBuffer) {
+        self.state.borrow_mut().framebuffer = fb.0;
+    }
+
+    pub fn framebuffer_texture2d(
+        &self,
+        _target: Buffers,
+        attachment: Buffers,
+        textarget: TextureBindPoint,
+        texture: &WebGLTexture,
+        _level: i32,
+    ) {
+        let mut state = self.state.borrow_mut();
+        let current = state.framebuffer;
+
+        if let Some(fb) = state.framebuffers.get_mut(&current) {
+            let attach = Some((texture.0, face_index(textarget)));
+            match attachment {
+                Buffers::ColorAttachment0 => fb.color = attach,
+                Buffers::DepthAttachment => fb.depth = attach,
+                _ => (),
+            }
+        }
+    }
+
+    pub fn unbind_framebuffer(&self, _buffer: Buffers) {
+        self.state.borrow_mut().framebuffer = 0;
+    }
+}
+
+fn align(size: usize, alignment: usize) -> usize {
+    (size + alignment - 1) / alignment * alignment
+}
+
+fn read_u16(data: &[u8], at: usize) -> u16 {
+    data[at] as u16 | (data[at + 1] as u16) << 8
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    data[at] as u32 | (data[at + 1] as u32) << 8 | (data[at + 2] as u32) << 16
+        | (data[at + 3] as u32) << 24
+}
+
+fn data_type_size(kind: DataType) -> usize {
+    match kind {
+        DataType::I8 | DataType::U8 => 1,
+        DataType::I16 | DataType::U16 => 2,
+        DataType::I32 | DataType::U32 | DataType::Float => 4,
+    }
+}
+
+fn read_component(data: &[u8], at: usize, kind: DataType, normalized: bool) -> f32 {
+    let (v, max) = match kind {
+        DataType::Float => return f32::from_bits(read_u32(data, at)),
+        DataType::U8 => (data[at] as f32, 255.0),
+        DataType::I8 => (data[at] as i8 as f32, 127.0),
+        DataType::U16 => (read_u16(data, at) as f32, 65535.0),
+        DataType::I16 => (read_u16(data, at) as i16 as f32, 32767.0),
+        DataType::U32 => (read_u32(data, at) as f32, 4294967295.0),
+        DataType::I32 => (read_u32(data, at) as i32 as f32, 2147483647.0),
+    };
+
+    if normalized {
+        (v / max).max(-1.0)
+    } else {
+        v
+    }
+}
+
+fn fetch_attrib(buffers: &HashMap<Reference, Vec<u8>>, ptr: &AttribPointer, index: u32) -> Vec4 {
+    let mut v = [0.0, 0.0, 0.0, 1.0];
+
+    let data = match buffers.get(&ptr.buffer) {
+        Some(data) => data,
+        None => return v,
+    };
+
+    let csize = data_type_size(ptr.kind);
+    let stride = if ptr.stride == 0 {
+        ptr.size * csize
+    } else {
+        ptr.stride as usize
+    };
+    let base = ptr.offset as usize + stride * index as usize;
+
+    for c in 0..ptr.size.min(4) {
+        let at = base + c * csize;
+        if at + csize > data.len() {
+            break;
+        }
+        v[c] = read_component(data, at, ptr.kind, ptr.normalized);
+    }
+
+    v
+}
+
+fn half_to_f32(h: u16) -> f32 {
+    let sign = if h & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = ((h >> 10) & 0x1f) as i32;
+    let mantissa = (h & 0x3ff) as f32;
+
+    sign * match exp {
+        0 => mantissa * 2f32.powi(-24),
+        31 => if mantissa == 0.0 {
+            ::std::f32::INFINITY
+        } else {
+            ::std::f32::NAN
+        },
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exp - 15),
+    }
+}
+
+fn decode_pixels(
+    format: PixelFormat,
+    kind: PixelType,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+    alignment: usize,
+) -> Vec<Vec4> {
+    let channels = match format {
+        PixelFormat::Alpha | PixelFormat::Luminance | PixelFormat::DepthComponent => 1,
+        PixelFormat::LuminanceAlpha => 2,
+        PixelFormat::Rgb => 3,
+        PixelFormat::Rgba => 4,
+    };
+
+    let packed = match kind {
+        PixelType::UnsignedShort4444
+        | PixelType::UnsignedShort5551
+        | PixelType::UnsignedShort565 => true,
+        _ => false,
+    };
+
+    let csize = match kind {
+        PixelType::UnsignedByte => 1,
+        PixelType::Float | PixelType::UnsignedInt | PixelType::UnsignedInt24 => 4,
+        _ => 2,
+    };
+
+    let pixel_size = if packed { 2 } else { channels * csize };
+    let row_size = align(width * pixel_size, alignment);
+
+    let mut out = vec![[0.0, 0.0, 0.0, 1.0]; width * height];
+
+    for y in 0..height {
+        for x in 0..width {
+            let at = y * row_size + x * pixel_size;
+            if at + pixel_size > pixels.len() {
+                return out;
+            }
+
+            let mut c = [0.0f32; 4];
+
+            if packed {
+                let p = read_u16(pixels, at) as u32;
+                c = match kind {
+                    PixelType::UnsignedShort565 => [
+                        ((p >> 11) & 31) as f32 / 31.0,
+                        ((p >> 5) & 63) as f32 / 63.0,
+                        (p & 31) as f32 / 31.0,
+                        1.0,
+                    ],
+                    PixelType::UnsignedShort5551 => [
+                        ((p >> 11) & 31) as f32 / 31.0,
+                        ((p >> 6) & 31) as f32 / 31.0,
+                        ((p >> 1) & 31) as f32 / 31.0,
+                        (p & 1) as f32,
+                    ],
+                    _ => [
+                        ((p >> 12) & 15) as f32 / 15.0,
+                        ((p >> 8) & 15) as f32 / 15.0,
+                        ((p >> 4) & 15) as f32 / 15.0,
+                        (p & 15) as f32 / 15.0,
+                    ],
+                };
+            } else {
+                for ch in 0..channels {
+                    let cat = at + ch * csize;
+                    c[ch] = match kind {
+                        PixelType::UnsignedByte => pixels[cat] as f32 / 255.0,
+                        PixelType::Float => f32::from_bits(read_u32(pixels, cat)),
+                        PixelType::HalfFloat => half_to_f32(read_u16(pixels, cat)),
+                        PixelType::UnsignedInt | PixelType::UnsignedInt24 => {
+                            read_u32(pixels, cat) as f32 / 4294967295.0
+                        }
+                        _ => read_u16(pixels, cat) as f32 / 65535.0,
+                    };
+                }
+
+                c = match format {
+                    PixelFormat::Alpha => [0.0, 0.0, 0.0, c[0]],
+                    PixelFormat::Luminance | PixelFormat::DepthComponent => {
+                        [c[0], c[0], c[0], 1.0]
+                    }
+                    PixelFormat::LuminanceAlpha => [c[0], c[0], c[0], c[1]],
+                    PixelFormat::Rgb => [c[0], c[1], c[2], 1.0],
+                    PixelFormat::Rgba => c,
+                };
+            }
+
+            out[y * width + x] = c;
+        }
+    }
+
+    out
+}
+
+fn rgb565(c: u16) -> Vec4 {
+    [
+        ((c >> 11) & 31) as f32 / 31.0,
+        ((c >> 5) & 63) as f32 / 63.0,
+        (c & 31) as f32 / 31.0,
+        1.0,
+    ]
+}
+
+fn decode_dxt(compression: TextureCompression, width: usize, height: usize, data: &[u8]) -> Vec<Vec4> {
+    let (block_size, is_dxt1) = match compression {
+        TextureCompression::RgbDxt1 | TextureCompression::RgbaDxt1 => (8, true),
+        _ => (16, false),
+    };
+
+    let mut out = vec![[0.0, 0.0, 0.0, 1.0]; width * height];
+    let bw = (width + 3) / 4;
+    let bh = (height + 3) / 4;
+
+    for by in 0..bh {
+        for bx in 0..bw {
+            let at = (by * bw + bx) * block_size;
+            if at + block_size > data.len() {
+                return out;
+            }
+
+            let block = &data[at..at + block_size];
+            let color_block = if is_dxt1 { block } else { &block[8..] };
+
+            let c0 = read_u16(color_block, 0);
+            let c1 = read_u16(color_block, 2);
+            let (p0, p1) = (rgb565(c0), rgb565(c1));
+            let bits = read_u32(color_block, 4);
+
+            let colors = if c0 > c1 || !is_dxt1 {
+                [
+                    p0,
+                    p1,
+                    lerp4(p0, p1, 1.0 / 3.0),
+                    lerp4(p0, p1, 2.0 / 3.0),
+                ]
+            } else {
+                let black = match compression {
+                    TextureCompression::RgbaDxt1 => [0.0, 0.0, 0.0, 0.0],
+                    _ => [0.0, 0.0, 0.0, 1.0],
+                };
+                [p0, p1, lerp4(p0, p1, 0.5), black]
+            };
+
+            let alphas = match compression {
+                TextureCompression::RgbaDxt5 => {
+                    let (a0, a1) = (block[0] as f32 / 255.0, block[1] as f32 / 255.0);
+                    let mut a = [a0, a1, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0];
+                    if block[0] > block[1] {
+                        for i in 2..8 {
+                            a[i] = ((8 - i) as f32 * a0 + (i - 1) as f32 * a1) / 7.0;
+                        }
+                    } else {
+                        for i in 2..6 {
+                            a[i] = ((6 - i) as f32 * a0 + (i - 1) as f32 * a1) / 5.0;
+                        }
+                        a[6] = 0.0;
+                        a[7] = 1.0;
+                    }
+                    Some(a)
+                }
+                _ => None,
+            };
+
+            for py in 0..4 {
+                for px in 0..4 {
+                    let (x, y) = (bx * 4 + px, by * 4 + py);
+                    if x >= width || y >= height {
+                        continue;
+                    }
+
+                    let i = py * 4 + px;
+                    let mut c = colors[((bits >> (2 * i)) & 3) as usize];
+
+                    match compression {
+                        TextureCompression::RgbaDxt3 => {
+                            let nibble = (block[i / 2] >> (4 * (i % 2))) & 0xf;
+                            c[3] = nibble as f32 / 15.0;
+                        }
+                        TextureCompression::RgbaDxt5 => {
+                            let bit = 3 * i;
+                            let mut code = 0;
+                            for b in 0..3 {
+                                let n = bit + b;
+                                code |= ((block[2 + n / 8] >> (n % 8)) & 1) << b;
+                            }
+                            c[3] = alphas.unwrap()[code as usize];
+                        }
+                        _ => (),
+                    }
+
+                    out[y * width + x] = c;
+                }
+            }
+        }
+    }
+
+    out
+}
+
+fn clip_polygon<F>(poly: Vec<ClipVertex>, dist: F) -> Vec<ClipVertex>
+where
+    F: Fn(&Vec4) -> f32,
+{
+    let mut out = Vec::with_capacity(poly.len() + 1);
+
+    for i in 0..poly.len() {
+        let a = &poly[i];
+        let b = &poly[(i + 1) % poly.len()];
+        let (da, db) = (dist(&a.pos), dist(&b.pos));
+
+        if da >= 0.0 {
+            out.push(a.clone());
+        }
+
+        if (da >= 0.0) != (db >= 0.0) {
+            let t = da / (da - db);
+            out.push(ClipVertex {
+                pos: lerp4(a.pos, b.pos, t),
+                varyings: a.varyings
+                    .iter()
+                    .zip(b.varyings.iter())
+                    .map(|(va, vb)| va + (vb - va) * t)
+                    .collect(),
+            });
+        }
+    }
+
+    out
+}
+
+fn to_screen(v: &ClipVertex, viewport: (i32, i32, u32, u32)) -> ScreenVertex {
+    let inv_w = 1.0 / v.pos[3];
+    let (vx, vy, vw, vh) = viewport;
+
+    ScreenVertex {
+        x: vx as f32 + (v.pos[0] * inv_w + 1.0) * 0.5 * vw as f32,
+        y: vy as f32 + (v.pos[1] * inv_w + 1.0) * 0.5 * vh as f32,
+        z: (v.pos[2] * inv_w + 1.0) * 0.5,
+        inv_w,
+        varyings: v.varyings.iter().map(|f| f * inv_w).collect(),
+    }
+}
+
+fn edge(a: &ScreenVertex, b: &ScreenVertex, x: f32, y: f32) -> f32 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+// The top-left fill rule for counter clockwise triangles with y pointing up
+fn is_top_left(a: &ScreenVertex, b: &ScreenVertex) -> bool {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    dy < 0.0 || (dy == 0.0 && dx < 0.0)
+}
+
+fn depth_pass(func: DepthTest, z: f32, d: f32) -> bool {
+    match func {
+        DepthTest::Never => false,
+        DepthTest::Always => true,
+        DepthTest::Less => z < d,
+        DepthTest::Equal => z == d,
+        DepthTest::Lequal => z <= d,
+        DepthTest::Greater => z > d,
+        DepthTest::Gequal => z >= d,
+        DepthTest::Notequal => z != d,
+    }
+}
+
+fn blend_factor(mode: BlendMode, src: Vec4, dst: Vec4, constant: Vec4) -> Vec4 {
+    let splat = |f: f32| [f, f, f, f];
+    let inv = |c: Vec4| [1.0 - c[0], 1.0 - c[1], 1.0 - c[2], 1.0 - c[3]];
+
+    match mode {
+        BlendMode::Zero => splat(0.0),
+        BlendMode::One => splat(1.0),
+        BlendMode::SrcColor => src,
+        BlendMode::OneMinusSrcColor => inv(src),
+        BlendMode::SrcAlpha => splat(src[3]),
+        BlendMode::OneMinusSrcAlpha => splat(1.0 - src[3]),
+        BlendMode::DstAlpha => splat(dst[3]),
+        BlendMode::OneMinusDstAlpha => splat(1.0 - dst[3]),
+        BlendMode::DstColor => dst,
+        BlendMode::OneMinusDstColor => inv(dst),
+        BlendMode::SrcAlphaSaturate => {
+            let f = src[3].min(1.0 - dst[3]);
+            [f, f, f, 1.0]
+        }
+        BlendMode::ConstantColor => constant,
+        BlendMode::OneMinusConstantColor => inv(constant),
+        BlendMode::ConstantAlpha => splat(constant[3]),
+        BlendMode::OneMinusConstantAlpha => splat(1.0 - constant[3]),
+    }
+}
+
+fn blend(raster: &RasterState, src: Vec4, dst: Vec4) -> Vec4 {
+    let sf = blend_factor(raster.blend_src, src, dst, raster.blend_color);
+    let df = blend_factor(raster.blend_dst, src, dst, raster.blend_color);
+
+    let mut out = [0.0; 4];
+    for i in 0..4 {
+        let (s, d) = (src[i] * sf[i], dst[i] * df[i]);
+        out[i] = match raster.blend_equation {
+            BlendEquation::FuncAdd => s + d,
+            BlendEquation::FuncSubstract => s - d,
+            BlendEquation::FuncReverseSubtract => d - s,
+        };
+    }
+
+    out
+}
+
+fn rasterize(
+    raster: &RasterState,
+    env: &ShaderEnv,
+    program: &SoftProgram,
+    tri: [&ScreenVertex; 3],
+    targets: &mut Targets,
+) {
+    let a = tri[0];
+    let mut b = tri[1];
+    let mut c = tri[2];
+
+    let area = edge(a, b, c.x, c.y);
+    if area == 0.0 || !area.is_finite() {
+        return;
+    }
+
+    let front = area > 0.0;
+    match raster.cull {
+        Some(Culling::FrontAndBack) => return,
+        Some(Culling::Front) if front => return,
+        Some(Culling::Back) if !front => return,
+        _ => (),
+    }
+
+    // Make it counter clockwise
+    if !front {
+        mem::swap(&mut b, &mut c);
+    }
+    let area = area.abs();
+
+    let (width, height) = match targets.size() {
+        Some(size) => size,
+        None => return,
+    };
+
+    let (vx, vy, vw, vh) = raster.viewport;
+    let (sx, sy, sw, sh) = raster.scissor.unwrap_or((0, 0, width, height));
+    let min_x = vx.max(sx).max(0).max(a.x.min(b.x).min(c.x).floor() as i32);
+    let min_y = vy.max(sy).max(0).max(a.y.min(b.y).min(c.y).floor() as i32);
+    let max_x = (vx + vw as i32)
+        .min(sx + sw as i32)
+        .min(width as i32)
+        .min(a.x.max(b.x).max(c.x).ceil() as i32 + 1);
+    let max_y = (vy + vh as i32)
+        .min(sy + sh as i32)
+        .min(height as i32)
+        .min(a.y.max(b.y).max(c.y).ceil() as i32 + 1);
+
+    let top_left = [is_top_left(b, c), is_top_left(c, a), is_top_left(a, b)];
+    let mut varyings = vec![0.0; a.varyings.len()];
+
+    for py in min_y..max_y {
+        for px in min_x..max_x {
+            let (x, y) = (px as f32 + 0.5, py as f32 + 0.5);
+            let w = [edge(b, c, x, y), edge(c, a, x, y), edge(a, b, x, y)];
+
+            let inside = (0..3).all(|i| w[i] > 0.0 || (w[i] == 0.0 && top_left[i]));
+            if !inside {
+                continue;
+            }
+
+            let w = [w[0] / area, w[1] / area, w[2] / area];
+            let idx = (py as u32 * width + px as u32) as usize;
+
+            let z = w[0] * a.z + w[1] * b.z + w[2] * c.z;
+            if raster.depth_test {
+                if let Some(d) = targets.depth.as_ref().and_then(|img| img.data.get(idx)) {
+                    if !depth_pass(raster.depth_func, z, d[0]) {
+                        continue;
+                    }
+                }
+            }
+
+            // perspective correct interpolation
+            let inv_w = w[0] * a.inv_w + w[1] * b.inv_w + w[2] * c.inv_w;
+            for (i, v) in varyings.iter_mut().enumerate() {
+                *v = (w[0] * a.varyings[i] + w[1] * b.varyings[i] + w[2] * c.varyings[i]) / inv_w;
+            }
+
+            let color = match (program.fragment)(env, &varyings) {
+                Some(color) => color,
+                None => continue,
+            };
+
+            if raster.depth_test && raster.depth_mask {
+                if let Some(d) = targets.depth.as_mut().and_then(|img| img.data.get_mut(idx)) {
+                    let z = z.max(0.0).min(1.0);
+                    *d = [z, z, z, 1.0];
+                }
+            }
+
+            if let Some(ref mut target) = targets.color {
+                let out = if raster.blend {
+                    blend(raster, color, target.data[idx])
+                } else {
+                    color
+                };
+
+                target.data[idx] = target.store(out);
+            }
+        }
+    }
+}